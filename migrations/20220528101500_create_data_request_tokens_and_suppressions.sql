@@ -0,0 +1,18 @@
+BEGIN;
+    CREATE TYPE data_request_kind AS ENUM ('export', 'erasure');
+
+    CREATE TABLE data_request_tokens(
+        data_request_token TEXT NOT NULL PRIMARY KEY,
+        subscriber_id UUID NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        kind data_request_kind NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+
+    -- We only ever keep a hash of the canonical email here,
+    -- so that erased subscribers are not stored in plain text anywhere
+    CREATE TABLE suppressions(
+        email_hash TEXT NOT NULL PRIMARY KEY,
+        reason TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+COMMIT;
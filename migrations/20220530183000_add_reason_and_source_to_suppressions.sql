@@ -0,0 +1,17 @@
+BEGIN;
+    CREATE TYPE suppression_reason AS ENUM ('hard_bounce', 'complaint', 'unsubscribe', 'erasure', 'manual');
+
+    ALTER TABLE suppressions
+    ALTER COLUMN reason TYPE suppression_reason USING reason::suppression_reason;
+
+    -- So far only erasure requests could add suppressions
+    ALTER TABLE suppressions
+    ADD COLUMN source TEXT NULL;
+
+    UPDATE suppressions
+    SET source = 'data_request'
+    WHERE source IS NULL;
+
+    ALTER TABLE suppressions
+    ALTER COLUMN source SET NOT NULL;
+COMMIT;
@@ -0,0 +1,13 @@
+CREATE TABLE email_events(
+    id UUID NOT NULL PRIMARY KEY,
+    -- SendGrid retries webhooks, so the same event may arrive more than once
+    provider_event_id TEXT NULL UNIQUE,
+    provider_message_id TEXT NULL,
+    email TEXT NOT NULL,
+    event_type TEXT NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL,
+    payload JSONB NOT NULL
+);
+CREATE INDEX email_events_provider_message_id_idx ON email_events (provider_message_id);
+CREATE INDEX email_events_email_idx ON email_events (email);
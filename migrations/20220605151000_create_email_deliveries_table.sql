@@ -0,0 +1,20 @@
+BEGIN;
+    CREATE TYPE email_delivery_outcome AS ENUM ('sent', 'suppressed', 'failed');
+
+    CREATE TABLE email_deliveries(
+        id UUID NOT NULL PRIMARY KEY,
+        -- Erasing a subscriber erases its delivery history as well
+        subscription_id UUID NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        template TEXT NOT NULL,
+        outcome email_delivery_outcome NOT NULL,
+        -- X-Message-Id returned by SendGrid, joins with email_events.provider_message_id
+        provider_message_id TEXT NULL,
+        http_status INT NULL,
+        latency_ms INT NULL,
+        attempt INT NOT NULL,
+        error TEXT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+    CREATE INDEX email_deliveries_subscription_id_idx ON email_deliveries (subscription_id);
+    CREATE INDEX email_deliveries_provider_message_id_idx ON email_deliveries (provider_message_id);
+COMMIT;
@@ -0,0 +1,29 @@
+BEGIN;
+    -- Append-only consent audit trail: one row per subscription status transition
+    CREATE TABLE subscription_events(
+        id UUID NOT NULL PRIMARY KEY,
+        -- Erasing a subscriber erases its consent history as well
+        subscription_id UUID NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        -- NULL when the subscription was created
+        old_status subscription_status NULL,
+        new_status subscription_status NOT NULL,
+        source TEXT NOT NULL,
+        ip_address TEXT NULL,
+        user_agent TEXT NULL,
+        origin TEXT NULL,
+        -- Subscription token issued or consumed by the transition
+        token TEXT NULL,
+        occurred_at TIMESTAMPTZ NOT NULL
+    );
+    CREATE INDEX subscription_events_subscription_id_idx ON subscription_events (subscription_id);
+
+    CREATE FUNCTION reject_subscription_events_update() RETURNS TRIGGER AS $$
+    BEGIN
+        RAISE EXCEPTION 'subscription_events is append-only';
+    END;
+    $$ LANGUAGE plpgsql;
+
+    CREATE TRIGGER subscription_events_append_only
+        BEFORE UPDATE ON subscription_events
+        FOR EACH ROW EXECUTE FUNCTION reject_subscription_events_update();
+COMMIT;
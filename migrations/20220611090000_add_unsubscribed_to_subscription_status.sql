@@ -0,0 +1,2 @@
+-- List memberships share the subscription status type, see the next migration
+ALTER TYPE subscription_status ADD VALUE 'unsubscribed';
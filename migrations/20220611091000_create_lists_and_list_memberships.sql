@@ -0,0 +1,44 @@
+BEGIN;
+    CREATE TABLE lists(
+        id UUID NOT NULL PRIMARY KEY,
+        slug TEXT NOT NULL UNIQUE,
+        name TEXT NOT NULL,
+        -- NULL = EMAIL_CLIENT_SENDER_EMAIL
+        from_address TEXT NULL,
+        description TEXT NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+    -- The implicit list every existing subscriber has signed up for
+    INSERT INTO lists (id, slug, name, from_address, description, created_at)
+    VALUES (gen_random_uuid(), 'newsletter', 'Newsletter', NULL, '', now());
+
+    CREATE TABLE list_memberships(
+        subscription_id UUID NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        list_id UUID NOT NULL REFERENCES lists (id),
+        -- pending, confirmed or unsubscribed
+        status subscription_status NOT NULL,
+        unsubscribe_token TEXT NOT NULL UNIQUE,
+        created_at TIMESTAMPTZ NOT NULL,
+        updated_at TIMESTAMPTZ NOT NULL,
+        PRIMARY KEY (subscription_id, list_id)
+    );
+    CREATE INDEX list_memberships_list_id_idx ON list_memberships (list_id);
+    INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                  created_at, updated_at)
+    SELECT s.id,
+           l.id,
+           CASE WHEN s.status = 'confirmed' THEN 'confirmed' ELSE 'pending' END :: subscription_status,
+           gen_random_uuid() :: TEXT,
+           s.subscribed_at,
+           now()
+    FROM subscriptions s, lists l
+    WHERE l.slug = 'newsletter';
+
+    -- Confirmation tokens are issued per list
+    ALTER TABLE subscription_tokens ADD COLUMN list_id UUID NULL REFERENCES lists (id);
+    UPDATE subscription_tokens SET list_id = (SELECT id FROM lists WHERE slug = 'newsletter');
+    ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
+
+    -- NULL = a transition of the subscription itself, otherwise of a list membership
+    ALTER TABLE subscription_events ADD COLUMN list_id UUID NULL REFERENCES lists (id);
+COMMIT;
@@ -0,0 +1,10 @@
+BEGIN;
+    CREATE TYPE delivery_cadence AS ENUM ('immediate', 'digest');
+    ALTER TABLE subscriptions ADD COLUMN delivery_cadence delivery_cadence NOT NULL DEFAULT 'immediate';
+    ALTER TABLE subscriptions ADD COLUMN preferred_language TEXT NOT NULL DEFAULT 'en';
+    -- Stable token of the preference center link in every email footer
+    ALTER TABLE subscriptions ADD COLUMN preferences_token TEXT NULL UNIQUE;
+    UPDATE subscriptions SET preferences_token = gen_random_uuid() :: TEXT;
+    ALTER TABLE subscriptions ALTER COLUMN preferences_token SET NOT NULL;
+    ALTER TABLE subscriptions ALTER COLUMN preferences_token SET DEFAULT gen_random_uuid() :: TEXT;
+COMMIT;
@@ -0,0 +1,34 @@
+BEGIN;
+    CREATE TYPE issue_status AS ENUM ('draft', 'scheduled', 'sending', 'sent', 'cancelled');
+
+    -- IANA time zone names, e.g. Europe/Berlin
+    ALTER TABLE lists ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC';
+    -- NULL = the timezone of the list
+    ALTER TABLE subscriptions ADD COLUMN timezone TEXT NULL;
+
+    CREATE TABLE issues(
+        id UUID NOT NULL PRIMARY KEY,
+        list_id UUID NOT NULL REFERENCES lists (id),
+        subject TEXT NOT NULL,
+        text_content TEXT NOT NULL,
+        status issue_status NOT NULL,
+        -- Wall clock time of the send, in the list's or in each subscriber's timezone
+        send_at_local TIMESTAMP NULL,
+        per_subscriber_timezone BOOLEAN NOT NULL DEFAULT FALSE,
+        -- When the scheduler picks the issue up: the earliest instant send_at_local happens
+        scheduled_at TIMESTAMPTZ NULL,
+        created_at TIMESTAMPTZ NOT NULL,
+        updated_at TIMESTAMPTZ NOT NULL,
+        sent_at TIMESTAMPTZ NULL
+    );
+    CREATE INDEX issues_due_idx ON issues (scheduled_at) WHERE status IN ('scheduled', 'sending');
+
+    -- Claimed before the email is sent, so that no subscriber gets an issue twice
+    CREATE TABLE issue_deliveries(
+        issue_id UUID NOT NULL REFERENCES issues (id) ON DELETE CASCADE,
+        subscription_id UUID NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL,
+        PRIMARY KEY (issue_id, subscription_id)
+    );
+    CREATE INDEX issue_deliveries_subscription_id_idx ON issue_deliveries (subscription_id);
+COMMIT;
@@ -0,0 +1,17 @@
+ALTER TYPE delivery_cadence RENAME VALUE 'digest' TO 'daily';
+ALTER TYPE delivery_cadence ADD VALUE 'weekly';
+
+BEGIN;
+    -- When the fan-out started: digests collect the issues published since the previous one
+    ALTER TABLE issues ADD COLUMN published_at TIMESTAMPTZ NULL;
+    UPDATE issues SET published_at = updated_at WHERE status IN ('sending', 'sent');
+
+    ALTER TABLE issue_deliveries ADD COLUMN via_digest BOOLEAN NOT NULL DEFAULT FALSE;
+
+    CREATE TABLE digest_cursors(
+        subscription_id UUID NOT NULL PRIMARY KEY REFERENCES subscriptions (id) ON DELETE CASCADE,
+        -- Issues published up to this instant were included in a digest
+        last_issue_published_at TIMESTAMPTZ NOT NULL,
+        last_digest_at TIMESTAMPTZ NOT NULL
+    );
+COMMIT;
@@ -0,0 +1,32 @@
+BEGIN;
+    -- A/B tested subjects: every variant goes to a slice of the audience,
+    -- the winner goes to everyone else once the wait is over
+    CREATE TABLE issue_subject_variants(
+        id UUID NOT NULL PRIMARY KEY,
+        issue_id UUID NOT NULL REFERENCES issues (id) ON DELETE CASCADE,
+        position SMALLINT NOT NULL,
+        subject TEXT NOT NULL,
+        UNIQUE (issue_id, position)
+    );
+
+    -- Percent of the members of the list who get one of the variants
+    ALTER TABLE issues ADD COLUMN ab_test_audience_percent SMALLINT NULL;
+    ALTER TABLE issues ADD COLUMN ab_test_wait_minutes INTEGER NULL;
+    ALTER TABLE issues ADD COLUMN winning_variant_id UUID NULL REFERENCES issue_subject_variants (id);
+
+    -- The subject the subscriber got
+    ALTER TABLE issue_deliveries ADD COLUMN variant_id UUID NULL REFERENCES issue_subject_variants (id);
+
+    CREATE TYPE tracking_event_kind AS ENUM ('open', 'click');
+    CREATE TABLE issue_tracking_events(
+        id UUID NOT NULL PRIMARY KEY,
+        issue_id UUID NOT NULL REFERENCES issues (id) ON DELETE CASCADE,
+        subscription_id UUID NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        kind tracking_event_kind NOT NULL,
+        -- The link followed by a click
+        url TEXT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+    CREATE INDEX issue_tracking_events_issue_id_idx ON issue_tracking_events (issue_id);
+    CREATE INDEX issue_tracking_events_subscription_id_idx ON issue_tracking_events (subscription_id);
+COMMIT;
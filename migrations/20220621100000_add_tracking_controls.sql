@@ -0,0 +1,19 @@
+BEGIN;
+    -- Opens and clicks are only tracked on the lists that opt in
+    ALTER TABLE lists ADD COLUMN tracking_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+    -- Lists with A/B tested issues were tracked already
+    UPDATE lists SET tracking_enabled = TRUE
+    WHERE id IN (SELECT list_id FROM issues WHERE ab_test_audience_percent IS NOT NULL);
+    -- Honoured when an email is rendered, and when a tracking link is followed
+    ALTER TABLE subscriptions ADD COLUMN do_not_track BOOLEAN NOT NULL DEFAULT FALSE;
+
+    -- The links of an issue, found when it is written: click redirects can only go there
+    CREATE TABLE issue_links(
+        id UUID NOT NULL PRIMARY KEY,
+        issue_id UUID NOT NULL REFERENCES issues (id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        UNIQUE (issue_id, position)
+    );
+    ALTER TABLE issue_tracking_events ADD COLUMN link_id UUID NULL REFERENCES issue_links (id);
+COMMIT;
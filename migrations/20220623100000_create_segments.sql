@@ -0,0 +1,9 @@
+BEGIN;
+    -- Saved subscriber segments, see `domain::segment::Segment` for the definitions
+    CREATE TABLE segments(
+        id UUID NOT NULL PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        definition JSONB NOT NULL,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+COMMIT;
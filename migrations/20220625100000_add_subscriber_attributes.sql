@@ -0,0 +1,15 @@
+BEGIN;
+    -- Operator-defined attributes of the subscribers, see `domain::subscriber_attributes`
+    CREATE TYPE attribute_kind AS ENUM ('string', 'number', 'bool', 'date', 'enum');
+    CREATE TABLE attribute_definitions(
+        name TEXT NOT NULL PRIMARY KEY,
+        kind attribute_kind NOT NULL,
+        -- Enforced on new input only: existing subscribers may lack the attribute
+        required BOOLEAN NOT NULL,
+        -- The allowed values of enum attributes
+        enum_values TEXT[] NOT NULL DEFAULT '{}',
+        created_at TIMESTAMPTZ NOT NULL
+    );
+
+    ALTER TABLE subscriptions ADD COLUMN attributes JSONB NOT NULL DEFAULT '{}';
+COMMIT;
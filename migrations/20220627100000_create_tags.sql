@@ -0,0 +1,15 @@
+BEGIN;
+    -- Free-form labels set by the admins, created on first use
+    CREATE TABLE tags(
+        id UUID NOT NULL PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        created_at TIMESTAMPTZ NOT NULL
+    );
+    CREATE TABLE subscriber_tags(
+        subscription_id UUID NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+        tag_id UUID NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+        created_at TIMESTAMPTZ NOT NULL,
+        PRIMARY KEY (subscription_id, tag_id)
+    );
+    CREATE INDEX subscriber_tags_tag_id_idx ON subscriber_tags (tag_id);
+COMMIT;
@@ -0,0 +1,12 @@
+BEGIN;
+    -- The events each consumer has handled, so that redeliveries are skipped
+    CREATE TABLE processed_events(
+        consumer TEXT NOT NULL,
+        event_id UUID NOT NULL,
+        processed_at TIMESTAMPTZ NOT NULL,
+        -- Deliveries skipped since
+        duplicates INT NOT NULL DEFAULT 0,
+        PRIMARY KEY (consumer, event_id)
+    );
+    CREATE INDEX processed_events_processed_at_idx ON processed_events (processed_at);
+COMMIT;
@@ -0,0 +1,18 @@
+BEGIN;
+    -- The events a consumer gave up on, to be replayed with zero2prodctl
+    CREATE TABLE dead_letters(
+        id UUID NOT NULL PRIMARY KEY,
+        -- The subject without the application id prefix, e.g. subscription-created
+        subject TEXT NOT NULL,
+        -- NULL if the envelope could not be decoded
+        event_id UUID NULL,
+        -- The envelope as it was delivered
+        data BYTEA NOT NULL,
+        error TEXT NOT NULL,
+        attempts INT NOT NULL,
+        dead_lettered_at TIMESTAMPTZ NOT NULL,
+        replayed_at TIMESTAMPTZ NULL
+    );
+    CREATE INDEX dead_letters_not_replayed_idx ON dead_letters (dead_lettered_at)
+        WHERE replayed_at IS NULL;
+COMMIT;
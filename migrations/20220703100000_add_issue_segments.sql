@@ -0,0 +1,2 @@
+-- Issues go to the members of the list in the segment, or to every member if NULL
+ALTER TABLE issues ADD COLUMN segment_id UUID NULL REFERENCES segments (id);
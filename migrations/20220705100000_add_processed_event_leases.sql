@@ -0,0 +1,3 @@
+-- Set while a delivery handles the event, other deliveries wait for it to expire.
+-- NULL once the event is processed.
+ALTER TABLE processed_events ADD COLUMN lease_expires_at TIMESTAMPTZ NULL;
@@ -0,0 +1,11 @@
+BEGIN;
+    -- The subscriber the event is about, so that their dead letters are exported and erased with them
+    ALTER TABLE dead_letters ADD COLUMN subscription_id UUID NULL;
+    -- Envelopes are compact JSON, whether they could be decoded or not
+    UPDATE dead_letters
+    SET subscription_id = substring(
+        encode(data, 'escape')
+        FROM '"subscription_id":"([0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12})"'
+    ) :: UUID;
+    CREATE INDEX dead_letters_subscription_id_idx ON dead_letters (subscription_id);
+COMMIT;
@@ -0,0 +1,241 @@
+use anyhow::Context;
+use clap::{Parser, Subcommand};
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use zero2prod::config::Config;
+use zero2prod::domain::subscriber_email::SubscriberEmail;
+use zero2prod::domain::tag_name::TagName;
+use zero2prod::event_bus::{EventBus, EventBusBackend};
+use zero2prod::handlers::manage_subscribers::{
+    confirm_subscriber, resend_confirmation, show_subscriber, unsubscribe_subscriber,
+    ChangeSubscriberOutput, ShowSubscriberOutput,
+};
+use zero2prod::handlers::manage_tags::list_subscribers;
+use zero2prod::handlers::purge_expired_tokens::purge_expired_tokens;
+use zero2prod::handlers::replay_dead_letters::{list_dead_letters, replay_dead_letters};
+use zero2prod::migrations::Migrations;
+use zero2prod::telemetry;
+
+/// Operates the service with the config of the `zero2prod` binary
+#[derive(Parser)]
+#[clap(name = "zero2prodctl")]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Applies the pending migrations
+    Migrate {
+        /// Only prints the pending migrations, as JSON
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Subscriptions, found by their email
+    #[clap(subcommand)]
+    Subscribers(SubscribersCommand),
+    /// Events the consumers gave up on
+    #[clap(subcommand)]
+    DeadLetters(DeadLettersCommand),
+    /// Tokens sent in the emails
+    #[clap(subcommand)]
+    Tokens(TokensCommand),
+    /// Prints the effective config, secrets redacted
+    Config,
+}
+
+#[derive(Subcommand)]
+enum SubscribersCommand {
+    /// Most recent first, as JSON
+    List {
+        #[clap(long)]
+        tag: Option<String>,
+        #[clap(long, default_value_t = 50)]
+        limit: u32,
+        #[clap(long, default_value_t = 0)]
+        offset: u32,
+    },
+    /// The subscription and its lists, as JSON
+    Show { email: String },
+    /// Confirms the address and its pending lists without a confirmation link
+    Confirm { email: String },
+    /// Leaves every list
+    Unsubscribe { email: String },
+    /// Sends new confirmation links for the pending lists
+    ResendConfirmation { email: String },
+}
+
+#[derive(Subcommand)]
+enum DeadLettersCommand {
+    /// Oldest first, as JSON
+    List {
+        #[clap(long)]
+        subject: Option<String>,
+        /// Including the replayed ones
+        #[clap(long)]
+        all: bool,
+    },
+    /// Publishes the dead letters again, all of them unless ids are given
+    Replay {
+        #[clap(long)]
+        subject: Option<String>,
+        ids: Vec<Uuid>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TokensCommand {
+    /// Deletes the expired data request tokens
+    PurgeExpired,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let config = Config::new().context("Failed to load config")?;
+    // Only warnings and errors, on stderr: stdout is for the output of the command
+    let subscriber =
+        telemetry::get_subscriber("zero2prodctl".into(), "warn".into(), std::io::stderr);
+    telemetry::init_subscriber(subscriber);
+
+    match cli.command {
+        Command::Migrate { dry_run: true } => {
+            let pg_pool = connect_postgres(&config).await?;
+            let pending = Migrations::pending(&pg_pool)
+                .await
+                .context("Failed to check the database migrations")?;
+            print_json(&pending)?;
+        }
+        Command::Migrate { dry_run: false } => {
+            let pg_pool = connect_postgres(&config).await?;
+            let applied = Migrations::run(&pg_pool)
+                .await
+                .context("Failed to migrate the database")?;
+            println!("Applied {} migrations", applied.len());
+        }
+        Command::Subscribers(command) => subscribers(&config, command).await?,
+        Command::DeadLetters(command) => dead_letters(&config, command).await?,
+        Command::Tokens(TokensCommand::PurgeExpired) => {
+            let pg_pool = connect_postgres(&config).await?;
+            let deleted = purge_expired_tokens(&pg_pool, config.data_request_token_ttl_minutes)
+                .await
+                .context("Failed to purge the expired tokens")?;
+            println!("Deleted {} expired data request tokens", deleted);
+        }
+        // `Secret` fields are redacted by their `Debug` implementation
+        Command::Config => println!("{:#?}", config),
+    }
+    Ok(())
+}
+
+async fn subscribers(config: &Config, command: SubscribersCommand) -> anyhow::Result<()> {
+    let pg_pool = connect_postgres(config).await?;
+    let (email, output) = match command {
+        SubscribersCommand::List { tag, limit, offset } => {
+            let tag = tag
+                .map(TagName::parse)
+                .transpose()
+                .map_err(anyhow::Error::msg)?;
+            let subscribers = list_subscribers(&pg_pool, tag.as_ref(), limit, offset)
+                .await
+                .context("Failed to list the subscribers")?;
+            return print_json(&subscribers);
+        }
+        SubscribersCommand::Show { email } => {
+            let email = parse_email(email)?;
+            return match show_subscriber(&pg_pool, &email)
+                .await
+                .context("Failed to show the subscriber")?
+            {
+                ShowSubscriberOutput::Success(details) => print_json(&details),
+                ShowSubscriberOutput::SubscriptionNotFound => not_found(&email),
+            };
+        }
+        SubscribersCommand::Confirm { email } => {
+            let email = parse_email(email)?;
+            let output = confirm_subscriber(&pg_pool, &email)
+                .await
+                .context("Failed to confirm the subscriber")?;
+            (email, output)
+        }
+        SubscribersCommand::Unsubscribe { email } => {
+            let email = parse_email(email)?;
+            let output = unsubscribe_subscriber(&pg_pool, &email)
+                .await
+                .context("Failed to unsubscribe the subscriber")?;
+            (email, output)
+        }
+        SubscribersCommand::ResendConfirmation { email } => {
+            let email = parse_email(email)?;
+            let event_bus = connect_event_bus(config).await?;
+            let output = resend_confirmation(&pg_pool, &event_bus, &email)
+                .await
+                .context("Failed to resend the confirmation")?;
+            event_bus.close().await?;
+            (email, output)
+        }
+    };
+    match output {
+        ChangeSubscriberOutput::Success => println!("Done"),
+        ChangeSubscriberOutput::Unchanged => println!("Nothing to change"),
+        ChangeSubscriberOutput::SubscriptionNotFound => return not_found(&email),
+        ChangeSubscriberOutput::Conflict => {
+            anyhow::bail!("The subscription changed concurrently, run the command again")
+        }
+    }
+    Ok(())
+}
+
+async fn dead_letters(config: &Config, command: DeadLettersCommand) -> anyhow::Result<()> {
+    let pg_pool = connect_postgres(config).await?;
+    match command {
+        DeadLettersCommand::List { subject, all } => {
+            let dead_letters = list_dead_letters(&pg_pool, subject.as_deref(), &[], all)
+                .await
+                .context("Failed to list the dead letters")?;
+            print_json(&dead_letters)
+        }
+        DeadLettersCommand::Replay { subject, ids } => {
+            let event_bus = connect_event_bus(config).await?;
+            let replayed = replay_dead_letters(&pg_pool, &event_bus, subject.as_deref(), &ids)
+                .await
+                .context("Failed to replay the dead letters")?;
+            event_bus.close().await?;
+            println!("Replayed {} dead letters", replayed.len());
+            Ok(())
+        }
+    }
+}
+
+async fn connect_postgres(config: &Config) -> anyhow::Result<PgPool> {
+    PgPool::connect(config.database_url.expose_secret())
+        .await
+        .context("Failed to connect to Postgres")
+}
+
+/// With the in-process backend, the events would only reach this process
+async fn connect_event_bus(config: &Config) -> anyhow::Result<EventBus> {
+    if config.event_bus_backend == EventBusBackend::InProcess {
+        anyhow::bail!("Events cannot be published with the in_process event bus backend");
+    }
+    EventBus::connect(config)
+        .await
+        .context("Failed to connect to the event bus")
+}
+
+fn parse_email(email: String) -> anyhow::Result<SubscriberEmail> {
+    SubscriberEmail::parse(email).map_err(anyhow::Error::msg)
+}
+
+fn not_found(email: &SubscriberEmail) -> anyhow::Result<()> {
+    anyhow::bail!("There is no subscription for {}", email.as_ref())
+}
+
+fn print_json(value: &impl Serialize) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+
+use zero2prod::bootstrap::{bootstrap, Dependencies};
+use zero2prod::event_bus::EventBusBackend;
+use zero2prod::shutdown::{shutdown_signal, ShutdownCoordinator};
+use zero2prod::startup::spawn_event_consumers;
+
+/// Runs the event consumers without the HTTP server
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let Dependencies {
+        config,
+        pg_pool,
+        event_bus,
+        email_client,
+    } = bootstrap("zero2prod-worker").await?;
+    if config.event_bus_backend == EventBusBackend::InProcess {
+        anyhow::bail!(
+            "The worker cannot receive the events of the API with the in_process event bus backend"
+        );
+    }
+    let shutdown_deadline = Duration::from_millis(config.shutdown_deadline_millis);
+
+    let mut shutdown = ShutdownCoordinator::new();
+    spawn_event_consumers(
+        Arc::new(config),
+        Arc::new(email_client),
+        Arc::new(pg_pool.clone()),
+        &event_bus,
+        &mut shutdown,
+    )
+    .await
+    .context("Failed to start the event consumers")?;
+    tracing::info!("Consuming events");
+
+    shutdown_signal().await;
+    shutdown
+        .shutdown(&event_bus, &pg_pool, shutdown_deadline)
+        .await;
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use anyhow::Context;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::config::Config;
+use crate::email_client::EmailClient;
+use crate::event_bus::EventBus;
+use crate::migrations::Migrations;
+use crate::telemetry;
+
+/// What the API and the worker binaries start with
+pub struct Dependencies {
+    pub config: Config,
+    pub pg_pool: PgPool,
+    pub event_bus: EventBus,
+    pub email_client: EmailClient,
+}
+
+/// Loads the config, logs as `name`, connects to Postgres and the event bus,
+/// and migrates the database or checks that its schema matches the binary
+pub async fn bootstrap(name: &str) -> anyhow::Result<Dependencies> {
+    let config = Config::new().context("Failed to load config")?;
+
+    let subscriber = telemetry::get_subscriber(name.into(), "info".into(), std::io::stdout);
+    telemetry::init_subscriber(subscriber);
+    tracing::info!("Connecting to Postgres");
+
+    let pg_pool = PgPool::connect(config.database_url.expose_secret())
+        .await
+        .context("Failed to connect to Postgres")?;
+    if config.database_migrate_on_startup {
+        let applied = Migrations::run(&pg_pool)
+            .await
+            .context("Failed to migrate the database")?;
+        tracing::info!(applied = applied.len(), "Database migrated");
+    } else {
+        let pending = Migrations::pending(&pg_pool)
+            .await
+            .context("Failed to check the database migrations")?;
+        if !pending.is_empty() {
+            let versions: Vec<i64> = pending.iter().map(|migration| migration.version).collect();
+            tracing::warn!(?versions, "Pending migrations, run `zero2prodctl migrate`");
+        }
+    }
+
+    let event_bus = EventBus::connect(&config)
+        .await
+        .context("Failed to connect to the event bus")?
+        .with_dead_letters(pg_pool.clone());
+
+    let email_client = EmailClient::new(&config, pg_pool.clone());
+    Ok(Dependencies {
+        config,
+        pg_pool,
+        event_bus,
+        email_client,
+    })
+}
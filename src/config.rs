@@ -4,29 +4,64 @@ use std::io::Read;
 use secrecy::Secret;
 use serde::Deserialize;
 
+use crate::event_bus::EventBusBackend;
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Config {
     pub application_id: String,
     pub application_host: String,
     pub application_port: u16,
     pub application_protocol: String,
+    /// How long the shutdown waits for the requests and the events in flight
+    pub shutdown_deadline_millis: u64,
     pub database_url: Secret<String>,
+    /// Off when migrating with `zero2prodctl migrate` instead.
+    /// Either way, a binary older than the database schema refuses to start
+    pub database_migrate_on_startup: bool,
+    pub event_bus_backend: EventBusBackend,
+    /// Off when the consumers run in the `zero2prod-worker` binary instead
+    pub event_consumers_enabled: bool,
     pub nats_host: String,
     pub nats_port: u16,
-    nats_subscription_created_subject: String,
-    nats_subscription_created_group: String,
+    /// Shared by every instance, so that each event is handled once
+    pub nats_queue_group: String,
+    /// The stream of the `jetstream` backend, capturing every `<application id>.` subject
+    pub jetstream_stream: String,
+    pub jetstream_max_age_hours: u32,
+    /// Deliveries of an event before giving up on it
+    pub jetstream_max_deliver: u32,
+    /// An unacked delivery is redelivered after this long
+    pub jetstream_ack_wait_millis: u64,
+    /// Multiplied by the attempt to get the delay of a redelivery after a failure
+    pub jetstream_nack_delay_millis: u64,
     pub sendgrid_api_key: Secret<String>,
+    /// Base64 DER encoded ECDSA public key from the SendGrid Mail Settings
+    pub sendgrid_webhook_public_key: String,
     pub email_client_sender_email: String,
     pub email_client_base_url: String,
     pub email_client_timeout_millis: u16,
+    pub email_client_max_attempts: u32,
+    pub data_request_token_ttl_minutes: u32,
+    pub issue_scheduler_poll_interval_millis: u64,
+    /// Longer than redeliveries can happen, e.g. the JetStream max age
+    pub processed_events_retention_hours: u32,
+    pub processed_events_prune_interval_millis: u64,
+    /// How long a delivery has to handle an event before another one can take it over,
+    /// longer than an email with all its attempts
+    pub processed_events_lease_millis: u64,
+    /// Comma separated: the only addresses issue test sends can go to
+    pub issue_seed_addresses: Vec<String>,
+    pub admin_api_token: Secret<String>,
+    /// HMAC key of the open and click tracking links
+    pub tracking_signing_key: Secret<String>,
 }
 
 impl Config {
     pub fn new() -> anyhow::Result<Self> {
         let env = envy::from_env::<Config>();
-        match env {
+        let config = match env {
             // if we could load the config using the existing env variables - use that
-            Ok(config) => Ok(config),
+            Ok(config) => config,
             // otherwise, try to load the .env file
             Err(_) => {
                 // simulate https://www.npmjs.com/package/dotenv behavior
@@ -37,11 +72,26 @@ impl Config {
                     and there is also no .env file",
                 );
                 match envy::from_env::<Config>() {
-                    Ok(config) => Ok(config),
+                    Ok(config) => config,
                     Err(e) => panic!("Failed to read the config from env: {}", e),
                 }
             }
-        }
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Values that deserialize fine but would fail later, e.g. in a background task
+    pub fn validate(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.processed_events_prune_interval_millis > 0,
+            "PROCESSED_EVENTS_PRUNE_INTERVAL_MILLIS must be greater than 0"
+        );
+        anyhow::ensure!(
+            self.processed_events_lease_millis > 0,
+            "PROCESSED_EVENTS_LEASE_MILLIS must be greater than 0"
+        );
+        Ok(())
     }
 
     // TODO memoize?
@@ -51,20 +101,6 @@ impl Config {
             self.application_protocol, self.application_host, self.application_port
         )
     }
-
-    pub fn nats_subscription_created_subject(&self) -> String {
-        format!(
-            "{}-{}",
-            self.application_id, self.nats_subscription_created_subject
-        )
-    }
-
-    pub fn nats_subscription_created_group(&self) -> String {
-        format!(
-            "{}-{}",
-            self.application_id, self.nats_subscription_created_group
-        )
-    }
 }
 
 fn set_env_from_file_content(file_path: &str) -> anyhow::Result<()> {
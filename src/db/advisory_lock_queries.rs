@@ -0,0 +1,18 @@
+use crate::db::types::Tx;
+
+pub struct AdvisoryLockQueries;
+
+impl AdvisoryLockQueries {
+    /// Returns `false` if another session holds the lock.
+    /// The lock is released when the transaction ends, or when its connection is lost.
+    #[tracing::instrument(name = "Try to acquire an advisory lock", skip(tx))]
+    pub async fn try_lock_for_transaction(tx: &mut Tx<'_>, key: i64) -> anyhow::Result<bool> {
+        let record = sqlx::query!(
+            r#"SELECT pg_try_advisory_xact_lock($1) AS "acquired!""#,
+            key
+        )
+        .fetch_one(tx)
+        .await?;
+        Ok(record.acquired)
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+
+use crate::db::types::Tx;
+use crate::domain::attribute_kind::AttributeKind;
+use crate::domain::subscriber_attributes::{AttributeDefinition, AttributeSchema};
+
+pub struct AttributeDefinitionQueries;
+
+#[derive(Serialize)]
+pub struct AttributeDefinitionRecord {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: AttributeKind,
+    pub required: bool,
+    pub enum_values: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AttributeDefinitionQueries {
+    /// Returns `false` if an attribute with the same name already exists.
+    #[tracing::instrument(name = "Insert attribute definition into the database", skip(tx))]
+    pub async fn insert_definition(
+        tx: &mut Tx<'_>,
+        definition: &AttributeDefinition,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                INSERT INTO attribute_definitions (name, kind, required, enum_values, created_at)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(definition.name())
+        .bind(definition.kind())
+        .bind(definition.required())
+        .bind(definition.values())
+        .bind(Utc::now())
+        .execute(tx)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(name = "Fetch attribute definitions from the database", skip(executor))]
+    pub async fn fetch_definitions<'a, E>(
+        executor: E,
+    ) -> anyhow::Result<Vec<AttributeDefinitionRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            AttributeDefinitionRecord,
+            r#"
+                SELECT name, kind AS "kind: _", required, enum_values, created_at
+                FROM attribute_definitions
+                ORDER BY name
+            "#,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    pub async fn fetch_schema<'a, E>(executor: E) -> anyhow::Result<AttributeSchema>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let definitions = Self::fetch_definitions(executor)
+            .await?
+            .into_iter()
+            .map(|record| {
+                AttributeDefinition::parse(
+                    record.name,
+                    record.kind,
+                    record.required,
+                    record.enum_values,
+                )
+                .map_err(anyhow::Error::msg)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .context("A stored attribute definition is no longer valid")?;
+        Ok(AttributeSchema::new(definitions))
+    }
+}
@@ -0,0 +1,124 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::data_request_kind::DataRequestKind;
+
+pub struct DataRequestQueries;
+
+pub struct DataRequestRecord {
+    pub subscriber_id: Uuid,
+    pub kind: DataRequestKind,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DataRequestRecord {
+    pub fn is_expired(&self, ttl_minutes: u32) -> bool {
+        self.created_at + Duration::minutes(ttl_minutes as i64) < Utc::now()
+    }
+}
+
+/// The token itself is deliberately left out - it is a credential, not personal data.
+#[derive(Serialize)]
+pub struct DataRequestSummary {
+    pub kind: DataRequestKind,
+    pub created_at: DateTime<Utc>,
+}
+
+impl DataRequestQueries {
+    #[tracing::instrument(name = "Store data request token in the database", skip(tx))]
+    pub async fn store_token(
+        tx: &mut Tx<'_>,
+        subscriber_id: &Uuid,
+        data_request_token: &Uuid,
+        kind: DataRequestKind,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO data_request_tokens (data_request_token, subscriber_id, kind, created_at)
+                VALUES ($1, $2, $3, $4)
+            "#,
+        )
+        .bind(data_request_token.to_string().as_str())
+        .bind(subscriber_id)
+        .bind(kind)
+        .bind(Utc::now())
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Fetch data request by token from the database", skip(executor))]
+    pub async fn fetch_data_request_by_token<'a, E>(
+        executor: E,
+        data_request_token: &str,
+    ) -> anyhow::Result<Option<DataRequestRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let maybe_record = sqlx::query_as!(
+            DataRequestRecord,
+            r#"
+                SELECT subscriber_id, kind AS "kind: _", created_at
+                FROM data_request_tokens
+                WHERE data_request_token = $1
+            "#,
+            data_request_token,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(maybe_record)
+    }
+
+    #[tracing::instrument(
+        name = "Fetch data requests by subscriber id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_data_requests_by_subscriber_id<'a, E>(
+        executor: E,
+        subscriber_id: &Uuid,
+    ) -> anyhow::Result<Vec<DataRequestSummary>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            DataRequestSummary,
+            r#"
+                SELECT kind AS "kind: _", created_at
+                FROM data_request_tokens
+                WHERE subscriber_id = $1
+                ORDER BY created_at
+            "#,
+            subscriber_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Returns the number of deleted tokens
+    #[tracing::instrument(
+        name = "Delete old data request tokens from the database",
+        skip(executor)
+    )]
+    pub async fn delete_tokens_created_before<'a, E>(
+        executor: E,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM data_request_tokens
+                WHERE created_at < $1
+            "#,
+            before,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
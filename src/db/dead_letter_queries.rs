@@ -0,0 +1,162 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+pub struct DeadLetterQueries;
+
+#[derive(Debug)]
+pub struct NewDeadLetter<'a> {
+    pub subject: &'a str,
+    pub event_id: Option<&'a Uuid>,
+    /// The subscriber the event is about, see `Envelope::subscription_id`
+    pub subscription_id: Option<Uuid>,
+    pub data: &'a [u8],
+    pub error: &'a str,
+    pub attempts: u32,
+}
+
+#[derive(Serialize)]
+pub struct DeadLetterRecord {
+    pub id: Uuid,
+    pub subject: String,
+    pub event_id: Option<Uuid>,
+    /// The envelope is only needed to replay the event
+    #[serde(skip)]
+    pub data: Vec<u8>,
+    pub error: String,
+    pub attempts: i32,
+    pub dead_lettered_at: DateTime<Utc>,
+    pub replayed_at: Option<DateTime<Utc>>,
+}
+
+impl DeadLetterQueries {
+    #[tracing::instrument(
+        name = "Insert dead letter into the database",
+        skip(executor, dead_letter),
+        fields(subject = dead_letter.subject, event_id = ?dead_letter.event_id)
+    )]
+    pub async fn insert_dead_letter<'a, E>(
+        executor: E,
+        dead_letter: &NewDeadLetter<'_>,
+    ) -> anyhow::Result<Uuid>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+                INSERT INTO dead_letters (id, subject, event_id, subscription_id, data, error,
+                                          attempts, dead_lettered_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            id,
+            dead_letter.subject,
+            dead_letter.event_id,
+            dead_letter.subscription_id,
+            dead_letter.data,
+            dead_letter.error,
+            dead_letter.attempts as i32,
+            Utc::now(),
+        )
+        .execute(executor)
+        .await?;
+        Ok(id)
+    }
+
+    /// Oldest first, optionally only those of a subject or with the given ids
+    #[tracing::instrument(name = "Fetch dead letters from the database", skip(executor, ids))]
+    pub async fn fetch_dead_letters<'a, E>(
+        executor: E,
+        subject: Option<&str>,
+        ids: &[Uuid],
+        include_replayed: bool,
+    ) -> anyhow::Result<Vec<DeadLetterRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            DeadLetterRecord,
+            r#"
+                SELECT id, subject, event_id, data, error, attempts, dead_lettered_at, replayed_at
+                FROM dead_letters
+                WHERE ($1::TEXT IS NULL OR subject = $1)
+                  AND (cardinality($2::UUID[]) = 0 OR id = ANY($2))
+                  AND ($3 OR replayed_at IS NULL)
+                ORDER BY dead_lettered_at, id
+            "#,
+            subject,
+            ids,
+            include_replayed,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Oldest first, replayed or not
+    #[tracing::instrument(
+        name = "Fetch dead letters by subscription id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_dead_letters_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<DeadLetterRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            DeadLetterRecord,
+            r#"
+                SELECT id, subject, event_id, data, error, attempts, dead_lettered_at, replayed_at
+                FROM dead_letters
+                WHERE subscription_id = $1
+                ORDER BY dead_lettered_at, id
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    #[tracing::instrument(
+        name = "Delete dead letters by subscription id from the database",
+        skip(executor)
+    )]
+    pub async fn delete_dead_letters_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<()>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        sqlx::query!(
+            "DELETE FROM dead_letters WHERE subscription_id = $1",
+            subscription_id,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Mark dead letter as replayed in the database", skip(executor))]
+    pub async fn mark_as_replayed<'a, E>(executor: E, id: &Uuid) -> anyhow::Result<()>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        sqlx::query!(
+            r#"
+                UPDATE dead_letters
+                SET replayed_at = $2
+                WHERE id = $1
+            "#,
+            id,
+            Utc::now(),
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::delivery_cadence::DeliveryCadence;
+
+pub struct DigestQueries;
+
+pub struct DigestSubscriberRecord {
+    pub subscription_id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub delivery_cadence: DeliveryCadence,
+    pub attributes: serde_json::Value,
+}
+
+pub struct DigestIssueRecord {
+    pub issue_id: Uuid,
+    pub subject: String,
+    pub text_content: String,
+    pub published_at: DateTime<Utc>,
+    pub list_name: String,
+    pub unsubscribe_token: String,
+    /// The segment the issue targets, if any
+    pub segment_definition: Option<serde_json::Value>,
+}
+
+impl DigestQueries {
+    /// Digest subscribers whose previous digest, or subscription, is from before
+    /// the current period: the UTC day for `daily`, the ISO week for `weekly`
+    #[tracing::instrument(
+        name = "Fetch due digest subscribers from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_due_subscribers<'a, E>(
+        executor: E,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<DigestSubscriberRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            DigestSubscriberRecord,
+            r#"
+                SELECT s.id AS subscription_id, s.email, s.name,
+                       s.delivery_cadence AS "delivery_cadence: _", s.attributes
+                FROM subscriptions s
+                LEFT JOIN digest_cursors c ON c.subscription_id = s.id
+                WHERE s.delivery_cadence IN ('daily', 'weekly')
+                  AND COALESCE(c.last_digest_at, s.subscribed_at) < date_trunc(
+                    CASE s.delivery_cadence WHEN 'weekly' THEN 'week' ELSE 'day' END,
+                    $1 :: TIMESTAMPTZ AT TIME ZONE 'UTC'
+                  ) AT TIME ZONE 'UTC'
+            "#,
+            now,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Issues of the subscriber's confirmed lists published since the previous digest,
+    /// or since joining the list, that were not sent to the subscriber yet, oldest first
+    #[tracing::instrument(name = "Fetch digest issues from the database", skip(executor))]
+    pub async fn fetch_issues_since_last_digest<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<DigestIssueRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            DigestIssueRecord,
+            r#"
+                SELECT i.id AS issue_id, i.subject, i.text_content,
+                       i.published_at AS "published_at!", l.name AS list_name,
+                       m.unsubscribe_token, g.definition AS "segment_definition?"
+                FROM list_memberships m
+                JOIN lists l ON l.id = m.list_id
+                JOIN issues i ON i.list_id = m.list_id
+                LEFT JOIN segments g ON g.id = i.segment_id
+                LEFT JOIN digest_cursors c ON c.subscription_id = m.subscription_id
+                WHERE m.subscription_id = $1
+                  AND m.status = 'confirmed'
+                  AND i.published_at > COALESCE(c.last_issue_published_at, m.created_at)
+                  AND NOT EXISTS (
+                    SELECT 1 FROM issue_deliveries d
+                    WHERE d.issue_id = i.id AND d.subscription_id = m.subscription_id
+                  )
+                ORDER BY i.published_at, i.created_at
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    #[tracing::instrument(name = "Move the digest cursor", skip(tx))]
+    pub async fn upsert_cursor(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+        last_issue_published_at: &DateTime<Utc>,
+        last_digest_at: &DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO digest_cursors (subscription_id, last_issue_published_at, last_digest_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (subscription_id) DO UPDATE
+                SET last_issue_published_at = EXCLUDED.last_issue_published_at,
+                    last_digest_at = EXCLUDED.last_digest_at
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(last_issue_published_at)
+        .bind(last_digest_at)
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Starts a new period when there was nothing to send.
+    /// Without a cursor yet, no issue was published since joining the lists.
+    #[tracing::instrument(name = "Skip an empty digest", skip(tx))]
+    pub async fn skip_digest(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+        last_digest_at: &DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO digest_cursors (subscription_id, last_issue_published_at, last_digest_at)
+                VALUES ($1, $2, $2)
+                ON CONFLICT (subscription_id) DO UPDATE
+                SET last_digest_at = EXCLUDED.last_digest_at
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(last_digest_at)
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+}
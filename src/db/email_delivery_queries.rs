@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::domain::email_delivery_outcome::EmailDeliveryOutcome;
+use crate::domain::email_template::EmailTemplate;
+
+pub struct EmailDeliveryQueries;
+
+#[derive(Debug)]
+pub struct NewEmailDelivery<'a> {
+    pub subscription_id: Option<&'a Uuid>,
+    pub template: EmailTemplate,
+    pub outcome: EmailDeliveryOutcome,
+    pub provider_message_id: Option<&'a str>,
+    pub http_status: Option<u16>,
+    pub latency_ms: Option<u128>,
+    pub attempt: u32,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct EmailDeliveryRecord {
+    pub template: String,
+    pub outcome: EmailDeliveryOutcome,
+    pub provider_message_id: Option<String>,
+    pub http_status: Option<i32>,
+    pub latency_ms: Option<i32>,
+    pub attempt: i32,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl EmailDeliveryQueries {
+    #[tracing::instrument(name = "Insert email delivery into the database", skip(executor))]
+    pub async fn insert_delivery<'a, E>(
+        executor: E,
+        delivery: &NewEmailDelivery<'_>,
+    ) -> anyhow::Result<Uuid>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let id = Uuid::new_v4();
+        sqlx::query(
+            r#"
+                INSERT INTO email_deliveries (id, subscription_id, template, outcome,
+                                              provider_message_id, http_status, latency_ms,
+                                              attempt, error, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            "#,
+        )
+        .bind(id)
+        .bind(delivery.subscription_id)
+        .bind(delivery.template.as_ref())
+        .bind(delivery.outcome)
+        .bind(delivery.provider_message_id)
+        .bind(delivery.http_status.map(i32::from))
+        .bind(
+            delivery
+                .latency_ms
+                .map(|ms| ms.min(i32::MAX as u128) as i32),
+        )
+        .bind(delivery.attempt as i32)
+        .bind(delivery.error.as_deref())
+        .bind(Utc::now())
+        .execute(executor)
+        .await?;
+        Ok(id)
+    }
+
+    #[tracing::instrument(
+        name = "Fetch email deliveries by subscription id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_deliveries_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<EmailDeliveryRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            EmailDeliveryRecord,
+            r#"
+                SELECT template, outcome AS "outcome: _", provider_message_id, http_status,
+                       latency_ms, attempt, error, created_at
+                FROM email_deliveries
+                WHERE subscription_id = $1
+                ORDER BY created_at
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+
+pub struct EmailEventQueries;
+
+#[derive(Serialize)]
+pub struct EmailEventRecord {
+    pub provider_message_id: Option<String>,
+    pub event_type: String,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl EmailEventQueries {
+    /// Returns `false` if an event with the same provider event id was already stored.
+    #[tracing::instrument(name = "Insert email event into the database", skip(tx, payload))]
+    pub async fn insert_email_event(
+        tx: &mut Tx<'_>,
+        provider_event_id: Option<&str>,
+        provider_message_id: Option<&str>,
+        email: &str,
+        event_type: &str,
+        occurred_at: DateTime<Utc>,
+        payload: &serde_json::Value,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                INSERT INTO email_events (id, provider_event_id, provider_message_id, email,
+                                          event_type, occurred_at, received_at, payload)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (provider_event_id) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(provider_event_id)
+        .bind(provider_message_id)
+        .bind(email)
+        .bind(event_type)
+        .bind(occurred_at)
+        .bind(Utc::now())
+        .bind(payload)
+        .execute(tx)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Fetch email events by email from the database", skip(executor))]
+    pub async fn fetch_email_events_by_email<'a, E>(
+        executor: E,
+        email: &str,
+    ) -> anyhow::Result<Vec<EmailEventRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            EmailEventRecord,
+            r#"
+                SELECT provider_message_id, event_type, occurred_at
+                FROM email_events
+                WHERE email = $1
+                ORDER BY occurred_at
+            "#,
+            email,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    #[tracing::instrument(name = "Delete email events by email from the database", skip(tx))]
+    pub async fn delete_email_events_by_email(tx: &mut Tx<'_>, email: &str) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                DELETE FROM email_events
+                WHERE email = $1
+            "#,
+        )
+        .bind(email)
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,66 @@
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+
+pub struct IssueLinkQueries;
+
+#[derive(Serialize)]
+pub struct IssueLinkRecord {
+    pub id: Uuid,
+    pub position: i32,
+    pub url: String,
+}
+
+impl IssueLinkQueries {
+    /// `urls` in the order they appear in the issue.
+    /// The positions already stored are left alone, so that links can be backfilled.
+    #[tracing::instrument(name = "Insert issue links into the database", skip(tx, urls))]
+    pub async fn insert_links(
+        tx: &mut Tx<'_>,
+        issue_id: &Uuid,
+        urls: &[&str],
+    ) -> anyhow::Result<()> {
+        for (position, url) in urls.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                    INSERT INTO issue_links (id, issue_id, position, url)
+                    VALUES ($1, $2, $3, $4)
+                    ON CONFLICT (issue_id, position) DO NOTHING
+                "#,
+                Uuid::new_v4(),
+                issue_id,
+                position as i32,
+                url,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "Fetch issue link from the database", skip(executor))]
+    pub async fn fetch_link<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        position: i32,
+    ) -> anyhow::Result<Option<IssueLinkRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query_as!(
+            IssueLinkRecord,
+            r#"
+                SELECT id, position, url
+                FROM issue_links
+                WHERE issue_id = $1 AND position = $2
+            "#,
+            issue_id,
+            position,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record)
+    }
+}
@@ -0,0 +1,422 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::segment_queries::{CompiledSegment, SegmentArg};
+use crate::db::types::Tx;
+use crate::domain::ab_test::AbTest;
+use crate::domain::issue_status::IssueStatus;
+use crate::domain::segment::Segment;
+
+pub struct IssueQueries;
+
+#[derive(Serialize, Debug)]
+pub struct IssueRecord {
+    pub id: Uuid,
+    #[serde(skip)]
+    pub list_id: Uuid,
+    pub list: String,
+    #[serde(skip)]
+    pub list_name: String,
+    #[serde(skip)]
+    pub list_from_address: Option<String>,
+    pub list_timezone: String,
+    #[serde(skip)]
+    pub list_tracking_enabled: bool,
+    pub subject: String,
+    pub text_content: String,
+    pub status: IssueStatus,
+    pub send_at_local: Option<NaiveDateTime>,
+    pub per_subscriber_timezone: bool,
+    pub scheduled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub sent_at: Option<DateTime<Utc>>,
+    /// Set for A/B tested subjects, see `SubjectVariantQueries`
+    pub ab_test_audience_percent: Option<i16>,
+    pub ab_test_wait_minutes: Option<i32>,
+    pub winning_variant_id: Option<Uuid>,
+    /// The name of the segment the issue targets, `None` = every member of the list
+    pub segment: Option<String>,
+    #[serde(skip)]
+    pub segment_definition: Option<serde_json::Value>,
+}
+
+/// A member of the list, with what an issue needs to be rendered for them
+#[derive(sqlx::FromRow)]
+pub struct IssueRecipientRecord {
+    pub subscription_id: Uuid,
+    pub email: String,
+    pub name: String,
+    /// `None` = the timezone of the list
+    pub timezone: Option<String>,
+    pub unsubscribe_token: String,
+    pub do_not_track: bool,
+    pub attributes: serde_json::Value,
+}
+
+impl IssueQueries {
+    #[tracing::instrument(name = "Insert issue into the database", skip(tx, text_content))]
+    pub async fn insert_issue(
+        tx: &mut Tx<'_>,
+        list_id: &Uuid,
+        subject: &str,
+        text_content: &str,
+        ab_test: Option<&AbTest>,
+        segment_id: Option<&Uuid>,
+    ) -> anyhow::Result<Uuid> {
+        let id = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+                INSERT INTO issues (id, list_id, subject, text_content, status,
+                                    ab_test_audience_percent, ab_test_wait_minutes,
+                                    segment_id, created_at, updated_at)
+                VALUES ($1, $2, $3, $4, 'draft', $5, $6, $7, $8, $8)
+            "#,
+        )
+        .bind(id)
+        .bind(list_id)
+        .bind(subject)
+        .bind(text_content)
+        .bind(ab_test.map(|ab_test| i16::from(ab_test.audience_percent())))
+        .bind(ab_test.map(|ab_test| ab_test.wait_minutes() as i32))
+        .bind(segment_id)
+        .bind(now)
+        .execute(tx)
+        .await?;
+        Ok(id)
+    }
+
+    #[tracing::instrument(name = "Fetch issues from the database", skip(executor))]
+    pub async fn fetch_issues<'a, E>(executor: E) -> anyhow::Result<Vec<IssueRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            IssueRecord,
+            r#"
+                SELECT i.id, i.list_id, l.slug AS list, l.name AS list_name,
+                       l.from_address AS list_from_address, l.timezone AS list_timezone,
+                       l.tracking_enabled AS list_tracking_enabled,
+                       i.subject, i.text_content, i.status AS "status: _", i.send_at_local,
+                       i.per_subscriber_timezone, i.scheduled_at, i.created_at, i.published_at,
+                       i.sent_at, i.ab_test_audience_percent, i.ab_test_wait_minutes,
+                       i.winning_variant_id, g.name AS "segment?",
+                       g.definition AS "segment_definition?"
+                FROM issues i
+                JOIN lists l ON l.id = i.list_id
+                LEFT JOIN segments g ON g.id = i.segment_id
+                ORDER BY i.created_at DESC
+            "#,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    #[tracing::instrument(name = "Fetch issue from the database", skip(executor))]
+    pub async fn fetch_issue<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+    ) -> anyhow::Result<Option<IssueRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query_as!(
+            IssueRecord,
+            r#"
+                SELECT i.id, i.list_id, l.slug AS list, l.name AS list_name,
+                       l.from_address AS list_from_address, l.timezone AS list_timezone,
+                       l.tracking_enabled AS list_tracking_enabled,
+                       i.subject, i.text_content, i.status AS "status: _", i.send_at_local,
+                       i.per_subscriber_timezone, i.scheduled_at, i.created_at, i.published_at,
+                       i.sent_at, i.ab_test_audience_percent, i.ab_test_wait_minutes,
+                       i.winning_variant_id, g.name AS "segment?",
+                       g.definition AS "segment_definition?"
+                FROM issues i
+                JOIN lists l ON l.id = i.list_id
+                LEFT JOIN segments g ON g.id = i.segment_id
+                WHERE i.id = $1
+            "#,
+            issue_id,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record)
+    }
+
+    /// Locks the issue until the end of the transaction
+    #[tracing::instrument(name = "Fetch issue for update from the database", skip(tx))]
+    pub async fn fetch_issue_for_update(
+        tx: &mut Tx<'_>,
+        issue_id: &Uuid,
+    ) -> anyhow::Result<Option<IssueRecord>> {
+        let record = sqlx::query_as!(
+            IssueRecord,
+            r#"
+                SELECT i.id, i.list_id, l.slug AS list, l.name AS list_name,
+                       l.from_address AS list_from_address, l.timezone AS list_timezone,
+                       l.tracking_enabled AS list_tracking_enabled,
+                       i.subject, i.text_content, i.status AS "status: _", i.send_at_local,
+                       i.per_subscriber_timezone, i.scheduled_at, i.created_at, i.published_at,
+                       i.sent_at, i.ab_test_audience_percent, i.ab_test_wait_minutes,
+                       i.winning_variant_id, g.name AS "segment?",
+                       g.definition AS "segment_definition?"
+                FROM issues i
+                JOIN lists l ON l.id = i.list_id
+                LEFT JOIN segments g ON g.id = i.segment_id
+                WHERE i.id = $1
+                FOR UPDATE OF i
+            "#,
+            issue_id,
+        )
+        .fetch_optional(tx)
+        .await?;
+        Ok(record)
+    }
+
+    /// Scheduled issues whose send time has come, and issues still being sent
+    #[tracing::instrument(name = "Fetch due issues from the database", skip(executor))]
+    pub async fn fetch_due_issues<'a, E>(
+        executor: E,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IssueRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            IssueRecord,
+            r#"
+                SELECT i.id, i.list_id, l.slug AS list, l.name AS list_name,
+                       l.from_address AS list_from_address, l.timezone AS list_timezone,
+                       l.tracking_enabled AS list_tracking_enabled,
+                       i.subject, i.text_content, i.status AS "status: _", i.send_at_local,
+                       i.per_subscriber_timezone, i.scheduled_at, i.created_at, i.published_at,
+                       i.sent_at, i.ab_test_audience_percent, i.ab_test_wait_minutes,
+                       i.winning_variant_id, g.name AS "segment?",
+                       g.definition AS "segment_definition?"
+                FROM issues i
+                JOIN lists l ON l.id = i.list_id
+                LEFT JOIN segments g ON g.id = i.segment_id
+                WHERE i.status IN ('scheduled', 'sending') AND i.scheduled_at <= $1
+                ORDER BY i.scheduled_at, i.created_at
+            "#,
+            now,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Compare-and-set, see `SubscriptionQueries::compare_and_set_status`.
+    #[tracing::instrument(name = "Schedule issue", skip(tx))]
+    pub async fn compare_and_set_schedule(
+        tx: &mut Tx<'_>,
+        issue_id: &Uuid,
+        expected: IssueStatus,
+        send_at_local: &NaiveDateTime,
+        per_subscriber_timezone: bool,
+        scheduled_at: &DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                UPDATE issues
+                SET status = 'scheduled', send_at_local = $1, per_subscriber_timezone = $2,
+                    scheduled_at = $3, updated_at = $4
+                WHERE id = $5 AND status = $6
+            "#,
+        )
+        .bind(send_at_local)
+        .bind(per_subscriber_timezone)
+        .bind(scheduled_at)
+        .bind(Utc::now())
+        .bind(issue_id)
+        .bind(expected)
+        .execute(tx)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Compare-and-set, see `SubscriptionQueries::compare_and_set_status`.
+    /// Callers are expected to validate the transition with `IssueStatus::can_transition_to`.
+    #[tracing::instrument(name = "Compare and set issue status", skip(executor))]
+    pub async fn compare_and_set_status<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        expected: IssueStatus,
+        status: IssueStatus,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let now = Utc::now();
+        let result = sqlx::query(
+            r#"
+                UPDATE issues
+                SET status = $1, updated_at = $2,
+                    sent_at = CASE WHEN $1 = 'sent' :: issue_status THEN $2 ELSE sent_at END
+                WHERE id = $3 AND status = $4
+            "#,
+        )
+        .bind(status)
+        .bind(now)
+        .bind(issue_id)
+        .bind(expected)
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Scheduled -> Sending, unless the issue was rescheduled or cancelled
+    /// since `scheduled_at` was observed. The issue counts as published from now on.
+    #[tracing::instrument(name = "Start sending issue", skip(executor))]
+    pub async fn start_sending<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        scheduled_at: &DateTime<Utc>,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+                UPDATE issues
+                SET status = 'sending', updated_at = $1, published_at = $1
+                WHERE id = $2 AND status = 'scheduled' AND scheduled_at = $3
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(issue_id)
+        .bind(scheduled_at)
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Confirmed members of the list, in the segment if any, who have not been sent the issue yet
+    /// and get every issue right away
+    #[tracing::instrument(name = "Fetch issue recipients from the database", skip(executor))]
+    pub async fn fetch_pending_recipients<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        list_id: &Uuid,
+        segment: Option<&Segment>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<IssueRecipientRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let mut compiled = CompiledSegment::compile_optional(segment, now);
+        let list_id = compiled.push_arg(SegmentArg::Uuid(*list_id));
+        let issue_id = compiled.push_arg(SegmentArg::Uuid(*issue_id));
+        let sql = format!(
+            r#"
+                SELECT s.id AS subscription_id, s.email, s.name, s.timezone, m.unsubscribe_token,
+                       s.do_not_track, s.attributes
+                FROM list_memberships m
+                JOIN subscriptions s ON s.id = m.subscription_id
+                WHERE m.list_id = {}
+                  AND m.status = 'confirmed'
+                  AND s.delivery_cadence = 'immediate'
+                  AND NOT EXISTS (
+                    SELECT 1 FROM issue_deliveries d
+                    WHERE d.issue_id = {} AND d.subscription_id = s.id
+                  )
+                  AND {}
+            "#,
+            list_id, issue_id, compiled.condition
+        );
+        let records = compiled
+            .bind(sqlx::query_as(&sql))
+            .fetch_all(executor)
+            .await?;
+        Ok(records)
+    }
+
+    /// Confirmed, pending or unsubscribed: previews can use any member as a sample
+    #[tracing::instrument(
+        name = "Fetch list member by email from the database",
+        skip(executor, email)
+    )]
+    pub async fn fetch_member_by_email<'a, E>(
+        executor: E,
+        list_id: &Uuid,
+        email: &str,
+    ) -> anyhow::Result<Option<IssueRecipientRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query_as!(
+            IssueRecipientRecord,
+            r#"
+                SELECT s.id AS subscription_id, s.email, s.name, s.timezone, m.unsubscribe_token,
+                       s.do_not_track, s.attributes
+                FROM list_memberships m
+                JOIN subscriptions s ON s.id = m.subscription_id
+                WHERE m.list_id = $1 AND s.email = $2
+            "#,
+            list_id,
+            email,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record)
+    }
+
+    /// Returns `false` if the issue was already claimed for the subscriber
+    #[tracing::instrument(name = "Claim issue delivery", skip(executor))]
+    pub async fn claim_delivery<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        subscription_id: &Uuid,
+        via_digest: bool,
+        variant_id: Option<&Uuid>,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+                INSERT INTO issue_deliveries (issue_id, subscription_id, via_digest, variant_id,
+                                              created_at)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(issue_id)
+        .bind(subscription_id)
+        .bind(via_digest)
+        .bind(variant_id)
+        .bind(Utc::now())
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Compare-and-set: the winner is picked once, even if schedulers race
+    #[tracing::instrument(name = "Set winning subject variant", skip(executor))]
+    pub async fn set_winning_variant<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        variant_id: &Uuid,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query(
+            r#"
+                UPDATE issues
+                SET winning_variant_id = $2, updated_at = $3
+                WHERE id = $1 AND winning_variant_id IS NULL
+            "#,
+        )
+        .bind(issue_id)
+        .bind(variant_id)
+        .bind(Utc::now())
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+}
@@ -0,0 +1,192 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::subscription_status::SubscriptionStatus;
+
+pub struct ListMembershipQueries;
+
+pub struct UpsertedListMembership {
+    pub status: SubscriptionStatus,
+    /// `false` if the subscriber was already a member of the list
+    pub inserted: bool,
+}
+
+pub struct ListMembershipByTokenRecord {
+    pub subscription_id: Uuid,
+    pub list_id: Uuid,
+    pub list_name: String,
+    pub status: SubscriptionStatus,
+}
+
+#[derive(Serialize)]
+pub struct ListMembershipRecord {
+    pub list: String,
+    pub status: SubscriptionStatus,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl ListMembershipQueries {
+    /// Inserts a pending membership, or returns the existing one.
+    /// Either way the row stays locked until the end of the transaction.
+    #[tracing::instrument(name = "Upsert pending list membership", skip(tx))]
+    pub async fn upsert_pending_membership(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+        list_id: &Uuid,
+    ) -> anyhow::Result<UpsertedListMembership> {
+        let now = Utc::now();
+        let record = sqlx::query!(
+            r#"
+                INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                              created_at, updated_at)
+                VALUES ($1, $2, 'pending', $3, $4, $4)
+                ON CONFLICT (subscription_id, list_id) DO UPDATE
+                SET subscription_id = EXCLUDED.subscription_id
+                RETURNING status AS "status: SubscriptionStatus", (xmax = 0) AS "inserted!"
+            "#,
+            subscription_id,
+            list_id,
+            Uuid::new_v4().to_string(),
+            now,
+        )
+        .fetch_one(tx)
+        .await?;
+        Ok(UpsertedListMembership {
+            status: record.status,
+            inserted: record.inserted,
+        })
+    }
+
+    /// For subscriptions inserted in the same transaction, which cannot have memberships yet
+    #[tracing::instrument(name = "Insert confirmed list membership", skip(tx))]
+    pub async fn insert_confirmed_membership(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+        list_id: &Uuid,
+    ) -> anyhow::Result<()> {
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+                INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                              created_at, updated_at)
+                VALUES ($1, $2, 'confirmed', $3, $4, $4)
+            "#,
+        )
+        .bind(subscription_id)
+        .bind(list_id)
+        .bind(Uuid::new_v4().to_string())
+        .bind(now)
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Compare-and-set, see `SubscriptionQueries::compare_and_set_status`.
+    #[tracing::instrument(name = "Compare and set list membership status", skip(tx))]
+    pub async fn compare_and_set_status(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+        list_id: &Uuid,
+        expected: SubscriptionStatus,
+        status: SubscriptionStatus,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                UPDATE list_memberships
+                SET status = $1, updated_at = $2
+                WHERE subscription_id = $3 AND list_id = $4 AND status = $5
+            "#,
+        )
+        .bind(status)
+        .bind(Utc::now())
+        .bind(subscription_id)
+        .bind(list_id)
+        .bind(expected)
+        .execute(tx)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(
+        name = "Fetch list membership status from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_membership_status<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+        list_id: &Uuid,
+    ) -> anyhow::Result<Option<SubscriptionStatus>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query!(
+            r#"
+                SELECT status AS "status: SubscriptionStatus"
+                FROM list_memberships
+                WHERE subscription_id = $1 AND list_id = $2
+            "#,
+            subscription_id,
+            list_id,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record.map(|r| r.status))
+    }
+
+    #[tracing::instrument(
+        name = "Fetch list membership by unsubscribe token from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_membership_by_unsubscribe_token<'a, E>(
+        executor: E,
+        unsubscribe_token: &str,
+    ) -> anyhow::Result<Option<ListMembershipByTokenRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query_as!(
+            ListMembershipByTokenRecord,
+            r#"
+                SELECT m.subscription_id, m.list_id, l.name AS list_name, m.status AS "status: _"
+                FROM list_memberships m
+                JOIN lists l ON l.id = m.list_id
+                WHERE m.unsubscribe_token = $1
+            "#,
+            unsubscribe_token,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record)
+    }
+
+    #[tracing::instrument(
+        name = "Fetch list memberships by subscription id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_memberships_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<ListMembershipRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            ListMembershipRecord,
+            r#"
+                SELECT l.slug AS list, m.status AS "status: _", m.created_at, m.updated_at
+                FROM list_memberships m
+                JOIN lists l ON l.id = m.list_id
+                WHERE m.subscription_id = $1
+                ORDER BY l.slug
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
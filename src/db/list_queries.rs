@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::time_zone::IanaTimeZone;
+
+pub struct ListQueries;
+
+#[derive(Serialize, Debug)]
+pub struct ListRecord {
+    #[serde(skip)]
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub from_address: Option<String>,
+    pub description: String,
+    pub timezone: String,
+    /// Opens and clicks of its issues are tracked
+    pub tracking_enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ListQueries {
+    /// Returns `false` if a list with the same slug already exists.
+    #[tracing::instrument(name = "Insert list into the database", skip(tx))]
+    pub async fn insert_list(
+        tx: &mut Tx<'_>,
+        slug: &ListSlug,
+        name: &str,
+        from_address: Option<&str>,
+        description: &str,
+        timezone: &IanaTimeZone,
+        tracking_enabled: bool,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                INSERT INTO lists (id, slug, name, from_address, description, timezone,
+                                   tracking_enabled, created_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(slug.as_ref())
+        .bind(name)
+        .bind(from_address)
+        .bind(description)
+        .bind(timezone.name())
+        .bind(tracking_enabled)
+        .bind(Utc::now())
+        .execute(tx)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(name = "Fetch lists from the database", skip(executor))]
+    pub async fn fetch_lists<'a, E>(executor: E) -> anyhow::Result<Vec<ListRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            ListRecord,
+            r#"
+                SELECT id, slug, name, from_address, description, timezone, tracking_enabled,
+                       created_at
+                FROM lists
+                ORDER BY slug
+            "#,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Unknown slugs are not reported, compare the lengths to detect them.
+    #[tracing::instrument(name = "Fetch lists by slugs from the database", skip(executor))]
+    pub async fn fetch_lists_by_slugs<'a, E>(
+        executor: E,
+        slugs: &[ListSlug],
+    ) -> anyhow::Result<Vec<ListRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let slugs: Vec<String> = slugs.iter().map(|slug| slug.as_ref().to_owned()).collect();
+        let records = sqlx::query_as!(
+            ListRecord,
+            r#"
+                SELECT id, slug, name, from_address, description, timezone, tracking_enabled,
+                       created_at
+                FROM lists
+                WHERE slug = ANY($1)
+                ORDER BY slug
+            "#,
+            &slugs,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
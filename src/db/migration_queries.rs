@@ -0,0 +1,18 @@
+use sqlx::{Executor, Postgres};
+
+pub struct MigrationQueries;
+
+impl MigrationQueries {
+    /// `_sqlx_migrations` is only created by the first migration run
+    #[tracing::instrument(name = "Check the migrations table in the database", skip(executor))]
+    pub async fn migrations_table_exists<'a, E>(executor: E) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record =
+            sqlx::query!(r#"SELECT to_regclass('_sqlx_migrations') IS NOT NULL AS "exists!""#)
+                .fetch_one(executor)
+                .await?;
+        Ok(record.exists)
+    }
+}
@@ -1,3 +1,22 @@
+pub mod advisory_lock_queries;
+pub mod attribute_definition_queries;
+pub mod data_request_queries;
+pub mod dead_letter_queries;
+pub mod digest_queries;
+pub mod email_delivery_queries;
+pub mod email_event_queries;
+pub mod issue_link_queries;
+pub mod issue_queries;
+pub mod list_membership_queries;
+pub mod list_queries;
+pub mod migration_queries;
+pub mod processed_event_queries;
+pub mod segment_queries;
+pub mod subject_variant_queries;
+pub mod subscription_event_queries;
 pub mod subscription_queries;
+pub mod suppression_queries;
+pub mod tag_queries;
+pub mod tracking_event_queries;
 pub mod transaction;
 pub mod types;
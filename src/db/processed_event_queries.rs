@@ -0,0 +1,157 @@
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+pub struct ProcessedEventQueries;
+
+#[derive(Debug, PartialEq)]
+pub enum EventClaim {
+    /// The delivery handles the event until the lease expires
+    Claimed,
+    /// Another delivery is handling the event
+    InProgress,
+    AlreadyProcessed,
+}
+
+impl ProcessedEventQueries {
+    /// Claims the event for `consumer` until the lease expires, or takes over an expired claim.
+    /// Returns `AlreadyProcessed` if `consumer` already processed the event, counting the duplicate.
+    #[tracing::instrument(name = "Claim event in the database", skip(executor))]
+    pub async fn claim_event<'a, E>(
+        executor: E,
+        consumer: &str,
+        event_id: &Uuid,
+        lease: Duration,
+    ) -> anyhow::Result<EventClaim>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let now = Utc::now();
+        // Postgres keeps microseconds: the returned lease is compared with this one
+        let lease_expires_at = (now + lease).trunc_subsecs(6);
+        let record = sqlx::query!(
+            r#"
+                INSERT INTO processed_events (consumer, event_id, processed_at, lease_expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (consumer, event_id) DO UPDATE
+                SET processed_at = CASE WHEN processed_events.lease_expires_at < $3
+                                        THEN EXCLUDED.processed_at
+                                        ELSE processed_events.processed_at END,
+                    lease_expires_at = CASE WHEN processed_events.lease_expires_at < $3
+                                            THEN EXCLUDED.lease_expires_at
+                                            ELSE processed_events.lease_expires_at END,
+                    duplicates = CASE WHEN processed_events.lease_expires_at IS NULL
+                                      THEN processed_events.duplicates + 1
+                                      ELSE processed_events.duplicates END
+                RETURNING lease_expires_at
+            "#,
+            consumer,
+            event_id,
+            now,
+            lease_expires_at,
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(match record.lease_expires_at {
+            None => EventClaim::AlreadyProcessed,
+            Some(returned) if returned == lease_expires_at => EventClaim::Claimed,
+            Some(_) => EventClaim::InProgress,
+        })
+    }
+
+    /// Ends the claim: redeliveries of the event are skipped from now on
+    #[tracing::instrument(name = "Record processed event in the database", skip(executor))]
+    pub async fn complete_event<'a, E>(
+        executor: E,
+        consumer: &str,
+        event_id: &Uuid,
+    ) -> anyhow::Result<()>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        sqlx::query!(
+            r#"
+                UPDATE processed_events
+                SET processed_at = $3, lease_expires_at = NULL
+                WHERE consumer = $1 AND event_id = $2
+            "#,
+            consumer,
+            event_id,
+            Utc::now(),
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    /// Drops the claim after a failure, so that the next delivery does not wait for the lease
+    #[tracing::instrument(name = "Release event claim in the database", skip(executor))]
+    pub async fn release_event<'a, E>(
+        executor: E,
+        consumer: &str,
+        event_id: &Uuid,
+    ) -> anyhow::Result<()>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        sqlx::query!(
+            r#"
+                DELETE FROM processed_events
+                WHERE consumer = $1 AND event_id = $2 AND lease_expires_at IS NOT NULL
+            "#,
+            consumer,
+            event_id,
+        )
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    /// Processed, or claimed by a delivery whose lease has not expired
+    #[tracing::instrument(name = "Check event claim in the database", skip(executor))]
+    pub async fn is_claimed<'a, E>(
+        executor: E,
+        consumer: &str,
+        event_id: &Uuid,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query!(
+            r#"
+                SELECT EXISTS (
+                    SELECT 1 FROM processed_events
+                    WHERE consumer = $1 AND event_id = $2
+                      AND (lease_expires_at IS NULL OR lease_expires_at > $3)
+                ) AS "claimed!"
+            "#,
+            consumer,
+            event_id,
+            Utc::now(),
+        )
+        .fetch_one(executor)
+        .await?;
+        Ok(record.claimed)
+    }
+
+    /// Returns the number of deleted events
+    #[tracing::instrument(name = "Delete old processed events from the database", skip(executor))]
+    pub async fn delete_processed_events_before<'a, E>(
+        executor: E,
+        before: DateTime<Utc>,
+    ) -> anyhow::Result<u64>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query!(
+            r#"
+                DELETE FROM processed_events
+                WHERE processed_at < $1
+            "#,
+            before,
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,306 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::postgres::PgArguments;
+use sqlx::query::QueryAs;
+use sqlx::types::Json;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::segment::Segment;
+use crate::domain::subscription_status::SubscriptionStatus;
+
+pub struct SegmentQueries;
+
+#[derive(Serialize)]
+pub struct SegmentRecord {
+    #[serde(skip)]
+    pub id: Uuid,
+    pub name: String,
+    pub definition: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(sqlx::FromRow, Serialize)]
+pub struct SegmentMemberRecord {
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+/// A value bound to a placeholder of a compiled segment
+pub(crate) enum SegmentArg {
+    Text(String),
+    Integer(i64),
+    Timestamp(DateTime<Utc>),
+    Status(SubscriptionStatus),
+    Uuid(Uuid),
+}
+
+/// The condition of a segment over `subscriptions s`, with its placeholders' values
+pub(crate) struct CompiledSegment {
+    pub(crate) condition: String,
+    args: Vec<SegmentArg>,
+}
+
+impl CompiledSegment {
+    pub(crate) fn compile(segment: &Segment, now: DateTime<Utc>) -> Self {
+        let mut compiled = Self {
+            condition: String::new(),
+            args: vec![],
+        };
+        compiled.condition = compiled.condition_of(segment, now);
+        compiled
+    }
+
+    /// Every subscriber without a segment
+    pub(crate) fn compile_optional(segment: Option<&Segment>, now: DateTime<Utc>) -> Self {
+        match segment {
+            Some(segment) => Self::compile(segment, now),
+            None => Self {
+                condition: "TRUE".to_owned(),
+                args: vec![],
+            },
+        }
+    }
+
+    /// Returns the placeholder of the value
+    pub(crate) fn push_arg(&mut self, arg: SegmentArg) -> String {
+        self.args.push(arg);
+        format!("${}", self.args.len())
+    }
+
+    fn condition_of(&mut self, segment: &Segment, now: DateTime<Utc>) -> String {
+        match segment {
+            Segment::All(segments) => self.join(segments, " AND ", now),
+            Segment::Any(segments) => self.join(segments, " OR ", now),
+            Segment::Not(segment) => format!("(NOT {})", self.condition_of(segment, now)),
+            Segment::Status(status) => {
+                format!("s.status = {}", self.push_arg(SegmentArg::Status(*status)))
+            }
+            Segment::SubscribedWithinDays(days) => {
+                let since = now - Duration::days((*days).into());
+                format!(
+                    "s.subscribed_at >= {}",
+                    self.push_arg(SegmentArg::Timestamp(since))
+                )
+            }
+            Segment::SubscribedBefore(date) => {
+                let midnight = DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc);
+                format!(
+                    "s.subscribed_at < {}",
+                    self.push_arg(SegmentArg::Timestamp(midnight))
+                )
+            }
+            Segment::SubscribedSince(date) => {
+                let midnight = DateTime::<Utc>::from_utc(date.and_hms(0, 0, 0), Utc);
+                format!(
+                    "s.subscribed_at >= {}",
+                    self.push_arg(SegmentArg::Timestamp(midnight))
+                )
+            }
+            Segment::EmailDomain(domain) => format!(
+                "lower(split_part(s.email, '@', 2)) = {}",
+                self.push_arg(SegmentArg::Text(domain.to_lowercase()))
+            ),
+            Segment::NameMatches(pattern) => format!(
+                r"s.name ILIKE {} ESCAPE '\'",
+                self.push_arg(SegmentArg::Text(like_pattern(pattern)))
+            ),
+            Segment::MemberOf(slug) => format!(
+                r#"EXISTS (
+                    SELECT 1 FROM list_memberships m
+                    JOIN lists l ON l.id = m.list_id
+                    WHERE m.subscription_id = s.id AND m.status = 'confirmed' AND l.slug = {}
+                )"#,
+                self.push_arg(SegmentArg::Text(slug.as_ref().to_owned()))
+            ),
+            Segment::Tagged(tag) => format!(
+                r#"EXISTS (
+                    SELECT 1 FROM subscriber_tags st
+                    JOIN tags t ON t.id = st.tag_id
+                    WHERE st.subscription_id = s.id AND t.name = {}
+                )"#,
+                self.push_arg(SegmentArg::Text(tag.as_ref().to_owned()))
+            ),
+        }
+    }
+
+    fn join(&mut self, segments: &[Segment], operator: &str, now: DateTime<Utc>) -> String {
+        let conditions: Vec<String> = segments
+            .iter()
+            .map(|segment| self.condition_of(segment, now))
+            .collect();
+        format!("({})", conditions.join(operator))
+    }
+
+    pub(crate) fn bind<'q, O>(
+        self,
+        mut query: QueryAs<'q, Postgres, O, PgArguments>,
+    ) -> QueryAs<'q, Postgres, O, PgArguments> {
+        for arg in self.args {
+            query = match arg {
+                SegmentArg::Text(text) => query.bind(text),
+                SegmentArg::Integer(integer) => query.bind(integer),
+                SegmentArg::Timestamp(timestamp) => query.bind(timestamp),
+                SegmentArg::Status(status) => query.bind(status),
+                SegmentArg::Uuid(uuid) => query.bind(uuid),
+            };
+        }
+        query
+    }
+}
+
+/// `*` matches any characters, everything else is literal
+fn like_pattern(pattern: &str) -> String {
+    pattern
+        .replace('\\', r"\\")
+        .replace('%', r"\%")
+        .replace('_', r"\_")
+        .replace('*', "%")
+}
+
+impl SegmentQueries {
+    /// Returns `None` if a segment with the same name already exists.
+    #[tracing::instrument(name = "Insert segment into the database", skip(tx))]
+    pub async fn insert_segment(
+        tx: &mut Tx<'_>,
+        name: &str,
+        segment: &Segment,
+    ) -> anyhow::Result<Option<Uuid>> {
+        let id = Uuid::new_v4();
+        let result = sqlx::query(
+            r#"
+                INSERT INTO segments (id, name, definition, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(id)
+        .bind(name)
+        .bind(Json(segment))
+        .bind(Utc::now())
+        .execute(tx)
+        .await?;
+        if result.rows_affected() == 1 {
+            Ok(Some(id))
+        } else {
+            Ok(None)
+        }
+    }
+
+    #[tracing::instrument(name = "Fetch segments from the database", skip(executor))]
+    pub async fn fetch_segments<'a, E>(executor: E) -> anyhow::Result<Vec<SegmentRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SegmentRecord,
+            r#"
+                SELECT id, name, definition, created_at
+                FROM segments
+                ORDER BY name
+            "#,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    #[tracing::instrument(name = "Fetch segment by name from the database", skip(executor))]
+    pub async fn fetch_segment_by_name<'a, E>(
+        executor: E,
+        name: &str,
+    ) -> anyhow::Result<Option<SegmentRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query_as!(
+            SegmentRecord,
+            r#"
+                SELECT id, name, definition, created_at
+                FROM segments
+                WHERE name = $1
+            "#,
+            name,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record)
+    }
+
+    #[tracing::instrument(name = "Count segment members in the database", skip(executor))]
+    pub async fn count_members<'a, E>(
+        executor: E,
+        segment: &Segment,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<i64>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let compiled = CompiledSegment::compile(segment, now);
+        let sql = format!(
+            "SELECT COUNT(*) FROM subscriptions s WHERE {}",
+            compiled.condition
+        );
+        let (count,): (i64,) = compiled
+            .bind(sqlx::query_as(&sql))
+            .fetch_one(executor)
+            .await?;
+        Ok(count)
+    }
+
+    #[tracing::instrument(name = "Check segment membership in the database", skip(executor))]
+    pub async fn contains<'a, E>(
+        executor: E,
+        segment: &Segment,
+        now: DateTime<Utc>,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let mut compiled = CompiledSegment::compile(segment, now);
+        let subscription_id = compiled.push_arg(SegmentArg::Uuid(*subscription_id));
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM subscriptions s WHERE s.id = {} AND {})",
+            subscription_id, compiled.condition
+        );
+        let (contains,): (bool,) = compiled
+            .bind(sqlx::query_as(&sql))
+            .fetch_one(executor)
+            .await?;
+        Ok(contains)
+    }
+
+    /// The most recent subscribers of the segment
+    #[tracing::instrument(name = "Fetch segment members from the database", skip(executor))]
+    pub async fn fetch_members<'a, E>(
+        executor: E,
+        segment: &Segment,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> anyhow::Result<Vec<SegmentMemberRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let mut compiled = CompiledSegment::compile(segment, now);
+        let limit = compiled.push_arg(SegmentArg::Integer(limit.into()));
+        let sql = format!(
+            r#"
+                SELECT s.email, s.name, s.status, s.subscribed_at
+                FROM subscriptions s
+                WHERE {}
+                ORDER BY s.subscribed_at DESC, s.email
+                LIMIT {}
+            "#,
+            compiled.condition, limit
+        );
+        let records = compiled
+            .bind(sqlx::query_as(&sql))
+            .fetch_all(executor)
+            .await?;
+        Ok(records)
+    }
+}
@@ -0,0 +1,110 @@
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+
+pub struct SubjectVariantQueries;
+
+pub struct SubjectVariantRecord {
+    pub id: Uuid,
+    pub position: i16,
+    pub subject: String,
+}
+
+/// Unique opens and clicks of the subscribers who got the variant
+#[derive(Serialize)]
+pub struct SubjectVariantStatsRecord {
+    pub id: Uuid,
+    pub position: i16,
+    pub subject: String,
+    pub sent: i64,
+    pub opens: i64,
+    pub clicks: i64,
+}
+
+impl SubjectVariantQueries {
+    #[tracing::instrument(name = "Insert subject variants into the database", skip(tx))]
+    pub async fn insert_variants(
+        tx: &mut Tx<'_>,
+        issue_id: &Uuid,
+        subjects: &[String],
+    ) -> anyhow::Result<()> {
+        for (position, subject) in subjects.iter().enumerate() {
+            sqlx::query!(
+                r#"
+                    INSERT INTO issue_subject_variants (id, issue_id, position, subject)
+                    VALUES ($1, $2, $3, $4)
+                "#,
+                Uuid::new_v4(),
+                issue_id,
+                position as i16,
+                subject,
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Ordered by position
+    #[tracing::instrument(name = "Fetch subject variants from the database", skip(executor))]
+    pub async fn fetch_variants<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+    ) -> anyhow::Result<Vec<SubjectVariantRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SubjectVariantRecord,
+            r#"
+                SELECT id, position, subject
+                FROM issue_subject_variants
+                WHERE issue_id = $1
+                ORDER BY position
+            "#,
+            issue_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Ordered by position
+    #[tracing::instrument(name = "Fetch subject variant stats from the database", skip(executor))]
+    pub async fn fetch_variant_stats<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+    ) -> anyhow::Result<Vec<SubjectVariantStatsRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SubjectVariantStatsRecord,
+            r#"
+                SELECT v.id, v.position, v.subject,
+                       COUNT(d.subscription_id) AS "sent!",
+                       COUNT(d.subscription_id) FILTER (WHERE EXISTS (
+                         SELECT 1 FROM issue_tracking_events e
+                         WHERE e.issue_id = v.issue_id AND e.subscription_id = d.subscription_id
+                           AND e.kind = 'open'
+                       )) AS "opens!",
+                       COUNT(d.subscription_id) FILTER (WHERE EXISTS (
+                         SELECT 1 FROM issue_tracking_events e
+                         WHERE e.issue_id = v.issue_id AND e.subscription_id = d.subscription_id
+                           AND e.kind = 'click'
+                       )) AS "clicks!"
+                FROM issue_subject_variants v
+                LEFT JOIN issue_deliveries d ON d.variant_id = v.id
+                WHERE v.issue_id = $1
+                GROUP BY v.id
+                ORDER BY v.position
+            "#,
+            issue_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
@@ -0,0 +1,98 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::SubscriptionStatus;
+
+pub struct SubscriptionEventQueries;
+
+#[derive(Debug)]
+pub struct NewSubscriptionEvent<'a> {
+    pub subscription_id: &'a Uuid,
+    /// `None` for transitions of the subscription itself
+    pub list_id: Option<&'a Uuid>,
+    pub old_status: Option<SubscriptionStatus>,
+    pub new_status: SubscriptionStatus,
+    pub source: SubscriptionEventSource,
+    pub context: &'a RequestContext,
+    pub token: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+pub struct SubscriptionEventRecord {
+    /// Slug of the list, `None` for transitions of the subscription itself
+    pub list: Option<String>,
+    pub old_status: Option<SubscriptionStatus>,
+    pub new_status: SubscriptionStatus,
+    pub source: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub origin: Option<String>,
+    pub token: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl SubscriptionEventQueries {
+    /// Should be called in the same transaction as the status update it records.
+    #[tracing::instrument(name = "Insert subscription event into the database", skip(tx))]
+    pub async fn insert_event(
+        tx: &mut Tx<'_>,
+        event: &NewSubscriptionEvent<'_>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO subscription_events (id, subscription_id, list_id, old_status,
+                                                 new_status, source, ip_address, user_agent,
+                                                 origin, token, occurred_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(event.subscription_id)
+        .bind(event.list_id)
+        .bind(event.old_status)
+        .bind(event.new_status)
+        .bind(event.source.as_ref())
+        .bind(event.context.ip_address.as_deref())
+        .bind(event.context.user_agent.as_deref())
+        .bind(event.context.origin.as_deref())
+        .bind(event.token)
+        .bind(Utc::now())
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(
+        name = "Fetch subscription events by subscription id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_events_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<SubscriptionEventRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SubscriptionEventRecord,
+            r#"
+                SELECT l.slug AS "list?", e.old_status AS "old_status: _",
+                       e.new_status AS "new_status: _", e.source, e.ip_address, e.user_agent,
+                       e.origin, e.token, e.occurred_at
+                FROM subscription_events e
+                LEFT JOIN lists l ON l.id = e.list_id
+                WHERE e.subscription_id = $1
+                ORDER BY e.occurred_at
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
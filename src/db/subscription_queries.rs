@@ -1,19 +1,62 @@
 use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::types::Json;
 use sqlx::{Executor, Postgres};
 use uuid::Uuid;
 
 use crate::db::types::Tx;
+use crate::domain::delivery_cadence::DeliveryCadence;
 use crate::domain::new_subscriber::NewSubscriber;
+use crate::domain::preferred_language::PreferredLanguage;
+use crate::domain::subscriber_name::SubscriberName;
 use crate::domain::subscription_status::SubscriptionStatus;
+use crate::domain::time_zone::IanaTimeZone;
 
 pub struct SubscriptionQueries;
 
+#[derive(Serialize)]
 pub struct SubscriptionRecord {
     pub id: Uuid,
     pub email: String,
     pub name: String,
     pub status: SubscriptionStatus,
     pub subscribed_at: DateTime<Utc>,
+    pub delivery_cadence: DeliveryCadence,
+    pub preferred_language: String,
+    /// `None` = the timezone of each list
+    pub timezone: Option<String>,
+    /// Opens and clicks are not tracked
+    pub do_not_track: bool,
+    /// Validated against the `AttributeSchema` when they were set
+    pub attributes: serde_json::Value,
+}
+
+/// A row of the admin subscriber listing
+#[derive(Serialize)]
+pub struct SubscriberSummaryRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+    pub tags: Vec<String>,
+}
+
+pub struct SubscriberRefRecord {
+    pub id: Uuid,
+    pub email: String,
+}
+
+pub struct SubscriptionTokenRecord {
+    pub subscriber_id: Uuid,
+    pub list_id: Uuid,
+}
+
+pub struct UpsertedSubscription {
+    pub id: Uuid,
+    pub status: SubscriptionStatus,
+    /// `false` if the subscription already existed
+    pub inserted: bool,
 }
 
 impl SubscriptionQueries {
@@ -26,9 +69,8 @@ impl SubscriptionQueries {
         let id = Uuid::new_v4();
         sqlx::query(
             r#"
-                INSERT INTO subscriptions (id, email, name, status, subscribed_at)
-                VALUES ($1, $2, $3, $4, $5)
-                ON CONFLICT DO NOTHING
+                INSERT INTO subscriptions (id, email, name, status, subscribed_at, attributes)
+                VALUES ($1, $2, $3, $4, $5, $6)
             "#,
         )
         .bind(id)
@@ -36,64 +78,133 @@ impl SubscriptionQueries {
         .bind(new_subscriber.name.as_ref())
         .bind(status)
         .bind(Utc::now())
+        .bind(Json(&new_subscriber.attributes))
         .execute(tx)
         .await?;
         Ok(id)
     }
 
-    #[tracing::instrument(name = "Update subscription status", skip(tx))]
-    pub async fn update_subscription_status(
+    /// Inserts a confirmed subscription.
+    /// Returns `None` if the email is already subscribed: imports never override a subscriber.
+    #[tracing::instrument(name = "Insert imported subscription", skip(tx))]
+    pub async fn insert_imported_subscriber(
+        tx: &mut Tx<'_>,
+        new_subscriber: &NewSubscriber,
+    ) -> anyhow::Result<Option<Uuid>> {
+        let id = Uuid::new_v4();
+        let result = sqlx::query(
+            r#"
+                INSERT INTO subscriptions (id, email, name, status, subscribed_at, attributes)
+                VALUES ($1, $2, $3, 'confirmed', $4, $5)
+                ON CONFLICT (email) DO NOTHING
+            "#,
+        )
+        .bind(id)
+        .bind(new_subscriber.email.as_ref())
+        .bind(new_subscriber.name.as_ref())
+        .bind(Utc::now())
+        .bind(Json(&new_subscriber.attributes))
+        .execute(tx)
+        .await?;
+        if result.rows_affected() == 1 {
+            Ok(Some(id))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Inserts a pending subscription, or returns the existing one for the email.
+    /// Either way the row stays locked until the end of the transaction,
+    /// so concurrent sign-ups for the same email are serialized.
+    /// Like the name, the attributes of an existing subscription are left alone:
+    /// anyone can submit the form with someone else's email.
+    #[tracing::instrument(name = "Upsert pending subscription", skip(tx))]
+    pub async fn upsert_pending_subscriber(
+        tx: &mut Tx<'_>,
+        new_subscriber: &NewSubscriber,
+    ) -> anyhow::Result<UpsertedSubscription> {
+        let record = sqlx::query!(
+            r#"
+                INSERT INTO subscriptions (id, email, name, status, subscribed_at, attributes)
+                VALUES ($1, $2, $3, 'pending', $4, $5)
+                ON CONFLICT (email) DO UPDATE
+                SET email = EXCLUDED.email
+                RETURNING id, status AS "status: SubscriptionStatus", (xmax = 0) AS "inserted!"
+            "#,
+            Uuid::new_v4(),
+            new_subscriber.email.as_ref(),
+            new_subscriber.name.as_ref(),
+            Utc::now(),
+            Json(&new_subscriber.attributes) as _,
+        )
+        .fetch_one(tx)
+        .await?;
+        Ok(UpsertedSubscription {
+            id: record.id,
+            status: record.status,
+            inserted: record.inserted,
+        })
+    }
+
+    /// Compare-and-set: only updates the subscription if its status is still `expected`.
+    /// Returns `false` if a concurrent change won the race (or the subscription is gone).
+    /// Callers are expected to validate the transition with `SubscriptionStatus::transition_to`.
+    #[tracing::instrument(name = "Compare and set subscription status", skip(tx))]
+    pub async fn compare_and_set_status(
         tx: &mut Tx<'_>,
         subscription_id: &Uuid,
+        expected: SubscriptionStatus,
         status: SubscriptionStatus,
-    ) -> anyhow::Result<()> {
-        sqlx::query(
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
             r#"
-                UPDATE subscriptions 
+                UPDATE subscriptions
                 SET status = $1
-                WHERE id = $2
+                WHERE id = $2 AND status = $3
             "#,
         )
         .bind(status)
         .bind(subscription_id)
+        .bind(expected)
         .execute(tx)
         .await?;
-        Ok(())
+        Ok(result.rows_affected() == 1)
     }
 
+    /// Confirmation tokens are scoped to a single list
     #[tracing::instrument(name = "Store subscription token in the database", skip(tx))]
     pub async fn store_token(
         tx: &mut Tx<'_>,
         subscriber_id: &Uuid,
+        list_id: &Uuid,
         subscription_token: &Uuid,
     ) -> anyhow::Result<()> {
         sqlx::query(
             r#"
-                INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-                VALUES ($1, $2)
+                INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+                VALUES ($1, $2, $3)
             "#,
         )
         .bind(subscription_token.to_string().as_str())
         .bind(subscriber_id)
+        .bind(list_id)
         .execute(tx)
         .await?;
         Ok(())
     }
 
-    #[tracing::instrument(
-        name = "Fetch subscription id by token from the database",
-        skip(executor)
-    )]
-    pub async fn fetch_subscription_id_by_token<'a, E>(
+    #[tracing::instrument(name = "Fetch subscription token from the database", skip(executor))]
+    pub async fn fetch_token<'a, E>(
         executor: E,
         subscription_token: &str,
-    ) -> anyhow::Result<Option<Uuid>>
+    ) -> anyhow::Result<Option<SubscriptionTokenRecord>>
     where
         E: Executor<'a, Database = Postgres>,
     {
-        let result = sqlx::query!(
+        let record = sqlx::query_as!(
+            SubscriptionTokenRecord,
             r#"
-                SELECT subscriber_id 
+                SELECT subscriber_id, list_id
                 FROM subscription_tokens 
                 WHERE subscription_token = $1
             "#,
@@ -101,7 +212,7 @@ impl SubscriptionQueries {
         )
         .fetch_optional(executor)
         .await?;
-        Ok(result.map(|r| r.subscriber_id))
+        Ok(record)
     }
 
     #[tracing::instrument(name = "Delete subscription token from the database", skip(tx))]
@@ -132,8 +243,10 @@ impl SubscriptionQueries {
         let maybe_record = sqlx::query_as!(
             SubscriptionRecord,
             r#"
-                SELECT id, email, name, subscribed_at, status AS "status: _" 
-                FROM subscriptions 
+                SELECT id, email, name, subscribed_at, status AS "status: _",
+                       delivery_cadence AS "delivery_cadence: _", preferred_language,
+                       timezone, do_not_track, attributes
+                FROM subscriptions
                 WHERE email = $1
             "#,
             email,
@@ -142,4 +255,211 @@ impl SubscriptionQueries {
         .await?;
         Ok(maybe_record)
     }
+
+    #[tracing::instrument(
+        name = "Fetching a subscription by id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_subscription_by_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Option<SubscriptionRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let maybe_record = sqlx::query_as!(
+            SubscriptionRecord,
+            r#"
+                SELECT id, email, name, subscribed_at, status AS "status: _",
+                       delivery_cadence AS "delivery_cadence: _", preferred_language,
+                       timezone, do_not_track, attributes
+                FROM subscriptions
+                WHERE id = $1
+            "#,
+            subscription_id,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(maybe_record)
+    }
+
+    /// Subscriptions matching any of the ids or emails, the unknown ones are left out
+    #[tracing::instrument(
+        name = "Fetching subscriptions by ids or emails from the database",
+        skip(executor, ids, emails)
+    )]
+    pub async fn fetch_subscriptions_by_ids_or_emails<'a, E>(
+        executor: E,
+        ids: &[Uuid],
+        emails: &[String],
+    ) -> anyhow::Result<Vec<SubscriberRefRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SubscriberRefRecord,
+            r#"
+                SELECT id, email
+                FROM subscriptions
+                WHERE id = ANY($1) OR email = ANY($2)
+            "#,
+            ids,
+            emails,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Most recent subscribers first, optionally only those with the tag
+    #[tracing::instrument(
+        name = "Fetching a page of subscriptions from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_subscriptions_page<'a, E>(
+        executor: E,
+        tag: Option<&str>,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<SubscriberSummaryRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SubscriberSummaryRecord,
+            r#"
+                SELECT s.id, s.email, s.name, s.status AS "status: _", s.subscribed_at,
+                       COALESCE(
+                           array_agg(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL),
+                           '{}'
+                       ) AS "tags!"
+                FROM subscriptions s
+                LEFT JOIN subscriber_tags st ON st.subscription_id = s.id
+                LEFT JOIN tags t ON t.id = st.tag_id
+                WHERE $1::TEXT IS NULL OR EXISTS (
+                    SELECT 1 FROM subscriber_tags fst
+                    JOIN tags ft ON ft.id = fst.tag_id
+                    WHERE fst.subscription_id = s.id AND ft.name = $1
+                )
+                GROUP BY s.id
+                ORDER BY s.subscribed_at DESC, s.email
+                LIMIT $2 OFFSET $3
+            "#,
+            tag,
+            limit,
+            offset,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Locks the subscription until the end of the transaction,
+    /// so concurrent preference changes are applied one after the other.
+    #[tracing::instrument(
+        name = "Fetching a subscription by preferences token from the database",
+        skip(tx)
+    )]
+    pub async fn fetch_subscription_by_preferences_token(
+        tx: &mut Tx<'_>,
+        preferences_token: &str,
+    ) -> anyhow::Result<Option<SubscriptionRecord>> {
+        let maybe_record = sqlx::query_as!(
+            SubscriptionRecord,
+            r#"
+                SELECT id, email, name, subscribed_at, status AS "status: _",
+                       delivery_cadence AS "delivery_cadence: _", preferred_language,
+                       timezone, do_not_track, attributes
+                FROM subscriptions
+                WHERE preferences_token = $1
+                FOR UPDATE
+            "#,
+            preferences_token,
+        )
+        .fetch_optional(tx)
+        .await?;
+        Ok(maybe_record)
+    }
+
+    #[tracing::instrument(
+        name = "Fetching the preferences token of a subscription from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_preferences_token<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Option<String>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query!(
+            r#"
+                SELECT preferences_token
+                FROM subscriptions
+                WHERE id = $1
+            "#,
+            subscription_id,
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record.map(|r| r.preferences_token))
+    }
+
+    #[tracing::instrument(name = "Update subscription preferences", skip(tx))]
+    pub async fn update_preferences(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+        name: &SubscriberName,
+        delivery_cadence: DeliveryCadence,
+        preferred_language: &PreferredLanguage,
+        timezone: Option<&IanaTimeZone>,
+        do_not_track: bool,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                UPDATE subscriptions
+                SET name = $1, delivery_cadence = $2, preferred_language = $3, timezone = $4,
+                    do_not_track = $5
+                WHERE id = $6
+            "#,
+        )
+        .bind(name.as_ref())
+        .bind(delivery_cadence)
+        .bind(preferred_language.as_ref())
+        .bind(timezone.map(|timezone| timezone.name()))
+        .bind(do_not_track)
+        .bind(subscription_id)
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the subscription together with everything that references it.
+    #[tracing::instrument(name = "Delete subscription from the database", skip(tx))]
+    pub async fn delete_subscription(
+        tx: &mut Tx<'_>,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                DELETE FROM subscription_tokens
+                WHERE subscriber_id = $1
+            "#,
+        )
+        .bind(subscription_id)
+        .execute(&mut *tx)
+        .await?;
+        // list_memberships, data_request_tokens, email_deliveries, subscription_events
+        // and subscriber_tags are removed by ON DELETE CASCADE
+        sqlx::query(
+            r#"
+                DELETE FROM subscriptions
+                WHERE id = $1
+            "#,
+        )
+        .bind(subscription_id)
+        .execute(&mut *tx)
+        .await?;
+        Ok(())
+    }
 }
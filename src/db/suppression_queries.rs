@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+
+use crate::db::types::Tx;
+use crate::domain::suppression_reason::SuppressionReason;
+
+pub struct SuppressionQueries;
+
+#[derive(Serialize)]
+pub struct SuppressionRecord {
+    pub email_hash: String,
+    pub reason: SuppressionReason,
+    pub source: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SuppressionQueries {
+    /// An already suppressed email keeps its original reason and source.
+    #[tracing::instrument(name = "Insert suppression into the database", skip(tx))]
+    pub async fn insert_suppression(
+        tx: &mut Tx<'_>,
+        email_hash: &str,
+        reason: SuppressionReason,
+        source: &str,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+                INSERT INTO suppressions (email_hash, reason, source, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(email_hash)
+        .bind(reason)
+        .bind(source)
+        .bind(Utc::now())
+        .execute(tx)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns `false` if there was nothing to delete.
+    #[tracing::instrument(name = "Delete suppression from the database", skip(tx))]
+    pub async fn delete_suppression(tx: &mut Tx<'_>, email_hash: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+                DELETE FROM suppressions
+                WHERE email_hash = $1
+            "#,
+        )
+        .bind(email_hash)
+        .execute(tx)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    #[tracing::instrument(name = "Check if an email is suppressed", skip(executor))]
+    pub async fn is_suppressed<'a, E>(executor: E, email_hash: &str) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query!(
+            r#"
+                SELECT email_hash
+                FROM suppressions
+                WHERE email_hash = $1
+            "#,
+            email_hash
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(result.is_some())
+    }
+
+    #[tracing::instrument(name = "Fetch suppressions from the database", skip(executor))]
+    pub async fn fetch_suppressions<'a, E>(
+        executor: E,
+        limit: i64,
+        offset: i64,
+    ) -> anyhow::Result<Vec<SuppressionRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            SuppressionRecord,
+            r#"
+                SELECT email_hash, reason AS "reason: _", source, created_at
+                FROM suppressions
+                ORDER BY created_at DESC, email_hash
+                LIMIT $1
+                OFFSET $2
+            "#,
+            limit,
+            offset,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
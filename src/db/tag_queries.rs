@@ -0,0 +1,166 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::types::Tx;
+use crate::domain::tag_name::TagName;
+
+pub struct TagQueries;
+
+#[derive(Serialize)]
+pub struct TagRecord {
+    pub name: String,
+    pub subscribers: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A subscriber whose tags were changed
+pub struct TaggedSubscriberRecord {
+    pub subscription_id: Uuid,
+    pub email: String,
+}
+
+impl TagQueries {
+    /// Creates the tag on first use
+    #[tracing::instrument(name = "Upsert tag into the database", skip(tx))]
+    pub async fn upsert_tag(tx: &mut Tx<'_>, name: &TagName) -> anyhow::Result<Uuid> {
+        // DO UPDATE rather than DO NOTHING, so that RETURNING yields the existing row
+        let record = sqlx::query!(
+            r#"
+                INSERT INTO tags (id, name, created_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+                RETURNING id
+            "#,
+            Uuid::new_v4(),
+            name.as_ref(),
+            Utc::now(),
+        )
+        .fetch_one(tx)
+        .await?;
+        Ok(record.id)
+    }
+
+    #[tracing::instrument(name = "Fetch tag id by name from the database", skip(executor))]
+    pub async fn fetch_tag_id<'a, E>(executor: E, name: &TagName) -> anyhow::Result<Option<Uuid>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let record = sqlx::query!(
+            r#"
+                SELECT id
+                FROM tags
+                WHERE name = $1
+            "#,
+            name.as_ref(),
+        )
+        .fetch_optional(executor)
+        .await?;
+        Ok(record.map(|record| record.id))
+    }
+
+    #[tracing::instrument(name = "Fetch tags from the database", skip(executor))]
+    pub async fn fetch_tags<'a, E>(executor: E) -> anyhow::Result<Vec<TagRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            TagRecord,
+            r#"
+                SELECT t.name, COUNT(st.subscription_id) AS "subscribers!", t.created_at
+                FROM tags t
+                LEFT JOIN subscriber_tags st ON st.tag_id = t.id
+                GROUP BY t.id
+                ORDER BY t.name
+            "#,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+
+    /// Returns the subscribers who did not have the tag yet
+    #[tracing::instrument(name = "Tag subscribers in the database", skip(tx, subscription_ids))]
+    pub async fn add_tag(
+        tx: &mut Tx<'_>,
+        tag_id: &Uuid,
+        subscription_ids: &[Uuid],
+    ) -> anyhow::Result<Vec<TaggedSubscriberRecord>> {
+        let records = sqlx::query_as!(
+            TaggedSubscriberRecord,
+            r#"
+                WITH tagged AS (
+                    INSERT INTO subscriber_tags (subscription_id, tag_id, created_at)
+                    SELECT id, $1, $3 FROM subscriptions WHERE id = ANY($2)
+                    ON CONFLICT DO NOTHING
+                    RETURNING subscription_id
+                )
+                SELECT s.id AS subscription_id, s.email
+                FROM tagged
+                JOIN subscriptions s ON s.id = tagged.subscription_id
+                ORDER BY s.email
+            "#,
+            tag_id,
+            subscription_ids,
+            Utc::now(),
+        )
+        .fetch_all(tx)
+        .await?;
+        Ok(records)
+    }
+
+    /// Returns the subscribers who had the tag
+    #[tracing::instrument(name = "Untag subscribers in the database", skip(tx, subscription_ids))]
+    pub async fn remove_tag(
+        tx: &mut Tx<'_>,
+        tag_id: &Uuid,
+        subscription_ids: &[Uuid],
+    ) -> anyhow::Result<Vec<TaggedSubscriberRecord>> {
+        let records = sqlx::query_as!(
+            TaggedSubscriberRecord,
+            r#"
+                WITH untagged AS (
+                    DELETE FROM subscriber_tags
+                    WHERE tag_id = $1 AND subscription_id = ANY($2)
+                    RETURNING subscription_id
+                )
+                SELECT s.id AS subscription_id, s.email
+                FROM untagged
+                JOIN subscriptions s ON s.id = untagged.subscription_id
+                ORDER BY s.email
+            "#,
+            tag_id,
+            subscription_ids,
+        )
+        .fetch_all(tx)
+        .await?;
+        Ok(records)
+    }
+
+    #[tracing::instrument(
+        name = "Fetch the tags of a subscription from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_tags_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<String>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query!(
+            r#"
+                SELECT t.name
+                FROM subscriber_tags st
+                JOIN tags t ON t.id = st.tag_id
+                WHERE st.subscription_id = $1
+                ORDER BY t.name
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records.into_iter().map(|record| record.name).collect())
+    }
+}
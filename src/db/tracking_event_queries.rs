@@ -0,0 +1,81 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, Postgres};
+use uuid::Uuid;
+
+use crate::db::issue_link_queries::IssueLinkRecord;
+use crate::domain::tracking_event_kind::TrackingEventKind;
+
+pub struct TrackingEventQueries;
+
+#[derive(Serialize)]
+pub struct TrackingEventRecord {
+    pub issue_id: Uuid,
+    pub kind: TrackingEventKind,
+    pub url: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TrackingEventQueries {
+    /// Only subscribers who were sent the issue are tracked:
+    /// test sends and forwarded previews do not count.
+    /// Neither do subscribers who opted out since the email was sent.
+    #[tracing::instrument(name = "Insert tracking event into the database", skip(executor, link))]
+    pub async fn insert_event<'a, E>(
+        executor: E,
+        issue_id: &Uuid,
+        subscription_id: &Uuid,
+        kind: TrackingEventKind,
+        link: Option<&IssueLinkRecord>,
+    ) -> anyhow::Result<bool>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let result = sqlx::query!(
+            r#"
+                INSERT INTO issue_tracking_events (id, issue_id, subscription_id, kind, link_id,
+                                                   url, created_at)
+                SELECT $1, d.issue_id, d.subscription_id, $4, $5, $6, $7
+                FROM issue_deliveries d
+                JOIN subscriptions s ON s.id = d.subscription_id
+                WHERE d.issue_id = $2 AND d.subscription_id = $3 AND NOT s.do_not_track
+            "#,
+            Uuid::new_v4(),
+            issue_id,
+            subscription_id,
+            kind as TrackingEventKind,
+            link.map(|link| link.id),
+            link.map(|link| link.url.as_str()),
+            Utc::now(),
+        )
+        .execute(executor)
+        .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    #[tracing::instrument(
+        name = "Fetch tracking events by subscription id from the database",
+        skip(executor)
+    )]
+    pub async fn fetch_events_by_subscription_id<'a, E>(
+        executor: E,
+        subscription_id: &Uuid,
+    ) -> anyhow::Result<Vec<TrackingEventRecord>>
+    where
+        E: Executor<'a, Database = Postgres>,
+    {
+        let records = sqlx::query_as!(
+            TrackingEventRecord,
+            r#"
+                SELECT issue_id, kind AS "kind: _", url, created_at
+                FROM issue_tracking_events
+                WHERE subscription_id = $1
+                ORDER BY created_at
+            "#,
+            subscription_id,
+        )
+        .fetch_all(executor)
+        .await?;
+        Ok(records)
+    }
+}
@@ -0,0 +1,85 @@
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const MAX_SUBJECT_VARIANTS: usize = 10;
+
+/// Subject variants of an issue, each sent to a slice of the audience.
+/// The winner goes to everyone else after `wait_minutes`.
+#[derive(Debug)]
+pub struct AbTest {
+    subjects: Vec<String>,
+    audience_percent: u8,
+    wait_minutes: u32,
+}
+
+impl AbTest {
+    pub fn parse(
+        subjects: Vec<String>,
+        audience_percent: u8,
+        wait_minutes: u32,
+    ) -> Result<AbTest, String> {
+        if subjects.len() < 2 || subjects.len() > MAX_SUBJECT_VARIANTS {
+            return Err(format!(
+                "An A/B test has between 2 and {} subjects.",
+                MAX_SUBJECT_VARIANTS
+            ));
+        }
+        if subjects.iter().any(|subject| subject.trim().is_empty()) {
+            return Err("A/B test subjects cannot be empty.".to_owned());
+        }
+        if !(1..=100).contains(&audience_percent) {
+            return Err(format!(
+                "{} is not a valid A/B test audience percent.",
+                audience_percent
+            ));
+        }
+        Ok(Self {
+            subjects,
+            audience_percent,
+            wait_minutes,
+        })
+    }
+
+    pub fn subjects(&self) -> &[String] {
+        &self.subjects
+    }
+
+    pub fn audience_percent(&self) -> u8 {
+        self.audience_percent
+    }
+
+    pub fn wait_minutes(&self) -> u32 {
+        self.wait_minutes
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SubjectAssignment {
+    /// Position of the variant
+    Variant(usize),
+    /// Waits for the winner
+    Holdout,
+}
+
+/// Deterministic: a subscriber always lands in the same slice for a given issue,
+/// so a retried send cannot flip their variant
+pub fn assign_subject(
+    issue_id: &Uuid,
+    subscription_id: &Uuid,
+    audience_percent: u8,
+    variants: usize,
+) -> SubjectAssignment {
+    let digest = Sha256::new()
+        .chain_update(issue_id.as_bytes())
+        .chain_update(subscription_id.as_bytes())
+        .finalize();
+    let mut first_bytes = [0u8; 8];
+    first_bytes.copy_from_slice(&digest[..8]);
+    // One bucket per 0.01% of the audience
+    let bucket = u64::from_be_bytes(first_bytes) % 10_000;
+    if variants == 0 || bucket >= u64::from(audience_percent) * 100 {
+        SubjectAssignment::Holdout
+    } else {
+        SubjectAssignment::Variant((bucket % variants as u64) as usize)
+    }
+}
@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "attribute_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AttributeKind {
+    String,
+    Number,
+    Bool,
+    /// `YYYY-MM-DD`
+    Date,
+    /// One of the values of the definition
+    Enum,
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "data_request_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DataRequestKind {
+    Export,
+    Erasure,
+}
+
+impl Display for DataRequestKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
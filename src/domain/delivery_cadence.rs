@@ -0,0 +1,35 @@
+use serde::{Deserialize, Serialize};
+
+/// How often a subscriber wants to receive issues
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "delivery_cadence", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryCadence {
+    /// Every issue as soon as it is published
+    Immediate,
+    /// Issues are bundled into a digest once a day
+    Daily,
+    /// Issues are bundled into a digest once a week
+    Weekly,
+}
+
+impl DeliveryCadence {
+    pub fn parse(s: &str) -> Result<DeliveryCadence, String> {
+        match s {
+            "immediate" => Ok(DeliveryCadence::Immediate),
+            "daily" => Ok(DeliveryCadence::Daily),
+            "weekly" => Ok(DeliveryCadence::Weekly),
+            other => Err(format!("{} is not a valid delivery cadence.", other)),
+        }
+    }
+}
+
+impl AsRef<str> for DeliveryCadence {
+    fn as_ref(&self) -> &str {
+        match self {
+            DeliveryCadence::Immediate => "immediate",
+            DeliveryCadence::Daily => "daily",
+            DeliveryCadence::Weekly => "weekly",
+        }
+    }
+}
@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+#[derive(sqlx::Type, Serialize, Debug, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "email_delivery_outcome", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum EmailDeliveryOutcome {
+    Sent,
+    Suppressed,
+    Failed,
+}
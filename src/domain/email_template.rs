@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifies which kind of email was sent, stored with every delivery
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTemplate {
+    SubscriptionConfirmation,
+    DataExport,
+    DataErasure,
+    Issue,
+    Digest,
+}
+
+impl AsRef<str> for EmailTemplate {
+    fn as_ref(&self) -> &str {
+        match self {
+            EmailTemplate::SubscriptionConfirmation => "subscription_confirmation",
+            EmailTemplate::DataExport => "data_export",
+            EmailTemplate::DataErasure => "data_erasure",
+            EmailTemplate::Issue => "issue",
+            EmailTemplate::Digest => "digest",
+        }
+    }
+}
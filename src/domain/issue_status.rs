@@ -0,0 +1,38 @@
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+#[derive(sqlx::Type, Serialize, Debug, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "issue_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum IssueStatus {
+    Draft,
+    Scheduled,
+    Sending,
+    Sent,
+    Cancelled,
+}
+
+impl IssueStatus {
+    /// * Draft | Scheduled -> Scheduled when the issue is (re)scheduled
+    /// * Draft | Scheduled -> Cancelled
+    /// * Scheduled -> Sending when the scheduler starts the fan-out
+    /// * Sending -> Sent once every subscriber got the issue
+    ///
+    /// Once the fan-out has started the issue can no longer be cancelled or rescheduled.
+    pub fn can_transition_to(self, to: IssueStatus) -> bool {
+        use IssueStatus::*;
+        matches!(
+            (self, to),
+            (Draft | Scheduled, Scheduled)
+                | (Draft | Scheduled, Cancelled)
+                | (Scheduled, Sending)
+                | (Sending, Sent)
+        )
+    }
+}
+
+impl Display for IssueStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
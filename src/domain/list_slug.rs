@@ -0,0 +1,40 @@
+use derive_more::AsRef;
+use serde::{Deserialize, Serialize};
+
+/// The list created by the lists migration, used when no list is requested
+pub const DEFAULT_LIST_SLUG: &str = "newsletter";
+pub const DEFAULT_LIST_NAME: &str = "Newsletter";
+
+#[derive(AsRef, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    /// Lowercase ASCII letters, digits and dashes, up to 64 characters
+    pub fn parse(s: String) -> Result<ListSlug, String> {
+        let is_valid = !s.is_empty()
+            && s.len() <= 64
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid list slug.", s))
+        }
+    }
+
+    /// Parses a comma-separated list of slugs, duplicates are dropped.
+    /// An empty input means the default list.
+    pub fn parse_many(s: &str) -> Result<Vec<ListSlug>, String> {
+        let mut slugs: Vec<ListSlug> = vec![];
+        for slug in s.split(',').map(str::trim).filter(|slug| !slug.is_empty()) {
+            let slug = ListSlug::parse(slug.to_owned())?;
+            if !slugs.contains(&slug) {
+                slugs.push(slug);
+            }
+        }
+        if slugs.is_empty() {
+            slugs.push(ListSlug(DEFAULT_LIST_SLUG.to_owned()));
+        }
+        Ok(slugs)
+    }
+}
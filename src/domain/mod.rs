@@ -1,4 +1,21 @@
+pub mod ab_test;
+pub mod attribute_kind;
+pub mod data_request_kind;
+pub mod delivery_cadence;
+pub mod email_delivery_outcome;
+pub mod email_template;
+pub mod issue_status;
+pub mod list_slug;
 pub mod new_subscriber;
+pub mod preferred_language;
+pub mod request_context;
+pub mod segment;
+pub mod subscriber_attributes;
 pub mod subscriber_email;
 pub mod subscriber_name;
+pub mod subscription_event_source;
 pub mod subscription_status;
+pub mod suppression_reason;
+pub mod tag_name;
+pub mod time_zone;
+pub mod tracking_event_kind;
@@ -1,3 +1,4 @@
+use crate::domain::subscriber_attributes::SubscriberAttributes;
 use crate::domain::subscriber_email::SubscriberEmail;
 use crate::domain::subscriber_name::SubscriberName;
 
@@ -5,4 +6,5 @@ use crate::domain::subscriber_name::SubscriberName;
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    pub attributes: SubscriberAttributes,
 }
@@ -0,0 +1,24 @@
+use derive_more::AsRef;
+use serde::{Deserialize, Serialize};
+
+#[derive(AsRef, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PreferredLanguage(String);
+
+impl PreferredLanguage {
+    /// A two letter ISO 639-1 code with an optional ISO 3166-1 region, e.g. `en` or `pt-BR`
+    pub fn parse(s: String) -> Result<PreferredLanguage, String> {
+        let mut parts = s.splitn(2, '-');
+        let language = parts.next().unwrap_or_default();
+        let is_valid_language =
+            language.len() == 2 && language.chars().all(|c| c.is_ascii_lowercase());
+        let is_valid_region = parts
+            .next()
+            .map(|region| region.len() == 2 && region.chars().all(|c| c.is_ascii_uppercase()))
+            .unwrap_or(true);
+        if is_valid_language && is_valid_region {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid language.", s))
+        }
+    }
+}
@@ -0,0 +1,9 @@
+/// Where a subscription change came from, stored in the consent audit trail.
+/// Empty for changes made by background workers.
+#[derive(Debug, Default, Clone)]
+pub struct RequestContext {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    /// `Origin` header, or `Referer` if the browser did not send one
+    pub origin: Option<String>,
+}
@@ -0,0 +1,97 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::domain::list_slug::ListSlug;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::domain::tag_name::TagName;
+
+const MAX_SEGMENT_DEPTH: usize = 8;
+const MAX_SEGMENT_CONDITIONS: usize = 64;
+const MAX_SUBSCRIBED_WITHIN_DAYS: u32 = 36_500;
+
+/// A subset of the subscribers, e.g.
+/// `{"all": [{"status": "confirmed"}, {"subscribed_within_days": 30}]}`.
+/// Compiled to parameterised SQL by `SegmentQueries`: values are never spliced into the query.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Segment {
+    /// Every condition holds
+    All(Vec<Segment>),
+    /// At least one condition holds
+    Any(Vec<Segment>),
+    Not(Box<Segment>),
+    Status(SubscriptionStatus),
+    SubscribedWithinDays(u32),
+    /// Before midnight UTC of the date
+    SubscribedBefore(NaiveDate),
+    /// On the date or later, from midnight UTC
+    SubscribedSince(NaiveDate),
+    /// Case-insensitive, e.g. `gmail.com`
+    EmailDomain(String),
+    /// Case-insensitive, `*` matches any characters, e.g. `Ann*`
+    NameMatches(String),
+    /// Confirmed member of the list
+    MemberOf(ListSlug),
+    /// Has the tag
+    Tagged(TagName),
+}
+
+impl Segment {
+    pub fn parse(definition: serde_json::Value) -> Result<Segment, String> {
+        let segment: Segment = serde_json::from_value(definition)
+            .map_err(|err| format!("Invalid segment definition: {}.", err))?;
+        let mut conditions = 0;
+        segment.validate(1, &mut conditions)?;
+        Ok(segment)
+    }
+
+    fn validate(&self, depth: usize, conditions: &mut usize) -> Result<(), String> {
+        *conditions += 1;
+        if depth > MAX_SEGMENT_DEPTH || *conditions > MAX_SEGMENT_CONDITIONS {
+            return Err(format!(
+                "Segments have up to {} conditions, nested up to {} levels.",
+                MAX_SEGMENT_CONDITIONS, MAX_SEGMENT_DEPTH
+            ));
+        }
+        match self {
+            Segment::All(segments) | Segment::Any(segments) => {
+                if segments.is_empty() {
+                    return Err("`all` and `any` need at least one condition.".to_owned());
+                }
+                segments
+                    .iter()
+                    .try_for_each(|segment| segment.validate(depth + 1, conditions))
+            }
+            Segment::Not(segment) => segment.validate(depth + 1, conditions),
+            Segment::EmailDomain(domain) => {
+                let is_valid = !domain.is_empty()
+                    && !domain.contains('@')
+                    && !domain.chars().any(char::is_whitespace);
+                if is_valid {
+                    Ok(())
+                } else {
+                    Err(format!("{} is not a valid email domain.", domain))
+                }
+            }
+            Segment::NameMatches(pattern) => {
+                if pattern.trim().is_empty() {
+                    Err("Name patterns cannot be empty.".to_owned())
+                } else {
+                    Ok(())
+                }
+            }
+            Segment::MemberOf(slug) => ListSlug::parse(slug.as_ref().to_owned()).map(|_| ()),
+            Segment::Tagged(tag) => TagName::parse(tag.as_ref().to_owned()).map(|_| ()),
+            Segment::SubscribedWithinDays(days) => {
+                if *days <= MAX_SUBSCRIBED_WITHIN_DAYS {
+                    Ok(())
+                } else {
+                    Err(format!("{} is too many days.", days))
+                }
+            }
+            Segment::Status(_) | Segment::SubscribedBefore(_) | Segment::SubscribedSince(_) => {
+                Ok(())
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::domain::attribute_kind::AttributeKind;
+
+const MAX_ATTRIBUTE_NAME_LENGTH: usize = 64;
+const MAX_STRING_ATTRIBUTE_LENGTH: usize = 1024;
+
+/// An attribute subscribers can have, defined by the operator
+#[derive(Debug, Clone, Serialize)]
+pub struct AttributeDefinition {
+    name: String,
+    kind: AttributeKind,
+    required: bool,
+    values: Vec<String>,
+}
+
+impl AttributeDefinition {
+    /// Names are lowercase ASCII letters, digits and underscores, starting with a letter.
+    /// Enums need their values, other kinds cannot have any.
+    pub fn parse(
+        name: String,
+        kind: AttributeKind,
+        required: bool,
+        values: Vec<String>,
+    ) -> Result<AttributeDefinition, String> {
+        let is_valid_name = name.len() <= MAX_ATTRIBUTE_NAME_LENGTH
+            && name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !is_valid_name {
+            return Err(format!("{} is not a valid attribute name.", name));
+        }
+        match kind {
+            AttributeKind::Enum => {
+                if values.is_empty() || values.iter().any(|value| value.trim().is_empty()) {
+                    return Err(format!(
+                        "The enum attribute {} needs non-empty values.",
+                        name
+                    ));
+                }
+                let mut unique = values.clone();
+                unique.sort();
+                unique.dedup();
+                if unique.len() != values.len() {
+                    return Err(format!(
+                        "The values of the enum attribute {} are not unique.",
+                        name
+                    ));
+                }
+            }
+            _ => {
+                if !values.is_empty() {
+                    return Err(format!("Only enum attributes have values, not {}.", name));
+                }
+            }
+        }
+        Ok(Self {
+            name,
+            kind,
+            required,
+            values,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn kind(&self) -> AttributeKind {
+        self.kind
+    }
+
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    /// Forms only send strings, so numbers and booleans are also accepted as strings
+    fn parse_value(&self, value: Value) -> Result<Value, String> {
+        let invalid = || format!("Invalid value for the {} attribute.", self.name);
+        match (self.kind, value) {
+            (AttributeKind::String, Value::String(s)) => {
+                if s.chars().count() <= MAX_STRING_ATTRIBUTE_LENGTH {
+                    Ok(Value::String(s))
+                } else {
+                    Err(format!("The {} attribute is too long.", self.name))
+                }
+            }
+            (AttributeKind::Number, Value::Number(n)) => Ok(Value::Number(n)),
+            (AttributeKind::Number, Value::String(s)) => s
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(invalid),
+            (AttributeKind::Bool, Value::Bool(b)) => Ok(Value::Bool(b)),
+            (AttributeKind::Bool, Value::String(s)) => match s.as_str() {
+                // Checked checkboxes send `on`
+                "true" | "on" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(invalid()),
+            },
+            (AttributeKind::Date, Value::String(s)) => NaiveDate::parse_from_str(&s, "%Y-%m-%d")
+                .map(|date| Value::String(date.to_string()))
+                .map_err(|_| invalid()),
+            (AttributeKind::Enum, Value::String(s)) if self.values.contains(&s) => {
+                Ok(Value::String(s))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Every attribute definition
+#[derive(Debug, Clone, Default)]
+pub struct AttributeSchema {
+    definitions: Vec<AttributeDefinition>,
+}
+
+impl AttributeSchema {
+    pub fn new(definitions: Vec<AttributeDefinition>) -> Self {
+        Self { definitions }
+    }
+
+    pub fn definitions(&self) -> &[AttributeDefinition] {
+        &self.definitions
+    }
+
+    /// Unknown attributes are rejected, required ones must be present.
+    /// `null` and empty strings count as missing.
+    pub fn validate(&self, mut input: Map<String, Value>) -> Result<SubscriberAttributes, String> {
+        let mut attributes = Map::new();
+        for definition in &self.definitions {
+            let value = match input.remove(definition.name()) {
+                None | Some(Value::Null) => None,
+                Some(Value::String(s)) if s.trim().is_empty() => None,
+                Some(value) => Some(value),
+            };
+            match value {
+                Some(value) => {
+                    attributes.insert(definition.name.clone(), definition.parse_value(value)?);
+                }
+                None if definition.required => {
+                    return Err(format!("The {} attribute is required.", definition.name));
+                }
+                None => {}
+            }
+        }
+        if let Some(unknown) = input.keys().next() {
+            return Err(format!("{} is not a known attribute.", unknown));
+        }
+        Ok(SubscriberAttributes(attributes))
+    }
+}
+
+/// Attributes validated against the `AttributeSchema`
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SubscriberAttributes(Map<String, Value>);
+
+impl AsRef<Map<String, Value>> for SubscriberAttributes {
+    fn as_ref(&self) -> &Map<String, Value> {
+        &self.0
+    }
+}
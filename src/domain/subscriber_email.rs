@@ -1,5 +1,6 @@
 use derive_more::AsRef;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use validator::validate_email;
 
 #[derive(AsRef, Debug, Serialize, Deserialize, Clone)]
@@ -13,4 +14,11 @@ impl SubscriberEmail {
             Err(format!("{} is not a valid subscriber email.", s))
         }
     }
+
+    /// Hex-encoded SHA-256 of the trimmed, lowercased email.
+    /// Lets us recognise an address later without keeping it in plain text.
+    pub fn canonical_hash(&self) -> String {
+        let canonical = self.0.trim().to_lowercase();
+        hex::encode(Sha256::digest(canonical.as_bytes()))
+    }
 }
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+/// What caused a subscription status transition, stored in the consent audit trail
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionEventSource {
+    SubscribeForm,
+    ConfirmationLink,
+    ConfirmationEmailFailure,
+    SendgridWebhook,
+    UnsubscribeLink,
+    PreferencesPage,
+    /// Consent was collected elsewhere and vouched for by an admin
+    AdminImport,
+    /// Changed by an operator with `zero2prodctl`
+    Operator,
+}
+
+impl AsRef<str> for SubscriptionEventSource {
+    fn as_ref(&self) -> &str {
+        match self {
+            SubscriptionEventSource::SubscribeForm => "subscribe_form",
+            SubscriptionEventSource::ConfirmationLink => "confirmation_link",
+            SubscriptionEventSource::ConfirmationEmailFailure => "confirmation_email_failure",
+            SubscriptionEventSource::SendgridWebhook => "sendgrid_webhook",
+            SubscriptionEventSource::UnsubscribeLink => "unsubscribe_link",
+            SubscriptionEventSource::PreferencesPage => "preferences_page",
+            SubscriptionEventSource::AdminImport => "admin_import",
+            SubscriptionEventSource::Operator => "operator",
+        }
+    }
+}
@@ -1,11 +1,74 @@
+use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
-#[derive(sqlx::Type, Debug, PartialEq)]
+#[derive(sqlx::Type, Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
 #[sqlx(type_name = "subscription_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
 pub enum SubscriptionStatus {
     Pending,
     Confirmed,
     Failed,
+    Unsubscribed,
+}
+
+/// Whose status changes: a few transitions only apply to one of them
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StatusOwner {
+    /// The email address
+    Subscription,
+    ListMembership,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+#[error("Illegal {owner:?} status transition from {from} to {to}")]
+pub struct IllegalTransition {
+    pub from: SubscriptionStatus,
+    pub to: SubscriptionStatus,
+    pub owner: StatusOwner,
+}
+
+impl SubscriptionStatus {
+    /// The lifecycle shared by subscriptions (the email address)
+    /// and their list memberships:
+    /// * Pending -> Confirmed when the confirmation link is followed
+    /// * Pending -> Failed when the confirmation email cannot be delivered (subscriptions only)
+    /// * Failed -> Pending when the subscriber signs up again
+    /// * Failed -> Confirmed when an earlier confirmation link is followed,
+    ///   which proves that the address works after all
+    /// * Pending | Confirmed -> Unsubscribed when the unsubscribe link is followed (memberships only)
+    /// * Unsubscribed -> Pending when the subscriber signs up for the list again
+    ///
+    /// Only the subscriber can take back a confirmation: late delivery errors must not undo a consent.
+    pub fn can_transition_to(self, to: SubscriptionStatus, owner: StatusOwner) -> bool {
+        use StatusOwner::*;
+        use SubscriptionStatus::*;
+        matches!(
+            (self, to, owner),
+            (Pending, Confirmed, _)
+                | (Pending, Failed, Subscription)
+                | (Pending, Unsubscribed, ListMembership)
+                | (Failed, Pending, Subscription)
+                | (Failed, Confirmed, Subscription)
+                | (Confirmed, Unsubscribed, ListMembership)
+                | (Unsubscribed, Pending, ListMembership)
+        )
+    }
+
+    pub fn transition_to(
+        self,
+        to: SubscriptionStatus,
+        owner: StatusOwner,
+    ) -> Result<SubscriptionStatus, IllegalTransition> {
+        if self.can_transition_to(to, owner) {
+            Ok(to)
+        } else {
+            Err(IllegalTransition {
+                from: self,
+                to,
+                owner,
+            })
+        }
+    }
 }
 
 impl Display for SubscriptionStatus {
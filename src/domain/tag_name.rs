@@ -0,0 +1,20 @@
+use derive_more::AsRef;
+use serde::{Deserialize, Serialize};
+
+#[derive(AsRef, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TagName(String);
+
+impl TagName {
+    /// Lowercase ASCII letters, digits, dashes and underscores, up to 64 characters
+    pub fn parse(s: String) -> Result<TagName, String> {
+        let is_valid = !s.is_empty()
+            && s.len() <= 64
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid tag name.", s))
+        }
+    }
+}
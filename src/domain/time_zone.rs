@@ -0,0 +1,38 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// An IANA time zone, e.g. `Europe/Berlin`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IanaTimeZone(Tz);
+
+impl IanaTimeZone {
+    pub fn parse(s: &str) -> Result<IanaTimeZone, String> {
+        s.parse::<Tz>()
+            .map(Self)
+            .map_err(|_| format!("{} is not a valid time zone.", s))
+    }
+
+    pub fn utc() -> IanaTimeZone {
+        Self(Tz::UTC)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0.name()
+    }
+
+    /// The instant the wall clock shows `local` in this time zone.
+    /// Ambiguous times (clocks going back) resolve to the earlier instant,
+    /// skipped times (clocks going forward) are moved past the gap.
+    pub fn to_utc(&self, local: &NaiveDateTime) -> DateTime<Utc> {
+        match self.0.from_local_datetime(local) {
+            LocalResult::Single(time) => time.with_timezone(&Utc),
+            LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+            LocalResult::None => self.to_utc(&(*local + Duration::minutes(30))),
+        }
+    }
+}
+
+/// The earliest instant a wall clock time happens anywhere (UTC+14)
+pub fn earliest_instant(local: &NaiveDateTime) -> DateTime<Utc> {
+    DateTime::from_utc(*local - Duration::hours(14), Utc)
+}
@@ -0,0 +1,9 @@
+use serde::Serialize;
+
+#[derive(sqlx::Type, Serialize, Debug, PartialEq, Clone, Copy)]
+#[sqlx(type_name = "tracking_event_kind", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum TrackingEventKind {
+    Open,
+    Click,
+}
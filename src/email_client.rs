@@ -1,18 +1,60 @@
 use crate::config::Config;
-use reqwest::Client;
+use anyhow::Context;
+use reqwest::{Client, StatusCode};
 use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
 use std::borrow::Cow;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
 
+use crate::db::email_delivery_queries::{EmailDeliveryQueries, NewEmailDelivery};
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::suppression_queries::SuppressionQueries;
+use crate::domain::email_delivery_outcome::EmailDeliveryOutcome;
+use crate::domain::email_template::EmailTemplate;
 use crate::domain::subscriber_email::SubscriberEmail;
+use crate::html::escape_html;
+
+// Delay before the second attempt, doubled for every next one
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
 
 pub struct EmailClient {
     http_client: Client,
     sender: String,
     base_url: String,
     timeout: Duration,
+    max_attempts: u32,
     sendgrid_api_key: Secret<String>,
+    // Every send is checked against the suppression list
+    pg_pool: PgPool,
+    // Base of the preference center link in the footer of subscriber emails
+    application_base_url: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SendOutcome {
+    Sent(SentEmail),
+    /// The recipient is on the suppression list, nothing was sent
+    Suppressed,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SentEmail {
+    /// `X-Message-Id` response header, SendGrid webhook events refer to it
+    pub provider_message_id: Option<String>,
+    pub status: u16,
+    /// Duration of the successful attempt
+    pub latency: Duration,
+    /// Starts at 1
+    pub attempt: u32,
+}
+
+struct SendFailure {
+    status: Option<u16>,
+    latency: Duration,
+    attempt: u32,
+    error: anyhow::Error,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -33,6 +75,12 @@ pub struct Content<'a> {
     pub value: Cow<'a, str>,
 }
 
+/// The footer of subscriber emails is part of it
+pub struct EmailBody {
+    pub text_content: String,
+    pub html_content: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SendEmailRequest<'a> {
     pub personalizations: Vec<Personalization<'a>>,
@@ -42,47 +90,295 @@ pub struct SendEmailRequest<'a> {
 }
 
 impl EmailClient {
-    pub fn new(config: &Config) -> Self {
+    pub fn new(config: &Config, pg_pool: PgPool) -> Self {
         Self {
             http_client: Client::new(),
             sender: config.email_client_sender_email.to_owned(),
             base_url: config.email_client_base_url.to_owned(),
             timeout: Duration::from_millis(config.email_client_timeout_millis as u64),
+            max_attempts: config.email_client_max_attempts.max(1),
             sendgrid_api_key: config.sendgrid_api_key.clone(),
+            pg_pool,
+            application_base_url: config.application_base_url(),
         }
     }
 
-    pub async fn send_email(
+    /// Sends the email and records the outcome in `email_deliveries`.
+    /// Every email goes through here, except the test sends of issues:
+    /// like every send with a `subscription_id`, it appends the preference center link of the subscriber.
+    pub async fn deliver(
         &self,
+        subscription_id: Option<&Uuid>,
+        template: EmailTemplate,
         recipient: &SubscriberEmail,
         subject: &str,
         text_content: &str,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<SendOutcome> {
+        self.deliver_from(
+            &self.sender,
+            subscription_id,
+            template,
+            recipient,
+            subject,
+            text_content,
+            None,
+        )
+        .await
+    }
+
+    /// Same as `deliver`, with a sender other than `EMAIL_CLIENT_SENDER_EMAIL`
+    /// and an optional HTML alternative to the text
+    #[allow(clippy::too_many_arguments)]
+    #[tracing::instrument(
+        name = "Delivering an email",
+        skip(self, recipient, text_content, html_content),
+        fields(template = template.as_ref())
+    )]
+    pub async fn deliver_from(
+        &self,
+        sender: &str,
+        subscription_id: Option<&Uuid>,
+        template: EmailTemplate,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        text_content: &str,
+        html_content: Option<&str>,
+    ) -> anyhow::Result<SendOutcome> {
+        let result = self
+            .send(
+                sender,
+                subscription_id,
+                recipient,
+                subject,
+                text_content,
+                html_content,
+            )
+            .await;
+        let delivery = match &result {
+            Ok(SendOutcome::Sent(sent)) => NewEmailDelivery {
+                subscription_id,
+                template,
+                outcome: EmailDeliveryOutcome::Sent,
+                provider_message_id: sent.provider_message_id.as_deref(),
+                http_status: Some(sent.status),
+                latency_ms: Some(sent.latency.as_millis()),
+                attempt: sent.attempt,
+                error: None,
+            },
+            Ok(SendOutcome::Suppressed) => NewEmailDelivery {
+                subscription_id,
+                template,
+                outcome: EmailDeliveryOutcome::Suppressed,
+                provider_message_id: None,
+                http_status: None,
+                latency_ms: None,
+                attempt: 0,
+                error: None,
+            },
+            Err(failure) => NewEmailDelivery {
+                subscription_id,
+                template,
+                outcome: EmailDeliveryOutcome::Failed,
+                provider_message_id: None,
+                http_status: failure.status,
+                latency_ms: Some(failure.latency.as_millis()),
+                attempt: failure.attempt,
+                error: Some(format!("{:#}", failure.error)),
+            },
+        };
+        // The email is already out (or not), a logging failure should not change the outcome
+        if let Err(err) = EmailDeliveryQueries::insert_delivery(&self.pg_pool, &delivery).await {
+            tracing::error!(error = ?err, "Failed to record the email delivery");
+        }
+        result.map_err(anyhow::Error::from)
+    }
+
+    /// The body as `deliver` sends it to the subscriber.
+    /// The subscription can be gone already, e.g. for the erasure confirmation
+    pub async fn with_footer(
+        &self,
+        subscription_id: &Uuid,
+        text_content: &str,
+        html_content: Option<&str>,
+    ) -> anyhow::Result<EmailBody> {
+        let preferences_token =
+            SubscriptionQueries::fetch_preferences_token(&self.pg_pool, subscription_id)
+                .await
+                .context("Failed to fetch the preferences token")?;
+        let preferences_url = match preferences_token {
+            Some(preferences_token) => format!(
+                "{}/preferences?preferences_token={}",
+                self.application_base_url, preferences_token
+            ),
+            None => {
+                return Ok(EmailBody {
+                    text_content: text_content.to_owned(),
+                    html_content: html_content.map(|html_content| html_content.to_owned()),
+                })
+            }
+        };
+        Ok(EmailBody {
+            text_content: format!(
+                "{}\n\n--\nManage your email preferences: {}",
+                text_content, preferences_url
+            ),
+            html_content: html_content.map(|html_content| {
+                format!(
+                    "{}\n<p>--<br>\n<a href=\"{}\">Manage your email preferences</a></p>",
+                    html_content,
+                    escape_html(&preferences_url)
+                )
+            }),
+        })
+    }
+
+    /// Sends a test of an issue to a seed address, without recording it as a delivery:
+    /// the subscriber whose footer it carries still gets the issue once it is sent for real
+    #[tracing::instrument(
+        name = "Sending a test email",
+        skip(self, recipient, text_content, html_content)
+    )]
+    pub(crate) async fn send_test_email(
+        &self,
+        sender: &str,
+        subscription_id: &Uuid,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        text_content: &str,
+        html_content: Option<&str>,
+    ) -> anyhow::Result<SendOutcome> {
+        self.send(
+            sender,
+            Some(subscription_id),
+            recipient,
+            subject,
+            text_content,
+            html_content,
+        )
+        .await
+        .map_err(anyhow::Error::from)
+    }
+
+    /// The shared send path: emails on behalf of a subscriber get their footer here
+    async fn send(
+        &self,
+        sender: &str,
+        subscription_id: Option<&Uuid>,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        text_content: &str,
+        html_content: Option<&str>,
+    ) -> Result<SendOutcome, SendFailure> {
+        let is_suppressed =
+            SuppressionQueries::is_suppressed(&self.pg_pool, &recipient.canonical_hash())
+                .await
+                .context("Failed to check the suppression list")
+                .map_err(|error| SendFailure {
+                    status: None,
+                    latency: Duration::ZERO,
+                    attempt: 0,
+                    error,
+                })?;
+        if is_suppressed {
+            tracing::info!("Recipient is suppressed, skipping the email");
+            return Ok(SendOutcome::Suppressed);
+        }
+        let body = match subscription_id {
+            Some(subscription_id) => self
+                .with_footer(subscription_id, text_content, html_content)
+                .await
+                .map_err(|error| SendFailure {
+                    status: None,
+                    latency: Duration::ZERO,
+                    attempt: 0,
+                    error,
+                })?,
+            None => EmailBody {
+                text_content: text_content.to_owned(),
+                html_content: html_content.map(|html_content| html_content.to_owned()),
+            },
+        };
         let url = format!("{}/mail/send", &self.base_url);
+        // SendGrid wants the text before the HTML
+        let mut content = vec![Content {
+            value: Cow::Borrowed(body.text_content.as_str()),
+            r#type: "text/plain",
+        }];
+        if let Some(html_content) = body.html_content.as_deref() {
+            content.push(Content {
+                value: Cow::Borrowed(html_content),
+                r#type: "text/html",
+            });
+        }
         let request = SendEmailRequest {
             subject,
-            from: Email {
-                email: &self.sender,
-            },
+            from: Email { email: sender },
             personalizations: vec![Personalization {
                 to: vec![Email {
                     email: recipient.as_ref(),
                 }],
             }],
-            content: vec![Content {
-                value: Cow::Borrowed(text_content),
-                r#type: "text/plain",
-            }],
+            content,
         };
-        self.http_client
-            .post(&url)
-            .bearer_auth(self.sendgrid_api_key.expose_secret())
-            .header("Content-Type", "application/json")
-            .json(&request)
-            .timeout(self.timeout)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(())
+        let mut attempt = 1;
+        loop {
+            let started_at = Instant::now();
+            let response = self
+                .http_client
+                .post(&url)
+                .bearer_auth(self.sendgrid_api_key.expose_secret())
+                .header("Content-Type", "application/json")
+                .json(&request)
+                .timeout(self.timeout)
+                .send()
+                .await;
+            let latency = started_at.elapsed();
+            let (status, error) = match response {
+                Ok(response) if response.status().is_success() => {
+                    let provider_message_id = response
+                        .headers()
+                        .get("X-Message-Id")
+                        .and_then(|value| value.to_str().ok())
+                        .map(|value| value.to_owned());
+                    return Ok(SendOutcome::Sent(SentEmail {
+                        provider_message_id,
+                        status: response.status().as_u16(),
+                        latency,
+                        attempt,
+                    }));
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let error = response.error_for_status().unwrap_err();
+                    (Some(status), anyhow::Error::from(error))
+                }
+                Err(error) => (None, anyhow::Error::from(error)),
+            };
+            // Other client errors will not get any better on a retry
+            let is_retryable = match status {
+                Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+                None => true,
+            };
+            if !is_retryable || attempt >= self.max_attempts {
+                return Err(SendFailure {
+                    status: status.map(|s| s.as_u16()),
+                    latency,
+                    attempt,
+                    error,
+                });
+            }
+            tracing::warn!(error = %error, attempt, "Failed to send an email, retrying");
+            tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt - 1)).await;
+            attempt += 1;
+        }
+    }
+}
+
+impl From<SendFailure> for anyhow::Error {
+    fn from(failure: SendFailure) -> Self {
+        let attempt = failure.attempt;
+        failure
+            .error
+            .context(format!("Failed to send the email, attempt {}", attempt))
     }
 }
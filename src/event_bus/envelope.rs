@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::{Builder, Uuid, Variant, Version};
+
+use crate::event_bus::Event;
+
+/// What is actually sent over the wire: the payload and its metadata, as JSON
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Envelope<E> {
+    pub event_id: Uuid,
+    pub occurred_at: DateTime<Utc>,
+    /// The version the payload was published with
+    pub schema_version: u32,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub payload: E,
+}
+
+impl<E: Event> Envelope<E> {
+    pub fn new(payload: E) -> Self {
+        Self {
+            event_id: Uuid::new_v4(),
+            occurred_at: Utc::now(),
+            schema_version: E::SCHEMA_VERSION,
+            headers: BTreeMap::new(),
+            payload,
+        }
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_owned(), value.to_owned());
+        self
+    }
+
+    pub fn encode(&self) -> anyhow::Result<Vec<u8>> {
+        serde_json::to_vec(self).context("Failed to serialize the event envelope")
+    }
+
+    /// Payloads of older schema versions are upcast one version at a time.
+    /// Newer versions are rejected: this consumer cannot know what changed.
+    /// Payloads published before the envelopes have version 0, see `Envelope::legacy`.
+    pub fn decode(data: &[u8]) -> anyhow::Result<Self> {
+        let value: serde_json::Value =
+            serde_json::from_slice(data).context("Failed to deserialize the event envelope")?;
+        let envelope = if value.get("schema_version").is_some() && value.get("payload").is_some() {
+            serde_json::from_value(value).context("Failed to deserialize the event envelope")?
+        } else {
+            Envelope::legacy(data, value)
+        };
+        if envelope.schema_version > E::SCHEMA_VERSION {
+            anyhow::bail!(
+                "{} version {} is newer than the supported version {}",
+                E::SUBJECT,
+                envelope.schema_version,
+                E::SCHEMA_VERSION
+            );
+        }
+        let mut payload = envelope.payload;
+        for version in envelope.schema_version..E::SCHEMA_VERSION {
+            payload = E::upcast(version, payload)
+                .with_context(|| format!("Failed to upcast {} version {}", E::SUBJECT, version))?;
+        }
+        let payload = serde_json::from_value(payload)
+            .with_context(|| format!("Failed to deserialize the {} payload", E::SUBJECT))?;
+        Ok(Envelope {
+            event_id: envelope.event_id,
+            occurred_at: envelope.occurred_at,
+            schema_version: envelope.schema_version,
+            headers: envelope.headers,
+            payload,
+        })
+    }
+}
+
+impl Envelope<serde_json::Value> {
+    /// The `subscription_id` of the payload, with or without an envelope.
+    /// Read from the raw data, so that it is known even if the payload does not decode.
+    pub(crate) fn subscription_id(data: &[u8]) -> Option<Uuid> {
+        let value: serde_json::Value = serde_json::from_slice(data).ok()?;
+        let payload = value.get("payload").unwrap_or(&value);
+        serde_json::from_value(payload.get("subscription_id")?.clone()).ok()
+    }
+
+    /// A bare payload, published before the envelopes.
+    /// The event id is derived from the data, so that redeliveries are still recognised,
+    /// and the payloads back then carried their own `occurred_at`.
+    fn legacy(data: &[u8], payload: serde_json::Value) -> Self {
+        let digest = Sha256::digest(data);
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&digest[..16]);
+        let event_id = Builder::from_bytes(bytes)
+            .set_variant(Variant::RFC4122)
+            .set_version(Version::Sha1)
+            .build();
+        let occurred_at = payload
+            .get("occurred_at")
+            .and_then(|occurred_at| serde_json::from_value(occurred_at.clone()).ok())
+            .unwrap_or_else(Utc::now);
+        Self {
+            event_id,
+            occurred_at,
+            schema_version: 0,
+            headers: BTreeMap::new(),
+            payload,
+        }
+    }
+}
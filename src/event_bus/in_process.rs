@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::event_bus::transport::{
+    Delivery, EventTransport, SubscriptionDrainer, TransportSubscription,
+};
+
+/// Delivers the messages to the subscribers of the same process, with the same
+/// queue group semantics as NATS. Nothing is persisted: messages published
+/// while nobody is subscribed are dropped, like with core NATS.
+#[derive(Default)]
+pub struct InProcessTransport {
+    subjects: Arc<Mutex<HashMap<String, SubjectSubscribers>>>,
+}
+
+#[derive(Default)]
+struct SubjectSubscribers {
+    subscribers: Vec<Subscriber>,
+    /// Round-robins the messages between the members of a queue group
+    published: usize,
+}
+
+struct Subscriber {
+    id: Uuid,
+    queue_group: Option<String>,
+    sender: mpsc::UnboundedSender<Vec<u8>>,
+}
+
+impl InProcessTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl EventTransport for InProcessTransport {
+    async fn publish(&self, subject: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        let mut subjects = self.subjects.lock().unwrap();
+        let subject = match subjects.get_mut(subject) {
+            Some(subject) => subject,
+            None => return Ok(()),
+        };
+        subject
+            .subscribers
+            .retain(|subscriber| !subscriber.sender.is_closed());
+        let mut queue_groups: HashMap<&str, Vec<&Subscriber>> = HashMap::new();
+        for subscriber in &subject.subscribers {
+            match &subscriber.queue_group {
+                Some(queue_group) => queue_groups
+                    .entry(queue_group.as_str())
+                    .or_default()
+                    .push(subscriber),
+                // A send only fails if the receiver was dropped in the meantime
+                None => {
+                    let _ = subscriber.sender.send(data.clone());
+                }
+            }
+        }
+        for members in queue_groups.values() {
+            let member = members[subject.published % members.len()];
+            let _ = member.sender.send(data.clone());
+        }
+        subject.published = subject.published.wrapping_add(1);
+        Ok(())
+    }
+
+    async fn subscribe(
+        &self,
+        subject: &str,
+        queue_group: Option<&str>,
+    ) -> anyhow::Result<Box<dyn TransportSubscription>> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let id = Uuid::new_v4();
+        self.subjects
+            .lock()
+            .unwrap()
+            .entry(subject.to_owned())
+            .or_default()
+            .subscribers
+            .push(Subscriber {
+                id,
+                queue_group: queue_group.map(str::to_owned),
+                sender,
+            });
+        Ok(Box::new(InProcessSubscription {
+            receiver,
+            drainer: InProcessDrainer {
+                subjects: self.subjects.clone(),
+                subject: subject.to_owned(),
+                id,
+            },
+        }))
+    }
+}
+
+struct InProcessSubscription {
+    receiver: mpsc::UnboundedReceiver<Vec<u8>>,
+    drainer: InProcessDrainer,
+}
+
+#[async_trait]
+impl TransportSubscription for InProcessSubscription {
+    async fn next(&mut self) -> Option<Delivery> {
+        let data = self.receiver.recv().await?;
+        Some(Delivery::at_most_once(data))
+    }
+
+    fn drainer(&self) -> Box<dyn SubscriptionDrainer> {
+        Box::new(self.drainer.clone())
+    }
+}
+
+#[derive(Clone)]
+struct InProcessDrainer {
+    subjects: Arc<Mutex<HashMap<String, SubjectSubscribers>>>,
+    subject: String,
+    id: Uuid,
+}
+
+#[async_trait]
+impl SubscriptionDrainer for InProcessDrainer {
+    /// Dropping the sender ends the subscription once the messages sent so far are received
+    async fn drain(&self) -> anyhow::Result<()> {
+        if let Some(subject) = self.subjects.lock().unwrap().get_mut(&self.subject) {
+            subject
+                .subscribers
+                .retain(|subscriber| subscriber.id != self.id);
+        }
+        Ok(())
+    }
+}
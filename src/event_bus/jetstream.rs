@@ -0,0 +1,326 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::config::Config;
+use crate::event_bus::nats::{NatsDrainer, NatsTransport};
+use crate::event_bus::transport::{
+    Acknowledger, Delivery, EventTransport, Settlement, SubscriptionDrainer, TransportSubscription,
+};
+
+const API_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a pull request waits for a message before it is renewed
+const PULL_EXPIRY: Duration = Duration::from_secs(30);
+
+/// Persists the events in a JetStream stream, so that the events published
+/// while no consumer runs are not lost. Each queue group is a durable pull
+/// consumer: messages are acked once handled, nacked with a growing delay when
+/// handling fails, and delivered at most `JETSTREAM_MAX_DELIVER` times.
+/// Plain subscribers (`queue_group: None`) go through core NATS.
+pub struct JetStreamTransport {
+    core: NatsTransport,
+    nats_connection: async_nats::Connection,
+    stream: String,
+    max_deliver: u32,
+    ack_wait: Duration,
+    nack_delay: Duration,
+}
+
+/// An error returned by the JetStream API
+#[derive(thiserror::Error, Debug, Deserialize)]
+#[error("JetStream API error {code}: {description}")]
+struct ApiError {
+    code: u16,
+    #[serde(default)]
+    err_code: u32,
+    description: String,
+}
+
+/// The stream already exists
+const STREAM_NAME_IN_USE: u32 = 10058;
+
+impl JetStreamTransport {
+    /// Creates the stream of the application events, or updates its settings
+    pub async fn new(
+        nats_connection: async_nats::Connection,
+        config: &Config,
+    ) -> anyhow::Result<Self> {
+        let transport = Self {
+            core: NatsTransport::new(nats_connection.clone()),
+            nats_connection,
+            stream: config.jetstream_stream.clone(),
+            max_deliver: config.jetstream_max_deliver,
+            ack_wait: Duration::from_millis(config.jetstream_ack_wait_millis),
+            nack_delay: Duration::from_millis(config.jetstream_nack_delay_millis),
+        };
+        let stream_config = json!({
+            "name": transport.stream,
+            "subjects": [format!("{}.>", config.application_id)],
+            "retention": "limits",
+            "storage": "file",
+            "max_age": duration_nanos(Duration::from_secs(
+                u64::from(config.jetstream_max_age_hours) * 60 * 60
+            )),
+        });
+        let created = transport
+            .api_request(
+                &format!("STREAM.CREATE.{}", transport.stream),
+                &stream_config,
+            )
+            .await?;
+        match created {
+            Err(ApiError {
+                err_code: STREAM_NAME_IN_USE,
+                ..
+            }) => {
+                transport
+                    .api_request(
+                        &format!("STREAM.UPDATE.{}", transport.stream),
+                        &stream_config,
+                    )
+                    .await??;
+            }
+            created => {
+                created?;
+            }
+        }
+        Ok(transport)
+    }
+
+    /// The consumer is shared by every instance with the same `queue_group`
+    async fn create_consumer(&self, subject: &str, queue_group: &str) -> anyhow::Result<String> {
+        // Durable names cannot contain dots
+        let event = subject.rsplit('.').next().unwrap_or(subject);
+        let durable = format!("{}-{}", queue_group, event);
+        let consumer_config = json!({
+            "stream_name": self.stream,
+            "config": {
+                "durable_name": durable,
+                "deliver_policy": "all",
+                "ack_policy": "explicit",
+                "ack_wait": duration_nanos(self.ack_wait),
+                "max_deliver": self.max_deliver,
+                "filter_subject": subject,
+                "replay_policy": "instant",
+            },
+        });
+        self.api_request(
+            &format!("CONSUMER.DURABLE.CREATE.{}.{}", self.stream, durable),
+            &consumer_config,
+        )
+        .await??;
+        Ok(durable)
+    }
+
+    async fn api_request(
+        &self,
+        operation: &str,
+        body: &serde_json::Value,
+    ) -> anyhow::Result<Result<serde_json::Value, ApiError>> {
+        let response = self
+            .nats_connection
+            .request_timeout(
+                &format!("$JS.API.{}", operation),
+                serde_json::to_vec(body)?,
+                API_TIMEOUT,
+            )
+            .await
+            .with_context(|| format!("JetStream API request {} failed", operation))?;
+        let mut response: serde_json::Value = serde_json::from_slice(&response.data)
+            .with_context(|| format!("Invalid JetStream API response to {}", operation))?;
+        match response.get_mut("error") {
+            Some(error) => Ok(Err(serde_json::from_value(error.take())?)),
+            None => Ok(Ok(response)),
+        }
+    }
+}
+
+#[async_trait]
+impl EventTransport for JetStreamTransport {
+    /// Waits for the stream to store the message
+    async fn publish(&self, subject: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        let response = self
+            .nats_connection
+            .request_timeout(subject, data, API_TIMEOUT)
+            .await
+            .with_context(|| format!("JetStream did not store the message on {}", subject))?;
+        let mut ack: serde_json::Value = serde_json::from_slice(&response.data)
+            .with_context(|| format!("Invalid JetStream publish ack on {}", subject))?;
+        if let Some(error) = ack.get_mut("error") {
+            let error: ApiError = serde_json::from_value(error.take())?;
+            return Err(error).with_context(|| format!("Failed to publish to {}", subject));
+        }
+        Ok(())
+    }
+
+    async fn close(&self) -> anyhow::Result<()> {
+        self.core.close().await
+    }
+
+    async fn subscribe(
+        &self,
+        subject: &str,
+        queue_group: Option<&str>,
+    ) -> anyhow::Result<Box<dyn TransportSubscription>> {
+        let queue_group = match queue_group {
+            Some(queue_group) => queue_group,
+            None => return self.core.subscribe(subject, None).await,
+        };
+        let durable = self
+            .create_consumer(subject, queue_group)
+            .await
+            .with_context(|| format!("Failed to create the consumer of {}", subject))?;
+        let inbox = self.nats_connection.new_inbox();
+        let inbox_subscription = self
+            .nats_connection
+            .subscribe(&inbox)
+            .await
+            .context("Failed to subscribe to the pull inbox")?;
+        Ok(Box::new(PullSubscription {
+            nats_connection: self.nats_connection.clone(),
+            next_subject: format!("$JS.API.CONSUMER.MSG.NEXT.{}.{}", self.stream, durable),
+            inbox,
+            inbox_subscription: Arc::new(inbox_subscription),
+            pulling: false,
+            draining: Arc::new(AtomicBool::new(false)),
+            max_deliver: self.max_deliver,
+            nack_delay: self.nack_delay,
+        }))
+    }
+}
+
+/// Pulls the messages one at a time, so that other instances get the next ones
+struct PullSubscription {
+    nats_connection: async_nats::Connection,
+    next_subject: String,
+    inbox: String,
+    inbox_subscription: Arc<async_nats::Subscription>,
+    /// A pull request is waiting for a message
+    pulling: bool,
+    /// No more pull requests once set
+    draining: Arc<AtomicBool>,
+    max_deliver: u32,
+    nack_delay: Duration,
+}
+
+#[async_trait]
+impl TransportSubscription for PullSubscription {
+    async fn next(&mut self) -> Option<Delivery> {
+        loop {
+            if !self.pulling && !self.draining.load(Ordering::SeqCst) {
+                let request = json!({ "batch": 1, "expires": duration_nanos(PULL_EXPIRY) });
+                let published = self
+                    .nats_connection
+                    .publish_request(&self.next_subject, &self.inbox, request.to_string())
+                    .await;
+                if let Err(err) = published {
+                    tracing::warn!(error = %err, "Failed to pull from JetStream, retrying");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+                self.pulling = true;
+            }
+            // The server answers an expired pull request, but the answer can be lost.
+            // A message received as the wait times out is dropped, and redelivered after the ack wait.
+            let received = tokio::time::timeout(
+                PULL_EXPIRY + Duration::from_secs(1),
+                self.inbox_subscription.next(),
+            )
+            .await;
+            let message = match received {
+                Ok(Some(message)) => message,
+                Ok(None) => return None,
+                Err(_) => {
+                    self.pulling = false;
+                    continue;
+                }
+            };
+            self.pulling = false;
+            // Status messages (e.g. 408 Request Timeout) have no ack subject
+            let reply = match message.reply {
+                Some(reply) if reply.starts_with("$JS.ACK.") => reply,
+                _ => continue,
+            };
+            let attempt = delivery_attempt(&reply).unwrap_or(1);
+            let acknowledger = JetStreamAcknowledger {
+                nats_connection: self.nats_connection.clone(),
+                reply,
+                nack_delay: self.nack_delay,
+            };
+            return Some(Delivery::redeliverable(
+                message.data,
+                attempt,
+                attempt >= self.max_deliver,
+                Box::new(acknowledger),
+            ));
+        }
+    }
+
+    fn drainer(&self) -> Box<dyn SubscriptionDrainer> {
+        Box::new(PullDrainer {
+            draining: self.draining.clone(),
+            inbox: NatsDrainer(self.inbox_subscription.clone()),
+        })
+    }
+}
+
+/// A message answering a pending pull request after the drain is not acked,
+/// so it is redelivered once `JETSTREAM_ACK_WAIT_MILLIS` is over
+struct PullDrainer {
+    draining: Arc<AtomicBool>,
+    inbox: NatsDrainer,
+}
+
+#[async_trait]
+impl SubscriptionDrainer for PullDrainer {
+    async fn drain(&self) -> anyhow::Result<()> {
+        self.draining.store(true, Ordering::SeqCst);
+        self.inbox.drain().await
+    }
+}
+
+struct JetStreamAcknowledger {
+    nats_connection: async_nats::Connection,
+    reply: String,
+    nack_delay: Duration,
+}
+
+#[async_trait]
+impl Acknowledger for JetStreamAcknowledger {
+    async fn settle(&self, attempt: u32, settlement: Settlement) -> anyhow::Result<()> {
+        let body = match settlement {
+            Settlement::Ack => "+ACK".to_owned(),
+            // The delay grows with every failed attempt
+            Settlement::Nack => format!(
+                "-NAK {}",
+                json!({ "delay": duration_nanos(self.nack_delay * attempt) })
+            ),
+            Settlement::Term => "+TERM".to_owned(),
+        };
+        self.nats_connection
+            .publish(&self.reply, body)
+            .await
+            .context("Failed to settle the JetStream message")
+    }
+}
+
+/// Ack subjects are `$JS.ACK.<stream>.<consumer>.<delivered>.<stream seq>.<consumer seq>.<timestamp>.<pending>`,
+/// with the domain and the account hash after `$JS.ACK` on newer servers
+fn delivery_attempt(reply: &str) -> Option<u32> {
+    let tokens: Vec<&str> = reply.split('.').collect();
+    let delivered = match tokens.len() {
+        9 => tokens[4],
+        len if len >= 12 => tokens[6],
+        _ => return None,
+    };
+    delivered.parse().ok()
+}
+
+fn duration_nanos(duration: Duration) -> u64 {
+    duration.as_nanos().try_into().unwrap_or(u64::MAX)
+}
@@ -0,0 +1,310 @@
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::dead_letter_queries::{DeadLetterQueries, NewDeadLetter};
+use crate::event_bus::in_process::InProcessTransport;
+use crate::event_bus::jetstream::JetStreamTransport;
+use crate::event_bus::nats::NatsTransport;
+use crate::event_bus::transport::{
+    Delivery, EventTransport, Settlement, SubscriptionDrainer, TransportSubscription,
+};
+
+pub use envelope::Envelope;
+
+mod envelope;
+mod in_process;
+mod jetstream;
+mod nats;
+mod transport;
+
+/// A message published on the `EventBus`.
+/// Every event has its own subject, and a schema version so that consumers
+/// can still read what was published before a change of the payload.
+pub trait Event: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Prefixed with `<application id>.` to get the subject
+    const SUBJECT: &'static str;
+    /// Bumped on every breaking change of the payload, together with an `upcast` step
+    const SCHEMA_VERSION: u32 = 1;
+
+    /// Turns a payload of `version` into a payload of `version + 1`.
+    /// Version 0 is a payload published without an envelope, the same as version 1 by default.
+    fn upcast(version: u32, payload: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        match version {
+            0 => Ok(payload),
+            _ => anyhow::bail!("{} has no upcaster from version {}", Self::SUBJECT, version),
+        }
+    }
+}
+
+/// Consumes the events of one type.
+/// A failed event is delivered again while the transport allows it,
+/// then stored as a dead letter if the bus has a `dead_letters` pool.
+#[async_trait]
+pub trait Handler<E: Event>: Send + Sync + 'static {
+    async fn handle(&self, envelope: Envelope<E>) -> anyhow::Result<()>;
+
+    /// Called once the last delivery of the event failed
+    async fn give_up(&self, _envelope: Envelope<E>) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum EventBusBackend {
+    Nats,
+    /// Persisted events, redelivered until handled, see `JetStreamTransport`
+    #[serde(rename = "jetstream")]
+    JetStream,
+    /// Single node deployments: publishers and consumers have to run in the same process
+    InProcess,
+}
+
+/// Typed publishing and consuming, on top of NATS, JetStream or in-process channels
+#[derive(Clone)]
+pub struct EventBus {
+    transport: Arc<dyn EventTransport>,
+    application_id: String,
+    queue_group: String,
+    consumers: Arc<Mutex<Vec<Consumer>>>,
+    dead_letters: Option<PgPool>,
+}
+
+/// The subscription of a handler
+struct Consumer {
+    subject: &'static str,
+    drainer: Box<dyn SubscriptionDrainer>,
+}
+
+impl EventBus {
+    /// Connects to NATS if it is the configured backend, and creates the JetStream stream
+    pub async fn connect(config: &Config) -> anyhow::Result<Self> {
+        let transport: Arc<dyn EventTransport> = match config.event_bus_backend {
+            EventBusBackend::Nats => Arc::new(NatsTransport::new(connect_nats(config).await?)),
+            EventBusBackend::JetStream => Arc::new(
+                JetStreamTransport::new(connect_nats(config).await?, config)
+                    .await
+                    .context("Failed to set up the JetStream stream")?,
+            ),
+            EventBusBackend::InProcess => Arc::new(InProcessTransport::new()),
+        };
+        Ok(Self {
+            transport,
+            application_id: config.application_id.clone(),
+            queue_group: config.nats_queue_group.clone(),
+            consumers: Arc::default(),
+            dead_letters: None,
+        })
+    }
+
+    /// The events the handlers give up on are stored in `dead_letters`, to be replayed later.
+    /// Only the handlers subscribed afterwards store them.
+    pub fn with_dead_letters(mut self, pg_pool: PgPool) -> Self {
+        self.dead_letters = Some(pg_pool);
+        self
+    }
+
+    pub fn subject<E: Event>(&self) -> String {
+        format!("{}.{}", self.application_id, E::SUBJECT)
+    }
+
+    /// Returns the id of the published event
+    pub async fn publish<E: Event>(&self, event: E) -> anyhow::Result<Uuid> {
+        let envelope = Envelope::new(event);
+        self.publish_envelope(&envelope).await?;
+        Ok(envelope.event_id)
+    }
+
+    pub async fn publish_envelope<E: Event>(&self, envelope: &Envelope<E>) -> anyhow::Result<()> {
+        self.transport
+            .publish(&self.subject::<E>(), envelope.encode()?)
+            .await
+            .with_context(|| format!("Failed to publish {}", E::SUBJECT))
+    }
+
+    /// Publishes an envelope as it was delivered, e.g. a dead letter.
+    /// `subject` is the `Event::SUBJECT` of the envelope.
+    pub async fn republish(&self, subject: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        self.transport
+            .publish(&format!("{}.{}", self.application_id, subject), data)
+            .await
+            .with_context(|| format!("Failed to republish {}", subject))
+    }
+
+    /// Every instance joins the same queue group, so each event is handled once.
+    /// Events published once this returns reach the handler.
+    /// The task ends once the bus is drained and the events received so far are handled.
+    pub async fn subscribe<E: Event, H: Handler<E>>(
+        &self,
+        handler: H,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        let mut subscription = self
+            .transport
+            .subscribe(&self.subject::<E>(), Some(&self.queue_group))
+            .await
+            .with_context(|| format!("Failed to subscribe to {}", E::SUBJECT))?;
+        self.consumers.lock().unwrap().push(Consumer {
+            subject: E::SUBJECT,
+            drainer: subscription.drainer(),
+        });
+        let dead_letters = self.dead_letters.clone();
+        Ok(tokio::spawn(async move {
+            while let Some(delivery) = subscription.next().await {
+                dispatch(&handler, delivery, dead_letters.as_ref()).await;
+            }
+            tracing::info!(subject = E::SUBJECT, "Event consumer stopped");
+        }))
+    }
+
+    /// Stops the handlers from receiving events, e.g. leaves the queue group on shutdown
+    pub async fn drain(&self) {
+        let consumers = std::mem::take(&mut *self.consumers.lock().unwrap());
+        for consumer in consumers {
+            tracing::info!(subject = consumer.subject, "Draining the event consumer");
+            if let Err(err) = consumer.drainer.drain().await {
+                tracing::error!(
+                    error = ?err,
+                    subject = consumer.subject,
+                    "Failed to drain the event consumer"
+                );
+            }
+        }
+    }
+
+    /// Nothing can be published afterwards
+    pub async fn close(&self) -> anyhow::Result<()> {
+        self.transport.close().await
+    }
+
+    /// Every event of the type, outside of the queue group, e.g. to observe them in tests
+    pub async fn listen<E: Event>(&self) -> anyhow::Result<EventStream<E>> {
+        let subscription = self
+            .transport
+            .subscribe(&self.subject::<E>(), None)
+            .await
+            .with_context(|| format!("Failed to listen to {}", E::SUBJECT))?;
+        Ok(EventStream {
+            subscription,
+            event: PhantomData,
+        })
+    }
+}
+
+pub struct EventStream<E> {
+    subscription: Box<dyn TransportSubscription>,
+    event: PhantomData<E>,
+}
+
+impl<E: Event> EventStream<E> {
+    /// `None` once the transport is closed
+    pub async fn next(&mut self) -> Option<anyhow::Result<Envelope<E>>> {
+        let delivery = self.subscription.next().await?;
+        Some(Envelope::decode(&delivery.data))
+    }
+}
+
+async fn connect_nats(config: &Config) -> anyhow::Result<async_nats::Connection> {
+    async_nats::connect(&format!("{}:{}", config.nats_host, config.nats_port))
+        .await
+        .context("Failed to connect to NATS")
+}
+
+/// Acks the handled events, nacks the failed ones until the last attempt
+async fn dispatch<E: Event, H: Handler<E>>(
+    handler: &H,
+    delivery: Delivery,
+    dead_letters: Option<&PgPool>,
+) {
+    let envelope = match Envelope::<E>::decode(&delivery.data) {
+        Ok(envelope) => envelope,
+        Err(err) => {
+            tracing::error!(error = ?err, subject = E::SUBJECT, "Could not decode the event");
+            store_dead_letter::<E>(dead_letters, &delivery.data, delivery.attempt, None, &err)
+                .await;
+            // Would fail the same way on every delivery
+            settle(delivery, Settlement::Term).await;
+            return;
+        }
+    };
+    let event_id = envelope.event_id;
+    let span = tracing::info_span!(
+        "Handling event",
+        subject = E::SUBJECT,
+        event_id = %envelope.event_id,
+        schema_version = envelope.schema_version,
+        attempt = delivery.attempt,
+    );
+    let settlement = match handler.handle(envelope).instrument(span.clone()).await {
+        Ok(()) => Settlement::Ack,
+        Err(err) if !delivery.last_attempt => {
+            span.in_scope(|| {
+                tracing::warn!(error = ?err, "Failed to handle the event, it will be redelivered")
+            });
+            Settlement::Nack
+        }
+        Err(err) => {
+            span.in_scope(
+                || tracing::error!(error = ?err, "Failed to handle the event, giving up"),
+            );
+            // The envelope was moved into the handler, and decoding succeeded once already
+            if let Ok(envelope) = Envelope::<E>::decode(&delivery.data) {
+                if let Err(err) = handler.give_up(envelope).instrument(span.clone()).await {
+                    span.in_scope(
+                        || tracing::error!(error = ?err, "Failed to give up on the event"),
+                    );
+                }
+            }
+            store_dead_letter::<E>(
+                dead_letters,
+                &delivery.data,
+                delivery.attempt,
+                Some(&event_id),
+                &err,
+            )
+            .instrument(span.clone())
+            .await;
+            Settlement::Term
+        }
+    };
+    settle(delivery, settlement).instrument(span).await;
+}
+
+async fn store_dead_letter<E: Event>(
+    dead_letters: Option<&PgPool>,
+    data: &[u8],
+    attempts: u32,
+    event_id: Option<&Uuid>,
+    err: &anyhow::Error,
+) {
+    let pg_pool = match dead_letters {
+        Some(pg_pool) => pg_pool,
+        None => return,
+    };
+    let dead_letter = NewDeadLetter {
+        subject: E::SUBJECT,
+        event_id,
+        subscription_id: Envelope::subscription_id(data),
+        data,
+        error: &format!("{:#}", err),
+        attempts,
+    };
+    if let Err(err) = DeadLetterQueries::insert_dead_letter(pg_pool, &dead_letter).await {
+        tracing::error!(error = ?err, "Failed to store the dead letter, the event is lost");
+    }
+}
+
+async fn settle(delivery: Delivery, settlement: Settlement) {
+    if let Err(err) = delivery.settle(settlement).await {
+        tracing::error!(error = ?err, "Failed to settle the event");
+    }
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+
+use anyhow::Context;
+use async_trait::async_trait;
+
+use crate::event_bus::transport::{
+    Delivery, EventTransport, SubscriptionDrainer, TransportSubscription,
+};
+
+pub struct NatsTransport {
+    nats_connection: async_nats::Connection,
+}
+
+impl NatsTransport {
+    pub fn new(nats_connection: async_nats::Connection) -> Self {
+        Self { nats_connection }
+    }
+}
+
+#[async_trait]
+impl EventTransport for NatsTransport {
+    async fn publish(&self, subject: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        self.nats_connection
+            .publish(subject, data)
+            .await
+            .with_context(|| format!("Failed to publish to {}", subject))
+    }
+
+    async fn subscribe(
+        &self,
+        subject: &str,
+        queue_group: Option<&str>,
+    ) -> anyhow::Result<Box<dyn TransportSubscription>> {
+        let subscription = match queue_group {
+            Some(queue_group) => {
+                self.nats_connection
+                    .queue_subscribe(subject, queue_group)
+                    .await
+            }
+            None => self.nats_connection.subscribe(subject).await,
+        }
+        .with_context(|| format!("Failed to subscribe to {}", subject))?;
+        Ok(Box::new(NatsSubscription(Arc::new(subscription))))
+    }
+
+    async fn close(&self) -> anyhow::Result<()> {
+        self.nats_connection
+            .close()
+            .await
+            .context("Failed to close the NATS connection")
+    }
+}
+
+/// Shared with its drainer: `next` is not cancel safe, messages would be lost
+/// if the consumers stopped awaiting it instead of draining
+struct NatsSubscription(Arc<async_nats::Subscription>);
+
+#[async_trait]
+impl TransportSubscription for NatsSubscription {
+    async fn next(&mut self) -> Option<Delivery> {
+        let message = self.0.next().await?;
+        Some(Delivery::at_most_once(message.data))
+    }
+
+    fn drainer(&self) -> Box<dyn SubscriptionDrainer> {
+        Box::new(NatsDrainer(self.0.clone()))
+    }
+}
+
+pub struct NatsDrainer(pub Arc<async_nats::Subscription>);
+
+#[async_trait]
+impl SubscriptionDrainer for NatsDrainer {
+    /// Unsubscribes, e.g. leaves the queue group, so that the other members get the next messages
+    async fn drain(&self) -> anyhow::Result<()> {
+        self.0
+            .drain()
+            .await
+            .context("Failed to drain the NATS subscription")
+    }
+}
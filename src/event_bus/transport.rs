@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+
+/// Moves encoded envelopes between publishers and subscribers
+#[async_trait]
+pub trait EventTransport: Send + Sync + 'static {
+    async fn publish(&self, subject: &str, data: Vec<u8>) -> anyhow::Result<()>;
+
+    /// Every message goes to each plain subscriber (`queue_group: None`),
+    /// and to a single subscriber of each queue group
+    async fn subscribe(
+        &self,
+        subject: &str,
+        queue_group: Option<&str>,
+    ) -> anyhow::Result<Box<dyn TransportSubscription>>;
+
+    /// Sends what is still buffered, nothing can be published afterwards
+    async fn close(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+pub trait TransportSubscription: Send {
+    /// `None` once the subscription is drained or the transport is closed
+    async fn next(&mut self) -> Option<Delivery>;
+
+    /// Stops the subscription from another task, while `next` is awaited
+    fn drainer(&self) -> Box<dyn SubscriptionDrainer>;
+}
+
+#[async_trait]
+pub trait SubscriptionDrainer: Send + Sync {
+    /// No more messages are received: `next` returns the ones received so far, then `None`
+    async fn drain(&self) -> anyhow::Result<()>;
+}
+
+/// A message taken from a subscription.
+/// Transports with redelivery expect every delivery to be settled.
+pub struct Delivery {
+    pub data: Vec<u8>,
+    /// Starts at 1
+    pub attempt: u32,
+    /// A failure is final: the message is not delivered again
+    pub last_attempt: bool,
+    acknowledger: Option<Box<dyn Acknowledger>>,
+}
+
+pub enum Settlement {
+    /// Handled, not to be delivered again
+    Ack,
+    /// Failed, to be delivered again after a delay
+    Nack,
+    /// Failed for good, not to be delivered again
+    Term,
+}
+
+#[async_trait]
+pub trait Acknowledger: Send {
+    async fn settle(&self, attempt: u32, settlement: Settlement) -> anyhow::Result<()>;
+}
+
+impl Delivery {
+    /// Delivered once whatever happens, there is nothing to settle
+    pub fn at_most_once(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            attempt: 1,
+            last_attempt: true,
+            acknowledger: None,
+        }
+    }
+
+    pub fn redeliverable(
+        data: Vec<u8>,
+        attempt: u32,
+        last_attempt: bool,
+        acknowledger: Box<dyn Acknowledger>,
+    ) -> Self {
+        Self {
+            data,
+            attempt,
+            last_attempt,
+            acknowledger: Some(acknowledger),
+        }
+    }
+
+    pub async fn settle(self, settlement: Settlement) -> anyhow::Result<()> {
+        match self.acknowledger {
+            Some(acknowledger) => acknowledger.settle(self.attempt, settlement).await,
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::future::Future;
+
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::processed_event_queries::{EventClaim, ProcessedEventQueries};
+
+/// Runs `handle` once per event for `consumer`, without holding a transaction while it runs:
+/// the event is claimed for `PROCESSED_EVENTS_LEASE_MILLIS`, handled, then recorded as processed.
+/// A failure releases the claim, so that the next delivery handles the event again.
+/// A delivery that crashed keeps its claim until the lease expires.
+pub async fn handle_once<F, Fut>(
+    config: &Config,
+    pg_pool: &PgPool,
+    consumer: &str,
+    event_id: &Uuid,
+    handle: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let lease = chrono::Duration::milliseconds(config.processed_events_lease_millis as i64);
+    match ProcessedEventQueries::claim_event(pg_pool, consumer, event_id, lease)
+        .await
+        .context("Failed to claim the event")?
+    {
+        EventClaim::Claimed => {}
+        EventClaim::AlreadyProcessed => {
+            tracing::info!(consumer, "Event already processed, skipping");
+            return Ok(());
+        }
+        // Redelivered later, in case the other delivery fails
+        EventClaim::InProgress => anyhow::bail!("The event is being handled by another delivery"),
+    }
+    if let Err(err) = handle().await {
+        if let Err(release_err) =
+            ProcessedEventQueries::release_event(pg_pool, consumer, event_id).await
+        {
+            tracing::error!(
+                error = ?release_err,
+                "Failed to release the event, the next delivery waits for the lease"
+            );
+        }
+        return Err(err);
+    }
+    ProcessedEventQueries::complete_event(pg_pool, consumer, event_id)
+        .await
+        .context("Failed to record the processed event")
+}
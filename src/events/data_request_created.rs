@@ -0,0 +1,107 @@
+use crate::config::Config;
+use crate::event_bus::{Envelope, Event, Handler};
+use crate::events::claim::handle_once;
+use anyhow::Context;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::sync::Arc;
+use uuid::Uuid;
+
+use crate::domain::data_request_kind::DataRequestKind;
+use crate::domain::email_template::EmailTemplate;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::{EmailClient, SendOutcome};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DataRequestCreated {
+    pub subscription_id: Uuid,
+    pub email: SubscriberEmail,
+    pub kind: DataRequestKind,
+    pub data_request_token: Uuid,
+}
+
+impl Event for DataRequestCreated {
+    const SUBJECT: &'static str = "data-request-created";
+}
+
+/// Emails the export or erasure link, once per event
+pub struct DataRequestCreatedHandler {
+    pub config: Arc<Config>,
+    pub email_client: Arc<EmailClient>,
+    pub pg_pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl Handler<DataRequestCreated> for DataRequestCreatedHandler {
+    #[tracing::instrument(
+        name = "Processing DataRequestCreated event",
+        skip(self, envelope),
+        fields(subscription_id = %envelope.payload.subscription_id)
+    )]
+    async fn handle(&self, envelope: Envelope<DataRequestCreated>) -> anyhow::Result<()> {
+        handle_once(
+            &self.config,
+            &self.pg_pool,
+            DataRequestCreatedHandler::CONSUMER,
+            &envelope.event_id,
+            || self.send_link(&envelope.payload),
+        )
+        .await
+    }
+}
+
+impl DataRequestCreatedHandler {
+    /// Names the handler in `processed_events`
+    pub const CONSUMER: &'static str = "data-request-created-email";
+
+    async fn send_link(&self, event: &DataRequestCreated) -> anyhow::Result<()> {
+        let (template, subject, text_content) = match event.kind {
+            DataRequestKind::Export => (
+                EmailTemplate::DataExport,
+                "Your personal data export",
+                format!(
+                    "We received a request to export the data we store about you.\n\
+                    Visit {}/subscriptions/data_export?data_request_token={} \
+                    to download it.\n\
+                    If you did not request this, you can ignore this email.",
+                    self.config.application_base_url(),
+                    event.data_request_token
+                ),
+            ),
+            DataRequestKind::Erasure => (
+                EmailTemplate::DataErasure,
+                "Confirm the erasure of your personal data",
+                format!(
+                    "We received a request to erase the data we store about you.\n\
+                    Visit {}/subscriptions/erase?data_request_token={} \
+                    to confirm. This cannot be undone.\n\
+                    If you did not request this, you can ignore this email.",
+                    self.config.application_base_url(),
+                    event.data_request_token
+                ),
+            ),
+        };
+        // Nothing to roll back if every delivery fails: the token simply expires unused
+        let outcome = self
+            .email_client
+            .deliver(
+                Some(&event.subscription_id),
+                template,
+                &event.email,
+                subject,
+                &text_content,
+            )
+            .await
+            .context("Failed to send DataRequestCreated event mail")?;
+        match outcome {
+            SendOutcome::Sent(_) => {
+                tracing::info!("DataRequestCreated event email sent")
+            }
+            SendOutcome::Suppressed => {
+                tracing::info!("DataRequestCreated event email suppressed")
+            }
+        }
+        Ok(())
+    }
+}
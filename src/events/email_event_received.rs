@@ -0,0 +1,20 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::event_bus::Event;
+
+/// Published for every new event reported by the email provider.
+/// No consumer in this service yet, other services can react to bounces, opens, etc.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmailEventReceived {
+    pub email: String,
+    pub event_type: String,
+    pub provider_message_id: Option<String>,
+    pub occurred_at: DateTime<Utc>,
+    /// `true` if the event got the recipient suppressed (hard bounce or spam report)
+    pub suppressed: bool,
+}
+
+impl Event for EmailEventReceived {
+    const SUBJECT: &'static str = "email-event-received";
+}
@@ -1 +1,5 @@
+pub mod claim;
+pub mod data_request_created;
+pub mod email_event_received;
+pub mod subscriber_tag_changed;
 pub mod subscription_created;
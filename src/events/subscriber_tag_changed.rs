@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::event_bus::Event;
+
+/// Published for every subscriber that gained or lost a tag.
+/// No consumer in this service yet, other services can react to tagging, e.g. CRMs.
+/// Version 1 had an `occurred_at`, in the envelope since version 2.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SubscriberTagChanged {
+    pub subscription_id: Uuid,
+    pub email: String,
+    pub tag: String,
+    pub change: TagChange,
+}
+
+impl Event for SubscriberTagChanged {
+    const SUBJECT: &'static str = "subscriber-tag-changed";
+    const SCHEMA_VERSION: u32 = 2;
+
+    fn upcast(version: u32, mut payload: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        match version {
+            0 => Ok(payload),
+            1 => {
+                if let Some(payload) = payload.as_object_mut() {
+                    payload.remove("occurred_at");
+                }
+                Ok(payload)
+            }
+            _ => anyhow::bail!("Unknown version {}", version),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagChange {
+    Added,
+    Removed,
+}
@@ -1,114 +1,207 @@
 use crate::config::Config;
+use crate::db::processed_event_queries::ProcessedEventQueries;
 use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::event_bus::{Envelope, Event, Handler};
+use crate::events::claim::handle_once;
 use anyhow::Context;
-use async_nats::Message;
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 use std::sync::Arc;
-use tokio::task::JoinHandle;
 use uuid::Uuid;
 
+use crate::domain::email_template::EmailTemplate;
+use crate::domain::list_slug::{DEFAULT_LIST_NAME, DEFAULT_LIST_SLUG};
+use crate::domain::request_context::RequestContext;
 use crate::domain::subscriber_email::SubscriberEmail;
 use crate::domain::subscriber_name::SubscriberName;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
 use crate::domain::subscription_status::SubscriptionStatus;
-use crate::email_client::EmailClient;
+use crate::email_client::{EmailClient, SendOutcome};
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SubscriptionCreated {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
-    pub subscription_token: Uuid,
     pub subscription_id: Uuid,
+    /// Lists requested and not confirmed yet, each with its own confirmation token
+    pub lists: Vec<ListConfirmation>,
 }
 
-impl SubscriptionCreated {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListConfirmation {
+    pub slug: String,
+    pub name: String,
+    pub subscription_token: Uuid,
+}
+
+impl Event for SubscriptionCreated {
+    const SUBJECT: &'static str = "subscription-created";
+
+    /// Before the lists, version 0 payloads had a single `subscription_token`,
+    /// for what is now the default list
+    fn upcast(version: u32, mut payload: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        match version {
+            0 => {
+                if let Some(payload) = payload.as_object_mut() {
+                    if !payload.contains_key("lists") {
+                        if let Some(subscription_token) = payload.remove("subscription_token") {
+                            payload.insert(
+                                "lists".to_owned(),
+                                serde_json::json!([{
+                                    "slug": DEFAULT_LIST_SLUG,
+                                    "name": DEFAULT_LIST_NAME,
+                                    "subscription_token": subscription_token
+                                }]),
+                            );
+                        }
+                    }
+                }
+                Ok(payload)
+            }
+            _ => anyhow::bail!("Unknown version {}", version),
+        }
+    }
+}
+
+/// Sends the confirmation email, once per event
+pub struct SubscriptionCreatedHandler {
+    pub config: Arc<Config>,
+    pub email_client: Arc<EmailClient>,
+    pub pg_pool: Arc<PgPool>,
+}
+
+#[async_trait]
+impl Handler<SubscriptionCreated> for SubscriptionCreatedHandler {
+    /// The event is recorded as processed only if the email is sent
     #[tracing::instrument(
         name = "Processing SubscriptionCreated event",
-        skip(config, email_client, pg_pool, message),
-        fields(
-            message_subject = %message.subject,
+        skip(self, envelope),
+        fields(subscription_id = %envelope.payload.subscription_id)
+    )]
+    async fn handle(&self, envelope: Envelope<SubscriptionCreated>) -> anyhow::Result<()> {
+        handle_once(
+            &self.config,
+            &self.pg_pool,
+            SubscriptionCreatedHandler::CONSUMER,
+            &envelope.event_id,
+            || self.send_confirmation(&envelope.payload),
         )
+        .await
+    }
+
+    /// Every delivery of the event failed to send the email
+    #[tracing::instrument(
+        name = "Giving up on SubscriptionCreated event",
+        skip(self, envelope),
+        fields(subscription_id = %envelope.payload.subscription_id)
     )]
-    pub async fn process(
-        config: &Config,
-        email_client: &EmailClient,
-        pg_pool: &PgPool,
-        message: Message,
-    ) -> anyhow::Result<()> {
-        match serde_json::from_slice::<SubscriptionCreated>(&message.data) {
-            Ok(event) => {
-                let text_content = format!(
-                    "Welcome to our newsletter!\n\
-                    Visit {}/subscriptions/confirm?subscription_token={} \
-                    to confirm your subscription",
-                    config.application_base_url(),
-                    event.subscription_token
-                );
-                let mail_send_result = email_client
-                    .send_email(&event.email, "Subscription confirmation", &text_content)
-                    .await;
-                // TODO: should be proper retry mechanism with different retry + final fail branches
-                match mail_send_result {
-                    Ok(_) => {
-                        tracing::info!("SubscriptionCreated event email sent")
-                    }
-                    Err(err) => {
-                        tracing::error!(
-                            error = %err,
-                            "Failed to send SubscriptionCreated event mail, \
-                            setting the subscription status to failed",
-                        );
-                        let mut tx = pg_pool
-                            .begin()
-                            .await
-                            .context("Failed to acquire a transaction")?;
-                        let update_result = SubscriptionQueries::update_subscription_status(
-                            &mut tx,
-                            &event.subscription_id,
-                            SubscriptionStatus::Failed,
-                        )
-                        .await;
-                        let _ = SubscriptionQueries::delete_token(
-                            &mut tx,
-                            &event.subscription_token.to_string(),
-                        )
-                        .await;
-                        match update_result {
-                            Ok(_) => {}
-                            Err(_) => {
-                                tracing::error!("Failed to mark subscription as failed")
-                            }
-                        }
-                        tx.commit()
-                            .await
-                            .context("Failed to commit the transaction")?;
-                    }
-                }
+    async fn give_up(&self, envelope: Envelope<SubscriptionCreated>) -> anyhow::Result<()> {
+        // Another delivery sent the email, or is still sending it
+        if ProcessedEventQueries::is_claimed(
+            &*self.pg_pool,
+            SubscriptionCreatedHandler::CONSUMER,
+            &envelope.event_id,
+        )
+        .await
+        .context("Failed to check the event claim")?
+        {
+            tracing::info!(
+                "SubscriptionCreated event claimed by another delivery, keeping the status"
+            );
+            return Ok(());
+        }
+        tracing::info!("Setting the subscription status to failed");
+        SubscriptionCreated::mark_as_failed(&self.pg_pool, &envelope.payload).await
+    }
+}
+
+impl SubscriptionCreatedHandler {
+    /// Names the handler in `processed_events`
+    pub const CONSUMER: &'static str = "subscription-created-confirmation-email";
+
+    async fn send_confirmation(&self, event: &SubscriptionCreated) -> anyhow::Result<()> {
+        let confirmation_links: Vec<String> = event
+            .lists
+            .iter()
+            .map(|list| {
+                format!(
+                    "{}: {}/subscriptions/confirm?subscription_token={}",
+                    list.name,
+                    self.config.application_base_url(),
+                    list.subscription_token
+                )
+            })
+            .collect();
+        let text_content = format!(
+            "Welcome to our newsletter!\n\
+            Visit the link next to each list to confirm your subscription:\n{}",
+            confirmation_links.join("\n")
+        );
+        let outcome = self
+            .email_client
+            .deliver(
+                Some(&event.subscription_id),
+                EmailTemplate::SubscriptionConfirmation,
+                &event.email,
+                "Subscription confirmation",
+                &text_content,
+            )
+            .await
+            .context("Failed to send SubscriptionCreated event mail")?;
+        match outcome {
+            SendOutcome::Sent(_) => {
+                tracing::info!("SubscriptionCreated event email sent")
             }
-            Err(_) => {
-                tracing::error!("Could not deserialize message");
+            // Not a delivery failure: we are not allowed to email this address
+            SendOutcome::Suppressed => {
+                tracing::info!("SubscriptionCreated event email suppressed")
             }
-        };
+        }
         Ok(())
     }
+}
 
-    pub fn subscribe(
-        nats_connection: Arc<async_nats::Connection>,
-        config: Arc<Config>,
-        email_client: Arc<EmailClient>,
-        pg_pool: Arc<PgPool>,
-    ) -> JoinHandle<()> {
-        tokio::spawn(async move {
-            let sub_created = nats_connection
-                .queue_subscribe(
-                    &config.nats_subscription_created_subject(),
-                    &config.nats_subscription_created_group(),
-                )
+impl SubscriptionCreated {
+    /// The tokens are kept: replaying the dead letter of the event sends them,
+    /// and a failed subscription is confirmed by following one of them.
+    /// The status only changes if the state machine allows it:
+    /// a subscription confirmed in the meantime (via an earlier email) stays confirmed.
+    /// List memberships stay pending until the subscriber signs up again.
+    async fn mark_as_failed(pg_pool: &PgPool, event: &SubscriptionCreated) -> anyhow::Result<()> {
+        let subscription_token = event
+            .lists
+            .first()
+            .map(|list| list.subscription_token.to_string());
+        let mut tx = begin_transaction(pg_pool).await?;
+        let maybe_subscription =
+            SubscriptionQueries::fetch_subscription_by_id(&mut tx, &event.subscription_id)
                 .await
-                .expect("Failed to subscribe to SubscriptionCreated subject");
-            if let Some(msg) = sub_created.next().await {
-                let _ = SubscriptionCreated::process(&config, &email_client, &pg_pool, msg).await;
+                .context("Failed to fetch a subscription by id")?;
+        if let Some(subscription) = maybe_subscription {
+            let transition = SubscriptionTransition {
+                subscription_id: &event.subscription_id,
+                list_id: None,
+                from: subscription.status,
+                to: SubscriptionStatus::Failed,
+                source: SubscriptionEventSource::ConfirmationEmailFailure,
+                context: &RequestContext::default(),
+                token: subscription_token.as_deref(),
+            };
+            match transition_subscription(&mut tx, transition).await? {
+                TransitionOutcome::Applied => {}
+                TransitionOutcome::Illegal(illegal) => {
+                    tracing::info!(error = %illegal, "Keeping the subscription status")
+                }
+                TransitionOutcome::LostRace => {
+                    tracing::warn!("Subscription status changed concurrently, keeping it")
+                }
             }
-        })
+        }
+        commit_transaction(tx).await
     }
 }
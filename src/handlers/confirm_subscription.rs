@@ -1,14 +1,22 @@
 use anyhow::Context;
 use sqlx::PgPool;
 
+use crate::db::list_membership_queries::ListMembershipQueries;
 use crate::db::subscription_queries::SubscriptionQueries;
 use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
 use crate::domain::subscription_status::SubscriptionStatus;
 use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
 
 pub enum ConfirmSubscriptionOutput {
     Success,
     TokenNotFound,
+    /// The subscription changed concurrently, the link can be followed again
+    Conflict,
 }
 
 #[derive(thiserror::Error)]
@@ -21,32 +29,73 @@ impl std::fmt::Debug for ConfirmSubscriptionError {
     }
 }
 
-#[tracing::instrument(name = "Confirm a pending subscription", skip(pg_pool))]
+#[tracing::instrument(name = "Confirm a pending subscription", skip(pg_pool, context))]
 pub async fn confirm_subscription(
     subscription_token: &str,
     pg_pool: &PgPool,
+    context: &RequestContext,
 ) -> Result<ConfirmSubscriptionOutput, ConfirmSubscriptionError> {
-    let maybe_subscription_id =
-        SubscriptionQueries::fetch_subscription_id_by_token(pg_pool, subscription_token)
-            .await
-            .context("Failed to fetch a subscription ID by the subscription token")?;
-    match maybe_subscription_id {
-        // Non-existing token = 400
-        None => Ok(ConfirmSubscriptionOutput::TokenNotFound),
-        Some(id) => {
-            let mut tx = begin_transaction(pg_pool).await?;
-            SubscriptionQueries::update_subscription_status(
-                &mut tx,
-                &id,
-                SubscriptionStatus::Confirmed,
-            )
-            .await
-            .context("Failed to update a subscription status to Confirmed")?;
-            let _ = SubscriptionQueries::delete_token(&mut tx, subscription_token)
-                .await
-                .context("Failed to delete the subscription token")?;
-            commit_transaction(tx).await?;
-            Ok(ConfirmSubscriptionOutput::Success)
+    let maybe_token = SubscriptionQueries::fetch_token(pg_pool, subscription_token)
+        .await
+        .context("Failed to fetch the subscription token")?;
+    let (id, list_id) = match maybe_token {
+        // Non-existing token = 401
+        None => return Ok(ConfirmSubscriptionOutput::TokenNotFound),
+        Some(token) => (token.subscriber_id, token.list_id),
+    };
+    let mut tx = begin_transaction(pg_pool).await?;
+    let membership_status = ListMembershipQueries::fetch_membership_status(&mut tx, &id, &list_id)
+        .await
+        .context("Failed to fetch the list membership")?
+        .context("Subscription token points to a missing list membership")?;
+    // A leftover token of an already confirmed membership, nothing to change
+    if membership_status != SubscriptionStatus::Confirmed {
+        let transition = SubscriptionTransition {
+            subscription_id: &id,
+            list_id: Some(&list_id),
+            from: membership_status,
+            to: SubscriptionStatus::Confirmed,
+            source: SubscriptionEventSource::ConfirmationLink,
+            context,
+            token: Some(subscription_token),
+        };
+        match transition_subscription(&mut tx, transition).await? {
+            TransitionOutcome::Applied => {}
+            TransitionOutcome::LostRace => return Ok(ConfirmSubscriptionOutput::Conflict),
+            // Unsubscribed from the list after the token was issued, the token is stale
+            TransitionOutcome::Illegal(_) => {
+                SubscriptionQueries::delete_token(&mut tx, subscription_token)
+                    .await
+                    .context("Failed to delete the subscription token")?;
+                commit_transaction(tx).await?;
+                return Ok(ConfirmSubscriptionOutput::TokenNotFound);
+            }
         }
     }
+    let subscription = SubscriptionQueries::fetch_subscription_by_id(&mut tx, &id)
+        .await
+        .context("Failed to fetch a subscription by id")?
+        .context("Subscription token points to a missing subscription")?;
+    // Following the link of any list confirms the address itself
+    if subscription.status != SubscriptionStatus::Confirmed {
+        let transition = SubscriptionTransition {
+            subscription_id: &id,
+            list_id: None,
+            from: subscription.status,
+            to: SubscriptionStatus::Confirmed,
+            source: SubscriptionEventSource::ConfirmationLink,
+            context,
+            token: Some(subscription_token),
+        };
+        match transition_subscription(&mut tx, transition).await? {
+            TransitionOutcome::Applied => {}
+            TransitionOutcome::LostRace => return Ok(ConfirmSubscriptionOutput::Conflict),
+            TransitionOutcome::Illegal(illegal) => return Err(anyhow::Error::from(illegal).into()),
+        }
+    }
+    SubscriptionQueries::delete_token(&mut tx, subscription_token)
+        .await
+        .context("Failed to delete the subscription token")?;
+    commit_transaction(tx).await?;
+    Ok(ConfirmSubscriptionOutput::Success)
 }
@@ -0,0 +1,61 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::data_request_queries::DataRequestQueries;
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::data_request_kind::DataRequestKind;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::event_bus::EventBus;
+use crate::events::data_request_created::DataRequestCreated;
+use crate::handlers::errors::error_chain_fmt;
+
+pub enum CreateDataRequestOutput {
+    Success,
+    SubscriptionNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct CreateDataRequestError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for CreateDataRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(
+    name = "Creating a personal data request",
+    skip(pg_pool, event_bus, email)
+)]
+pub async fn create_data_request(
+    pg_pool: &PgPool,
+    event_bus: &EventBus,
+    email: SubscriberEmail,
+    kind: DataRequestKind,
+) -> Result<CreateDataRequestOutput, CreateDataRequestError> {
+    let maybe_subscription =
+        SubscriptionQueries::fetch_subscription_by_email(pg_pool, email.as_ref())
+            .await
+            .context("Failed to fetch a subscription by the email")?;
+    let subscription = match maybe_subscription {
+        None => return Ok(CreateDataRequestOutput::SubscriptionNotFound),
+        Some(subscription) => subscription,
+    };
+    let data_request_token = Uuid::new_v4();
+    let mut tx = begin_transaction(pg_pool).await?;
+    DataRequestQueries::store_token(&mut tx, &subscription.id, &data_request_token, kind)
+        .await
+        .context("Failed to store the data request token")?;
+    commit_transaction(tx).await?;
+    let event = DataRequestCreated {
+        subscription_id: subscription.id,
+        email,
+        kind,
+        data_request_token,
+    };
+    event_bus.publish(event).await?;
+    Ok(CreateDataRequestOutput::Success)
+}
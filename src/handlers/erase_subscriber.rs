@@ -0,0 +1,119 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::data_request_queries::{DataRequestQueries, DataRequestRecord};
+use crate::db::dead_letter_queries::DeadLetterQueries;
+use crate::db::email_event_queries::EmailEventQueries;
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::suppression_queries::SuppressionQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::data_request_kind::DataRequestKind;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::suppression_reason::SuppressionReason;
+use crate::handlers::errors::error_chain_fmt;
+
+pub enum EraseSubscriberOutput {
+    Success,
+    TokenNotFound,
+}
+
+pub enum FetchErasureRequestOutput {
+    /// The email of the subscriber to erase
+    Success(String),
+    TokenNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct EraseSubscriberError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for EraseSubscriberError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Checks the token without erasing anything, for the confirmation page
+#[tracing::instrument(name = "Fetch erasure request", skip(config, pg_pool))]
+pub async fn fetch_erasure_request(
+    config: &Config,
+    data_request_token: &str,
+    pg_pool: &PgPool,
+) -> Result<FetchErasureRequestOutput, EraseSubscriberError> {
+    let maybe_data_request =
+        DataRequestQueries::fetch_data_request_by_token(pg_pool, data_request_token)
+            .await
+            .context("Failed to fetch a data request by the data request token")?;
+    let subscriber_id = match erasure_subscriber_id(config, maybe_data_request) {
+        Some(subscriber_id) => subscriber_id,
+        None => return Ok(FetchErasureRequestOutput::TokenNotFound),
+    };
+    let subscription = SubscriptionQueries::fetch_subscription_by_id(pg_pool, &subscriber_id)
+        .await
+        .context("Failed to fetch a subscription by id")?
+        .context("Data request token points to a missing subscription")?;
+    Ok(FetchErasureRequestOutput::Success(subscription.email))
+}
+
+#[tracing::instrument(name = "Erase subscriber personal data", skip(config, pg_pool))]
+pub async fn erase_subscriber(
+    config: &Config,
+    data_request_token: &str,
+    pg_pool: &PgPool,
+) -> Result<EraseSubscriberOutput, EraseSubscriberError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let maybe_data_request =
+        DataRequestQueries::fetch_data_request_by_token(&mut tx, data_request_token)
+            .await
+            .context("Failed to fetch a data request by the data request token")?;
+    let subscriber_id = match erasure_subscriber_id(config, maybe_data_request) {
+        Some(subscriber_id) => subscriber_id,
+        None => return Ok(EraseSubscriberOutput::TokenNotFound),
+    };
+    let subscription = SubscriptionQueries::fetch_subscription_by_id(&mut tx, &subscriber_id)
+        .await
+        .context("Failed to fetch a subscription by id")?
+        .context("Data request token points to a missing subscription")?;
+    // Only the hash survives the erasure, so that the address is never re-imported
+    let email = SubscriberEmail::parse(subscription.email)
+        .map_err(|e| anyhow::anyhow!(e))
+        .context("Stored subscription email is invalid")?;
+    SuppressionQueries::insert_suppression(
+        &mut tx,
+        &email.canonical_hash(),
+        SuppressionReason::Erasure,
+        "data_request",
+    )
+    .await
+    .context("Failed to store the suppression")?;
+    EmailEventQueries::delete_email_events_by_email(&mut tx, email.as_ref())
+        .await
+        .context("Failed to delete the email events")?;
+    // The envelopes carry the email and the name
+    DeadLetterQueries::delete_dead_letters_by_subscription_id(&mut tx, &subscriber_id)
+        .await
+        .context("Failed to delete the dead letters")?;
+    SubscriptionQueries::delete_subscription(&mut tx, &subscriber_id)
+        .await
+        .context("Failed to delete the subscription")?;
+    commit_transaction(tx).await?;
+    Ok(EraseSubscriberOutput::Success)
+}
+
+/// Only unexpired erasure tokens, export tokens cannot erase
+fn erasure_subscriber_id(
+    config: &Config,
+    maybe_data_request: Option<DataRequestRecord>,
+) -> Option<Uuid> {
+    match maybe_data_request {
+        Some(data_request)
+            if data_request.kind == DataRequestKind::Erasure
+                && !data_request.is_expired(config.data_request_token_ttl_minutes) =>
+        {
+            Some(data_request.subscriber_id)
+        }
+        _ => None,
+    }
+}
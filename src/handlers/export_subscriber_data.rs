@@ -0,0 +1,119 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::config::Config;
+use crate::db::data_request_queries::{DataRequestQueries, DataRequestSummary};
+use crate::db::dead_letter_queries::{DeadLetterQueries, DeadLetterRecord};
+use crate::db::email_delivery_queries::{EmailDeliveryQueries, EmailDeliveryRecord};
+use crate::db::email_event_queries::{EmailEventQueries, EmailEventRecord};
+use crate::db::list_membership_queries::{ListMembershipQueries, ListMembershipRecord};
+use crate::db::subscription_event_queries::{SubscriptionEventQueries, SubscriptionEventRecord};
+use crate::db::subscription_queries::{SubscriptionQueries, SubscriptionRecord};
+use crate::db::tag_queries::TagQueries;
+use crate::db::tracking_event_queries::{TrackingEventQueries, TrackingEventRecord};
+use crate::domain::data_request_kind::DataRequestKind;
+use crate::handlers::errors::error_chain_fmt;
+
+/// Everything we store about a single subscriber.
+/// Tokens are left out like in `DataRequestSummary`: they are credentials, not personal data
+#[derive(Serialize)]
+pub struct SubscriberDataExport {
+    pub generated_at: DateTime<Utc>,
+    pub subscription: SubscriptionRecord,
+    pub list_memberships: Vec<ListMembershipRecord>,
+    pub subscription_events: Vec<SubscriptionEventRecord>,
+    pub data_requests: Vec<DataRequestSummary>,
+    pub email_deliveries: Vec<EmailDeliveryRecord>,
+    pub email_events: Vec<EmailEventRecord>,
+    pub tracking_events: Vec<TrackingEventRecord>,
+    pub tags: Vec<String>,
+    /// The events about the subscriber that could not be handled
+    pub dead_letters: Vec<DeadLetterRecord>,
+}
+
+pub enum ExportSubscriberDataOutput {
+    Success(Box<SubscriberDataExport>),
+    TokenNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ExportSubscriberDataError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ExportSubscriberDataError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Export subscriber personal data", skip(config, pg_pool))]
+pub async fn export_subscriber_data(
+    config: &Config,
+    data_request_token: &str,
+    pg_pool: &PgPool,
+) -> Result<ExportSubscriberDataOutput, ExportSubscriberDataError> {
+    let maybe_data_request =
+        DataRequestQueries::fetch_data_request_by_token(pg_pool, data_request_token)
+            .await
+            .context("Failed to fetch a data request by the data request token")?;
+    let subscriber_id = match maybe_data_request {
+        // The token is reusable until it expires, so the download can be retried
+        Some(data_request)
+            if data_request.kind == DataRequestKind::Export
+                && !data_request.is_expired(config.data_request_token_ttl_minutes) =>
+        {
+            data_request.subscriber_id
+        }
+        _ => return Ok(ExportSubscriberDataOutput::TokenNotFound),
+    };
+    let subscription = SubscriptionQueries::fetch_subscription_by_id(pg_pool, &subscriber_id)
+        .await
+        .context("Failed to fetch a subscription by id")?
+        .context("Data request token points to a missing subscription")?;
+    let list_memberships =
+        ListMembershipQueries::fetch_memberships_by_subscription_id(pg_pool, &subscriber_id)
+            .await
+            .context("Failed to fetch the list memberships")?;
+    let subscription_events =
+        SubscriptionEventQueries::fetch_events_by_subscription_id(pg_pool, &subscriber_id)
+            .await
+            .context("Failed to fetch the subscription events")?;
+    let data_requests =
+        DataRequestQueries::fetch_data_requests_by_subscriber_id(pg_pool, &subscriber_id)
+            .await
+            .context("Failed to fetch the data requests")?;
+    let email_deliveries =
+        EmailDeliveryQueries::fetch_deliveries_by_subscription_id(pg_pool, &subscriber_id)
+            .await
+            .context("Failed to fetch the email deliveries")?;
+    let email_events = EmailEventQueries::fetch_email_events_by_email(pg_pool, &subscription.email)
+        .await
+        .context("Failed to fetch the email events")?;
+    let tracking_events =
+        TrackingEventQueries::fetch_events_by_subscription_id(pg_pool, &subscriber_id)
+            .await
+            .context("Failed to fetch the tracking events")?;
+    let tags = TagQueries::fetch_tags_by_subscription_id(pg_pool, &subscriber_id)
+        .await
+        .context("Failed to fetch the tags")?;
+    let dead_letters =
+        DeadLetterQueries::fetch_dead_letters_by_subscription_id(pg_pool, &subscriber_id)
+            .await
+            .context("Failed to fetch the dead letters")?;
+    Ok(ExportSubscriberDataOutput::Success(Box::new(
+        SubscriberDataExport {
+            generated_at: Utc::now(),
+            subscription,
+            list_memberships,
+            subscription_events,
+            data_requests,
+            email_deliveries,
+            email_events,
+            tracking_events,
+            tags,
+            dead_letters,
+        },
+    )))
+}
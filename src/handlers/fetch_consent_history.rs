@@ -0,0 +1,66 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::db::list_membership_queries::{ListMembershipQueries, ListMembershipRecord};
+use crate::db::subscription_event_queries::{SubscriptionEventQueries, SubscriptionEventRecord};
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::handlers::errors::error_chain_fmt;
+
+/// Proof of consent: every status transition of a subscription, oldest first
+#[derive(Serialize)]
+pub struct ConsentHistory {
+    pub email: String,
+    pub status: SubscriptionStatus,
+    pub subscribed_at: DateTime<Utc>,
+    pub lists: Vec<ListMembershipRecord>,
+    pub events: Vec<SubscriptionEventRecord>,
+}
+
+pub enum FetchConsentHistoryOutput {
+    Success(ConsentHistory),
+    SubscriptionNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct FetchConsentHistoryError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for FetchConsentHistoryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Fetching the consent history", skip(pg_pool, email))]
+pub async fn fetch_consent_history(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<FetchConsentHistoryOutput, FetchConsentHistoryError> {
+    let subscription =
+        match SubscriptionQueries::fetch_subscription_by_email(pg_pool, email.as_ref())
+            .await
+            .context("Failed to fetch a subscription by the email")?
+        {
+            Some(subscription) => subscription,
+            None => return Ok(FetchConsentHistoryOutput::SubscriptionNotFound),
+        };
+    let lists =
+        ListMembershipQueries::fetch_memberships_by_subscription_id(pg_pool, &subscription.id)
+            .await
+            .context("Failed to fetch the list memberships")?;
+    let events =
+        SubscriptionEventQueries::fetch_events_by_subscription_id(pg_pool, &subscription.id)
+            .await
+            .context("Failed to fetch the subscription events")?;
+    Ok(FetchConsentHistoryOutput::Success(ConsentHistory {
+        email: subscription.email,
+        status: subscription.status,
+        subscribed_at: subscription.subscribed_at,
+        lists,
+        events,
+    }))
+}
@@ -0,0 +1,157 @@
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::attribute_definition_queries::AttributeDefinitionQueries;
+use crate::db::list_membership_queries::ListMembershipQueries;
+use crate::db::list_queries::ListQueries;
+use crate::db::subscription_event_queries::{NewSubscriptionEvent, SubscriptionEventQueries};
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::suppression_queries::SuppressionQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::new_subscriber::NewSubscriber;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_attributes::AttributeSchema;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::subscriber_name::SubscriberName;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::handlers::errors::error_chain_fmt;
+
+/// A subscriber as sent by the admin, validated row by row
+#[derive(Debug)]
+pub struct ImportRow {
+    pub email: String,
+    pub name: String,
+    pub attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SkippedRow {
+    /// Position of the row in the import
+    pub index: usize,
+    pub reason: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: Vec<SkippedRow>,
+}
+
+pub enum ImportSubscribersOutput {
+    Success(ImportReport),
+    ListNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ImportSubscribersError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ImportSubscribersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Imports subscribers whose consent was collected elsewhere as confirmed members of the list,
+/// without a confirmation email.
+/// Existing subscribers are never overridden, and suppressed emails are never imported.
+#[tracing::instrument(name = "Importing subscribers", skip(pg_pool, rows, context))]
+pub async fn import_subscribers(
+    pg_pool: &PgPool,
+    list_slug: &ListSlug,
+    rows: Vec<ImportRow>,
+    context: &RequestContext,
+) -> Result<ImportSubscribersOutput, ImportSubscribersError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let list = match ListQueries::fetch_lists_by_slugs(&mut tx, std::slice::from_ref(list_slug))
+        .await
+        .context("Failed to fetch the list")?
+        .pop()
+    {
+        Some(list) => list,
+        None => return Ok(ImportSubscribersOutput::ListNotFound),
+    };
+    let schema = AttributeDefinitionQueries::fetch_schema(&mut tx)
+        .await
+        .context("Failed to fetch the attribute schema")?;
+    let mut report = ImportReport {
+        imported: 0,
+        skipped: vec![],
+    };
+    for (index, row) in rows.into_iter().enumerate() {
+        let new_subscriber = match parse_row(row, &schema) {
+            Ok(new_subscriber) => new_subscriber,
+            Err(reason) => {
+                report.skipped.push(SkippedRow { index, reason });
+                continue;
+            }
+        };
+        if SuppressionQueries::is_suppressed(&mut tx, &new_subscriber.email.canonical_hash())
+            .await
+            .context("Failed to check the suppressions")?
+        {
+            report.skipped.push(SkippedRow {
+                index,
+                reason: "The email is suppressed.".to_owned(),
+            });
+            continue;
+        }
+        let subscription_id =
+            match SubscriptionQueries::insert_imported_subscriber(&mut tx, &new_subscriber)
+                .await
+                .context("Failed to insert the subscription")?
+            {
+                Some(subscription_id) => subscription_id,
+                None => {
+                    report.skipped.push(SkippedRow {
+                        index,
+                        reason: "The email is already subscribed.".to_owned(),
+                    });
+                    continue;
+                }
+            };
+        ListMembershipQueries::insert_confirmed_membership(&mut tx, &subscription_id, &list.id)
+            .await
+            .context("Failed to insert the list membership")?;
+        record_import(&mut tx, &subscription_id, None, context).await?;
+        record_import(&mut tx, &subscription_id, Some(&list.id), context).await?;
+        report.imported += 1;
+    }
+    commit_transaction(tx).await?;
+    Ok(ImportSubscribersOutput::Success(report))
+}
+
+fn parse_row(row: ImportRow, schema: &AttributeSchema) -> Result<NewSubscriber, String> {
+    Ok(NewSubscriber {
+        email: SubscriberEmail::parse(row.email)?,
+        name: SubscriberName::parse(row.name)?,
+        attributes: schema.validate(row.attributes)?,
+    })
+}
+
+async fn record_import(
+    tx: &mut Tx<'_>,
+    subscription_id: &Uuid,
+    list_id: Option<&Uuid>,
+    context: &RequestContext,
+) -> anyhow::Result<()> {
+    SubscriptionEventQueries::insert_event(
+        tx,
+        &NewSubscriptionEvent {
+            subscription_id,
+            list_id,
+            old_status: None,
+            new_status: SubscriptionStatus::Confirmed,
+            source: SubscriptionEventSource::AdminImport,
+            context,
+            token: None,
+        },
+    )
+    .await
+    .context("Failed to record the subscription event")
+}
@@ -0,0 +1,153 @@
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::db::email_event_queries::EmailEventQueries;
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::suppression_queries::SuppressionQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::domain::suppression_reason::SuppressionReason;
+use crate::event_bus::EventBus;
+use crate::events::email_event_received::EmailEventReceived;
+use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
+use crate::sendgrid_webhook::SendgridEvent;
+
+pub struct IngestSendgridEventsOutput {
+    pub recorded: usize,
+    pub duplicates: usize,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct IngestSendgridEventsError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for IngestSendgridEventsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(
+    name = "Ingesting SendGrid webhook events",
+    skip(pg_pool, event_bus, payload),
+    fields(batch_size = payload.len())
+)]
+pub async fn ingest_sendgrid_events(
+    pg_pool: &PgPool,
+    event_bus: &EventBus,
+    payload: Vec<serde_json::Value>,
+) -> Result<IngestSendgridEventsOutput, IngestSendgridEventsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let mut recorded = vec![];
+    let mut duplicates = 0;
+    for raw_event in payload {
+        let event = match serde_json::from_value::<SendgridEvent>(raw_event.clone()) {
+            Ok(event) => event,
+            Err(err) => {
+                // One malformed entry should not make SendGrid retry the whole batch
+                tracing::warn!(error = %err, "Skipping malformed SendGrid event");
+                continue;
+            }
+        };
+        let occurred_at = match event.occurred_at() {
+            Some(occurred_at) => occurred_at,
+            None => {
+                tracing::warn!(
+                    timestamp = event.timestamp,
+                    "Skipping SendGrid event with an out of range timestamp"
+                );
+                continue;
+            }
+        };
+        let is_new = EmailEventQueries::insert_email_event(
+            &mut tx,
+            event.sg_event_id.as_deref(),
+            event.provider_message_id(),
+            &event.email,
+            &event.event,
+            occurred_at,
+            &raw_event,
+        )
+        .await
+        .context("Failed to store the email event")?;
+        if !is_new {
+            duplicates += 1;
+            continue;
+        }
+        let reason = if event.is_hard_bounce() {
+            Some(SuppressionReason::HardBounce)
+        } else if event.is_complaint() {
+            Some(SuppressionReason::Complaint)
+        } else {
+            None
+        };
+        if let Some(reason) = reason {
+            suppress_recipient(&mut tx, &event.email, reason).await?;
+        }
+        recorded.push(EmailEventReceived {
+            provider_message_id: event.provider_message_id().map(|id| id.to_owned()),
+            occurred_at,
+            email: event.email,
+            event_type: event.event,
+            suppressed: reason.is_some(),
+        });
+    }
+    let output = IngestSendgridEventsOutput {
+        recorded: recorded.len(),
+        duplicates,
+    };
+    // Published before the commit: if publishing fails, nothing is recorded and the retry of
+    // SendGrid publishes them again. Duplicates were published when they were first recorded.
+    for event in recorded {
+        event_bus.publish(event).await?;
+    }
+    commit_transaction(tx).await?;
+    Ok(output)
+}
+
+async fn suppress_recipient(
+    tx: &mut Tx<'_>,
+    email: &str,
+    reason: SuppressionReason,
+) -> anyhow::Result<()> {
+    let email = match SubscriberEmail::parse(email.to_owned()) {
+        Ok(email) => email,
+        Err(err) => {
+            tracing::warn!(error = %err, "Cannot suppress an invalid email");
+            return Ok(());
+        }
+    };
+    SuppressionQueries::insert_suppression(tx, &email.canonical_hash(), reason, "sendgrid_webhook")
+        .await
+        .context("Failed to store the suppression")?;
+    let maybe_subscription =
+        SubscriptionQueries::fetch_subscription_by_email(&mut *tx, email.as_ref())
+            .await
+            .context("Failed to fetch a subscription by the email")?;
+    if let Some(subscription) = maybe_subscription {
+        let transition = SubscriptionTransition {
+            subscription_id: &subscription.id,
+            list_id: None,
+            from: subscription.status,
+            to: SubscriptionStatus::Failed,
+            source: SubscriptionEventSource::SendgridWebhook,
+            context: &RequestContext::default(),
+            token: None,
+        };
+        // Confirmed subscriptions keep their status, the suppression is enough to stop emails
+        match transition_subscription(tx, transition).await? {
+            TransitionOutcome::Applied | TransitionOutcome::Illegal(_) => {}
+            TransitionOutcome::LostRace => {
+                tracing::warn!("Subscription status changed concurrently, keeping it")
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::db::attribute_definition_queries::{
+    AttributeDefinitionQueries, AttributeDefinitionRecord,
+};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::subscriber_attributes::{AttributeDefinition, AttributeSchema};
+use crate::handlers::errors::error_chain_fmt;
+
+pub enum AddAttributeOutput {
+    Success,
+    NameTaken,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageAttributesError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageAttributesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Attributes cannot be changed once defined: subscribers already have values for them
+#[tracing::instrument(name = "Adding an attribute definition", skip(pg_pool))]
+pub async fn add_attribute(
+    pg_pool: &PgPool,
+    definition: &AttributeDefinition,
+) -> Result<AddAttributeOutput, ManageAttributesError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let inserted = AttributeDefinitionQueries::insert_definition(&mut tx, definition)
+        .await
+        .context("Failed to store the attribute definition")?;
+    commit_transaction(tx).await?;
+    if inserted {
+        Ok(AddAttributeOutput::Success)
+    } else {
+        Ok(AddAttributeOutput::NameTaken)
+    }
+}
+
+#[tracing::instrument(name = "Listing the attribute definitions", skip(pg_pool))]
+pub async fn list_attributes(
+    pg_pool: &PgPool,
+) -> Result<Vec<AttributeDefinitionRecord>, ManageAttributesError> {
+    let definitions = AttributeDefinitionQueries::fetch_definitions(pg_pool)
+        .await
+        .context("Failed to fetch the attribute definitions")?;
+    Ok(definitions)
+}
+
+#[tracing::instrument(name = "Fetching the attribute schema", skip(pg_pool))]
+pub async fn fetch_attribute_schema(
+    pg_pool: &PgPool,
+) -> Result<AttributeSchema, ManageAttributesError> {
+    let schema = AttributeDefinitionQueries::fetch_schema(pg_pool)
+        .await
+        .context("Failed to fetch the attribute schema")?;
+    Ok(schema)
+}
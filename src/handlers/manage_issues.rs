@@ -0,0 +1,210 @@
+use anyhow::Context;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::issue_link_queries::IssueLinkQueries;
+use crate::db::issue_queries::{IssueQueries, IssueRecord};
+use crate::db::list_queries::ListQueries;
+use crate::db::segment_queries::SegmentQueries;
+use crate::db::subject_variant_queries::{SubjectVariantQueries, SubjectVariantStatsRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::ab_test::AbTest;
+use crate::domain::issue_status::IssueStatus;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::time_zone::{earliest_instant, IanaTimeZone};
+use crate::handlers::errors::error_chain_fmt;
+use crate::tracking::find_links;
+
+pub enum AddIssueOutput {
+    Success(Uuid),
+    ListNotFound,
+    SegmentNotFound,
+    /// A/B tests are measured with opens and clicks
+    TrackingDisabled,
+}
+
+pub enum ChangeIssueOutput {
+    Success,
+    IssueNotFound,
+    /// The issue is being sent, was sent or was cancelled already
+    Conflict(IssueStatus),
+}
+
+#[derive(Serialize)]
+pub struct SubjectVariants {
+    pub audience_percent: Option<i16>,
+    pub wait_minutes: Option<i32>,
+    pub winning_variant_id: Option<Uuid>,
+    pub variants: Vec<SubjectVariantStatsRecord>,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageIssuesError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageIssuesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Issues start as drafts, nothing is sent until they are scheduled.
+/// `subject` is the one of digests, A/B tests only apply to immediate subscribers.
+/// With a saved segment, only the members of the list in the segment get the issue.
+#[tracing::instrument(name = "Adding an issue", skip(pg_pool, text_content))]
+pub async fn add_issue(
+    pg_pool: &PgPool,
+    list_slug: &ListSlug,
+    subject: &str,
+    text_content: &str,
+    ab_test: Option<&AbTest>,
+    segment_name: Option<&str>,
+) -> Result<AddIssueOutput, ManageIssuesError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let list = match ListQueries::fetch_lists_by_slugs(&mut tx, std::slice::from_ref(list_slug))
+        .await
+        .context("Failed to fetch the list")?
+        .pop()
+    {
+        Some(list) => list,
+        None => return Ok(AddIssueOutput::ListNotFound),
+    };
+    if ab_test.is_some() && !list.tracking_enabled {
+        return Ok(AddIssueOutput::TrackingDisabled);
+    }
+    let segment_id = match segment_name {
+        Some(segment_name) => match SegmentQueries::fetch_segment_by_name(&mut tx, segment_name)
+            .await
+            .context("Failed to fetch the segment")?
+        {
+            Some(segment) => Some(segment.id),
+            None => return Ok(AddIssueOutput::SegmentNotFound),
+        },
+        None => None,
+    };
+    let issue_id = IssueQueries::insert_issue(
+        &mut tx,
+        &list.id,
+        subject,
+        text_content,
+        ab_test,
+        segment_id.as_ref(),
+    )
+    .await
+    .context("Failed to store the issue")?;
+    let links: Vec<&str> = find_links(text_content)
+        .iter()
+        .map(|link| link.url)
+        .collect();
+    IssueLinkQueries::insert_links(&mut tx, &issue_id, &links)
+        .await
+        .context("Failed to store the issue links")?;
+    if let Some(ab_test) = ab_test {
+        SubjectVariantQueries::insert_variants(&mut tx, &issue_id, ab_test.subjects())
+            .await
+            .context("Failed to store the subject variants")?;
+    }
+    commit_transaction(tx).await?;
+    Ok(AddIssueOutput::Success(issue_id))
+}
+
+/// Sent, opened and clicked per subject variant. `None` if the issue is unknown.
+#[tracing::instrument(name = "Fetching the subject variant stats", skip(pg_pool))]
+pub async fn fetch_subject_variants(
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+) -> Result<Option<SubjectVariants>, ManageIssuesError> {
+    let issue = match IssueQueries::fetch_issue(pg_pool, issue_id)
+        .await
+        .context("Failed to fetch the issue")?
+    {
+        Some(issue) => issue,
+        None => return Ok(None),
+    };
+    let variants = SubjectVariantQueries::fetch_variant_stats(pg_pool, issue_id)
+        .await
+        .context("Failed to fetch the subject variant stats")?;
+    Ok(Some(SubjectVariants {
+        audience_percent: issue.ab_test_audience_percent,
+        wait_minutes: issue.ab_test_wait_minutes,
+        winning_variant_id: issue.winning_variant_id,
+        variants,
+    }))
+}
+
+#[tracing::instrument(name = "Listing the issues", skip(pg_pool))]
+pub async fn list_issues(pg_pool: &PgPool) -> Result<Vec<IssueRecord>, ManageIssuesError> {
+    let issues = IssueQueries::fetch_issues(pg_pool)
+        .await
+        .context("Failed to fetch the issues")?;
+    Ok(issues)
+}
+
+/// Schedules a draft, or moves the send time of a scheduled issue.
+/// `send_at_local` is a wall clock time in the list's timezone,
+/// or in each subscriber's timezone if `per_subscriber_timezone` is set:
+/// the scheduler then starts at the earliest timezone and keeps going for a day.
+#[tracing::instrument(name = "Scheduling an issue", skip(pg_pool))]
+pub async fn schedule_issue(
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    send_at_local: &NaiveDateTime,
+    per_subscriber_timezone: bool,
+) -> Result<ChangeIssueOutput, ManageIssuesError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let issue = match IssueQueries::fetch_issue_for_update(&mut tx, issue_id)
+        .await
+        .context("Failed to fetch the issue")?
+    {
+        Some(issue) => issue,
+        None => return Ok(ChangeIssueOutput::IssueNotFound),
+    };
+    if !issue.status.can_transition_to(IssueStatus::Scheduled) {
+        return Ok(ChangeIssueOutput::Conflict(issue.status));
+    }
+    let scheduled_at = if per_subscriber_timezone {
+        earliest_instant(send_at_local)
+    } else {
+        IanaTimeZone::parse(&issue.list_timezone)
+            .map_err(anyhow::Error::msg)?
+            .to_utc(send_at_local)
+    };
+    // The issue row is locked, its status cannot change under us
+    IssueQueries::compare_and_set_schedule(
+        &mut tx,
+        issue_id,
+        issue.status,
+        send_at_local,
+        per_subscriber_timezone,
+        &scheduled_at,
+    )
+    .await
+    .context("Failed to schedule the issue")?;
+    commit_transaction(tx).await?;
+    Ok(ChangeIssueOutput::Success)
+}
+
+#[tracing::instrument(name = "Cancelling an issue", skip(pg_pool))]
+pub async fn cancel_issue(
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+) -> Result<ChangeIssueOutput, ManageIssuesError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let issue = match IssueQueries::fetch_issue_for_update(&mut tx, issue_id)
+        .await
+        .context("Failed to fetch the issue")?
+    {
+        Some(issue) => issue,
+        None => return Ok(ChangeIssueOutput::IssueNotFound),
+    };
+    if !issue.status.can_transition_to(IssueStatus::Cancelled) {
+        return Ok(ChangeIssueOutput::Conflict(issue.status));
+    }
+    IssueQueries::compare_and_set_status(&mut tx, issue_id, issue.status, IssueStatus::Cancelled)
+        .await
+        .context("Failed to cancel the issue")?;
+    commit_transaction(tx).await?;
+    Ok(ChangeIssueOutput::Success)
+}
@@ -0,0 +1,62 @@
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::db::list_queries::{ListQueries, ListRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::list_slug::ListSlug;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::time_zone::IanaTimeZone;
+use crate::handlers::errors::error_chain_fmt;
+
+pub enum AddListOutput {
+    Success,
+    SlugTaken,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageListsError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageListsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Adding a mailing list", skip(pg_pool, description))]
+pub async fn add_list(
+    pg_pool: &PgPool,
+    slug: &ListSlug,
+    name: &str,
+    from_address: Option<&SubscriberEmail>,
+    description: &str,
+    timezone: &IanaTimeZone,
+    tracking_enabled: bool,
+) -> Result<AddListOutput, ManageListsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let inserted = ListQueries::insert_list(
+        &mut tx,
+        slug,
+        name,
+        from_address.map(|email| email.as_ref().as_str()),
+        description,
+        timezone,
+        tracking_enabled,
+    )
+    .await
+    .context("Failed to store the list")?;
+    commit_transaction(tx).await?;
+    if inserted {
+        Ok(AddListOutput::Success)
+    } else {
+        Ok(AddListOutput::SlugTaken)
+    }
+}
+
+#[tracing::instrument(name = "Listing the mailing lists", skip(pg_pool))]
+pub async fn list_lists(pg_pool: &PgPool) -> Result<Vec<ListRecord>, ManageListsError> {
+    let lists = ListQueries::fetch_lists(pg_pool)
+        .await
+        .context("Failed to fetch the lists")?;
+    Ok(lists)
+}
@@ -0,0 +1,267 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::list_membership_queries::ListMembershipQueries;
+use crate::db::list_queries::{ListQueries, ListRecord};
+use crate::db::subscription_event_queries::{NewSubscriptionEvent, SubscriptionEventQueries};
+use crate::db::subscription_queries::{SubscriptionQueries, SubscriptionRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::delivery_cadence::DeliveryCadence;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::preferred_language::PreferredLanguage;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_name::SubscriberName;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::domain::time_zone::IanaTimeZone;
+use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
+
+/// Everything the preference center shows
+pub struct Preferences {
+    pub subscription: SubscriptionRecord,
+    pub lists: Vec<ListPreference>,
+}
+
+pub struct ListPreference {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+    pub description: String,
+    /// `Some` if the subscriber was ever a member of the list
+    pub status: Option<SubscriptionStatus>,
+}
+
+impl ListPreference {
+    /// Pending memberships count, so that an unconfirmed list can still be left
+    pub fn is_subscribed(&self) -> bool {
+        matches!(
+            self.status,
+            Some(SubscriptionStatus::Pending | SubscriptionStatus::Confirmed)
+        )
+    }
+}
+
+/// The whole form is submitted at once: lists missing from `lists` are left
+pub struct PreferencesUpdate {
+    pub name: SubscriberName,
+    pub delivery_cadence: DeliveryCadence,
+    pub preferred_language: PreferredLanguage,
+    /// `None` = the timezone of each list
+    pub timezone: Option<IanaTimeZone>,
+    pub do_not_track: bool,
+    pub lists: Vec<ListSlug>,
+}
+
+pub enum FetchPreferencesOutput {
+    Success(Preferences),
+    TokenNotFound,
+}
+
+pub enum UpdatePreferencesOutput {
+    Success(Preferences),
+    TokenNotFound,
+    ListNotFound,
+    /// A membership changed concurrently, the form can be submitted again
+    Conflict,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManagePreferencesError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManagePreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Fetching subscriber preferences", skip(pg_pool))]
+pub async fn fetch_preferences(
+    preferences_token: &str,
+    pg_pool: &PgPool,
+) -> Result<FetchPreferencesOutput, ManagePreferencesError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let subscription = match SubscriptionQueries::fetch_subscription_by_preferences_token(
+        &mut tx,
+        preferences_token,
+    )
+    .await
+    .context("Failed to fetch a subscription by the preferences token")?
+    {
+        Some(subscription) => subscription,
+        None => return Ok(FetchPreferencesOutput::TokenNotFound),
+    };
+    let all_lists = ListQueries::fetch_lists(&mut tx)
+        .await
+        .context("Failed to fetch the lists")?;
+    let preferences = load_preferences(&mut tx, subscription, &all_lists).await?;
+    commit_transaction(tx).await?;
+    Ok(FetchPreferencesOutput::Success(preferences))
+}
+
+/// Leaving a list unsubscribes from it.
+/// Joining a list confirms the membership right away: the preferences link is only ever
+/// sent to the subscriber's address, so following it proves the ownership just like
+/// a confirmation link does.
+#[tracing::instrument(
+    name = "Updating subscriber preferences",
+    skip(pg_pool, update, context)
+)]
+pub async fn update_preferences(
+    preferences_token: &str,
+    update: PreferencesUpdate,
+    pg_pool: &PgPool,
+    context: &RequestContext,
+) -> Result<UpdatePreferencesOutput, ManagePreferencesError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let subscription = match SubscriptionQueries::fetch_subscription_by_preferences_token(
+        &mut tx,
+        preferences_token,
+    )
+    .await
+    .context("Failed to fetch a subscription by the preferences token")?
+    {
+        Some(subscription) => subscription,
+        None => return Ok(UpdatePreferencesOutput::TokenNotFound),
+    };
+    let requested_lists = ListQueries::fetch_lists_by_slugs(&mut tx, &update.lists)
+        .await
+        .context("Failed to fetch the requested lists")?;
+    if requested_lists.len() != update.lists.len() {
+        return Ok(UpdatePreferencesOutput::ListNotFound);
+    }
+    SubscriptionQueries::update_preferences(
+        &mut tx,
+        &subscription.id,
+        &update.name,
+        update.delivery_cadence,
+        &update.preferred_language,
+        update.timezone.as_ref(),
+        update.do_not_track,
+    )
+    .await
+    .context("Failed to update the subscription preferences")?;
+
+    let all_lists = ListQueries::fetch_lists(&mut tx)
+        .await
+        .context("Failed to fetch the lists")?;
+    let current = load_preferences(&mut tx, subscription, &all_lists).await?;
+    for preference in &current.lists {
+        let is_requested = requested_lists
+            .iter()
+            .any(|list| list.id == preference.list_id);
+        let steps = match (is_requested, preference.status) {
+            // Leaving the list
+            (false, Some(from @ (SubscriptionStatus::Pending | SubscriptionStatus::Confirmed))) => {
+                vec![(from, SubscriptionStatus::Unsubscribed)]
+            }
+            // Joining the list again
+            (true, Some(SubscriptionStatus::Unsubscribed)) => vec![
+                (
+                    SubscriptionStatus::Unsubscribed,
+                    SubscriptionStatus::Pending,
+                ),
+                (SubscriptionStatus::Pending, SubscriptionStatus::Confirmed),
+            ],
+            // Joining the list for the first time
+            (true, None) => {
+                join_list(
+                    &mut tx,
+                    &current.subscription.id,
+                    &preference.list_id,
+                    context,
+                    preferences_token,
+                )
+                .await?;
+                vec![(SubscriptionStatus::Pending, SubscriptionStatus::Confirmed)]
+            }
+            _ => vec![],
+        };
+        for (from, to) in steps {
+            let transition = SubscriptionTransition {
+                subscription_id: &current.subscription.id,
+                list_id: Some(&preference.list_id),
+                from,
+                to,
+                source: SubscriptionEventSource::PreferencesPage,
+                context,
+                token: Some(preferences_token),
+            };
+            match transition_subscription(&mut tx, transition).await? {
+                TransitionOutcome::Applied => {}
+                TransitionOutcome::LostRace => return Ok(UpdatePreferencesOutput::Conflict),
+                TransitionOutcome::Illegal(illegal) => {
+                    return Err(anyhow::Error::from(illegal).into())
+                }
+            }
+        }
+    }
+
+    let subscription =
+        SubscriptionQueries::fetch_subscription_by_id(&mut tx, &current.subscription.id)
+            .await
+            .context("Failed to fetch the subscription")?
+            .context("The subscription is locked, it cannot be gone")?;
+    let preferences = load_preferences(&mut tx, subscription, &all_lists).await?;
+    commit_transaction(tx).await?;
+    Ok(UpdatePreferencesOutput::Success(preferences))
+}
+
+async fn load_preferences(
+    tx: &mut Tx<'_>,
+    subscription: SubscriptionRecord,
+    all_lists: &[ListRecord],
+) -> anyhow::Result<Preferences> {
+    let memberships =
+        ListMembershipQueries::fetch_memberships_by_subscription_id(&mut *tx, &subscription.id)
+            .await
+            .context("Failed to fetch the list memberships")?;
+    let lists = all_lists
+        .iter()
+        .map(|list| ListPreference {
+            status: memberships
+                .iter()
+                .find(|membership| membership.list == list.slug)
+                .map(|membership| membership.status),
+            list_id: list.id,
+            slug: list.slug.clone(),
+            name: list.name.clone(),
+            description: list.description.clone(),
+        })
+        .collect();
+    Ok(Preferences {
+        subscription,
+        lists,
+    })
+}
+
+async fn join_list(
+    tx: &mut Tx<'_>,
+    subscription_id: &Uuid,
+    list_id: &Uuid,
+    context: &RequestContext,
+    preferences_token: &str,
+) -> anyhow::Result<()> {
+    ListMembershipQueries::upsert_pending_membership(tx, subscription_id, list_id)
+        .await
+        .context("Failed to upsert the list membership")?;
+    SubscriptionEventQueries::insert_event(
+        tx,
+        &NewSubscriptionEvent {
+            subscription_id,
+            list_id: Some(list_id),
+            old_status: None,
+            new_status: SubscriptionStatus::Pending,
+            source: SubscriptionEventSource::PreferencesPage,
+            context,
+            token: Some(preferences_token),
+        },
+    )
+    .await
+    .context("Failed to record the subscription event")
+}
@@ -0,0 +1,99 @@
+use anyhow::Context;
+use chrono::Utc;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::segment_queries::{SegmentMemberRecord, SegmentQueries, SegmentRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::segment::Segment;
+use crate::handlers::errors::error_chain_fmt;
+
+pub enum AddSegmentOutput {
+    Success(Uuid),
+    NameTaken,
+}
+
+/// How many subscribers a segment targets, and the most recent of them
+#[derive(Serialize)]
+pub struct SegmentPreview {
+    pub count: i64,
+    pub sample: Vec<SegmentMemberRecord>,
+}
+
+pub enum PreviewSegmentOutput {
+    Success(SegmentPreview),
+    SegmentNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageSegmentsError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageSegmentsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Adding a segment", skip(pg_pool))]
+pub async fn add_segment(
+    pg_pool: &PgPool,
+    name: &str,
+    segment: &Segment,
+) -> Result<AddSegmentOutput, ManageSegmentsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let segment_id = SegmentQueries::insert_segment(&mut tx, name, segment)
+        .await
+        .context("Failed to store the segment")?;
+    commit_transaction(tx).await?;
+    match segment_id {
+        Some(segment_id) => Ok(AddSegmentOutput::Success(segment_id)),
+        None => Ok(AddSegmentOutput::NameTaken),
+    }
+}
+
+#[tracing::instrument(name = "Listing the segments", skip(pg_pool))]
+pub async fn list_segments(pg_pool: &PgPool) -> Result<Vec<SegmentRecord>, ManageSegmentsError> {
+    let segments = SegmentQueries::fetch_segments(pg_pool)
+        .await
+        .context("Failed to fetch the segments")?;
+    Ok(segments)
+}
+
+#[tracing::instrument(name = "Previewing a segment", skip(pg_pool))]
+pub async fn preview_segment(
+    pg_pool: &PgPool,
+    segment: &Segment,
+    sample_size: u32,
+) -> Result<SegmentPreview, ManageSegmentsError> {
+    // Both queries see the same window for relative conditions
+    let now = Utc::now();
+    let count = SegmentQueries::count_members(pg_pool, segment, now)
+        .await
+        .context("Failed to count the segment members")?;
+    let sample = SegmentQueries::fetch_members(pg_pool, segment, now, sample_size)
+        .await
+        .context("Failed to fetch a sample of the segment members")?;
+    Ok(SegmentPreview { count, sample })
+}
+
+#[tracing::instrument(name = "Previewing a saved segment", skip(pg_pool))]
+pub async fn preview_saved_segment(
+    pg_pool: &PgPool,
+    name: &str,
+    sample_size: u32,
+) -> Result<PreviewSegmentOutput, ManageSegmentsError> {
+    let record = match SegmentQueries::fetch_segment_by_name(pg_pool, name)
+        .await
+        .context("Failed to fetch the segment")?
+    {
+        Some(record) => record,
+        None => return Ok(PreviewSegmentOutput::SegmentNotFound),
+    };
+    let segment = Segment::parse(record.definition)
+        .map_err(anyhow::Error::msg)
+        .context("A saved segment is no longer valid")?;
+    let preview = preview_segment(pg_pool, &segment, sample_size).await?;
+    Ok(PreviewSegmentOutput::Success(preview))
+}
@@ -0,0 +1,254 @@
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::list_membership_queries::{ListMembershipQueries, ListMembershipRecord};
+use crate::db::list_queries::ListQueries;
+use crate::db::subscription_queries::{SubscriptionQueries, SubscriptionRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::new_subscriber::NewSubscriber;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_attributes::SubscriberAttributes;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::subscriber_name::SubscriberName;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::event_bus::EventBus;
+use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::save_new_subscriber::{save_new_subscriber, SaveNewSubscriberOutput};
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
+
+#[derive(Serialize)]
+pub struct SubscriberDetails {
+    #[serde(flatten)]
+    pub subscription: SubscriptionRecord,
+    pub lists: Vec<ListMembershipRecord>,
+}
+
+pub enum ShowSubscriberOutput {
+    Success(SubscriberDetails),
+    SubscriptionNotFound,
+}
+
+pub enum ChangeSubscriberOutput {
+    Success,
+    /// Nothing was changed, e.g. unsubscribing from no list
+    Unchanged,
+    SubscriptionNotFound,
+    /// The subscription changed concurrently, the command can be run again
+    Conflict,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageSubscribersError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageSubscribersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Showing a subscriber", skip(pg_pool, email))]
+pub async fn show_subscriber(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<ShowSubscriberOutput, ManageSubscribersError> {
+    let subscription =
+        match SubscriptionQueries::fetch_subscription_by_email(pg_pool, email.as_ref())
+            .await
+            .context("Failed to fetch a subscription by the email")?
+        {
+            Some(subscription) => subscription,
+            None => return Ok(ShowSubscriberOutput::SubscriptionNotFound),
+        };
+    let lists =
+        ListMembershipQueries::fetch_memberships_by_subscription_id(pg_pool, &subscription.id)
+            .await
+            .context("Failed to fetch the list memberships")?;
+    Ok(ShowSubscriberOutput::Success(SubscriberDetails {
+        subscription,
+        lists,
+    }))
+}
+
+/// Confirms the address and every pending list membership without a confirmation link,
+/// e.g. when the subscriber confirmed by other means.
+/// Unsubscribed lists are left alone.
+#[tracing::instrument(name = "Confirming a subscriber", skip(pg_pool, email))]
+pub async fn confirm_subscriber(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<ChangeSubscriberOutput, ManageSubscribersError> {
+    change_statuses(
+        pg_pool,
+        email,
+        &[SubscriptionStatus::Pending, SubscriptionStatus::Failed],
+        SubscriptionStatus::Confirmed,
+        true,
+    )
+    .await
+}
+
+/// Leaves every list, the address itself stays as it is
+#[tracing::instrument(name = "Unsubscribing a subscriber", skip(pg_pool, email))]
+pub async fn unsubscribe_subscriber(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<ChangeSubscriberOutput, ManageSubscribersError> {
+    change_statuses(
+        pg_pool,
+        email,
+        &[SubscriptionStatus::Pending, SubscriptionStatus::Confirmed],
+        SubscriptionStatus::Unsubscribed,
+        false,
+    )
+    .await
+}
+
+/// Sends new confirmation links for the pending lists, like signing up again would.
+/// A failed subscription goes back to pending.
+#[tracing::instrument(
+    name = "Resending the confirmation email",
+    skip(pg_pool, event_bus, email)
+)]
+pub async fn resend_confirmation(
+    pg_pool: &PgPool,
+    event_bus: &EventBus,
+    email: &SubscriberEmail,
+) -> Result<ChangeSubscriberOutput, ManageSubscribersError> {
+    let subscription =
+        match SubscriptionQueries::fetch_subscription_by_email(pg_pool, email.as_ref())
+            .await
+            .context("Failed to fetch a subscription by the email")?
+        {
+            Some(subscription) => subscription,
+            None => return Ok(ChangeSubscriberOutput::SubscriptionNotFound),
+        };
+    let memberships =
+        ListMembershipQueries::fetch_memberships_by_subscription_id(pg_pool, &subscription.id)
+            .await
+            .context("Failed to fetch the list memberships")?;
+    let pending_lists = memberships
+        .into_iter()
+        .filter(|membership| membership.status == SubscriptionStatus::Pending)
+        .map(|membership| ListSlug::parse(membership.list).map_err(anyhow::Error::msg))
+        .collect::<Result<Vec<ListSlug>, _>>()
+        .context("Failed to parse a list slug")?;
+    if pending_lists.is_empty() {
+        return Ok(ChangeSubscriberOutput::Unchanged);
+    }
+    let new_subscriber = NewSubscriber {
+        email: email.clone(),
+        name: SubscriberName::parse(subscription.name).map_err(anyhow::Error::msg)?,
+        // Left alone for an existing subscription
+        attributes: SubscriberAttributes::default(),
+    };
+    let output = save_new_subscriber(
+        pg_pool,
+        event_bus,
+        new_subscriber,
+        &pending_lists,
+        &RequestContext::default(),
+    )
+    .await
+    .context("Failed to resend the confirmation")?;
+    match output {
+        SaveNewSubscriberOutput::Success | SaveNewSubscriberOutput::ResendConfirmation => {
+            Ok(ChangeSubscriberOutput::Success)
+        }
+        // Confirmed or deleted in the meantime
+        SaveNewSubscriberOutput::AlreadySubscribed | SaveNewSubscriberOutput::ListNotFound => {
+            Ok(ChangeSubscriberOutput::Conflict)
+        }
+    }
+}
+
+/// Moves the subscription if `including_subscription`, then each list membership,
+/// from any of the `from` statuses to `to`
+async fn change_statuses(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+    from: &[SubscriptionStatus],
+    to: SubscriptionStatus,
+    including_subscription: bool,
+) -> Result<ChangeSubscriberOutput, ManageSubscribersError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let subscription =
+        match SubscriptionQueries::fetch_subscription_by_email(&mut tx, email.as_ref())
+            .await
+            .context("Failed to fetch a subscription by the email")?
+        {
+            Some(subscription) => subscription,
+            None => return Ok(ChangeSubscriberOutput::SubscriptionNotFound),
+        };
+    let all_lists = ListQueries::fetch_lists(&mut tx)
+        .await
+        .context("Failed to fetch the lists")?;
+    let memberships =
+        ListMembershipQueries::fetch_memberships_by_subscription_id(&mut tx, &subscription.id)
+            .await
+            .context("Failed to fetch the list memberships")?;
+    let mut changed = false;
+    if including_subscription && from.contains(&subscription.status) {
+        if !apply(&mut tx, &subscription, None, subscription.status, to).await? {
+            return Ok(ChangeSubscriberOutput::Conflict);
+        }
+        changed = true;
+    }
+    for membership in memberships {
+        if !from.contains(&membership.status) {
+            continue;
+        }
+        let list = all_lists
+            .iter()
+            .find(|list| list.slug == membership.list)
+            .context("A list membership points to a missing list")?;
+        if !apply(
+            &mut tx,
+            &subscription,
+            Some(&list.id),
+            membership.status,
+            to,
+        )
+        .await?
+        {
+            return Ok(ChangeSubscriberOutput::Conflict);
+        }
+        changed = true;
+    }
+    if !changed {
+        return Ok(ChangeSubscriberOutput::Unchanged);
+    }
+    commit_transaction(tx).await?;
+    Ok(ChangeSubscriberOutput::Success)
+}
+
+/// Returns `false` if a concurrent change won the race
+async fn apply(
+    tx: &mut Tx<'_>,
+    subscription: &SubscriptionRecord,
+    list_id: Option<&Uuid>,
+    from: SubscriptionStatus,
+    to: SubscriptionStatus,
+) -> Result<bool, ManageSubscribersError> {
+    let transition = SubscriptionTransition {
+        subscription_id: &subscription.id,
+        list_id,
+        from,
+        to,
+        source: SubscriptionEventSource::Operator,
+        context: &RequestContext::default(),
+        token: None,
+    };
+    match transition_subscription(tx, transition).await? {
+        TransitionOutcome::Applied => Ok(true),
+        TransitionOutcome::LostRace => Ok(false),
+        TransitionOutcome::Illegal(illegal) => Err(anyhow::Error::from(illegal).into()),
+    }
+}
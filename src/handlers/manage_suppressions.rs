@@ -0,0 +1,70 @@
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::db::suppression_queries::{SuppressionQueries, SuppressionRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::suppression_reason::SuppressionReason;
+use crate::handlers::errors::error_chain_fmt;
+
+pub enum RemoveSuppressionOutput {
+    Success,
+    SuppressionNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageSuppressionsError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageSuppressionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Adding an email to the suppression list", skip(pg_pool, email))]
+pub async fn add_suppression(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+    reason: SuppressionReason,
+    source: &str,
+) -> Result<(), ManageSuppressionsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    SuppressionQueries::insert_suppression(&mut tx, &email.canonical_hash(), reason, source)
+        .await
+        .context("Failed to store the suppression")?;
+    commit_transaction(tx).await?;
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Removing an email from the suppression list",
+    skip(pg_pool, email)
+)]
+pub async fn remove_suppression(
+    pg_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<RemoveSuppressionOutput, ManageSuppressionsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let deleted = SuppressionQueries::delete_suppression(&mut tx, &email.canonical_hash())
+        .await
+        .context("Failed to delete the suppression")?;
+    commit_transaction(tx).await?;
+    if deleted {
+        Ok(RemoveSuppressionOutput::Success)
+    } else {
+        Ok(RemoveSuppressionOutput::SuppressionNotFound)
+    }
+}
+
+#[tracing::instrument(name = "Listing the suppression list", skip(pg_pool))]
+pub async fn list_suppressions(
+    pg_pool: &PgPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SuppressionRecord>, ManageSuppressionsError> {
+    let suppressions = SuppressionQueries::fetch_suppressions(pg_pool, limit, offset)
+        .await
+        .context("Failed to fetch the suppressions")?;
+    Ok(suppressions)
+}
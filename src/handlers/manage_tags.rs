@@ -0,0 +1,177 @@
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::subscription_queries::{
+    SubscriberRefRecord, SubscriberSummaryRecord, SubscriptionQueries,
+};
+use crate::db::tag_queries::{TagQueries, TagRecord, TaggedSubscriberRecord};
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::tag_name::TagName;
+use crate::event_bus::EventBus;
+use crate::events::subscriber_tag_changed::{SubscriberTagChanged, TagChange};
+use crate::handlers::errors::error_chain_fmt;
+
+/// The subscribers a bulk tag change applies to, by id or by email
+pub struct SubscriberRefs {
+    pub ids: Vec<Uuid>,
+    pub emails: Vec<SubscriberEmail>,
+}
+
+#[derive(Serialize)]
+pub struct TagChangeReport {
+    /// Subscribers that already had the tag (or did not have it) are not counted
+    pub changed: usize,
+    /// The ids and emails that match no subscriber
+    pub not_found: Vec<String>,
+}
+
+pub enum RemoveTagOutput {
+    Success(TagChangeReport),
+    TagNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ManageTagsError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ManageTagsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// The tag is created if it does not exist yet
+#[tracing::instrument(name = "Tagging subscribers", skip(pg_pool, event_bus, subscribers))]
+pub async fn add_tag(
+    pg_pool: &PgPool,
+    event_bus: &EventBus,
+    tag: &TagName,
+    subscribers: &SubscriberRefs,
+) -> Result<TagChangeReport, ManageTagsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let found = find_subscribers(&mut tx, subscribers).await?;
+    let tag_id = TagQueries::upsert_tag(&mut tx, tag)
+        .await
+        .context("Failed to store the tag")?;
+    let ids: Vec<Uuid> = found.iter().map(|subscriber| subscriber.id).collect();
+    let tagged = TagQueries::add_tag(&mut tx, &tag_id, &ids)
+        .await
+        .context("Failed to tag the subscribers")?;
+    publish_changes(event_bus, tag, TagChange::Added, &tagged).await?;
+    commit_transaction(tx).await?;
+    Ok(TagChangeReport {
+        changed: tagged.len(),
+        not_found: not_found(subscribers, &found),
+    })
+}
+
+#[tracing::instrument(name = "Untagging subscribers", skip(pg_pool, event_bus, subscribers))]
+pub async fn remove_tag(
+    pg_pool: &PgPool,
+    event_bus: &EventBus,
+    tag: &TagName,
+    subscribers: &SubscriberRefs,
+) -> Result<RemoveTagOutput, ManageTagsError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let tag_id = match TagQueries::fetch_tag_id(&mut tx, tag)
+        .await
+        .context("Failed to fetch the tag")?
+    {
+        Some(tag_id) => tag_id,
+        None => return Ok(RemoveTagOutput::TagNotFound),
+    };
+    let found = find_subscribers(&mut tx, subscribers).await?;
+    let ids: Vec<Uuid> = found.iter().map(|subscriber| subscriber.id).collect();
+    let untagged = TagQueries::remove_tag(&mut tx, &tag_id, &ids)
+        .await
+        .context("Failed to untag the subscribers")?;
+    publish_changes(event_bus, tag, TagChange::Removed, &untagged).await?;
+    commit_transaction(tx).await?;
+    Ok(RemoveTagOutput::Success(TagChangeReport {
+        changed: untagged.len(),
+        not_found: not_found(subscribers, &found),
+    }))
+}
+
+#[tracing::instrument(name = "Listing the tags", skip(pg_pool))]
+pub async fn list_tags(pg_pool: &PgPool) -> Result<Vec<TagRecord>, ManageTagsError> {
+    let tags = TagQueries::fetch_tags(pg_pool)
+        .await
+        .context("Failed to fetch the tags")?;
+    Ok(tags)
+}
+
+#[tracing::instrument(name = "Listing the subscribers", skip(pg_pool))]
+pub async fn list_subscribers(
+    pg_pool: &PgPool,
+    tag: Option<&TagName>,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<SubscriberSummaryRecord>, ManageTagsError> {
+    let subscribers = SubscriptionQueries::fetch_subscriptions_page(
+        pg_pool,
+        tag.map(|tag| tag.as_ref().as_str()),
+        limit.into(),
+        offset.into(),
+    )
+    .await
+    .context("Failed to fetch the subscribers")?;
+    Ok(subscribers)
+}
+
+async fn find_subscribers(
+    tx: &mut Tx<'_>,
+    subscribers: &SubscriberRefs,
+) -> anyhow::Result<Vec<SubscriberRefRecord>> {
+    let emails: Vec<String> = subscribers
+        .emails
+        .iter()
+        .map(|email| email.as_ref().to_owned())
+        .collect();
+    SubscriptionQueries::fetch_subscriptions_by_ids_or_emails(&mut *tx, &subscribers.ids, &emails)
+        .await
+        .context("Failed to fetch the subscribers to tag")
+}
+
+fn not_found(subscribers: &SubscriberRefs, found: &[SubscriberRefRecord]) -> Vec<String> {
+    let missing_ids = subscribers
+        .ids
+        .iter()
+        .filter(|id| !found.iter().any(|subscriber| subscriber.id == **id))
+        .map(|id| id.to_string());
+    let missing_emails = subscribers
+        .emails
+        .iter()
+        .filter(|email| {
+            !found
+                .iter()
+                .any(|subscriber| &subscriber.email == email.as_ref())
+        })
+        .map(|email| email.as_ref().to_owned());
+    missing_ids.chain(missing_emails).collect()
+}
+
+/// One event per subscriber that actually changed, published before the commit:
+/// if publishing fails nothing is changed, and the admin can retry the whole request.
+/// The changes published before the failure are published again by the retry.
+async fn publish_changes(
+    event_bus: &EventBus,
+    tag: &TagName,
+    change: TagChange,
+    subscribers: &[TaggedSubscriberRecord],
+) -> anyhow::Result<()> {
+    for subscriber in subscribers {
+        let event = SubscriberTagChanged {
+            subscription_id: subscriber.subscription_id,
+            email: subscriber.email.clone(),
+            tag: tag.as_ref().to_owned(),
+            change,
+        };
+        event_bus.publish(event).await?;
+    }
+    Ok(())
+}
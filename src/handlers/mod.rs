@@ -1,3 +1,25 @@
 pub mod confirm_subscription;
+pub mod create_data_request;
+pub mod erase_subscriber;
 pub mod errors;
+pub mod export_subscriber_data;
+pub mod fetch_consent_history;
+pub mod import_subscribers;
+pub mod ingest_sendgrid_events;
+pub mod manage_attributes;
+pub mod manage_issues;
+pub mod manage_lists;
+pub mod manage_preferences;
+pub mod manage_segments;
+pub mod manage_subscribers;
+pub mod manage_suppressions;
+pub mod manage_tags;
+pub mod preview_issue;
+pub mod purge_expired_tokens;
+pub mod record_tracking_event;
+pub mod replay_dead_letters;
 pub mod save_new_subscriber;
+pub mod send_due_digests;
+pub mod send_due_issues;
+pub mod transition_subscription;
+pub mod unsubscribe;
@@ -0,0 +1,182 @@
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::issue_queries::IssueQueries;
+use crate::db::subject_variant_queries::SubjectVariantQueries;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::send_due_issues::{issue_sender, recipient_subject, render_issue};
+
+/// The issue exactly as the sample subscriber would get it
+#[derive(Serialize)]
+pub struct IssuePreview {
+    /// The sample subscriber, whose footer is appended
+    #[serde(skip)]
+    pub subscription_id: Uuid,
+    pub from: String,
+    pub to: String,
+    pub subject: String,
+    pub text_content: String,
+    pub html_content: Option<String>,
+}
+
+pub enum PreviewIssueOutput {
+    Success(IssuePreview),
+    IssueNotFound,
+    /// The sample subscriber is not a member of the list of the issue
+    SubscriberNotFound,
+}
+
+pub enum TestSendIssueOutput {
+    Success,
+    IssueNotFound,
+    SubscriberNotFound,
+    /// The recipient is not in `ISSUE_SEED_ADDRESSES`
+    RecipientNotAllowed(String),
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct PreviewIssueError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for PreviewIssueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Renders the issue the same way the scheduler does, with the footer `EmailClient` appends.
+/// Any issue can be previewed, whatever its status.
+#[tracing::instrument(
+    name = "Previewing an issue",
+    skip(config, email_client, pg_pool, subscriber)
+)]
+pub async fn preview_issue(
+    config: &Config,
+    email_client: &EmailClient,
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    subscriber: &SubscriberEmail,
+) -> Result<PreviewIssueOutput, PreviewIssueError> {
+    let preview = match render_preview(config, pg_pool, issue_id, subscriber).await? {
+        PreviewIssueOutput::Success(preview) => preview,
+        not_found => return Ok(not_found),
+    };
+    let body = email_client
+        .with_footer(
+            &preview.subscription_id,
+            &preview.text_content,
+            preview.html_content.as_deref(),
+        )
+        .await?;
+    Ok(PreviewIssueOutput::Success(IssuePreview {
+        text_content: body.text_content,
+        html_content: body.html_content,
+        ..preview
+    }))
+}
+
+/// The issue as the scheduler renders it, before `EmailClient` appends the footer
+async fn render_preview(
+    config: &Config,
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    subscriber: &SubscriberEmail,
+) -> Result<PreviewIssueOutput, PreviewIssueError> {
+    let issue = match IssueQueries::fetch_issue(pg_pool, issue_id)
+        .await
+        .context("Failed to fetch the issue")?
+    {
+        Some(issue) => issue,
+        None => return Ok(PreviewIssueOutput::IssueNotFound),
+    };
+    let member =
+        match IssueQueries::fetch_member_by_email(pg_pool, &issue.list_id, subscriber.as_ref())
+            .await
+            .context("Failed to fetch the sample subscriber")?
+        {
+            Some(member) => member,
+            None => return Ok(PreviewIssueOutput::SubscriberNotFound),
+        };
+    let variants = SubjectVariantQueries::fetch_variants(pg_pool, issue_id)
+        .await
+        .context("Failed to fetch the subject variants")?;
+    // Before the A/B test is over, the winner of the holdout is unknown
+    let subject = recipient_subject(
+        &issue,
+        &variants,
+        issue.winning_variant_id.as_ref(),
+        &member.subscription_id,
+    )
+    .map(|(subject, _)| subject)
+    .unwrap_or(&issue.subject)
+    .to_owned();
+    let rendered = render_issue(config, &issue, &member);
+    Ok(PreviewIssueOutput::Success(IssuePreview {
+        subscription_id: member.subscription_id,
+        from: issue_sender(config, &issue).to_owned(),
+        to: member.email,
+        subject,
+        text_content: rendered.text_content,
+        html_content: rendered.html_content,
+    }))
+}
+
+/// Sends the preview to seed addresses only.
+/// Test sends are neither recorded in `issue_deliveries` nor in `email_deliveries`:
+/// the subscriber still gets the issue once it is sent for real.
+#[tracing::instrument(
+    name = "Test sending an issue",
+    skip(config, email_client, pg_pool, subscriber, recipients)
+)]
+pub async fn test_send_issue(
+    config: &Config,
+    email_client: &EmailClient,
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    subscriber: &SubscriberEmail,
+    recipients: &[SubscriberEmail],
+) -> Result<TestSendIssueOutput, PreviewIssueError> {
+    if let Some(recipient) = recipients
+        .iter()
+        .find(|recipient| !is_seed_address(config, recipient))
+    {
+        return Ok(TestSendIssueOutput::RecipientNotAllowed(
+            recipient.as_ref().to_owned(),
+        ));
+    }
+    // The footer is appended when sending, like for the subscribers
+    let preview = match render_preview(config, pg_pool, issue_id, subscriber).await? {
+        PreviewIssueOutput::Success(preview) => preview,
+        PreviewIssueOutput::IssueNotFound => return Ok(TestSendIssueOutput::IssueNotFound),
+        PreviewIssueOutput::SubscriberNotFound => {
+            return Ok(TestSendIssueOutput::SubscriberNotFound)
+        }
+    };
+    for recipient in recipients {
+        email_client
+            .send_test_email(
+                &preview.from,
+                &preview.subscription_id,
+                recipient,
+                &preview.subject,
+                &preview.text_content,
+                preview.html_content.as_deref(),
+            )
+            .await
+            .context("Failed to send the test email")?;
+    }
+    Ok(TestSendIssueOutput::Success)
+}
+
+fn is_seed_address(config: &Config, recipient: &SubscriberEmail) -> bool {
+    let recipient = recipient.as_ref().trim().to_lowercase();
+    config
+        .issue_seed_addresses
+        .iter()
+        .any(|seed| seed.trim().to_lowercase() == recipient)
+}
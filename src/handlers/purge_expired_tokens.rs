@@ -0,0 +1,31 @@
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::db::data_request_queries::DataRequestQueries;
+use crate::handlers::errors::error_chain_fmt;
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct PurgeExpiredTokensError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for PurgeExpiredTokensError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Expired data request tokens are rejected anyway, this only keeps the table small.
+/// Subscription and unsubscribe tokens do not expire.
+/// Returns the number of deleted tokens.
+#[tracing::instrument(name = "Purging the expired tokens", skip(pg_pool))]
+pub async fn purge_expired_tokens(
+    pg_pool: &PgPool,
+    data_request_token_ttl_minutes: u32,
+) -> Result<u64, PurgeExpiredTokensError> {
+    let before = Utc::now() - Duration::minutes(data_request_token_ttl_minutes.into());
+    let deleted = DataRequestQueries::delete_tokens_created_before(pg_pool, before)
+        .await
+        .context("Failed to delete the expired data request tokens")?;
+    Ok(deleted)
+}
@@ -0,0 +1,112 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::issue_link_queries::{IssueLinkQueries, IssueLinkRecord};
+use crate::db::issue_queries::IssueQueries;
+use crate::db::tracking_event_queries::TrackingEventQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::tracking_event_kind::TrackingEventKind;
+use crate::handlers::errors::error_chain_fmt;
+use crate::tracking::find_links;
+
+pub enum TrackClickOutput {
+    Redirect(String),
+    /// The issue is gone or has no such link
+    LinkNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct RecordTrackingEventError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for RecordTrackingEventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+#[tracing::instrument(name = "Tracking an open", skip(pg_pool))]
+pub async fn track_open(
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    subscription_id: &Uuid,
+) -> Result<(), RecordTrackingEventError> {
+    TrackingEventQueries::insert_event(
+        pg_pool,
+        issue_id,
+        subscription_id,
+        TrackingEventKind::Open,
+        None,
+    )
+    .await
+    .context("Failed to record the open")?;
+    Ok(())
+}
+
+/// Redirects to the link of the issue at the given position:
+/// the redirect can only ever go to a link stored when the issue was written
+#[tracing::instrument(name = "Tracking a click", skip(pg_pool))]
+pub async fn track_click(
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    subscription_id: &Uuid,
+    position: usize,
+) -> Result<TrackClickOutput, RecordTrackingEventError> {
+    let position = match i32::try_from(position) {
+        Ok(position) => position,
+        Err(_) => return Ok(TrackClickOutput::LinkNotFound),
+    };
+    let link = match IssueLinkQueries::fetch_link(pg_pool, issue_id, position)
+        .await
+        .context("Failed to fetch the issue link")?
+    {
+        Some(link) => link,
+        None => match backfill_link(pg_pool, issue_id, position).await? {
+            Some(link) => link,
+            None => return Ok(TrackClickOutput::LinkNotFound),
+        },
+    };
+    TrackingEventQueries::insert_event(
+        pg_pool,
+        issue_id,
+        subscription_id,
+        TrackingEventKind::Click,
+        Some(&link),
+    )
+    .await
+    .context("Failed to record the click")?;
+    Ok(TrackClickOutput::Redirect(link.url))
+}
+
+/// Issues written before the links were stored have none:
+/// their links are found in the text, like when an issue is written, and stored on the first click
+async fn backfill_link(
+    pg_pool: &PgPool,
+    issue_id: &Uuid,
+    position: i32,
+) -> Result<Option<IssueLinkRecord>, RecordTrackingEventError> {
+    let issue = match IssueQueries::fetch_issue(pg_pool, issue_id)
+        .await
+        .context("Failed to fetch the issue")?
+    {
+        Some(issue) => issue,
+        None => return Ok(None),
+    };
+    let links: Vec<&str> = find_links(&issue.text_content)
+        .iter()
+        .map(|link| link.url)
+        .collect();
+    if position as usize >= links.len() {
+        return Ok(None);
+    }
+    let mut tx = begin_transaction(pg_pool).await?;
+    IssueLinkQueries::insert_links(&mut tx, issue_id, &links)
+        .await
+        .context("Failed to backfill the issue links")?;
+    let link = IssueLinkQueries::fetch_link(&mut tx, issue_id, position)
+        .await
+        .context("Failed to fetch the issue link")?;
+    commit_transaction(tx).await?;
+    Ok(link)
+}
@@ -0,0 +1,56 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::db::dead_letter_queries::{DeadLetterQueries, DeadLetterRecord};
+use crate::event_bus::EventBus;
+use crate::handlers::errors::error_chain_fmt;
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct ReplayDeadLettersError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for ReplayDeadLettersError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// `ids` empty = every dead letter of the subject, or of every subject
+#[tracing::instrument(name = "Listing the dead letters", skip(pg_pool, ids))]
+pub async fn list_dead_letters(
+    pg_pool: &PgPool,
+    subject: Option<&str>,
+    ids: &[Uuid],
+    include_replayed: bool,
+) -> Result<Vec<DeadLetterRecord>, ReplayDeadLettersError> {
+    let dead_letters =
+        DeadLetterQueries::fetch_dead_letters(pg_pool, subject, ids, include_replayed)
+            .await
+            .context("Failed to fetch the dead letters")?;
+    Ok(dead_letters)
+}
+
+/// Publishes the dead letters not replayed yet again, as they were delivered:
+/// the consumers skip the events they already processed since.
+/// Returns the replayed ones, the replay stops at the first failure.
+#[tracing::instrument(name = "Replaying the dead letters", skip(pg_pool, event_bus, ids))]
+pub async fn replay_dead_letters(
+    pg_pool: &PgPool,
+    event_bus: &EventBus,
+    subject: Option<&str>,
+    ids: &[Uuid],
+) -> Result<Vec<DeadLetterRecord>, ReplayDeadLettersError> {
+    let dead_letters = list_dead_letters(pg_pool, subject, ids, false).await?;
+    for dead_letter in &dead_letters {
+        event_bus
+            .republish(&dead_letter.subject, dead_letter.data.clone())
+            .await?;
+        // Replayed twice if this fails, which the consumers skip
+        DeadLetterQueries::mark_as_replayed(pg_pool, &dead_letter.id)
+            .await
+            .context("Failed to mark the dead letter as replayed")?;
+        tracing::info!(dead_letter_id = %dead_letter.id, subject = %dead_letter.subject, "Replayed a dead letter");
+    }
+    Ok(dead_letters)
+}
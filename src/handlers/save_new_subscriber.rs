@@ -2,18 +2,30 @@ use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::config::Config;
+use crate::db::list_membership_queries::ListMembershipQueries;
+use crate::db::list_queries::ListQueries;
+use crate::db::subscription_event_queries::{NewSubscriptionEvent, SubscriptionEventQueries};
 use crate::db::subscription_queries::SubscriptionQueries;
 use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::list_slug::ListSlug;
 use crate::domain::new_subscriber::NewSubscriber;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
 use crate::domain::subscription_status::SubscriptionStatus;
-use crate::events::subscription_created::SubscriptionCreated;
+use crate::event_bus::EventBus;
+use crate::events::subscription_created::{ListConfirmation, SubscriptionCreated};
 use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
 
 pub enum SaveNewSubscriberOutput {
     Success,
     ResendConfirmation,
+    /// Already a confirmed member of every requested list
     AlreadySubscribed,
+    ListNotFound,
 }
 
 #[derive(thiserror::Error)]
@@ -28,76 +40,139 @@ impl std::fmt::Debug for SaveNewSubscriberError {
 
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
-    skip(config, pg_pool, nats_connection, new_subscriber)
+    skip(pg_pool, event_bus, new_subscriber, context)
 )]
 pub async fn save_new_subscriber(
-    config: &Config,
     pg_pool: &PgPool,
-    nats_connection: &async_nats::Connection,
+    event_bus: &EventBus,
     new_subscriber: NewSubscriber,
+    list_slugs: &[ListSlug],
+    context: &RequestContext,
 ) -> Result<SaveNewSubscriberOutput, SaveNewSubscriberError> {
-    let subscription_token = Uuid::new_v4();
-    let maybe_subscription =
-        SubscriptionQueries::fetch_subscription_by_email(pg_pool, new_subscriber.email.as_ref())
+    let mut tx = begin_transaction(pg_pool).await?;
+    let lists = ListQueries::fetch_lists_by_slugs(&mut tx, list_slugs)
+        .await
+        .context("Failed to fetch the requested lists")?;
+    if lists.len() != list_slugs.len() {
+        return Ok(SaveNewSubscriberOutput::ListNotFound);
+    }
+    let subscription = SubscriptionQueries::upsert_pending_subscriber(&mut tx, &new_subscriber)
+        .await
+        .context("Failed to upsert the subscription")?;
+    let subscription_id = subscription.id;
+    let mut confirmations = vec![];
+    for list in lists {
+        let subscription_token = Uuid::new_v4();
+        let membership =
+            ListMembershipQueries::upsert_pending_membership(&mut tx, &subscription_id, &list.id)
+                .await
+                .context("Failed to upsert the list membership")?;
+        match membership.status {
+            // Nothing to confirm
+            SubscriptionStatus::Confirmed => continue,
+            SubscriptionStatus::Pending if membership.inserted => {
+                record_creation(
+                    &mut tx,
+                    &subscription_id,
+                    Some(&list.id),
+                    context,
+                    &subscription_token,
+                )
+                .await?;
+            }
+            // Resend = keep it pending
+            SubscriptionStatus::Pending => {}
+            from => {
+                let transition = SubscriptionTransition {
+                    subscription_id: &subscription_id,
+                    list_id: Some(&list.id),
+                    from,
+                    to: SubscriptionStatus::Pending,
+                    source: SubscriptionEventSource::SubscribeForm,
+                    context,
+                    token: Some(&subscription_token.to_string()),
+                };
+                expect_applied(transition_subscription(&mut tx, transition).await?)?;
+            }
+        }
+        SubscriptionQueries::store_token(&mut tx, &subscription_id, &list.id, &subscription_token)
             .await
-            .context("Failed to fetch a subscription by the email")?;
-    let status: SaveNewSubscriberOutput;
-    let subscription_id: Uuid;
-    let mut tx = match maybe_subscription {
-        Some(sub) if sub.status == SubscriptionStatus::Confirmed => {
-            return Ok(SaveNewSubscriberOutput::AlreadySubscribed)
+            .context("Failed to store the subscription token")?;
+        confirmations.push(ListConfirmation {
+            slug: list.slug,
+            name: list.name,
+            subscription_token,
+        });
+    }
+    if confirmations.is_empty() {
+        // Dropping the transaction rolls back the no-op upserts
+        return Ok(SaveNewSubscriberOutput::AlreadySubscribed);
+    }
+    let status = match subscription.status {
+        // Subscription did not exist, a pending one was inserted
+        SubscriptionStatus::Pending if subscription.inserted => {
+            let subscription_token = &confirmations[0].subscription_token;
+            record_creation(&mut tx, &subscription_id, None, context, subscription_token).await?;
+            SaveNewSubscriberOutput::Success
         }
-        maybe_subscription => {
-            let mut tx = begin_transaction(pg_pool).await?;
-            match maybe_subscription {
-                // Failed = change to Pending
-                Some(sub) if sub.status == SubscriptionStatus::Failed => {
-                    SubscriptionQueries::update_subscription_status(
-                        &mut tx,
-                        &sub.id,
-                        SubscriptionStatus::Pending,
-                    )
-                    .await
-                    .context("Failed to update the subscription status to Pending")?;
-                    subscription_id = sub.id;
-                    status = SaveNewSubscriberOutput::ResendConfirmation;
-                }
-                // Resend = do nothing
-                Some(sub) if sub.status == SubscriptionStatus::Pending => {
-                    subscription_id = sub.id;
-                    status = SaveNewSubscriberOutput::ResendConfirmation;
-                }
-                // Subscription does not exist, insert a new one
-                _ => {
-                    subscription_id = SubscriptionQueries::insert_subscriber(
-                        &mut tx,
-                        &new_subscriber,
-                        SubscriptionStatus::Pending,
-                    )
-                    .await
-                    .context("Failed to insert a new subscription")?;
-                    status = SaveNewSubscriberOutput::Success;
-                }
-            }
-            tx
+        // Failed = change to Pending
+        SubscriptionStatus::Failed => {
+            let transition = SubscriptionTransition {
+                subscription_id: &subscription_id,
+                list_id: None,
+                from: SubscriptionStatus::Failed,
+                to: SubscriptionStatus::Pending,
+                source: SubscriptionEventSource::SubscribeForm,
+                context,
+                token: Some(&confirmations[0].subscription_token.to_string()),
+            };
+            expect_applied(transition_subscription(&mut tx, transition).await?)?;
+            SaveNewSubscriberOutput::ResendConfirmation
         }
+        // Pending = resend, Confirmed = the address is confirmed but some lists are not
+        _ => SaveNewSubscriberOutput::ResendConfirmation,
     };
-    SubscriptionQueries::store_token(&mut tx, &subscription_id, &subscription_token)
-        .await
-        .context("Failed to store the subscription token")?;
     commit_transaction(tx).await?;
     let event = SubscriptionCreated {
         email: new_subscriber.email,
         name: new_subscriber.name,
-        subscription_token,
         subscription_id,
+        lists: confirmations,
     };
-    nats_connection
-        .publish(
-            &config.nats_subscription_created_subject(),
-            serde_json::to_vec(&event).context("Failed to serialize SubscriptionCreated event")?,
-        )
-        .await
-        .context("Failed to publish SubscriptionCreated event")?;
+    event_bus.publish(event).await?;
     Ok(status)
 }
+
+/// Rows are locked by the upserts, so the race cannot be lost
+fn expect_applied(outcome: TransitionOutcome) -> anyhow::Result<()> {
+    match outcome {
+        TransitionOutcome::Applied => Ok(()),
+        outcome => Err(anyhow::anyhow!(
+            "Failed to move the subscription back to Pending: {:?}",
+            outcome
+        )),
+    }
+}
+
+async fn record_creation(
+    tx: &mut Tx<'_>,
+    subscription_id: &Uuid,
+    list_id: Option<&Uuid>,
+    context: &RequestContext,
+    subscription_token: &Uuid,
+) -> anyhow::Result<()> {
+    SubscriptionEventQueries::insert_event(
+        tx,
+        &NewSubscriptionEvent {
+            subscription_id,
+            list_id,
+            old_status: None,
+            new_status: SubscriptionStatus::Pending,
+            source: SubscriptionEventSource::SubscribeForm,
+            context,
+            token: Some(&subscription_token.to_string()),
+        },
+    )
+    .await
+    .context("Failed to record the subscription event")
+}
@@ -0,0 +1,163 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::config::Config;
+use crate::db::digest_queries::{DigestIssueRecord, DigestQueries, DigestSubscriberRecord};
+use crate::db::issue_queries::IssueQueries;
+use crate::db::segment_queries::SegmentQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::db::types::Tx;
+use crate::domain::delivery_cadence::DeliveryCadence;
+use crate::domain::email_template::EmailTemplate;
+use crate::domain::segment::Segment;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::personalisation::personalise;
+
+/// Sends one email with every issue published since the previous digest
+/// to the daily and weekly digest subscribers whose period is over.
+/// Periods follow the calendar in UTC: a digest goes out on the first run of each day, or week,
+/// and a subscriber who joined during the current period waits for the next one.
+/// The issues are claimed in `issue_deliveries` and the cursor is moved in one transaction
+/// before the email goes out, so no issue is sent twice.
+/// Callers must make sure that a single scheduler runs at a time.
+#[tracing::instrument(name = "Sending due digests", skip(config, email_client, pg_pool))]
+pub async fn send_due_digests(
+    config: &Config,
+    email_client: &EmailClient,
+    pg_pool: &PgPool,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let subscribers = DigestQueries::fetch_due_subscribers(pg_pool, now)
+        .await
+        .context("Failed to fetch the due digest subscribers")?;
+    for subscriber in subscribers {
+        if let Err(err) = send_digest(config, email_client, pg_pool, &subscriber, now).await {
+            tracing::error!(
+                error = ?err,
+                subscription_id = %subscriber.subscription_id,
+                "Failed to send the digest"
+            );
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Sending a digest",
+    skip(config, email_client, pg_pool, subscriber),
+    fields(subscription_id = %subscriber.subscription_id)
+)]
+async fn send_digest(
+    config: &Config,
+    email_client: &EmailClient,
+    pg_pool: &PgPool,
+    subscriber: &DigestSubscriberRecord,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let published =
+        DigestQueries::fetch_issues_since_last_digest(&mut tx, &subscriber.subscription_id)
+            .await
+            .context("Failed to fetch the digest issues")?;
+    let mut issues = Vec::with_capacity(published.len());
+    for issue in published {
+        if in_issue_segment(&mut tx, &issue, subscriber, now).await? {
+            issues.push(issue);
+        }
+    }
+    // Nothing new: the issues published from now on wait for the next period
+    let last_issue = match issues.last() {
+        Some(last_issue) => last_issue,
+        None => {
+            DigestQueries::skip_digest(&mut tx, &subscriber.subscription_id, &now)
+                .await
+                .context("Failed to skip the empty digest")?;
+            commit_transaction(tx).await?;
+            return Ok(());
+        }
+    };
+    for issue in &issues {
+        IssueQueries::claim_delivery(
+            &mut tx,
+            &issue.issue_id,
+            &subscriber.subscription_id,
+            true,
+            None,
+        )
+        .await
+        .context("Failed to claim the issue delivery")?;
+    }
+    DigestQueries::upsert_cursor(
+        &mut tx,
+        &subscriber.subscription_id,
+        &last_issue.published_at,
+        &now,
+    )
+    .await
+    .context("Failed to move the digest cursor")?;
+    commit_transaction(tx).await?;
+
+    let email = SubscriberEmail::parse(subscriber.email.clone()).map_err(anyhow::Error::msg)?;
+    let subject = match subscriber.delivery_cadence {
+        DeliveryCadence::Weekly => "Your weekly digest",
+        _ => "Your daily digest",
+    };
+    email_client
+        .deliver(
+            Some(&subscriber.subscription_id),
+            EmailTemplate::Digest,
+            &email,
+            subject,
+            &render_digest(config, subscriber, &issues),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Issues targeting a segment are only included for its members
+async fn in_issue_segment(
+    tx: &mut Tx<'_>,
+    issue: &DigestIssueRecord,
+    subscriber: &DigestSubscriberRecord,
+    now: DateTime<Utc>,
+) -> anyhow::Result<bool> {
+    let segment = match &issue.segment_definition {
+        Some(definition) => Segment::parse(definition.clone())
+            .map_err(anyhow::Error::msg)
+            .context("Failed to parse the segment of the issue")?,
+        None => return Ok(true),
+    };
+    SegmentQueries::contains(&mut *tx, &segment, now, &subscriber.subscription_id)
+        .await
+        .context("Failed to check the segment of the issue")
+}
+
+fn render_digest(
+    config: &Config,
+    subscriber: &DigestSubscriberRecord,
+    issues: &[DigestIssueRecord],
+) -> String {
+    let empty = serde_json::Map::new();
+    let attributes = subscriber.attributes.as_object().unwrap_or(&empty);
+    let sections: Vec<String> = issues
+        .iter()
+        .map(|issue| {
+            format!(
+                "{} - {}\n\n{}\n\nUnsubscribe from {}: \
+                {}/subscriptions/unsubscribe?unsubscribe_token={}",
+                issue.list_name,
+                issue.subject,
+                personalise(&issue.text_content, &subscriber.name, attributes),
+                issue.list_name,
+                config.application_base_url(),
+                issue.unsubscribe_token
+            )
+        })
+        .collect();
+    format!(
+        "Here is what was published since your last digest:\n\n{}",
+        sections.join("\n\n* * *\n\n")
+    )
+}
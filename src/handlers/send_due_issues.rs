@@ -0,0 +1,351 @@
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::db::issue_queries::{IssueQueries, IssueRecipientRecord, IssueRecord};
+use crate::db::subject_variant_queries::{SubjectVariantQueries, SubjectVariantRecord};
+use crate::domain::ab_test::{assign_subject, SubjectAssignment};
+use crate::domain::email_template::EmailTemplate;
+use crate::domain::issue_status::IssueStatus;
+use crate::domain::segment::Segment;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::time_zone::IanaTimeZone;
+use crate::email_client::{EmailBody, EmailClient};
+use crate::html::escape_html;
+use crate::personalisation::personalise;
+use crate::tracking::{find_links, TrackingSigner, TrackingToken};
+
+/// Sends every due issue to the confirmed members of its list, or of its segment,
+/// except for digest subscribers: see `send_due_digests`.
+/// Each delivery is claimed in `issue_deliveries` before the email goes out,
+/// so a subscriber never gets the same issue twice, even if the scheduler crashes midway.
+/// Callers must make sure that a single scheduler runs at a time.
+#[tracing::instrument(name = "Sending due issues", skip(config, email_client, pg_pool))]
+pub async fn send_due_issues(
+    config: &Config,
+    email_client: &EmailClient,
+    pg_pool: &PgPool,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let issues = IssueQueries::fetch_due_issues(pg_pool, now)
+        .await
+        .context("Failed to fetch the due issues")?;
+    for issue in issues {
+        if issue.status == IssueStatus::Scheduled {
+            let scheduled_at = issue
+                .scheduled_at
+                .context("A scheduled issue has a scheduled_at")?;
+            if !IssueQueries::start_sending(pg_pool, &issue.id, &scheduled_at)
+                .await
+                .context("Failed to start sending the issue")?
+            {
+                tracing::info!(issue_id = %issue.id, "Issue was rescheduled or cancelled meanwhile");
+                continue;
+            }
+        }
+        if let Err(err) = send_issue(config, email_client, pg_pool, &issue, now).await {
+            // The next tick picks the issue up again
+            tracing::error!(error = ?err, issue_id = %issue.id, "Failed to send the issue");
+        }
+    }
+    Ok(())
+}
+
+#[tracing::instrument(
+    name = "Sending an issue",
+    skip(config, email_client, pg_pool, issue),
+    fields(issue_id = %issue.id)
+)]
+async fn send_issue(
+    config: &Config,
+    email_client: &EmailClient,
+    pg_pool: &PgPool,
+    issue: &IssueRecord,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let list_timezone = IanaTimeZone::parse(&issue.list_timezone).map_err(anyhow::Error::msg)?;
+    let send_at_local = issue
+        .send_at_local
+        .context("A scheduled issue has a send_at_local")?;
+    let variants = SubjectVariantQueries::fetch_variants(pg_pool, &issue.id)
+        .await
+        .context("Failed to fetch the subject variants")?;
+    let winning_variant_id = pick_winner_if_due(pg_pool, issue, now).await?;
+    let segment = issue_segment(issue)?;
+    let recipients = IssueQueries::fetch_pending_recipients(
+        pg_pool,
+        &issue.id,
+        &issue.list_id,
+        segment.as_ref(),
+        now,
+    )
+    .await
+    .context("Failed to fetch the issue recipients")?;
+    let mut has_recipients_later = false;
+    for recipient in recipients {
+        if issue.per_subscriber_timezone {
+            let timezone = recipient
+                .timezone
+                .as_deref()
+                .and_then(|timezone| IanaTimeZone::parse(timezone).ok())
+                .unwrap_or(list_timezone);
+            if timezone.to_utc(&send_at_local) > now {
+                has_recipients_later = true;
+                continue;
+            }
+        }
+        let (subject, variant_id) = match recipient_subject(
+            issue,
+            &variants,
+            winning_variant_id.as_ref(),
+            &recipient.subscription_id,
+        ) {
+            Some(subject) => subject,
+            None => {
+                // Gets the winner of the A/B test once the wait is over
+                has_recipients_later = true;
+                continue;
+            }
+        };
+        let claimed = IssueQueries::claim_delivery(
+            pg_pool,
+            &issue.id,
+            &recipient.subscription_id,
+            false,
+            variant_id,
+        )
+        .await
+        .context("Failed to claim the issue delivery")?;
+        if claimed {
+            send_to_recipient(config, email_client, issue, subject, &recipient).await;
+        }
+    }
+    if !has_recipients_later {
+        IssueQueries::compare_and_set_status(
+            pg_pool,
+            &issue.id,
+            IssueStatus::Sending,
+            IssueStatus::Sent,
+        )
+        .await
+        .context("Failed to mark the issue as sent")?;
+    }
+    Ok(())
+}
+
+/// Failures are recorded in `email_deliveries`, the issue is not retried for the recipient
+async fn send_to_recipient(
+    config: &Config,
+    email_client: &EmailClient,
+    issue: &IssueRecord,
+    subject: &str,
+    recipient: &IssueRecipientRecord,
+) {
+    let email = match SubscriberEmail::parse(recipient.email.clone()) {
+        Ok(email) => email,
+        Err(err) => {
+            tracing::error!(error = %err, "Invalid subscriber email");
+            return;
+        }
+    };
+    let body = render_issue(config, issue, recipient);
+    if let Err(err) = email_client
+        .deliver_from(
+            issue_sender(config, issue),
+            Some(&recipient.subscription_id),
+            EmailTemplate::Issue,
+            &email,
+            subject,
+            &body.text_content,
+            body.html_content.as_deref(),
+        )
+        .await
+    {
+        tracing::error!(
+            error = ?err,
+            subscription_id = %recipient.subscription_id,
+            "Failed to send the issue"
+        );
+    }
+}
+
+/// The issue for the recipient, before `EmailClient` appends the preferences link.
+/// On lists with tracking, unless the recipient opted out, the HTML part also gets
+/// the open pixel, and the links go through the click redirect.
+pub fn render_issue(
+    config: &Config,
+    issue: &IssueRecord,
+    recipient: &IssueRecipientRecord,
+) -> EmailBody {
+    let base_url = config.application_base_url();
+    let unsubscribe_url = format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+        base_url, recipient.unsubscribe_token
+    );
+    let empty = serde_json::Map::new();
+    let attributes = recipient.attributes.as_object().unwrap_or(&empty);
+    let personalise = |text: &str| personalise(text, &recipient.name, attributes);
+    if !issue.list_tracking_enabled || recipient.do_not_track {
+        let text_content = personalise(&issue.text_content);
+        return EmailBody {
+            text_content: format!(
+                "{}\n\n--\nUnsubscribe from {}: {}",
+                text_content, issue.list_name, unsubscribe_url
+            ),
+            html_content: Some(format!(
+                "{}\n<p>--<br>\n<a href=\"{}\">Unsubscribe from {}</a></p>",
+                html_paragraphs(&escape_html(&text_content)),
+                escape_html(&unsubscribe_url),
+                escape_html(&issue.list_name)
+            )),
+        };
+    }
+    let signer = TrackingSigner::new(config);
+    let tracking_url = |kind: &str, token: TrackingToken| {
+        format!("{}/t/{}/{}", base_url, kind, signer.sign(&token))
+    };
+    let mut text_content = String::new();
+    let mut html_content = String::new();
+    let mut rest = 0;
+    for (position, link) in find_links(&issue.text_content).iter().enumerate() {
+        let click_url = tracking_url(
+            "c",
+            TrackingToken::Click {
+                issue_id: issue.id,
+                subscription_id: recipient.subscription_id,
+                link: position,
+            },
+        );
+        // Links are found in the issue as written, so that their positions match the stored ones
+        let before = personalise(&issue.text_content[rest..link.start]);
+        text_content.push_str(&before);
+        text_content.push_str(&click_url);
+        html_content.push_str(&escape_html(&before));
+        html_content.push_str(&format!(
+            r#"<a href="{}">{}</a>"#,
+            escape_html(&click_url),
+            escape_html(link.url)
+        ));
+        rest = link.end;
+    }
+    let after = personalise(&issue.text_content[rest..]);
+    text_content.push_str(&after);
+    html_content.push_str(&escape_html(&after));
+    let open_url = tracking_url(
+        "o",
+        TrackingToken::Open {
+            issue_id: issue.id,
+            subscription_id: recipient.subscription_id,
+        },
+    );
+    EmailBody {
+        text_content: format!(
+            "{}\n\n--\nUnsubscribe from {}: {}",
+            text_content, issue.list_name, unsubscribe_url
+        ),
+        html_content: Some(format!(
+            "{}\n<p>--<br>\n<a href=\"{}\">Unsubscribe from {}</a></p>\n<img src=\"{}\" width=\"1\" height=\"1\" alt=\"\">",
+            html_paragraphs(&html_content),
+            escape_html(&unsubscribe_url),
+            escape_html(&issue.list_name),
+            escape_html(&open_url)
+        )),
+    }
+}
+
+/// The blank line separated blocks of the text become paragraphs
+fn html_paragraphs(html_content: &str) -> String {
+    let paragraphs: Vec<String> = html_content
+        .split("\n\n")
+        .map(|paragraph| format!("<p>{}</p>", paragraph.replace('\n', "<br>\n")))
+        .collect();
+    paragraphs.join("\n")
+}
+
+/// The subject of the recipient and its A/B test variant.
+/// `None` if they wait for the winner of the A/B test.
+pub fn recipient_subject<'a>(
+    issue: &'a IssueRecord,
+    variants: &'a [SubjectVariantRecord],
+    winning_variant_id: Option<&Uuid>,
+    subscription_id: &Uuid,
+) -> Option<(&'a str, Option<&'a Uuid>)> {
+    let audience_percent = match issue.ab_test_audience_percent {
+        Some(audience_percent) if !variants.is_empty() => audience_percent as u8,
+        _ => return Some((&issue.subject, None)),
+    };
+    let variant = match assign_subject(&issue.id, subscription_id, audience_percent, variants.len())
+    {
+        SubjectAssignment::Variant(position) => &variants[position],
+        SubjectAssignment::Holdout => variants
+            .iter()
+            .find(|variant| Some(&variant.id) == winning_variant_id)?,
+    };
+    Some((&variant.subject, Some(&variant.id)))
+}
+
+/// The variant with the best open rate wins, then the best click rate, then the first one
+async fn pick_winner_if_due(
+    pg_pool: &PgPool,
+    issue: &IssueRecord,
+    now: DateTime<Utc>,
+) -> anyhow::Result<Option<Uuid>> {
+    if issue.winning_variant_id.is_some() {
+        return Ok(issue.winning_variant_id);
+    }
+    let is_due = match (issue.ab_test_wait_minutes, issue.published_at) {
+        (Some(wait_minutes), Some(published_at)) => {
+            published_at + Duration::minutes(wait_minutes.into()) <= now
+        }
+        _ => false,
+    };
+    if !is_due {
+        return Ok(None);
+    }
+    let stats = SubjectVariantQueries::fetch_variant_stats(pg_pool, &issue.id)
+        .await
+        .context("Failed to fetch the subject variant stats")?;
+    // Rates compared without dividing: a / b > c / d <=> a * d > c * b
+    let winner = match stats.iter().max_by(|a, b| {
+        (a.opens * b.sent)
+            .cmp(&(b.opens * a.sent))
+            .then((a.clicks * b.sent).cmp(&(b.clicks * a.sent)))
+            .then(b.position.cmp(&a.position))
+    }) {
+        Some(winner) => winner,
+        None => return Ok(None),
+    };
+    if IssueQueries::set_winning_variant(pg_pool, &issue.id, &winner.id)
+        .await
+        .context("Failed to set the winning subject variant")?
+    {
+        tracing::info!(issue_id = %issue.id, variant_id = %winner.id, "Picked the winning subject");
+        Ok(Some(winner.id))
+    } else {
+        // Picked meanwhile
+        let issue = IssueQueries::fetch_issue(pg_pool, &issue.id)
+            .await
+            .context("Failed to fetch the issue")?
+            .context("Issues are never deleted")?;
+        Ok(issue.winning_variant_id)
+    }
+}
+
+pub fn issue_sender<'a>(config: &'a Config, issue: &'a IssueRecord) -> &'a str {
+    issue
+        .list_from_address
+        .as_deref()
+        .unwrap_or(&config.email_client_sender_email)
+}
+
+/// The segment the issue targets, as it is defined now
+pub fn issue_segment(issue: &IssueRecord) -> anyhow::Result<Option<Segment>> {
+    issue
+        .segment_definition
+        .clone()
+        .map(|definition| Segment::parse(definition).map_err(anyhow::Error::msg))
+        .transpose()
+        .context("Failed to parse the segment of the issue")
+}
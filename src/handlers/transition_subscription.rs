@@ -0,0 +1,104 @@
+use anyhow::Context;
+use uuid::Uuid;
+
+use crate::db::list_membership_queries::ListMembershipQueries;
+use crate::db::subscription_event_queries::{NewSubscriptionEvent, SubscriptionEventQueries};
+use crate::db::subscription_queries::SubscriptionQueries;
+use crate::db::types::Tx;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::{IllegalTransition, StatusOwner, SubscriptionStatus};
+
+/// The only way subscription and list membership statuses change after they are created
+pub struct SubscriptionTransition<'a> {
+    pub subscription_id: &'a Uuid,
+    /// `Some` to transition the membership of that list instead of the subscription
+    pub list_id: Option<&'a Uuid>,
+    /// Status the caller has observed
+    pub from: SubscriptionStatus,
+    pub to: SubscriptionStatus,
+    pub source: SubscriptionEventSource,
+    pub context: &'a RequestContext,
+    pub token: Option<&'a str>,
+}
+
+#[derive(Debug)]
+pub enum TransitionOutcome {
+    Applied,
+    /// Rejected by the state machine, nothing was written
+    Illegal(IllegalTransition),
+    /// The status is no longer `from`: a concurrent change won, nothing was written
+    LostRace,
+}
+
+/// Validates the transition, applies it with a compare-and-set
+/// and records it in the consent audit trail, within the caller's transaction.
+#[tracing::instrument(
+    name = "Transitioning a subscription status",
+    skip(tx, transition),
+    fields(
+        subscription_id = %transition.subscription_id,
+        list_id = ?transition.list_id,
+        from = %transition.from,
+        to = %transition.to,
+    )
+)]
+pub async fn transition_subscription(
+    tx: &mut Tx<'_>,
+    transition: SubscriptionTransition<'_>,
+) -> anyhow::Result<TransitionOutcome> {
+    let owner = match transition.list_id {
+        None => StatusOwner::Subscription,
+        Some(_) => StatusOwner::ListMembership,
+    };
+    if let Err(illegal) = transition.from.transition_to(transition.to, owner) {
+        tracing::warn!(error = %illegal, "Rejected a subscription status transition");
+        return Ok(TransitionOutcome::Illegal(illegal));
+    }
+    let applied = match transition.list_id {
+        None => {
+            SubscriptionQueries::compare_and_set_status(
+                tx,
+                transition.subscription_id,
+                transition.from,
+                transition.to,
+            )
+            .await
+        }
+        Some(list_id) => {
+            ListMembershipQueries::compare_and_set_status(
+                tx,
+                transition.subscription_id,
+                list_id,
+                transition.from,
+                transition.to,
+            )
+            .await
+        }
+    }
+    .with_context(|| {
+        format!(
+            "Failed to update the subscription status to {}",
+            transition.to
+        )
+    })?;
+    if !applied {
+        tracing::warn!("Lost a race on the subscription status");
+        return Ok(TransitionOutcome::LostRace);
+    }
+    SubscriptionEventQueries::insert_event(
+        tx,
+        &NewSubscriptionEvent {
+            subscription_id: transition.subscription_id,
+            list_id: transition.list_id,
+            old_status: Some(transition.from),
+            new_status: transition.to,
+            source: transition.source,
+            context: transition.context,
+            token: transition.token,
+        },
+    )
+    .await
+    .context("Failed to record the subscription event")?;
+    Ok(TransitionOutcome::Applied)
+}
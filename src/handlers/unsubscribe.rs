@@ -0,0 +1,91 @@
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::db::list_membership_queries::ListMembershipQueries;
+use crate::db::transaction::{begin_transaction, commit_transaction};
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscription_event_source::SubscriptionEventSource;
+use crate::domain::subscription_status::SubscriptionStatus;
+use crate::handlers::errors::error_chain_fmt;
+use crate::handlers::transition_subscription::{
+    transition_subscription, SubscriptionTransition, TransitionOutcome,
+};
+
+pub enum UnsubscribeOutput {
+    Success,
+    TokenNotFound,
+    /// The membership changed concurrently, the link can be followed again
+    Conflict,
+}
+
+pub enum FetchUnsubscribeRequestOutput {
+    /// The name of the list the token was issued for
+    Success(String),
+    TokenNotFound,
+}
+
+#[derive(thiserror::Error)]
+#[error(transparent)]
+pub struct UnsubscribeError(#[from] anyhow::Error);
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Checks the token without leaving the list, for the confirmation page
+#[tracing::instrument(name = "Fetch unsubscribe request", skip(pg_pool))]
+pub async fn fetch_unsubscribe_request(
+    unsubscribe_token: &str,
+    pg_pool: &PgPool,
+) -> Result<FetchUnsubscribeRequestOutput, UnsubscribeError> {
+    let maybe_membership =
+        ListMembershipQueries::fetch_membership_by_unsubscribe_token(pg_pool, unsubscribe_token)
+            .await
+            .context("Failed to fetch a list membership by the unsubscribe token")?;
+    Ok(match maybe_membership {
+        Some(membership) => FetchUnsubscribeRequestOutput::Success(membership.list_name),
+        None => FetchUnsubscribeRequestOutput::TokenNotFound,
+    })
+}
+
+/// Only leaves the list the token was issued for.
+/// Unsubscribe tokens do not expire and can be followed more than once.
+#[tracing::instrument(name = "Unsubscribe from a list", skip(pg_pool, context))]
+pub async fn unsubscribe(
+    unsubscribe_token: &str,
+    pg_pool: &PgPool,
+    context: &RequestContext,
+) -> Result<UnsubscribeOutput, UnsubscribeError> {
+    let mut tx = begin_transaction(pg_pool).await?;
+    let membership = match ListMembershipQueries::fetch_membership_by_unsubscribe_token(
+        &mut tx,
+        unsubscribe_token,
+    )
+    .await
+    .context("Failed to fetch a list membership by the unsubscribe token")?
+    {
+        Some(membership) => membership,
+        None => return Ok(UnsubscribeOutput::TokenNotFound),
+    };
+    if membership.status == SubscriptionStatus::Unsubscribed {
+        return Ok(UnsubscribeOutput::Success);
+    }
+    let transition = SubscriptionTransition {
+        subscription_id: &membership.subscription_id,
+        list_id: Some(&membership.list_id),
+        from: membership.status,
+        to: SubscriptionStatus::Unsubscribed,
+        source: SubscriptionEventSource::UnsubscribeLink,
+        context,
+        token: Some(unsubscribe_token),
+    };
+    match transition_subscription(&mut tx, transition).await? {
+        TransitionOutcome::Applied => {}
+        TransitionOutcome::LostRace => return Ok(UnsubscribeOutput::Conflict),
+        TransitionOutcome::Illegal(illegal) => return Err(anyhow::Error::from(illegal).into()),
+    }
+    commit_transaction(tx).await?;
+    Ok(UnsubscribeOutput::Success)
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::db::advisory_lock_queries::AdvisoryLockQueries;
+use crate::db::transaction::begin_transaction;
+use crate::email_client::EmailClient;
+use crate::handlers::send_due_digests::send_due_digests;
+use crate::handlers::send_due_issues::send_due_issues;
+use crate::shutdown::ShutdownListener;
+
+/// Any constant works, as long as nothing else locks it
+const ISSUE_SCHEDULER_LOCK_KEY: i64 = 0x7a65_726f_3270_7201;
+
+pub struct IssueScheduler;
+
+impl IssueScheduler {
+    /// Every replica runs a scheduler, the advisory lock makes sure
+    /// that only one of them sends issues and digests at a time.
+    /// A tick in progress on shutdown completes.
+    pub fn spawn(
+        config: Arc<Config>,
+        email_client: Arc<EmailClient>,
+        pg_pool: Arc<PgPool>,
+        mut shutdown: ShutdownListener,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(
+                config.issue_scheduler_poll_interval_millis,
+            ));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    () = shutdown.recv() => break,
+                }
+                if let Err(err) = IssueScheduler::tick(&config, &email_client, &pg_pool).await {
+                    tracing::error!(error = ?err, "Issue scheduler tick failed");
+                }
+            }
+            tracing::info!("Issue scheduler stopped");
+        })
+    }
+
+    #[tracing::instrument(name = "Issue scheduler tick", skip(config, email_client, pg_pool))]
+    pub async fn tick(
+        config: &Config,
+        email_client: &EmailClient,
+        pg_pool: &PgPool,
+    ) -> anyhow::Result<()> {
+        // The lock lives as long as this transaction, the sends use other connections
+        let mut lock_tx = begin_transaction(pg_pool).await?;
+        let acquired =
+            AdvisoryLockQueries::try_lock_for_transaction(&mut lock_tx, ISSUE_SCHEDULER_LOCK_KEY)
+                .await
+                .context("Failed to acquire the issue scheduler lock")?;
+        if !acquired {
+            tracing::debug!("Another replica is sending issues");
+            return Ok(());
+        }
+        let now = Utc::now();
+        // Issues first, so that digests include everything published up to now
+        let result = match send_due_issues(config, email_client, pg_pool, now).await {
+            Ok(()) => send_due_digests(config, email_client, pg_pool, now).await,
+            Err(err) => Err(err),
+        };
+        lock_tx
+            .rollback()
+            .await
+            .context("Failed to release the issue scheduler lock")?;
+        result
+    }
+}
@@ -1,9 +1,19 @@
+pub mod bootstrap;
 pub mod config;
 pub mod db;
 pub mod domain;
 pub mod email_client;
+pub mod event_bus;
 pub mod events;
 pub mod handlers;
+pub mod html;
+pub mod issue_scheduler;
+pub mod migrations;
+pub mod personalisation;
+pub mod processed_events_pruner;
 pub mod routes;
+pub mod sendgrid_webhook;
+pub mod shutdown;
 pub mod startup;
 pub mod telemetry;
+pub mod tracking;
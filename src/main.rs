@@ -1,38 +1,51 @@
 use std::net::TcpListener;
+use std::time::Duration;
 
-use secrecy::ExposeSecret;
-use sqlx::PgPool;
+use anyhow::Context;
 
-use zero2prod::config::Config;
-use zero2prod::email_client::EmailClient;
+use zero2prod::bootstrap::{bootstrap, Dependencies};
+use zero2prod::event_bus::EventBusBackend;
+use zero2prod::shutdown::{shutdown_signal, ShutdownCoordinator};
 use zero2prod::startup::run;
-use zero2prod::telemetry;
 
 #[tokio::main]
-async fn main() -> std::io::Result<()> {
-    let config = Config::new().expect("Failed to load config");
-
-    let subscriber = telemetry::get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    telemetry::init_subscriber(subscriber);
-    tracing::info!("Connecting to Postgres");
-
-    let connection_pool = PgPool::connect(config.database_url.expose_secret())
-        .await
-        .expect("Failed to connect to Postgres");
-
-    let nats_connection =
-        async_nats::connect(&format!("{}:{}", config.nats_host, config.nats_port)).await?;
-
-    let email_client = EmailClient::new(&config);
+async fn main() -> anyhow::Result<()> {
+    let Dependencies {
+        config,
+        pg_pool,
+        event_bus,
+        email_client,
+    } = bootstrap("zero2prod").await?;
+    if !config.event_consumers_enabled && config.event_bus_backend == EventBusBackend::InProcess {
+        anyhow::bail!(
+            "EVENT_CONSUMERS_ENABLED=false needs a worker, \
+            which cannot receive the events of the API with the in_process event bus backend"
+        );
+    }
+    let shutdown_deadline = Duration::from_millis(config.shutdown_deadline_millis);
 
     let address = format!("{}:{}", config.application_host, config.application_port);
     let listener = TcpListener::bind(address)?;
-    run(
+    let mut shutdown = ShutdownCoordinator::new();
+    let server = run(
         listener,
-        connection_pool,
-        nats_connection,
+        pg_pool.clone(),
+        event_bus.clone(),
         email_client,
         config,
-    )?
+        &mut shutdown,
+    )
     .await
+    .context("Failed to start the application")?;
+    shutdown.register_http_server(server.handle());
+    // Keeps running until stopped through its handle
+    let mut server = tokio::spawn(server);
+    tokio::select! {
+        result = &mut server => result?.context("The HTTP server failed")?,
+        () = shutdown_signal() => {}
+    }
+    shutdown
+        .shutdown(&event_bus, &pg_pool, shutdown_deadline)
+        .await;
+    Ok(())
 }
@@ -0,0 +1,131 @@
+use anyhow::Context;
+use serde::Serialize;
+use sqlx::migrate::{Migrate, Migration, Migrator};
+use sqlx::{PgConnection, PgPool};
+
+use crate::db::migration_queries::MigrationQueries;
+
+/// The `./migrations` directory, embedded in the binaries at compile time
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, Serialize)]
+pub struct PendingMigration {
+    pub version: i64,
+    pub description: String,
+}
+
+/// The database cannot be used by this binary, migrating would not help
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum SchemaMismatch {
+    #[error(
+        "The database schema is newer than this binary: \
+        the migration {0} was applied but is not embedded, deploy a newer version"
+    )]
+    UnknownMigration(i64),
+    #[error(
+        "The migration {version} ({description}) was changed after being applied: \
+        its checksum does not match the database"
+    )]
+    ChecksumMismatch { version: i64, description: String },
+    #[error(
+        "The migration {0} was partially applied: \
+        fix the schema and remove its row from `_sqlx_migrations`"
+    )]
+    Dirty(i64),
+}
+
+pub struct Migrations;
+
+impl Migrations {
+    /// Checks the applied migrations against the embedded ones, without changing anything.
+    /// Fails with a `SchemaMismatch` if the binary cannot run against the database.
+    #[tracing::instrument(name = "Checking the database migrations", skip(pg_pool))]
+    pub async fn pending(pg_pool: &PgPool) -> anyhow::Result<Vec<PendingMigration>> {
+        let mut conn = pg_pool
+            .acquire()
+            .await
+            .context("Failed to acquire a Postgres connection")?;
+        let pending = Migrations::check(&mut conn).await?;
+        Ok(pending.into_iter().map(PendingMigration::from).collect())
+    }
+
+    /// Applies the pending migrations, returning them.
+    /// Every replica can call it: they wait for each other on the advisory lock
+    /// `sqlx migrate run` takes too, and the ones coming after find nothing pending.
+    #[tracing::instrument(name = "Applying the database migrations", skip(pg_pool))]
+    pub async fn run(pg_pool: &PgPool) -> anyhow::Result<Vec<PendingMigration>> {
+        // Detached, so that the session lock is released with the connection on errors
+        let mut conn = pg_pool
+            .acquire()
+            .await
+            .context("Failed to acquire a Postgres connection")?
+            .detach();
+        conn.lock()
+            .await
+            .context("Failed to acquire the migrations lock")?;
+        conn.ensure_migrations_table()
+            .await
+            .context("Failed to create the migrations table")?;
+        let pending = Migrations::check(&mut conn).await?;
+        for migration in &pending {
+            tracing::info!(
+                version = migration.version,
+                description = %migration.description,
+                "Applying a migration"
+            );
+            conn.apply(migration)
+                .await
+                .with_context(|| format!("Failed to apply the migration {}", migration.version))?;
+        }
+        conn.unlock()
+            .await
+            .context("Failed to release the migrations lock")?;
+        Ok(pending.into_iter().map(PendingMigration::from).collect())
+    }
+
+    /// The embedded migrations not applied yet, oldest first
+    async fn check(conn: &mut PgConnection) -> anyhow::Result<Vec<&'static Migration>> {
+        let applied = if MigrationQueries::migrations_table_exists(&mut *conn).await? {
+            if let Some(version) = conn.dirty_version().await? {
+                return Err(SchemaMismatch::Dirty(version).into());
+            }
+            conn.list_applied_migrations().await?
+        } else {
+            Vec::new()
+        };
+        let embedded: Vec<&'static Migration> = MIGRATOR
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .collect();
+        for applied_migration in &applied {
+            let migration = embedded
+                .iter()
+                .find(|migration| migration.version == applied_migration.version)
+                .ok_or(SchemaMismatch::UnknownMigration(applied_migration.version))?;
+            if migration.checksum != applied_migration.checksum {
+                return Err(SchemaMismatch::ChecksumMismatch {
+                    version: migration.version,
+                    description: migration.description.to_string(),
+                }
+                .into());
+            }
+        }
+        Ok(embedded
+            .into_iter()
+            .filter(|migration| {
+                !applied
+                    .iter()
+                    .any(|applied_migration| applied_migration.version == migration.version)
+            })
+            .collect())
+    }
+}
+
+impl From<&Migration> for PendingMigration {
+    fn from(migration: &Migration) -> Self {
+        PendingMigration {
+            version: migration.version,
+            description: migration.description.to_string(),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use serde_json::{Map, Value};
+
+/// Replaces the `{{ name }}` and `{{ attributes.<name> }}` placeholders of an email
+/// with the data of the subscriber.
+/// A fallback can follow a `|`, e.g. `{{ attributes.company | your team }}`:
+/// it is used when the subscriber has no value.
+/// Anything else between braces is left as it is.
+pub fn personalise(text: &str, name: &str, attributes: &Map<String, Value>) -> String {
+    let mut personalised = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        personalised.push_str(&rest[..start]);
+        let placeholder = &rest[start + 2..end];
+        match resolve(placeholder, name, attributes) {
+            Some(value) => personalised.push_str(&value),
+            None => personalised.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    personalised.push_str(rest);
+    personalised
+}
+
+fn resolve(placeholder: &str, name: &str, attributes: &Map<String, Value>) -> Option<String> {
+    let (key, fallback) = match placeholder.split_once('|') {
+        Some((key, fallback)) => (key.trim(), Some(fallback.trim())),
+        None => (placeholder.trim(), None),
+    };
+    let value = if key == "name" {
+        Some(name.to_owned())
+    } else {
+        let attribute = key.strip_prefix("attributes.")?;
+        match attributes.get(attribute) {
+            Some(Value::String(s)) => Some(s.clone()),
+            Some(Value::Number(n)) => Some(n.to_string()),
+            Some(Value::Bool(b)) => Some(b.to_string()),
+            _ => None,
+        }
+    };
+    Some(
+        value
+            .or_else(|| fallback.map(str::to_owned))
+            .unwrap_or_default(),
+    )
+}
@@ -0,0 +1,52 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::db::processed_event_queries::ProcessedEventQueries;
+use crate::shutdown::ShutdownListener;
+
+pub struct ProcessedEventsPruner;
+
+impl ProcessedEventsPruner {
+    /// Every replica prunes, deleting the same rows twice is harmless
+    pub fn spawn(
+        config: Arc<Config>,
+        pg_pool: Arc<PgPool>,
+        mut shutdown: ShutdownListener,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_millis(
+                config.processed_events_prune_interval_millis,
+            ));
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    () = shutdown.recv() => break,
+                }
+                let before = Utc::now()
+                    - chrono::Duration::hours(config.processed_events_retention_hours.into());
+                if let Err(err) = ProcessedEventsPruner::prune(&pg_pool, before).await {
+                    tracing::error!(error = ?err, "Processed events pruning failed");
+                }
+            }
+            tracing::info!("Processed events pruner stopped");
+        })
+    }
+
+    /// Events processed before `before` could be delivered again as new ones
+    #[tracing::instrument(name = "Pruning processed events", skip(pg_pool))]
+    pub async fn prune(pg_pool: &PgPool, before: DateTime<Utc>) -> anyhow::Result<u64> {
+        let deleted = ProcessedEventQueries::delete_processed_events_before(pg_pool, before)
+            .await
+            .context("Failed to delete the processed events")?;
+        if deleted > 0 {
+            tracing::info!(deleted, "Pruned processed events");
+        }
+        Ok(deleted)
+    }
+}
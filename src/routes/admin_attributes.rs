@@ -0,0 +1,57 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::domain::attribute_kind::AttributeKind;
+use crate::domain::subscriber_attributes::AttributeDefinition;
+use crate::handlers::manage_attributes::{add_attribute, list_attributes, AddAttributeOutput};
+use crate::routes::AdminAuth;
+
+#[derive(Deserialize, Debug)]
+pub struct AddAttributeBody {
+    name: String,
+    #[serde(rename = "type")]
+    kind: AttributeKind,
+    #[serde(default)]
+    required: bool,
+    /// The allowed values of enum attributes
+    #[serde(default)]
+    values: Vec<String>,
+}
+
+#[tracing::instrument(
+    name = "Admin: add an attribute definition",
+    skip(_auth, body, pg_pool),
+    fields(attribute_name = %body.name)
+)]
+pub async fn admin_add_attribute(
+    _auth: AdminAuth,
+    body: web::Json<AddAttributeBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let definition =
+        match AttributeDefinition::parse(body.name, body.kind, body.required, body.values) {
+            Ok(definition) => definition,
+            Err(_) => return HttpResponse::BadRequest().finish(),
+        };
+    match add_attribute(&pg_pool, &definition).await {
+        Ok(AddAttributeOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(AddAttributeOutput::NameTaken) => HttpResponse::Conflict().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to add an attribute definition");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list attribute definitions", skip(_auth, pg_pool))]
+pub async fn admin_list_attributes(_auth: AdminAuth, pg_pool: web::Data<PgPool>) -> HttpResponse {
+    match list_attributes(&pg_pool).await {
+        Ok(definitions) => HttpResponse::Ok().json(definitions),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list attribute definitions");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
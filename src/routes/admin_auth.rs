@@ -0,0 +1,39 @@
+use actix_web::dev::Payload;
+use actix_web::error::ErrorUnauthorized;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::{web, FromRequest, HttpRequest};
+use secrecy::ExposeSecret;
+use std::future::{ready, Ready};
+
+use crate::config::Config;
+
+/// Extractor guarding the `/admin` routes.
+/// Requires an `Authorization: Bearer <ADMIN_API_TOKEN>` header.
+pub struct AdminAuth;
+
+impl FromRequest for AdminAuth {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let expected = match req.app_data::<web::Data<Config>>() {
+            Some(config) => config.admin_api_token.expose_secret().to_owned(),
+            None => return ready(Err(ErrorUnauthorized("Unauthorized"))),
+        };
+        let provided = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        match provided {
+            Some(token) if constant_time_eq(token.as_bytes(), expected.as_bytes()) => {
+                ready(Ok(AdminAuth))
+            }
+            _ => ready(Err(ErrorUnauthorized("Unauthorized"))),
+        }
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,263 @@
+use actix_web::{web, HttpResponse};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::domain::ab_test::AbTest;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::handlers::manage_issues::{
+    add_issue, cancel_issue, fetch_subject_variants, list_issues, schedule_issue, AddIssueOutput,
+    ChangeIssueOutput,
+};
+use crate::handlers::preview_issue::{
+    preview_issue, test_send_issue, PreviewIssueOutput, TestSendIssueOutput,
+};
+use crate::routes::AdminAuth;
+
+#[derive(Deserialize, Debug)]
+pub struct AddIssueBody {
+    list: String,
+    subject: String,
+    text_content: String,
+    ab_test: Option<AbTestBody>,
+    /// The name of a saved segment
+    segment: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AbTestBody {
+    subjects: Vec<String>,
+    audience_percent: u8,
+    wait_minutes: u32,
+}
+
+#[derive(Serialize)]
+struct AddIssueResponse {
+    id: Uuid,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleTimezone {
+    List,
+    Subscriber,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ScheduleIssueBody {
+    /// Wall clock time without an offset, e.g. 2022-06-14T09:00:00
+    send_at: NaiveDateTime,
+    timezone: ScheduleTimezone,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PreviewIssueParameters {
+    /// Email of the list member whose data is rendered
+    subscriber: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct TestSendIssueBody {
+    subscriber: String,
+    recipients: Vec<String>,
+}
+
+#[tracing::instrument(name = "Admin: add an issue", skip(_auth, body, pg_pool))]
+pub async fn admin_add_issue(
+    _auth: AdminAuth,
+    body: web::Json<AddIssueBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let list_slug = match ListSlug::parse(body.list) {
+        Ok(list_slug) => list_slug,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    if body.subject.trim().is_empty() || body.text_content.trim().is_empty() {
+        return HttpResponse::BadRequest().finish();
+    }
+    let ab_test = match body
+        .ab_test
+        .map(|ab_test| {
+            AbTest::parse(
+                ab_test.subjects,
+                ab_test.audience_percent,
+                ab_test.wait_minutes,
+            )
+        })
+        .transpose()
+    {
+        Ok(ab_test) => ab_test,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match add_issue(
+        &pg_pool,
+        &list_slug,
+        &body.subject,
+        &body.text_content,
+        ab_test.as_ref(),
+        body.segment.as_deref(),
+    )
+    .await
+    {
+        Ok(AddIssueOutput::Success(id)) => HttpResponse::Ok().json(AddIssueResponse { id }),
+        Ok(
+            AddIssueOutput::ListNotFound
+            | AddIssueOutput::SegmentNotFound
+            | AddIssueOutput::TrackingDisabled,
+        ) => HttpResponse::BadRequest().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to add an issue");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list issues", skip(_auth, pg_pool))]
+pub async fn admin_list_issues(_auth: AdminAuth, pg_pool: web::Data<PgPool>) -> HttpResponse {
+    match list_issues(&pg_pool).await {
+        Ok(issues) => HttpResponse::Ok().json(issues),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list issues");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Admin: fetch the subject variants of an issue",
+    skip(_auth, pg_pool)
+)]
+pub async fn admin_issue_subject_variants(
+    _auth: AdminAuth,
+    issue_id: web::Path<Uuid>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    match fetch_subject_variants(&pg_pool, &issue_id).await {
+        Ok(Some(variants)) => HttpResponse::Ok().json(variants),
+        Ok(None) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to fetch the subject variants");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: schedule an issue", skip(_auth, pg_pool))]
+pub async fn admin_schedule_issue(
+    _auth: AdminAuth,
+    issue_id: web::Path<Uuid>,
+    body: web::Json<ScheduleIssueBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let per_subscriber_timezone = body.timezone == ScheduleTimezone::Subscriber;
+    let output = schedule_issue(&pg_pool, &issue_id, &body.send_at, per_subscriber_timezone).await;
+    change_issue_response(output)
+}
+
+#[tracing::instrument(name = "Admin: cancel an issue", skip(_auth, pg_pool))]
+pub async fn admin_cancel_issue(
+    _auth: AdminAuth,
+    issue_id: web::Path<Uuid>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    change_issue_response(cancel_issue(&pg_pool, &issue_id).await)
+}
+
+#[tracing::instrument(
+    name = "Admin: preview an issue",
+    skip(_auth, parameters, config, email_client, pg_pool)
+)]
+pub async fn admin_preview_issue(
+    _auth: AdminAuth,
+    issue_id: web::Path<Uuid>,
+    parameters: web::Query<PreviewIssueParameters>,
+    config: web::Data<Config>,
+    email_client: web::Data<EmailClient>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let subscriber = match SubscriberEmail::parse(parameters.0.subscriber) {
+        Ok(subscriber) => subscriber,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match preview_issue(&config, &email_client, &pg_pool, &issue_id, &subscriber).await {
+        Ok(PreviewIssueOutput::Success(preview)) => HttpResponse::Ok().json(preview),
+        Ok(PreviewIssueOutput::IssueNotFound | PreviewIssueOutput::SubscriberNotFound) => {
+            HttpResponse::NotFound().finish()
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to preview the issue");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Admin: test send an issue",
+    skip(_auth, body, config, email_client, pg_pool)
+)]
+pub async fn admin_test_send_issue(
+    _auth: AdminAuth,
+    issue_id: web::Path<Uuid>,
+    body: web::Json<TestSendIssueBody>,
+    config: web::Data<Config>,
+    email_client: web::Data<EmailClient>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let subscriber = match SubscriberEmail::parse(body.subscriber) {
+        Ok(subscriber) => subscriber,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    let recipients: Result<Vec<SubscriberEmail>, String> = body
+        .recipients
+        .into_iter()
+        .map(SubscriberEmail::parse)
+        .collect();
+    let recipients = match recipients {
+        Ok(recipients) if !recipients.is_empty() => recipients,
+        _ => return HttpResponse::BadRequest().finish(),
+    };
+    match test_send_issue(
+        &config,
+        &email_client,
+        &pg_pool,
+        &issue_id,
+        &subscriber,
+        &recipients,
+    )
+    .await
+    {
+        Ok(TestSendIssueOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(TestSendIssueOutput::IssueNotFound | TestSendIssueOutput::SubscriberNotFound) => {
+            HttpResponse::NotFound().finish()
+        }
+        Ok(TestSendIssueOutput::RecipientNotAllowed(recipient)) => {
+            tracing::info!(recipient = %recipient, "Not a seed address");
+            HttpResponse::Forbidden().finish()
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to test send the issue");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn change_issue_response<E: std::fmt::Debug>(output: Result<ChangeIssueOutput, E>) -> HttpResponse {
+    match output {
+        Ok(ChangeIssueOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(ChangeIssueOutput::IssueNotFound) => HttpResponse::NotFound().finish(),
+        Ok(ChangeIssueOutput::Conflict(status)) => {
+            tracing::info!(issue_status = %status, "The issue can no longer be changed");
+            HttpResponse::Conflict().finish()
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to change the issue");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::domain::list_slug::ListSlug;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::time_zone::IanaTimeZone;
+use crate::handlers::manage_lists::{add_list, list_lists, AddListOutput};
+use crate::routes::AdminAuth;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AddListBody {
+    slug: String,
+    name: String,
+    from_address: Option<String>,
+    #[serde(default)]
+    description: String,
+    /// IANA name, UTC by default
+    timezone: Option<String>,
+    /// Track opens and clicks of the issues, off by default
+    #[serde(default)]
+    tracking_enabled: bool,
+}
+
+#[tracing::instrument(
+    name = "Admin: add a mailing list",
+    skip(_auth, body, pg_pool),
+    fields(list_slug = %body.slug)
+)]
+pub async fn admin_add_list(
+    _auth: AdminAuth,
+    body: web::Json<AddListBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let slug = match ListSlug::parse(body.slug) {
+        Ok(slug) => slug,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    if body.name.trim().is_empty() {
+        return HttpResponse::BadRequest().finish();
+    }
+    let from_address = match body.from_address.map(SubscriberEmail::parse).transpose() {
+        Ok(from_address) => from_address,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    let timezone = match body
+        .timezone
+        .as_deref()
+        .map(IanaTimeZone::parse)
+        .transpose()
+    {
+        Ok(timezone) => timezone.unwrap_or_else(IanaTimeZone::utc),
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match add_list(
+        &pg_pool,
+        &slug,
+        &body.name,
+        from_address.as_ref(),
+        &body.description,
+        &timezone,
+        body.tracking_enabled,
+    )
+    .await
+    {
+        Ok(AddListOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(AddListOutput::SlugTaken) => HttpResponse::Conflict().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to add a mailing list");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list mailing lists", skip(_auth, pg_pool))]
+pub async fn admin_list_lists(_auth: AdminAuth, pg_pool: web::Data<PgPool>) -> HttpResponse {
+    match list_lists(&pg_pool).await {
+        Ok(lists) => HttpResponse::Ok().json(lists),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list mailing lists");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use actix_web::{web, HttpResponse};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::segment::Segment;
+use crate::handlers::manage_segments::{
+    add_segment, list_segments, preview_saved_segment, preview_segment, AddSegmentOutput,
+    PreviewSegmentOutput,
+};
+use crate::routes::AdminAuth;
+
+const DEFAULT_SAMPLE_SIZE: u32 = 10;
+const MAX_SAMPLE_SIZE: u32 = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct AddSegmentBody {
+    name: String,
+    definition: serde_json::Value,
+}
+
+#[derive(Serialize)]
+struct AddSegmentResponse {
+    id: Uuid,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PreviewSegmentBody {
+    definition: serde_json::Value,
+    sample_size: Option<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PreviewSegmentParameters {
+    sample_size: Option<u32>,
+}
+
+fn parse_sample_size(sample_size: Option<u32>) -> Option<u32> {
+    match sample_size {
+        Some(sample_size) if sample_size > MAX_SAMPLE_SIZE => None,
+        Some(sample_size) => Some(sample_size),
+        None => Some(DEFAULT_SAMPLE_SIZE),
+    }
+}
+
+#[tracing::instrument(
+    name = "Admin: add a segment",
+    skip(_auth, body, pg_pool),
+    fields(segment_name = %body.name)
+)]
+pub async fn admin_add_segment(
+    _auth: AdminAuth,
+    body: web::Json<AddSegmentBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    if body.name.trim().is_empty() {
+        return HttpResponse::BadRequest().finish();
+    }
+    let segment = match Segment::parse(body.definition) {
+        Ok(segment) => segment,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match add_segment(&pg_pool, &body.name, &segment).await {
+        Ok(AddSegmentOutput::Success(id)) => HttpResponse::Ok().json(AddSegmentResponse { id }),
+        Ok(AddSegmentOutput::NameTaken) => HttpResponse::Conflict().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to add a segment");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list segments", skip(_auth, pg_pool))]
+pub async fn admin_list_segments(_auth: AdminAuth, pg_pool: web::Data<PgPool>) -> HttpResponse {
+    match list_segments(&pg_pool).await {
+        Ok(segments) => HttpResponse::Ok().json(segments),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list segments");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: preview a segment", skip(_auth, body, pg_pool))]
+pub async fn admin_preview_segment(
+    _auth: AdminAuth,
+    body: web::Json<PreviewSegmentBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let sample_size = match parse_sample_size(body.sample_size) {
+        Some(sample_size) => sample_size,
+        None => return HttpResponse::BadRequest().finish(),
+    };
+    let segment = match Segment::parse(body.definition) {
+        Ok(segment) => segment,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match preview_segment(&pg_pool, &segment, sample_size).await {
+        Ok(preview) => HttpResponse::Ok().json(preview),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to preview a segment");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: preview a saved segment", skip(_auth, pg_pool))]
+pub async fn admin_preview_saved_segment(
+    _auth: AdminAuth,
+    name: web::Path<String>,
+    parameters: web::Query<PreviewSegmentParameters>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let sample_size = match parse_sample_size(parameters.sample_size) {
+        Some(sample_size) => sample_size,
+        None => return HttpResponse::BadRequest().finish(),
+    };
+    match preview_saved_segment(&pg_pool, &name, sample_size).await {
+        Ok(PreviewSegmentOutput::Success(preview)) => HttpResponse::Ok().json(preview),
+        Ok(PreviewSegmentOutput::SegmentNotFound) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to preview a saved segment");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::domain::list_slug::ListSlug;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::tag_name::TagName;
+use crate::handlers::fetch_consent_history::{fetch_consent_history, FetchConsentHistoryOutput};
+use crate::handlers::import_subscribers::{import_subscribers, ImportRow, ImportSubscribersOutput};
+use crate::handlers::manage_tags::list_subscribers;
+use crate::routes::AdminAuth;
+
+const MAX_IMPORT_ROWS: usize = 1000;
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Deserialize, Debug)]
+pub struct ListSubscribersParameters {
+    tag: Option<String>,
+    limit: Option<u32>,
+    #[serde(default)]
+    offset: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportSubscribersBody {
+    list: String,
+    subscribers: Vec<ImportSubscriberBody>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportSubscriberBody {
+    email: String,
+    name: String,
+    #[serde(default)]
+    attributes: serde_json::Map<String, serde_json::Value>,
+}
+
+#[tracing::instrument(name = "Admin: fetch the consent history", skip(_auth, email, pg_pool))]
+pub async fn admin_subscription_events(
+    _auth: AdminAuth,
+    email: web::Path<String>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let email = match SubscriberEmail::parse(email.into_inner()) {
+        Ok(email) => email,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match fetch_consent_history(&pg_pool, &email).await {
+        Ok(FetchConsentHistoryOutput::Success(history)) => HttpResponse::Ok().json(history),
+        Ok(FetchConsentHistoryOutput::SubscriptionNotFound) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to fetch the consent history");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Admin: import subscribers",
+    skip(_auth, body, context, pg_pool),
+    fields(list_slug = %body.list, rows = body.subscribers.len())
+)]
+pub async fn admin_import_subscribers(
+    _auth: AdminAuth,
+    body: web::Json<ImportSubscribersBody>,
+    context: RequestContext,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let list_slug = match ListSlug::parse(body.list) {
+        Ok(list_slug) => list_slug,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    if body.subscribers.len() > MAX_IMPORT_ROWS {
+        return HttpResponse::BadRequest().finish();
+    }
+    let rows = body
+        .subscribers
+        .into_iter()
+        .map(|subscriber| ImportRow {
+            email: subscriber.email,
+            name: subscriber.name,
+            attributes: subscriber.attributes,
+        })
+        .collect();
+    match import_subscribers(&pg_pool, &list_slug, rows, &context).await {
+        Ok(ImportSubscribersOutput::Success(report)) => HttpResponse::Ok().json(report),
+        Ok(ImportSubscribersOutput::ListNotFound) => HttpResponse::BadRequest().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to import subscribers");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list subscribers", skip(_auth, pg_pool))]
+pub async fn admin_list_subscribers(
+    _auth: AdminAuth,
+    parameters: web::Query<ListSubscribersParameters>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let parameters = parameters.into_inner();
+    let tag = match parameters.tag.map(TagName::parse).transpose() {
+        Ok(tag) => tag,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    let limit = match parameters.limit {
+        Some(limit) if limit > MAX_PAGE_SIZE => return HttpResponse::BadRequest().finish(),
+        Some(limit) => limit,
+        None => DEFAULT_PAGE_SIZE,
+    };
+    match list_subscribers(&pg_pool, tag.as_ref(), limit, parameters.offset).await {
+        Ok(subscribers) => HttpResponse::Ok().json(subscribers),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list subscribers");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
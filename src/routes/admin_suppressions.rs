@@ -0,0 +1,82 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::suppression_reason::SuppressionReason;
+use crate::handlers::manage_suppressions::{
+    add_suppression, list_suppressions, remove_suppression, RemoveSuppressionOutput,
+};
+use crate::routes::AdminAuth;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct AddSuppressionBody {
+    email: String,
+    reason: SuppressionReason,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ListSuppressionsParameters {
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+#[tracing::instrument(
+    name = "Admin: add a suppression",
+    skip(_auth, body, pg_pool),
+    fields(suppression_reason = %body.reason)
+)]
+pub async fn admin_add_suppression(
+    _auth: AdminAuth,
+    body: web::Json<AddSuppressionBody>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let body = body.0;
+    let email = match SubscriberEmail::parse(body.email) {
+        Ok(email) => email,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match add_suppression(&pg_pool, &email, body.reason, "admin").await {
+        Ok(()) => HttpResponse::Ok().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to add a suppression");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: remove a suppression", skip(_auth, email, pg_pool))]
+pub async fn admin_remove_suppression(
+    _auth: AdminAuth,
+    email: web::Path<String>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let email = match SubscriberEmail::parse(email.into_inner()) {
+        Ok(email) => email,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match remove_suppression(&pg_pool, &email).await {
+        Ok(RemoveSuppressionOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(RemoveSuppressionOutput::SuppressionNotFound) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to remove a suppression");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list suppressions", skip(_auth, pg_pool))]
+pub async fn admin_list_suppressions(
+    _auth: AdminAuth,
+    parameters: web::Query<ListSuppressionsParameters>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let limit = parameters.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = parameters.offset.unwrap_or(0).max(0);
+    match list_suppressions(&pg_pool, limit, offset).await {
+        Ok(suppressions) => HttpResponse::Ok().json(suppressions),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list suppressions");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use actix_web::{web, HttpResponse};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::domain::tag_name::TagName;
+use crate::event_bus::EventBus;
+use crate::handlers::manage_tags::{
+    add_tag, list_tags, remove_tag, RemoveTagOutput, SubscriberRefs,
+};
+use crate::routes::AdminAuth;
+
+const MAX_TAGGED_SUBSCRIBERS: usize = 1000;
+
+#[derive(Deserialize, Debug)]
+pub struct TagSubscribersBody {
+    #[serde(default)]
+    ids: Vec<Uuid>,
+    #[serde(default)]
+    emails: Vec<String>,
+}
+
+impl TagSubscribersBody {
+    fn parse(self) -> Result<SubscriberRefs, String> {
+        if self.ids.len() + self.emails.len() > MAX_TAGGED_SUBSCRIBERS {
+            return Err(format!(
+                "Up to {} subscribers can be tagged at once.",
+                MAX_TAGGED_SUBSCRIBERS
+            ));
+        }
+        let emails = self
+            .emails
+            .into_iter()
+            .map(SubscriberEmail::parse)
+            .collect::<Result<_, _>>()?;
+        Ok(SubscriberRefs {
+            ids: self.ids,
+            emails,
+        })
+    }
+}
+
+#[tracing::instrument(
+    name = "Admin: tag subscribers",
+    skip(_auth, body, pg_pool, event_bus),
+    fields(tag = %tag)
+)]
+pub async fn admin_add_tag(
+    _auth: AdminAuth,
+    tag: web::Path<String>,
+    body: web::Json<TagSubscribersBody>,
+    pg_pool: web::Data<PgPool>,
+    event_bus: web::Data<EventBus>,
+) -> HttpResponse {
+    let (tag, subscribers) = match (TagName::parse(tag.into_inner()), body.0.parse()) {
+        (Ok(tag), Ok(subscribers)) => (tag, subscribers),
+        _ => return HttpResponse::BadRequest().finish(),
+    };
+    match add_tag(&pg_pool, &event_bus, &tag, &subscribers).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to tag subscribers");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(
+    name = "Admin: untag subscribers",
+    skip(_auth, body, pg_pool, event_bus),
+    fields(tag = %tag)
+)]
+pub async fn admin_remove_tag(
+    _auth: AdminAuth,
+    tag: web::Path<String>,
+    body: web::Json<TagSubscribersBody>,
+    pg_pool: web::Data<PgPool>,
+    event_bus: web::Data<EventBus>,
+) -> HttpResponse {
+    let (tag, subscribers) = match (TagName::parse(tag.into_inner()), body.0.parse()) {
+        (Ok(tag), Ok(subscribers)) => (tag, subscribers),
+        _ => return HttpResponse::BadRequest().finish(),
+    };
+    match remove_tag(&pg_pool, &event_bus, &tag, &subscribers).await {
+        Ok(RemoveTagOutput::Success(report)) => HttpResponse::Ok().json(report),
+        Ok(RemoveTagOutput::TagNotFound) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to untag subscribers");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Admin: list tags", skip(_auth, pg_pool))]
+pub async fn admin_list_tags(_auth: AdminAuth, pg_pool: web::Data<PgPool>) -> HttpResponse {
+    match list_tags(&pg_pool).await {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to list tags");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -1,7 +1,38 @@
+pub use admin_attributes::*;
+pub use admin_auth::*;
+pub use admin_issues::*;
+pub use admin_lists::*;
+pub use admin_segments::*;
+pub use admin_subscriptions::*;
+pub use admin_suppressions::*;
+pub use admin_tags::*;
 pub use health_check::*;
+pub use preferences::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data_export::*;
+pub use subscriptions_data_requests::*;
+pub use subscriptions_erase::*;
+pub use subscriptions_unsubscribe::*;
+pub use tracking::*;
+pub use webhooks_sendgrid::*;
 
+mod admin_attributes;
+mod admin_auth;
+mod admin_issues;
+mod admin_lists;
+mod admin_segments;
+mod admin_subscriptions;
+mod admin_suppressions;
+mod admin_tags;
 mod health_check;
+mod preferences;
+mod request_context;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data_export;
+mod subscriptions_data_requests;
+mod subscriptions_erase;
+mod subscriptions_unsubscribe;
+mod tracking;
+mod webhooks_sendgrid;
@@ -0,0 +1,211 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::domain::delivery_cadence::DeliveryCadence;
+use crate::domain::list_slug::ListSlug;
+use crate::domain::preferred_language::PreferredLanguage;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_name::SubscriberName;
+use crate::domain::time_zone::IanaTimeZone;
+use crate::handlers::manage_preferences::{
+    fetch_preferences, update_preferences, FetchPreferencesOutput, Preferences, PreferencesUpdate,
+    UpdatePreferencesOutput,
+};
+use crate::html::escape_html;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PreferencesParameters {
+    preferences_token: String,
+}
+
+/// The form as a list of pairs: every checked list is sent as its own `lists` field
+#[derive(serde::Deserialize)]
+#[serde(transparent)]
+pub struct PreferencesFormData(Vec<(String, String)>);
+
+impl TryFrom<PreferencesFormData> for PreferencesUpdate {
+    type Error = String;
+
+    fn try_from(form: PreferencesFormData) -> Result<Self, Self::Error> {
+        let field = |key: &str| {
+            form.0
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.to_owned())
+                .ok_or(format!("{} is missing.", key))
+        };
+        let name = SubscriberName::parse(field("name")?)?;
+        let delivery_cadence = DeliveryCadence::parse(&field("delivery_cadence")?)?;
+        let preferred_language = PreferredLanguage::parse(field("preferred_language")?)?;
+        let timezone = match field("timezone").unwrap_or_default().trim() {
+            "" => None,
+            timezone => Some(IanaTimeZone::parse(timezone)?),
+        };
+        // Unchecked checkboxes are not sent
+        let do_not_track = field("do_not_track").is_ok();
+        let mut lists: Vec<ListSlug> = vec![];
+        for (_, slug) in form.0.iter().filter(|(k, _)| k == "lists") {
+            let slug = ListSlug::parse(slug.to_owned())?;
+            if !lists.contains(&slug) {
+                lists.push(slug);
+            }
+        }
+        Ok(PreferencesUpdate {
+            name,
+            delivery_cadence,
+            preferred_language,
+            timezone,
+            do_not_track,
+            lists,
+        })
+    }
+}
+
+#[tracing::instrument(name = "Show the preference center", skip(pg_pool))]
+pub async fn preferences_page(
+    parameters: web::Query<PreferencesParameters>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.preferences_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    match fetch_preferences(&parameters.preferences_token, &pg_pool).await {
+        Ok(FetchPreferencesOutput::Success(preferences)) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(render_preferences(
+                &parameters.preferences_token,
+                &preferences,
+                None,
+            )),
+        Ok(FetchPreferencesOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to fetch the preferences");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Save preferences", skip(form, context, pg_pool))]
+pub async fn save_preferences(
+    parameters: web::Query<PreferencesParameters>,
+    form: web::Form<PreferencesFormData>,
+    context: RequestContext,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.preferences_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    let update = match form.0.try_into() {
+        Ok(update) => update,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match update_preferences(&parameters.preferences_token, update, &pg_pool, &context).await {
+        Ok(UpdatePreferencesOutput::Success(preferences)) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(render_preferences(
+                &parameters.preferences_token,
+                &preferences,
+                Some("Your preferences have been saved."),
+            )),
+        Ok(UpdatePreferencesOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Ok(UpdatePreferencesOutput::ListNotFound) => HttpResponse::BadRequest().finish(),
+        Ok(UpdatePreferencesOutput::Conflict) => HttpResponse::Conflict().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to save the preferences");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn render_preferences(
+    preferences_token: &str,
+    preferences: &Preferences,
+    notice: Option<&str>,
+) -> String {
+    let subscription = &preferences.subscription;
+    let lists: String = preferences
+        .lists
+        .iter()
+        .map(|list| {
+            format!(
+                r#"<p><label><input type="checkbox" name="lists" value="{}"{}> {}</label> {}</p>"#,
+                escape_html(&list.slug),
+                checked(list.is_subscribed()),
+                escape_html(&list.name),
+                escape_html(&list.description),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let cadences: String = [
+        (
+            DeliveryCadence::Immediate,
+            "Every issue as soon as it is out",
+        ),
+        (DeliveryCadence::Daily, "A daily digest"),
+        (DeliveryCadence::Weekly, "A weekly digest"),
+    ]
+    .iter()
+    .map(|(cadence, label)| {
+        format!(
+            r#"<p><label><input type="radio" name="delivery_cadence" value="{}"{}> {}</label></p>"#,
+            cadence.as_ref(),
+            checked(*cadence == subscription.delivery_cadence),
+            label,
+        )
+    })
+    .collect::<Vec<_>>()
+    .join("\n");
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="{language}">
+<head>
+<meta charset="utf-8">
+<title>Email preferences</title>
+</head>
+<body>
+<h1>Email preferences of {email}</h1>
+{notice}
+<form action="preferences?preferences_token={token}" method="post">
+<p><label>Name <input type="text" name="name" value="{name}" required></label></p>
+<fieldset>
+<legend>Lists</legend>
+{lists}
+</fieldset>
+<fieldset>
+<legend>Delivery</legend>
+{cadences}
+</fieldset>
+<p><label>Language <input type="text" name="preferred_language" value="{language}" required></label></p>
+<p><label>Time zone <input type="text" name="timezone" value="{timezone}" placeholder="Europe/Berlin"></label>
+Leave empty to receive issues at the time set for each list.</p>
+<p><label><input type="checkbox" name="do_not_track" value="on"{do_not_track}> Do not track when I open emails or follow their links</label></p>
+<p><button type="submit">Save</button></p>
+</form>
+</body>
+</html>
+"#,
+        language = escape_html(&subscription.preferred_language),
+        email = escape_html(&subscription.email),
+        notice = notice
+            .map(|notice| format!("<p>{}</p>", escape_html(notice)))
+            .unwrap_or_default(),
+        token = escape_html(preferences_token),
+        name = escape_html(&subscription.name),
+        timezone = escape_html(subscription.timezone.as_deref().unwrap_or_default()),
+        do_not_track = checked(subscription.do_not_track),
+        lists = lists,
+        cadences = cadences,
+    )
+}
+
+fn checked(is_checked: bool) -> &'static str {
+    if is_checked {
+        " checked"
+    } else {
+        ""
+    }
+}
@@ -0,0 +1,29 @@
+use actix_web::dev::Payload;
+use actix_web::http::header::{ORIGIN, REFERER, USER_AGENT};
+use actix_web::{FromRequest, HttpRequest};
+use std::future::{ready, Ready};
+
+use crate::domain::request_context::RequestContext;
+
+impl FromRequest for RequestContext {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let header = |name| {
+            req.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_owned())
+        };
+        ready(Ok(RequestContext {
+            // Honours X-Forwarded-For / Forwarded, we are expected to run behind a proxy
+            ip_address: req
+                .connection_info()
+                .realip_remote_addr()
+                .map(|addr| addr.to_owned()),
+            user_agent: header(USER_AGENT),
+            origin: header(ORIGIN).or_else(|| header(REFERER)),
+        }))
+    }
+}
@@ -1,29 +1,56 @@
-use crate::config::Config;
+use crate::domain::list_slug::ListSlug;
 use crate::domain::new_subscriber::NewSubscriber;
+use crate::domain::request_context::RequestContext;
+use crate::domain::subscriber_attributes::AttributeSchema;
 use crate::domain::subscriber_email::SubscriberEmail;
 use crate::domain::subscriber_name::SubscriberName;
+use crate::event_bus::EventBus;
+use crate::handlers::manage_attributes::fetch_attribute_schema;
 use crate::handlers::save_new_subscriber::{save_new_subscriber, SaveNewSubscriberOutput};
 use actix_web::{web, HttpResponse};
 use sqlx::PgPool;
+use std::collections::HashMap;
 
 #[derive(serde::Deserialize, Debug)]
 pub struct FormData {
     email: String,
     name: String,
+    /// Comma-separated list slugs, the default list if missing
+    #[serde(default)]
+    lists: String,
+    /// `attributes[<name>]` fields, other fields are ignored
+    #[serde(flatten)]
+    fields: HashMap<String, String>,
 }
 
-impl TryFrom<FormData> for NewSubscriber {
-    type Error = String;
-    fn try_from(value: FormData) -> Result<Self, Self::Error> {
-        let name = SubscriberName::parse(value.name)?;
-        let email = SubscriberEmail::parse(value.email)?;
-        Ok(Self { email, name })
+impl FormData {
+    pub fn parse(self, schema: &AttributeSchema) -> Result<(NewSubscriber, Vec<ListSlug>), String> {
+        let name = SubscriberName::parse(self.name)?;
+        let email = SubscriberEmail::parse(self.email)?;
+        let lists = ListSlug::parse_many(&self.lists)?;
+        let attributes = self
+            .fields
+            .into_iter()
+            .filter_map(|(field, value)| {
+                let name = field.strip_prefix("attributes[")?.strip_suffix(']')?;
+                Some((name.to_owned(), serde_json::Value::String(value)))
+            })
+            .collect();
+        let attributes = schema.validate(attributes)?;
+        Ok((
+            NewSubscriber {
+                email,
+                name,
+                attributes,
+            },
+            lists,
+        ))
     }
 }
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pg_pool, nats_connection, config),
+    skip(form, context, pg_pool, event_bus),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -31,16 +58,24 @@ impl TryFrom<FormData> for NewSubscriber {
 )]
 pub async fn subscribe(
     form: web::Form<FormData>,
+    context: RequestContext,
     pg_pool: web::Data<PgPool>,
-    nats_connection: web::Data<async_nats::Connection>,
-    config: web::Data<Config>,
+    event_bus: web::Data<EventBus>,
 ) -> HttpResponse {
-    let new_subscriber = match NewSubscriber::try_from(form.0) {
-        Ok(new_subscriber) => new_subscriber,
+    let schema = match fetch_attribute_schema(&pg_pool).await {
+        Ok(schema) => schema,
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to fetch the attribute schema");
+            return HttpResponse::InternalServerError().finish();
+        }
+    };
+    let (new_subscriber, lists) = match form.0.parse(&schema) {
+        Ok(parsed) => parsed,
         Err(_) => return HttpResponse::BadRequest().finish(),
     };
-    match save_new_subscriber(&config, &pg_pool, &nats_connection, new_subscriber).await {
+    match save_new_subscriber(&pg_pool, &event_bus, new_subscriber, &lists, &context).await {
         Ok(SaveNewSubscriberOutput::AlreadySubscribed) => HttpResponse::Conflict().finish(),
+        Ok(SaveNewSubscriberOutput::ListNotFound) => HttpResponse::BadRequest().finish(),
         Ok(SaveNewSubscriberOutput::Success | SaveNewSubscriberOutput::ResendConfirmation) => {
             HttpResponse::Ok().finish()
         }
@@ -3,6 +3,7 @@ use sqlx::PgPool;
 use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::domain::request_context::RequestContext;
 use crate::handlers::confirm_subscription::{confirm_subscription, ConfirmSubscriptionOutput};
 
 #[derive(serde::Deserialize, Debug)]
@@ -10,15 +11,17 @@ pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(pg_pool))]
+#[tracing::instrument(name = "Confirm a pending subscriber", skip(context, pg_pool))]
 pub async fn subscriptions_confirm(
     parameters: web::Query<Parameters>,
+    context: RequestContext,
     pg_pool: web::Data<PgPool>,
 ) -> HttpResponse {
     if Uuid::from_str(&parameters.subscription_token).is_ok() {
-        match confirm_subscription(&parameters.subscription_token, &pg_pool).await {
+        match confirm_subscription(&parameters.subscription_token, &pg_pool, &context).await {
             Ok(ConfirmSubscriptionOutput::Success) => HttpResponse::Ok().finish(),
             Ok(ConfirmSubscriptionOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+            Ok(ConfirmSubscriptionOutput::Conflict) => HttpResponse::Conflict().finish(),
             Err(err) => {
                 tracing::error!(error = ?err, "Failed to confirm a subscription");
                 HttpResponse::InternalServerError().finish()
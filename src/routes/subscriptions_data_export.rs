@@ -0,0 +1,37 @@
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::handlers::export_subscriber_data::{export_subscriber_data, ExportSubscriberDataOutput};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DataRequestParameters {
+    pub data_request_token: String,
+}
+
+#[tracing::instrument(name = "Download subscriber personal data", skip(pg_pool, config))]
+pub async fn subscriptions_data_export(
+    parameters: web::Query<DataRequestParameters>,
+    pg_pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.data_request_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    match export_subscriber_data(&config, &parameters.data_request_token, &pg_pool).await {
+        Ok(ExportSubscriberDataOutput::Success(export)) => HttpResponse::Ok()
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename("personal-data.json".to_owned())],
+            })
+            .json(export),
+        Ok(ExportSubscriberDataOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to export subscriber data");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::domain::data_request_kind::DataRequestKind;
+use crate::domain::subscriber_email::SubscriberEmail;
+use crate::event_bus::EventBus;
+use crate::handlers::create_data_request::{create_data_request, CreateDataRequestOutput};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DataRequestFormData {
+    email: String,
+    kind: DataRequestKind,
+}
+
+#[tracing::instrument(
+    name = "Requesting a personal data export or erasure",
+    skip(form, pg_pool, event_bus),
+    fields(
+        data_request_kind = %form.kind
+    )
+)]
+pub async fn subscriptions_data_requests(
+    form: web::Form<DataRequestFormData>,
+    pg_pool: web::Data<PgPool>,
+    event_bus: web::Data<EventBus>,
+) -> HttpResponse {
+    let form = form.0;
+    let email = match SubscriberEmail::parse(form.email) {
+        Ok(email) => email,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match create_data_request(&pg_pool, &event_bus, email, form.kind).await {
+        // Same response for unknown emails, so that the endpoint
+        // cannot be used to find out who is subscribed
+        Ok(CreateDataRequestOutput::Success | CreateDataRequestOutput::SubscriptionNotFound) => {
+            HttpResponse::Ok().finish()
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to create a data request");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
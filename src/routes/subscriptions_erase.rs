@@ -0,0 +1,78 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::handlers::erase_subscriber::{
+    erase_subscriber, fetch_erasure_request, EraseSubscriberOutput, FetchErasureRequestOutput,
+};
+use crate::html::escape_html;
+use crate::routes::DataRequestParameters;
+
+/// Erases nothing: link prefetchers and mail scanners follow the emailed link too
+#[tracing::instrument(name = "Show the erasure confirmation page", skip(pg_pool, config))]
+pub async fn subscriptions_erase_page(
+    parameters: web::Query<DataRequestParameters>,
+    pg_pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.data_request_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    match fetch_erasure_request(&config, &parameters.data_request_token, &pg_pool).await {
+        Ok(FetchErasureRequestOutput::Success(email)) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(render_erasure_confirmation(
+                &parameters.data_request_token,
+                &email,
+            )),
+        Ok(FetchErasureRequestOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to fetch the erasure request");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[tracing::instrument(name = "Erase subscriber personal data", skip(pg_pool, config))]
+pub async fn subscriptions_erase(
+    parameters: web::Query<DataRequestParameters>,
+    pg_pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.data_request_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    match erase_subscriber(&config, &parameters.data_request_token, &pg_pool).await {
+        Ok(EraseSubscriberOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(EraseSubscriberOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to erase subscriber data");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn render_erasure_confirmation(data_request_token: &str, email: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Erase your personal data</title>
+</head>
+<body>
+<h1>Erase the personal data of {email}</h1>
+<p>Your subscriptions and everything we store about you will be deleted. This cannot be undone.</p>
+<form action="erase?data_request_token={token}" method="post">
+<p><button type="submit">Erase my data</button></p>
+</form>
+</body>
+</html>
+"#,
+        email = escape_html(email),
+        token = escape_html(data_request_token),
+    )
+}
@@ -0,0 +1,84 @@
+use actix_web::http::header::ContentType;
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::domain::request_context::RequestContext;
+use crate::handlers::unsubscribe::{
+    fetch_unsubscribe_request, unsubscribe, FetchUnsubscribeRequestOutput, UnsubscribeOutput,
+};
+use crate::html::escape_html;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct UnsubscribeParameters {
+    unsubscribe_token: String,
+}
+
+/// Leaves nothing: link prefetchers and mail scanners follow the links of the emails too
+#[tracing::instrument(name = "Show the unsubscribe confirmation page", skip(pg_pool))]
+pub async fn subscriptions_unsubscribe_page(
+    parameters: web::Query<UnsubscribeParameters>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.unsubscribe_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    match fetch_unsubscribe_request(&parameters.unsubscribe_token, &pg_pool).await {
+        Ok(FetchUnsubscribeRequestOutput::Success(list_name)) => {
+            HttpResponse::Ok().content_type(ContentType::html()).body(
+                render_unsubscribe_confirmation(&parameters.unsubscribe_token, &list_name),
+            )
+        }
+        Ok(FetchUnsubscribeRequestOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to fetch the unsubscribe request");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+/// The button of the confirmation page, and the one-click unsubscribe of RFC 8058:
+/// the body (`List-Unsubscribe=One-Click`) is not needed, the token is enough
+#[tracing::instrument(name = "Unsubscribe from a list", skip(context, pg_pool))]
+pub async fn subscriptions_unsubscribe(
+    parameters: web::Query<UnsubscribeParameters>,
+    context: RequestContext,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    if Uuid::from_str(&parameters.unsubscribe_token).is_err() {
+        return HttpResponse::BadRequest().finish();
+    }
+    match unsubscribe(&parameters.unsubscribe_token, &pg_pool, &context).await {
+        Ok(UnsubscribeOutput::Success) => HttpResponse::Ok().finish(),
+        Ok(UnsubscribeOutput::TokenNotFound) => HttpResponse::Unauthorized().finish(),
+        Ok(UnsubscribeOutput::Conflict) => HttpResponse::Conflict().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to unsubscribe");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+fn render_unsubscribe_confirmation(unsubscribe_token: &str, list_name: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>Unsubscribe</title>
+</head>
+<body>
+<h1>Unsubscribe from {list}</h1>
+<p>You will not receive {list} anymore. Your other lists are kept.</p>
+<form action="unsubscribe?unsubscribe_token={token}" method="post">
+<input type="hidden" name="List-Unsubscribe" value="One-Click">
+<p><button type="submit">Unsubscribe</button></p>
+</form>
+</body>
+</html>
+"#,
+        list = escape_html(list_name),
+        token = escape_html(unsubscribe_token),
+    )
+}
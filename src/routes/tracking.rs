@@ -0,0 +1,62 @@
+use actix_web::http::header::{self, CacheControl, CacheDirective};
+use actix_web::{web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::handlers::record_tracking_event::{track_click, track_open, TrackClickOutput};
+use crate::tracking::{TrackingSigner, TrackingToken};
+
+// 1x1 transparent GIF
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+#[tracing::instrument(name = "Track an open", skip(token, signer, pg_pool))]
+pub async fn tracking_open(
+    token: web::Path<String>,
+    signer: web::Data<TrackingSigner>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let (issue_id, subscription_id) = match signer.verify(&token) {
+        Some(TrackingToken::Open {
+            issue_id,
+            subscription_id,
+        }) => (issue_id, subscription_id),
+        _ => return HttpResponse::NotFound().finish(),
+    };
+    // The pixel is served anyway, a broken image helps nobody
+    if let Err(err) = track_open(&pg_pool, &issue_id, &subscription_id).await {
+        tracing::error!(error = ?err, "Failed to track an open");
+    }
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .body(PIXEL)
+}
+
+#[tracing::instrument(name = "Track a click", skip(token, signer, pg_pool))]
+pub async fn tracking_click(
+    token: web::Path<String>,
+    signer: web::Data<TrackingSigner>,
+    pg_pool: web::Data<PgPool>,
+) -> HttpResponse {
+    let (issue_id, subscription_id, link) = match signer.verify(&token) {
+        Some(TrackingToken::Click {
+            issue_id,
+            subscription_id,
+            link,
+        }) => (issue_id, subscription_id, link),
+        _ => return HttpResponse::NotFound().finish(),
+    };
+    match track_click(&pg_pool, &issue_id, &subscription_id, link).await {
+        Ok(TrackClickOutput::Redirect(url)) => HttpResponse::Found()
+            .insert_header((header::LOCATION, url))
+            .finish(),
+        Ok(TrackClickOutput::LinkNotFound) => HttpResponse::NotFound().finish(),
+        Err(err) => {
+            tracing::error!(error = ?err, "Failed to track a click");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
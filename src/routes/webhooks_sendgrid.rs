@@ -0,0 +1,52 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use sqlx::PgPool;
+
+use crate::event_bus::EventBus;
+use crate::handlers::ingest_sendgrid_events::ingest_sendgrid_events;
+use crate::sendgrid_webhook::{SendgridWebhookVerifier, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+#[tracing::instrument(
+    name = "Receiving SendGrid webhook events",
+    skip(request, body, verifier, pg_pool, event_bus)
+)]
+pub async fn webhooks_sendgrid(
+    request: HttpRequest,
+    body: web::Bytes,
+    verifier: web::Data<SendgridWebhookVerifier>,
+    pg_pool: web::Data<PgPool>,
+    event_bus: web::Data<EventBus>,
+) -> HttpResponse {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let is_verified = match (header(SIGNATURE_HEADER), header(TIMESTAMP_HEADER)) {
+        (Some(signature), Some(timestamp)) => verifier.verify(signature, timestamp, &body),
+        _ => false,
+    };
+    if !is_verified {
+        tracing::warn!("Rejected a SendGrid webhook request with an invalid signature");
+        return HttpResponse::Unauthorized().finish();
+    }
+    let payload = match serde_json::from_slice::<Vec<serde_json::Value>>(&body) {
+        Ok(payload) => payload,
+        Err(_) => return HttpResponse::BadRequest().finish(),
+    };
+    match ingest_sendgrid_events(&pg_pool, &event_bus, payload).await {
+        Ok(output) => {
+            tracing::info!(
+                recorded = output.recorded,
+                duplicates = output.duplicates,
+                "SendGrid webhook events ingested"
+            );
+            HttpResponse::Ok().finish()
+        }
+        Err(err) => {
+            // SendGrid retries on non-2xx responses, duplicates are skipped on the next attempt
+            tracing::error!(error = ?err, "Failed to ingest SendGrid webhook events");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use actix_web::dev::ServerHandle;
+use sqlx::PgPool;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::event_bus::EventBus;
+use crate::telemetry;
+
+/// Resolves on Ctrl-C or on SIGTERM, e.g. from `docker stop`
+pub async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to listen to SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => tracing::info!("Received SIGINT, shutting down"),
+        _ = terminate.recv() => tracing::info!("Received SIGTERM, shutting down"),
+    }
+}
+
+/// Stops what a binary runs, in order: the HTTP server first, once its requests in flight
+/// are done, so that no more work comes in, then the event consumers and the background tasks,
+/// then the connections. Every step logs its progress.
+pub struct ShutdownCoordinator {
+    sender: watch::Sender<bool>,
+    http_server: Option<ServerHandle>,
+    tasks: Vec<(String, JoinHandle<()>)>,
+}
+
+/// Handed to the background loops, to stop between two rounds of work
+#[derive(Clone)]
+pub struct ShutdownListener(watch::Receiver<bool>);
+
+impl ShutdownListener {
+    /// Never resolves if the coordinator is dropped without shutting down, as in the tests
+    pub async fn recv(&mut self) {
+        while !*self.0.borrow() {
+            if self.0.changed().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+impl Default for ShutdownCoordinator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownCoordinator {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender,
+            http_server: None,
+            tasks: vec![],
+        }
+    }
+
+    pub fn listener(&self) -> ShutdownListener {
+        ShutdownListener(self.sender.subscribe())
+    }
+
+    pub fn register_http_server(&mut self, http_server: ServerHandle) {
+        self.http_server = Some(http_server);
+    }
+
+    /// The task has to end on its own once the shutdown starts,
+    /// on a `ShutdownListener` or because the event bus is drained
+    pub fn register_task(&mut self, name: impl Into<String>, handle: JoinHandle<()>) {
+        self.tasks.push((name.into(), handle));
+    }
+
+    /// Tasks still running at the deadline are aborted, e.g. an email send in flight:
+    /// its event is redelivered with JetStream since it was not acked
+    pub async fn shutdown(self, event_bus: &EventBus, pg_pool: &PgPool, deadline: Duration) {
+        let deadline = Instant::now() + deadline;
+        if let Some(http_server) = self.http_server {
+            tracing::info!("Stopping the HTTP server");
+            // Graceful: the requests in flight complete, and their events are published
+            // before the consumers drain the event bus
+            match tokio::time::timeout_at(deadline, http_server.stop(true)).await {
+                Ok(()) => tracing::info!("HTTP server stopped"),
+                Err(_) => tracing::warn!("HTTP server did not stop before the deadline"),
+            }
+        }
+
+        // The idle pool connections opened by the HTTP workers broke with their runtimes,
+        // the pool pings every connection before handing it out and drops the broken ones
+        tracing::info!("Stopping the background tasks");
+        let _ = self.sender.send(true);
+        event_bus.drain().await;
+        for (name, mut handle) in self.tasks {
+            tracing::info!(task = %name, "Waiting for the task to stop");
+            match tokio::time::timeout_at(deadline, &mut handle).await {
+                Ok(Ok(())) => tracing::info!(task = %name, "Task stopped"),
+                Ok(Err(err)) => tracing::error!(task = %name, error = %err, "Task failed"),
+                Err(_) => {
+                    tracing::warn!(task = %name, "Task did not stop before the deadline, aborting it");
+                    handle.abort();
+                }
+            }
+        }
+
+        tracing::info!("Closing the event bus");
+        if let Err(err) = event_bus.close().await {
+            tracing::error!(error = ?err, "Failed to close the event bus");
+        }
+        tracing::info!("Closing the Postgres pool");
+        pg_pool.close().await;
+        tracing::info!("Shutdown complete");
+        telemetry::flush();
+    }
+}
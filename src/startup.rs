@@ -1,37 +1,69 @@
 use std::net::TcpListener;
+use std::sync::Arc;
 
 use crate::config::Config;
 use crate::email_client::EmailClient;
-use crate::events::subscription_created::SubscriptionCreated;
+use crate::event_bus::EventBus;
+use crate::events::data_request_created::DataRequestCreatedHandler;
+use crate::events::subscription_created::SubscriptionCreatedHandler;
+use crate::issue_scheduler::IssueScheduler;
+use crate::processed_events_pruner::ProcessedEventsPruner;
 use actix_web::dev::Server;
 use actix_web::{web, App, HttpServer};
 use sqlx::PgPool;
 use tracing_actix_web::TracingLogger;
 
-use crate::routes::{health_check, subscribe, subscriptions_confirm};
+use crate::routes::{
+    admin_add_attribute, admin_add_issue, admin_add_list, admin_add_segment, admin_add_suppression,
+    admin_add_tag, admin_cancel_issue, admin_import_subscribers, admin_issue_subject_variants,
+    admin_list_attributes, admin_list_issues, admin_list_lists, admin_list_segments,
+    admin_list_subscribers, admin_list_suppressions, admin_list_tags, admin_preview_issue,
+    admin_preview_saved_segment, admin_preview_segment, admin_remove_suppression, admin_remove_tag,
+    admin_schedule_issue, admin_subscription_events, admin_test_send_issue, health_check,
+    preferences_page, save_preferences, subscribe, subscriptions_confirm,
+    subscriptions_data_export, subscriptions_data_requests, subscriptions_erase,
+    subscriptions_erase_page, subscriptions_unsubscribe, subscriptions_unsubscribe_page,
+    tracking_click, tracking_open, webhooks_sendgrid,
+};
+use crate::sendgrid_webhook::SendgridWebhookVerifier;
+use crate::shutdown::ShutdownCoordinator;
+use crate::tracking::TrackingSigner;
 
-pub fn run(
+// SendGrid batches up to thousands of events in a single request
+const SENDGRID_WEBHOOK_PAYLOAD_LIMIT: usize = 5 * 1024 * 1024;
+
+pub async fn run(
     listener: TcpListener,
     pg_pool: PgPool,
-    nats_connection: async_nats::Connection,
+    event_bus: EventBus,
     email_client: EmailClient,
     config: Config,
-) -> Result<Server, std::io::Error> {
+    shutdown: &mut ShutdownCoordinator,
+) -> anyhow::Result<Server> {
     let pg_pool_data = web::Data::new(pg_pool);
-    let nats_connection_data = web::Data::new(nats_connection);
     let email_client_data = web::Data::new(email_client);
+    let sendgrid_webhook_verifier_data = web::Data::new(SendgridWebhookVerifier::new(&config));
+    let tracking_signer_data = web::Data::new(TrackingSigner::new(&config));
     let config_data = web::Data::new(config);
 
-    let nats_connection_data_clone = nats_connection_data.clone();
-    let email_client_data_clone = email_client_data.clone();
-    let pg_pool_data_clone = pg_pool_data.clone();
-    let config_data_clone = config_data.clone();
-    let _ = SubscriptionCreated::subscribe(
-        nats_connection_data_clone.into_inner(),
-        config_data_clone.into_inner(),
-        email_client_data_clone.into_inner(),
-        pg_pool_data_clone.into_inner(),
+    if config_data.event_consumers_enabled {
+        spawn_event_consumers(
+            config_data.clone().into_inner(),
+            email_client_data.clone().into_inner(),
+            pg_pool_data.clone().into_inner(),
+            &event_bus,
+            shutdown,
+        )
+        .await?;
+    }
+    let event_bus_data = web::Data::new(event_bus);
+    let issue_scheduler_handle = IssueScheduler::spawn(
+        config_data.clone().into_inner(),
+        email_client_data.clone().into_inner(),
+        pg_pool_data.clone().into_inner(),
+        shutdown.listener(),
     );
+    shutdown.register_task("issue scheduler", issue_scheduler_handle);
 
     let server = HttpServer::new(move || {
         App::new()
@@ -42,12 +74,137 @@ pub fn run(
                 "/subscriptions/confirm",
                 web::get().to(subscriptions_confirm),
             )
+            .route(
+                "/subscriptions/data_requests",
+                web::post().to(subscriptions_data_requests),
+            )
+            .route(
+                "/subscriptions/data_export",
+                web::get().to(subscriptions_data_export),
+            )
+            .route(
+                "/subscriptions/erase",
+                web::get().to(subscriptions_erase_page),
+            )
+            .route("/subscriptions/erase", web::post().to(subscriptions_erase))
+            .route(
+                "/subscriptions/unsubscribe",
+                web::get().to(subscriptions_unsubscribe_page),
+            )
+            .route(
+                "/subscriptions/unsubscribe",
+                web::post().to(subscriptions_unsubscribe),
+            )
+            .route("/preferences", web::get().to(preferences_page))
+            .route("/preferences", web::post().to(save_preferences))
+            .route("/t/o/{token}", web::get().to(tracking_open))
+            .route("/t/c/{token}", web::get().to(tracking_click))
+            .service(
+                web::resource("/webhooks/sendgrid")
+                    .app_data(web::PayloadConfig::new(SENDGRID_WEBHOOK_PAYLOAD_LIMIT))
+                    .route(web::post().to(webhooks_sendgrid)),
+            )
+            .route("/admin/issues", web::get().to(admin_list_issues))
+            .route("/admin/issues", web::post().to(admin_add_issue))
+            .route(
+                "/admin/issues/{issue_id}/schedule",
+                web::post().to(admin_schedule_issue),
+            )
+            .route(
+                "/admin/issues/{issue_id}/cancel",
+                web::post().to(admin_cancel_issue),
+            )
+            .route(
+                "/admin/issues/{issue_id}/variants",
+                web::get().to(admin_issue_subject_variants),
+            )
+            .route(
+                "/admin/issues/{issue_id}/preview",
+                web::get().to(admin_preview_issue),
+            )
+            .route(
+                "/admin/issues/{issue_id}/test-send",
+                web::post().to(admin_test_send_issue),
+            )
+            .route("/admin/lists", web::get().to(admin_list_lists))
+            .route("/admin/lists", web::post().to(admin_add_list))
+            .route("/admin/segments", web::get().to(admin_list_segments))
+            .route("/admin/segments", web::post().to(admin_add_segment))
+            .route(
+                "/admin/segments/preview",
+                web::post().to(admin_preview_segment),
+            )
+            .route(
+                "/admin/segments/{name}/preview",
+                web::get().to(admin_preview_saved_segment),
+            )
+            .route("/admin/attributes", web::get().to(admin_list_attributes))
+            .route("/admin/attributes", web::post().to(admin_add_attribute))
+            .route(
+                "/admin/subscriptions",
+                web::get().to(admin_list_subscribers),
+            )
+            .route(
+                "/admin/subscriptions/import",
+                web::post().to(admin_import_subscribers),
+            )
+            .route(
+                "/admin/subscriptions/{email}/events",
+                web::get().to(admin_subscription_events),
+            )
+            .route(
+                "/admin/suppressions",
+                web::get().to(admin_list_suppressions),
+            )
+            .route("/admin/suppressions", web::post().to(admin_add_suppression))
+            .route(
+                "/admin/suppressions/{email}",
+                web::delete().to(admin_remove_suppression),
+            )
+            .route("/admin/tags", web::get().to(admin_list_tags))
+            .route("/admin/tags/{tag}/add", web::post().to(admin_add_tag))
+            .route("/admin/tags/{tag}/remove", web::post().to(admin_remove_tag))
             .app_data(pg_pool_data.clone())
-            .app_data(nats_connection_data.clone())
+            .app_data(event_bus_data.clone())
             .app_data(email_client_data.clone())
             .app_data(config_data.clone())
+            .app_data(sendgrid_webhook_verifier_data.clone())
+            .app_data(tracking_signer_data.clone())
     })
+    // The binaries stop the server themselves, see `shutdown_signal`
+    .disable_signals()
     .listen(listener)?
     .run();
     Ok(server)
 }
+
+/// Run by the API unless `EVENT_CONSUMERS_ENABLED` is false, and by the worker.
+/// The pruning of `processed_events` goes with the consumers that fill it.
+pub async fn spawn_event_consumers(
+    config: Arc<Config>,
+    email_client: Arc<EmailClient>,
+    pg_pool: Arc<PgPool>,
+    event_bus: &EventBus,
+    shutdown: &mut ShutdownCoordinator,
+) -> anyhow::Result<()> {
+    let subscription_created_handle = event_bus
+        .subscribe(SubscriptionCreatedHandler {
+            config: config.clone(),
+            email_client: email_client.clone(),
+            pg_pool: pg_pool.clone(),
+        })
+        .await?;
+    let data_request_created_handle = event_bus
+        .subscribe(DataRequestCreatedHandler {
+            config: config.clone(),
+            email_client,
+            pg_pool: pg_pool.clone(),
+        })
+        .await?;
+    let processed_events_pruner_handle =
+        ProcessedEventsPruner::spawn(config, pg_pool, shutdown.listener());
+    shutdown.register_task("SubscriptionCreated consumer", subscription_created_handle);
+    shutdown.register_task("DataRequestCreated consumer", data_request_created_handle);
+    shutdown.register_task("processed events pruner", processed_events_pruner_handle);
+    Ok(())
+}
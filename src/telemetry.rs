@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use tracing::subscriber::set_global_default;
 use tracing::Subscriber;
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
@@ -31,3 +33,11 @@ pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to set logger");
     set_global_default(subscriber).expect("Failed to set subscriber");
 }
+
+/// The formatting layer writes every event to stdout as it happens,
+/// what is left is whatever stdout still buffers
+pub fn flush() {
+    if let Err(err) = std::io::stdout().flush() {
+        eprintln!("Failed to flush the logs: {}", err);
+    }
+}
@@ -0,0 +1,110 @@
+use hmac::{Hmac, Mac};
+use linkify::{LinkFinder, LinkKind};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::config::Config;
+
+/// What a tracking link records when it is requested
+#[derive(Debug, PartialEq)]
+pub enum TrackingToken {
+    Open {
+        issue_id: Uuid,
+        subscription_id: Uuid,
+    },
+    /// `link` is the position of the link in the text of the issue,
+    /// so that redirects can only go to the links of the issue
+    Click {
+        issue_id: Uuid,
+        subscription_id: Uuid,
+        link: usize,
+    },
+}
+
+/// Signs tracking tokens, so that nobody can record events for someone else
+/// or turn the redirect into an open one.
+/// Tokens look like `o.<issue_id>.<subscription_id>.<signature>`.
+pub struct TrackingSigner {
+    key: Secret<String>,
+}
+
+impl TrackingSigner {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            key: config.tracking_signing_key.clone(),
+        }
+    }
+
+    pub fn sign(&self, token: &TrackingToken) -> String {
+        let payload = match token {
+            TrackingToken::Open {
+                issue_id,
+                subscription_id,
+            } => format!("o.{}.{}", issue_id, subscription_id),
+            TrackingToken::Click {
+                issue_id,
+                subscription_id,
+                link,
+            } => format!("c.{}.{}.{}", issue_id, subscription_id, link),
+        };
+        let signature = self.mac(&payload).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            payload,
+            base64::encode_config(signature, base64::URL_SAFE_NO_PAD)
+        )
+    }
+
+    pub fn verify(&self, token: &str) -> Option<TrackingToken> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD).ok()?;
+        // Constant time comparison
+        self.mac(payload).verify_slice(&signature).ok()?;
+        let parts: Vec<&str> = payload.split('.').collect();
+        match parts.as_slice() {
+            ["o", issue_id, subscription_id] => Some(TrackingToken::Open {
+                issue_id: Uuid::from_str(issue_id).ok()?,
+                subscription_id: Uuid::from_str(subscription_id).ok()?,
+            }),
+            ["c", issue_id, subscription_id, link] => Some(TrackingToken::Click {
+                issue_id: Uuid::from_str(issue_id).ok()?,
+                subscription_id: Uuid::from_str(subscription_id).ok()?,
+                link: link.parse().ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// An http(s) link in the text of an issue
+pub struct TextLink<'a> {
+    pub start: usize,
+    pub end: usize,
+    pub url: &'a str,
+}
+
+/// The links of the text, in order: click tokens refer to them by position
+pub fn find_links(text: &str) -> Vec<TextLink<'_>> {
+    let mut finder = LinkFinder::new();
+    finder.kinds(&[LinkKind::Url]);
+    finder
+        .links(text)
+        .filter(|link| {
+            link.as_str().starts_with("http://") || link.as_str().starts_with("https://")
+        })
+        .map(|link| TextLink {
+            start: link.start(),
+            end: link.end(),
+            url: link.as_str(),
+        })
+        .collect()
+}
@@ -1,14 +1,22 @@
 use actix_web::dev::Server;
 use once_cell::sync::Lazy;
+use p256::ecdsa::signature::Signer;
+use p256::ecdsa::SigningKey;
+use p256::pkcs8::EncodePublicKey;
+use rand_core::OsRng;
 use secrecy::ExposeSecret;
 use sqlx::{Connection, PgConnection, PgPool};
 use std::future::Future;
 use std::net::TcpListener;
 use std::time::Duration;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero2prod::config::Config;
-use zero2prod::email_client::EmailClient;
+use zero2prod::email_client::{EmailClient, SendEmailRequest};
+use zero2prod::event_bus::{EventBus, EventBusBackend};
+use zero2prod::migrations::Migrations;
+use zero2prod::shutdown::ShutdownCoordinator;
 
 use zero2prod::startup::run;
 use zero2prod::telemetry;
@@ -32,7 +40,13 @@ static TRACING: Lazy<()> = Lazy::new(|| {
     };
 });
 
+#[allow(dead_code)] // not every test suite uses the defaults
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// `configure` runs last, overriding the test defaults
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Config)) -> TestApp {
     let mut config = Config::new().expect("Failed to load config");
     // set application to the current test suite name
     // this way, NATS subjects will be prefixed differently
@@ -53,26 +67,47 @@ pub async fn spawn_app() -> TestApp {
     let connection_string = &database_url[0..last_slash_index];
     let db_pool = get_db_pool(connection_string, &db_name).await;
 
-    let nats_connection =
-        async_nats::connect(&format!("{}:{}", config.nats_host, config.nats_port))
-            .await
-            .expect("Could not connect to NATS");
-
     let mock_server = MockServer::start().await;
     config.email_client_sender_email = "test@example.com".to_owned();
     config.email_client_base_url = mock_server.uri();
     config.email_client_timeout_millis = 10000;
-    let email_client = EmailClient::new(&config);
+    // Mocks expect an exact number of requests
+    config.email_client_max_attempts = 1;
+    // Due issues are sent without making the tests wait
+    config.issue_scheduler_poll_interval_millis = 100;
+    config.issue_seed_addresses = vec![
+        "seed@example.com".to_owned(),
+        "Editor@Example.com".to_owned(),
+    ];
+    // Webhook payloads are signed by the tests instead of SendGrid
+    let sendgrid_signing_key = SigningKey::random(&mut OsRng);
+    config.sendgrid_webhook_public_key = base64::encode(
+        p256::PublicKey::from(sendgrid_signing_key.verifying_key())
+            .to_public_key_der()
+            .expect("Failed to encode the webhook public key"),
+    );
+    // Tests run without NATS, `test_event_bus` covers the NATS backend
+    config.event_bus_backend = EventBusBackend::InProcess;
+    configure(&mut config);
+    let email_client = EmailClient::new(&config, db_pool.clone());
+    let event_bus = EventBus::connect(&config)
+        .await
+        .expect("Failed to connect to the event bus")
+        .with_dead_letters(db_pool.clone());
 
+    let mut shutdown = ShutdownCoordinator::new();
     let server: Server = run(
         listener,
         db_pool.clone(),
-        nats_connection.clone(),
+        event_bus.clone(),
         email_client,
         config.clone(),
+        &mut shutdown,
     )
+    .await
     .expect("Failed to bind address");
-    let _ = tokio::spawn(server);
+    shutdown.register_http_server(server.handle());
+    tokio::spawn(server);
 
     TestApp {
         address,
@@ -80,13 +115,23 @@ pub async fn spawn_app() -> TestApp {
         db_pool,
         db_name,
         mock_server,
-        nats_connection,
+        event_bus,
         config,
+        sendgrid_signing_key,
+        shutdown,
     }
 }
 
 pub async fn get_db_pool(connection_string: &str, db_name: &str) -> PgPool {
-    // Create database
+    let connection_pool = create_db(connection_string, db_name).await;
+    Migrations::run(&connection_pool)
+        .await
+        .expect("Failed to migrate the database");
+    connection_pool
+}
+
+/// An empty database, not migrated
+pub async fn create_db(connection_string: &str, db_name: &str) -> PgPool {
     let mut connection = PgConnection::connect(connection_string)
         .await
         .expect("Failed to connect to Postgres");
@@ -94,16 +139,10 @@ pub async fn get_db_pool(connection_string: &str, db_name: &str) -> PgPool {
         .execute(&mut connection)
         .await
         .expect("Failed to create database.");
-    // Migrate database
     let database_url = format!("{}/{}", connection_string, db_name);
-    let connection_pool = PgPool::connect(database_url.as_str())
+    PgPool::connect(database_url.as_str())
         .await
-        .expect("Failed to connect to Postgres.");
-    sqlx::migrate!("./migrations")
-        .run(&connection_pool)
-        .await
-        .expect("Failed to migrate the database");
-    connection_pool
+        .expect("Failed to connect to Postgres.")
 }
 
 #[allow(dead_code)] // FIXME: associated function is never used: `eventually`
@@ -127,21 +166,56 @@ where
     }
 }
 
+/// An email received by the mock server, with its own copy of the data
+#[allow(dead_code)]
+pub struct SentEmail {
+    pub to: String,
+    pub subject: String,
+    pub text_content: String,
+    pub html_content: Option<String>,
+}
+
+impl From<&wiremock::Request> for SentEmail {
+    fn from(request: &wiremock::Request) -> Self {
+        let body: SendEmailRequest = serde_json::from_slice(&request.body).unwrap();
+        let html_content = body
+            .content
+            .iter()
+            .find(|content| content.r#type == "text/html")
+            .map(|content| content.value.to_string());
+        Self {
+            to: body.personalizations[0].to[0].email.to_owned(),
+            subject: body.subject.to_owned(),
+            text_content: body.content[0].value.to_string(),
+            html_content,
+        }
+    }
+}
+
+#[allow(dead_code)]
+pub fn find_email<'a>(emails: &'a [SentEmail], to: &str) -> &'a SentEmail {
+    emails.iter().find(|email| email.to == to).unwrap()
+}
+
+#[allow(dead_code)] // not every test suite uses every field
 pub struct TestApp {
     pub address: String,
     pub port: u16,
     pub db_pool: PgPool,
     pub db_name: String,
     pub mock_server: MockServer,
-    pub nats_connection: async_nats::Connection,
+    pub event_bus: EventBus,
     pub config: Config,
+    pub sendgrid_signing_key: SigningKey,
+    /// Nothing stops unless a test calls `shutdown`
+    pub shutdown: ShutdownCoordinator,
 }
 
 impl TestApp {
     #[allow(dead_code)] // FIXME: associated function is never used: `post_subscriptions`
     pub async fn post_subscriptions(&self, body: &str) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body.to_owned())
             .send()
@@ -149,11 +223,166 @@ impl TestApp {
             .expect("Failed to execute request")
     }
 
+    #[allow(dead_code)]
+    pub async fn post_data_requests(&self, body: &str) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/subscriptions/data_requests", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body.to_owned())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Signs the body the same way SendGrid does before posting it to the webhook
+    #[allow(dead_code)]
+    pub async fn post_sendgrid_events(&self, body: &str) -> reqwest::Response {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let signature: p256::ecdsa::Signature = self
+            .sendgrid_signing_key
+            .sign(format!("{}{}", timestamp, body).as_bytes());
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/sendgrid", &self.address))
+            .header("Content-Type", "application/json")
+            .header(
+                "X-Twilio-Email-Event-Webhook-Signature",
+                base64::encode(signature.to_der()),
+            )
+            .header("X-Twilio-Email-Event-Webhook-Timestamp", timestamp)
+            .body(body.to_owned())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// A request to an `/admin` route, authenticated with the configured admin token
+    #[allow(dead_code)]
+    pub fn admin_request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        reqwest::Client::new()
+            .request(method, format!("{}{}", &self.address, path))
+            .bearer_auth(self.config.admin_api_token.expose_secret())
+    }
+
+    #[allow(dead_code)]
+    pub async fn mock_mail_send(&self, expected_requests: u64) {
+        Mock::given(path("/mail/send"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(expected_requests)
+            .mount(&self.mock_server)
+            .await;
+    }
+
+    /// Waits until at least `expected` emails were sent
+    #[allow(dead_code)]
+    pub async fn wait_for_emails(&self, expected: usize) -> Vec<SentEmail> {
+        let requests = eventually(
+            || async {
+                let requests = self.get_received_requests().await?;
+                if requests.len() >= expected {
+                    Ok(requests)
+                } else {
+                    anyhow::bail!("Only {} emails were sent", requests.len())
+                }
+            },
+            100,
+            50,
+        )
+        .await;
+        requests.iter().map(SentEmail::from).collect()
+    }
+
+    /// The first link of the content containing `needle`, pointing at the test app
+    #[allow(dead_code)]
+    pub fn find_url(&self, content: &str, needle: &str) -> String {
+        let link = linkify::LinkFinder::new()
+            .links(content)
+            .map(|link| link.as_str().to_owned())
+            .find(|link| link.contains(needle))
+            .unwrap();
+        let mut url = reqwest::Url::parse(&link).unwrap();
+        url.set_port(Some(self.port)).unwrap();
+        url.to_string()
+    }
+
+    /// Writes an issue for the `newsletter` list
+    #[allow(dead_code)]
+    pub async fn add_issue(&self, subject: &str, text_content: &str) -> Uuid {
+        let response = self
+            .admin_request(reqwest::Method::POST, "/admin/issues")
+            .json(&serde_json::json!({
+                "list": "newsletter",
+                "subject": subject,
+                "text_content": text_content
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        let body: serde_json::Value = response.json().await.unwrap();
+        Uuid::parse_str(body["id"].as_str().unwrap()).unwrap()
+    }
+
+    /// Schedules the issue a minute ago, for the next tick of the scheduler
+    #[allow(dead_code)]
+    pub async fn schedule_issue_now(&self, issue_id: &Uuid) {
+        let send_at = chrono::Utc::now().naive_utc() - chrono::Duration::minutes(1);
+        let response = self
+            .admin_request(
+                reqwest::Method::POST,
+                &format!("/admin/issues/{}/schedule", issue_id),
+            )
+            .json(&serde_json::json!({
+                "send_at": send_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                "timezone": "list"
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    /// A confirmed subscriber, member of the `newsletter` list
+    #[allow(dead_code)]
+    pub async fn insert_confirmed_member(&self, subscription_id: &Uuid, email: &str) {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, status, subscribed_at)
+            VALUES ($1, $2, 'Reader', 'confirmed', now())
+            "#,
+            subscription_id,
+            email,
+        )
+        .execute(&self.db_pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            r#"
+            INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                          created_at, updated_at)
+            SELECT $1, id, 'confirmed', $2, now(), now() FROM lists WHERE slug = 'newsletter'
+            "#,
+            subscription_id,
+            Uuid::new_v4().to_string(),
+        )
+        .execute(&self.db_pool)
+        .await
+        .unwrap();
+    }
+
+    #[allow(dead_code)]
+    pub async fn enable_tracking(&self) {
+        sqlx::query!("UPDATE lists SET tracking_enabled = TRUE WHERE slug = 'newsletter'")
+            .execute(&self.db_pool)
+            .await
+            .unwrap();
+    }
+
     #[allow(dead_code)] // FIXME: associated function is never used: `get_received_requests`
     pub async fn get_received_requests(&self) -> anyhow::Result<Vec<wiremock::Request>> {
         let maybe_requests = self.mock_server.received_requests().await;
         let requests = maybe_requests.unwrap();
-        if requests.len() > 0 {
+        if !requests.is_empty() {
             Ok(requests)
         } else {
             anyhow::bail!("Mock server has no received requests yet")
@@ -0,0 +1,265 @@
+use crate::common::TestApp;
+use reqwest::Method;
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::email_client::SendEmailRequest;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admins_can_define_and_list_attributes() {
+    let test_app = common::spawn_app().await;
+    add_attributes(&test_app).await;
+    let response = add_attribute(&test_app, json!({"name": "country", "type": "string"})).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let attributes: serde_json::Value = test_app
+        .admin_request(Method::GET, "/admin/attributes")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(attributes[0]["name"], "company");
+    assert_eq!(attributes[1]["name"], "country");
+    assert_eq!(attributes[1]["type"], "enum");
+    assert_eq!(attributes[1]["required"], true);
+    assert_eq!(attributes[1]["enum_values"], json!(["PT", "DE"]));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_attribute_definitions_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let test_cases = vec![
+        (
+            json!({"name": "Country", "type": "string"}),
+            "an invalid name",
+        ),
+        (json!({"name": "plan", "type": "enum"}), "no enum values"),
+        (json!({"name": "plan", "type": "list"}), "an unknown type"),
+    ];
+    for (body, description) in test_cases {
+        let response = add_attribute(&test_app, body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the attribute had {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_subscribe_form_stores_valid_attributes() {
+    let test_app = common::spawn_app().await;
+    add_attributes(&test_app).await;
+    let response = test_app
+        .post_subscriptions(
+            "name=Ann&email=ann%40gmail.com&attributes%5Bcountry%5D=PT&attributes%5Bcompany%5D=ACME",
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT attributes FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        saved.attributes,
+        json!({"country": "PT", "company": "ACME"})
+    );
+
+    let test_cases = vec![
+        (
+            "name=Bob&email=bob%40gmail.com",
+            "a missing required attribute",
+        ),
+        (
+            "name=Bob&email=bob%40gmail.com&attributes%5Bcountry%5D=FR",
+            "a value outside of the enum",
+        ),
+        (
+            "name=Bob&email=bob%40gmail.com&attributes%5Bcountry%5D=PT&attributes%5Bage%5D=3",
+            "an unknown attribute",
+        ),
+    ];
+    for (body, description) in test_cases {
+        let response = test_app.post_subscriptions(body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the form had {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn imports_add_confirmed_members_and_report_the_skipped_rows() {
+    let test_app = common::spawn_app().await;
+    add_attributes(&test_app).await;
+    test_app
+        .admin_request(Method::POST, "/admin/suppressions")
+        .json(&json!({"email": "bounced@gmail.com", "reason": "hard_bounce"}))
+        .send()
+        .await
+        .unwrap();
+    let response = import(
+        &test_app,
+        json!([
+            {"email": "ann@gmail.com", "name": "Ann", "attributes": {"country": "PT"}},
+            {"email": "bob@gmail.com", "name": "Bob", "attributes": {}},
+            {"email": "bounced@gmail.com", "name": "Bounced", "attributes": {"country": "DE"}},
+            {"email": "ann@gmail.com", "name": "Ann Again", "attributes": {"country": "DE"}},
+            {"email": "not-an-email", "name": "Nobody", "attributes": {"country": "DE"}}
+        ]),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 1);
+    let skipped: Vec<u64> = report["skipped"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|row| row["index"].as_u64().unwrap())
+        .collect();
+    assert_eq!(skipped, vec![1, 2, 3, 4]);
+
+    let saved = sqlx::query!(
+        r#"
+        SELECT s.name AS "name!", s.status :: TEXT AS "status!", s.attributes AS "attributes!",
+               m.status :: TEXT AS "membership_status!"
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscription_id = s.id
+        "#
+    )
+    .fetch_all(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].name, "Ann");
+    assert_eq!(saved[0].status, "confirmed");
+    assert_eq!(saved[0].membership_status, "confirmed");
+    assert_eq!(saved[0].attributes, json!({"country": "PT"}));
+
+    let history: serde_json::Value = test_app
+        .admin_request(Method::GET, "/admin/subscriptions/ann@gmail.com/events")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history["events"][0]["source"], "admin_import");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn imports_into_unknown_lists_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/subscriptions/import")
+        .json(&json!({"list": "unknown", "subscribers": []}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn issues_are_personalised_with_the_subscriber_attributes() {
+    let test_app = common::spawn_app().await;
+    add_attributes(&test_app).await;
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.mock_server)
+        .await;
+    import(
+        &test_app,
+        json!([
+            {"email": "ann@gmail.com", "name": "Ann", "attributes": {"country": "PT", "company": "ACME"}},
+            {"email": "bob@gmail.com", "name": "Bob", "attributes": {"country": "DE"}}
+        ]),
+    )
+    .await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&json!({
+            "list": "newsletter",
+            "subject": "News",
+            "text_content": "Hi {{ name }} at {{ attributes.company | your company }} ({{ attributes.country }})"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let issue: serde_json::Value = response.json().await.unwrap();
+    let send_at = chrono::Utc::now().naive_utc() - chrono::Duration::minutes(1);
+    test_app
+        .admin_request(
+            Method::POST,
+            &format!("/admin/issues/{}/schedule", issue["id"].as_str().unwrap()),
+        )
+        .json(&json!({
+            "send_at": send_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timezone": "list"
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    let requests = common::eventually(
+        || async {
+            let requests = test_app.get_received_requests().await?;
+            if requests.len() == 2 {
+                Ok(requests)
+            } else {
+                anyhow::bail!("Only {} emails were sent", requests.len())
+            }
+        },
+        100,
+        50,
+    )
+    .await;
+    let mut texts: Vec<String> = requests
+        .iter()
+        .map(|request| {
+            let body: SendEmailRequest = serde_json::from_slice(&request.body).unwrap();
+            body.content[0].value.to_string()
+        })
+        .collect();
+    texts.sort();
+    assert!(texts[0].starts_with("Hi Ann at ACME (PT)"));
+    assert!(texts[1].starts_with("Hi Bob at your company (DE)"));
+}
+
+async fn add_attributes(test_app: &TestApp) {
+    let response = add_attribute(
+        test_app,
+        json!({"name": "country", "type": "enum", "required": true, "values": ["PT", "DE"]}),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = add_attribute(test_app, json!({"name": "company", "type": "string"})).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn add_attribute(test_app: &TestApp, body: serde_json::Value) -> reqwest::Response {
+    test_app
+        .admin_request(Method::POST, "/admin/attributes")
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn import(test_app: &TestApp, subscribers: serde_json::Value) -> reqwest::Response {
+    test_app
+        .admin_request(Method::POST, "/admin/subscriptions/import")
+        .json(&json!({"list": "newsletter", "subscribers": subscribers}))
+        .send()
+        .await
+        .unwrap()
+}
@@ -0,0 +1,306 @@
+use std::process::{Command, Output};
+
+use chrono::Utc;
+use reqwest::Url;
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::subscriber_email::SubscriberEmail;
+use zero2prod::email_client::SendEmailRequest;
+use zero2prod::handlers::manage_subscribers::{resend_confirmation, ChangeSubscriberOutput};
+use zero2prod::handlers::replay_dead_letters::replay_dead_letters;
+use zero2prod::migrations::MIGRATOR;
+
+use crate::common::TestApp;
+
+mod common;
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_show_prints_the_subscription_and_its_lists() {
+    let test_app = common::spawn_app().await;
+    subscribe(&test_app).await;
+
+    let output = ctl(&test_app, &["subscribers", "show", EMAIL]);
+
+    assert!(output.status.success());
+    let details: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(details["email"], EMAIL);
+    assert_eq!(details["status"], "pending");
+    assert_eq!(details["lists"][0]["list"], "newsletter");
+    assert_eq!(details["lists"][0]["status"], "pending");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_show_fails_for_an_unknown_email() {
+    let test_app = common::spawn_app().await;
+
+    let output = ctl(&test_app, &["subscribers", "show", EMAIL]);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("There is no subscription"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_confirm_confirms_the_subscription_and_its_lists() {
+    let test_app = common::spawn_app().await;
+    subscribe(&test_app).await;
+
+    let output = ctl(&test_app, &["subscribers", "confirm", EMAIL]);
+
+    assert!(output.status.success());
+    let subscription = sqlx::query!(r#"SELECT status::TEXT AS "status!" FROM subscriptions"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, "confirmed");
+    let membership = sqlx::query!(r#"SELECT status::TEXT AS "status!" FROM list_memberships"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(membership.status, "confirmed");
+    let events = sqlx::query!("SELECT source FROM subscription_events WHERE source = 'operator'")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_unsubscribe_leaves_every_list_and_keeps_the_address() {
+    let test_app = common::spawn_app().await;
+    subscribe(&test_app).await;
+
+    let output = ctl(&test_app, &["subscribers", "unsubscribe", EMAIL]);
+
+    assert!(output.status.success());
+    let subscription = sqlx::query!(r#"SELECT status::TEXT AS "status!" FROM subscriptions"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, "pending");
+    let membership = sqlx::query!(r#"SELECT status::TEXT AS "status!" FROM list_memberships"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(membership.status, "unsubscribed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn resend_confirmation_sends_new_confirmation_links() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .post_subscriptions(&format!("name=le%20guin&email={}", EMAIL))
+        .await;
+    wait_for_emails(&test_app, 1).await;
+
+    let email = SubscriberEmail::parse(EMAIL.to_owned()).unwrap();
+    let output = resend_confirmation(&test_app.db_pool, &test_app.event_bus, &email)
+        .await
+        .unwrap();
+
+    assert!(matches!(output, ChangeSubscriberOutput::Success));
+    wait_for_emails(&test_app, 2).await;
+    let tokens = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_replayed_dead_letter_is_handled_again() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .post_subscriptions(&format!("name=le%20guin&email={}", EMAIL))
+        .await;
+    let dead_letters = common::eventually(
+        || async {
+            let output = ctl(&test_app, &["dead-letters", "list"]);
+            let dead_letters: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)?;
+            anyhow::ensure!(!dead_letters.is_empty(), "No dead letter yet");
+            Ok(dead_letters)
+        },
+        50,
+        100,
+    )
+    .await;
+    assert_eq!(dead_letters.len(), 1);
+    assert_eq!(dead_letters[0]["subject"], "subscription-created");
+
+    let replayed = replay_dead_letters(&test_app.db_pool, &test_app.event_bus, None, &[])
+        .await
+        .unwrap();
+
+    assert_eq!(replayed.len(), 1);
+    wait_for_emails(&test_app, 2).await;
+    let output = ctl(&test_app, &["dead-letters", "list"]);
+    let dead_letters: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert!(dead_letters.is_empty());
+    // The replayed email carries the tokens of the failed one, which still confirm
+    let received_requests = test_app.mock_server.received_requests().await.unwrap();
+    let confirmation_link = extract_confirmation_link(&test_app, &received_requests[1].body);
+    let response = reqwest::get(&confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let subscription = sqlx::query!(r#"SELECT status::TEXT AS "status!" FROM subscriptions"#)
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, "confirmed");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokens_purge_expired_deletes_the_expired_data_request_tokens() {
+    let test_app = common::spawn_app().await;
+    subscribe(&test_app).await;
+    let subscriber = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    let ttl = chrono::Duration::minutes(test_app.config.data_request_token_ttl_minutes.into());
+    for created_at in [Utc::now() - ttl - chrono::Duration::minutes(1), Utc::now()] {
+        sqlx::query!(
+            r#"
+            INSERT INTO data_request_tokens (data_request_token, subscriber_id, kind, created_at)
+            VALUES ($1, $2, 'export', $3)
+            "#,
+            Uuid::new_v4().to_string(),
+            subscriber.id,
+            created_at,
+        )
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    let output = ctl(&test_app, &["tokens", "purge-expired"]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        "Deleted 1 expired data request tokens"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn migrate_dry_run_prints_the_pending_migrations_without_applying_them() {
+    let test_app = common::spawn_app().await;
+    let last = MIGRATOR.iter().last().unwrap();
+    // The table of sqlx, not in the schema the queries are checked against
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version = $1")
+        .bind(last.version)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let output = ctl(&test_app, &["migrate", "--dry-run"]);
+
+    assert!(output.status.success());
+    let pending: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0]["version"], last.version);
+    let applied: Option<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE version = $1")
+            .bind(last.version)
+            .fetch_optional(&test_app.db_pool)
+            .await
+            .unwrap();
+    assert!(applied.is_none());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn config_redacts_the_secrets() {
+    let test_app = common::spawn_app().await;
+
+    let admin_api_token = Uuid::new_v4().to_string();
+
+    let output = ctl_command(&test_app)
+        .arg("config")
+        .env("ADMIN_API_TOKEN", &admin_api_token)
+        .output()
+        .expect("Failed to run zero2prodctl");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("admin_api_token: Secret([REDACTED"));
+    assert!(!stdout.contains(&admin_api_token));
+    assert!(!stdout.contains(&test_app.db_name));
+}
+
+/// Runs `zero2prodctl` against the database of the test
+fn ctl(test_app: &TestApp, args: &[&str]) -> Output {
+    ctl_command(test_app)
+        .args(args)
+        .output()
+        .expect("Failed to run zero2prodctl")
+}
+
+fn ctl_command(test_app: &TestApp) -> Command {
+    let database_url = test_app.config.database_url.expose_secret();
+    let last_slash_index = database_url.rfind('/').unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_zero2prodctl"));
+    command.env(
+        "DATABASE_URL",
+        format!(
+            "{}/{}",
+            &database_url[0..last_slash_index],
+            test_app.db_name
+        ),
+    );
+    command
+}
+
+async fn subscribe(test_app: &TestApp) {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.mock_server)
+        .await;
+    let response = test_app
+        .post_subscriptions(&format!("name=le%20guin&email={}", EMAIL))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn wait_for_emails(test_app: &TestApp, count: usize) {
+    common::eventually(
+        || async {
+            let requests = test_app.mock_server.received_requests().await.unwrap();
+            anyhow::ensure!(requests.len() >= count, "{} emails so far", requests.len());
+            Ok(())
+        },
+        50,
+        100,
+    )
+    .await;
+}
+
+fn extract_confirmation_link(test_app: &TestApp, body: &[u8]) -> String {
+    let body: SendEmailRequest = serde_json::from_slice(body).unwrap();
+    let link = linkify::LinkFinder::new()
+        .links(body.content.first().unwrap().value.as_ref())
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        // Skips the preference center link in the footer
+        .find(|l| l.as_str().contains("subscription_token"))
+        .unwrap();
+    let mut confirmation_url = Url::parse(link.as_str()).unwrap();
+    confirmation_url.set_port(Some(test_app.port)).unwrap();
+    confirmation_url.to_string()
+}
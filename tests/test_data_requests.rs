@@ -0,0 +1,301 @@
+use crate::common::TestApp;
+use reqwest::Url;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::db::subscription_queries::SubscriptionQueries;
+use zero2prod::domain::new_subscriber::NewSubscriber;
+use zero2prod::domain::subscriber_attributes::SubscriberAttributes;
+use zero2prod::domain::subscriber_email::SubscriberEmail;
+use zero2prod::domain::subscriber_name::SubscriberName;
+use zero2prod::domain::subscription_status::SubscriptionStatus;
+use zero2prod::email_client::SendEmailRequest;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn data_requests_with_invalid_data_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let test_cases = vec![
+        ("kind=export", "missing the email"),
+        ("email=ursula_le_guin%40gmail.com", "missing the kind"),
+        (
+            "email=ursula_le_guin%40gmail.com&kind=delete",
+            "unknown kind",
+        ),
+        ("email=not-an-email&kind=export", "invalid email"),
+    ];
+    for (body, description) in test_cases {
+        let response = test_app.post_data_requests(body).await;
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn data_requests_for_an_unknown_email_return_a_200_and_store_nothing() {
+    let test_app = common::spawn_app().await;
+    let response = test_app
+        .post_data_requests("email=unknown%40gmail.com&kind=export")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT data_request_token FROM data_request_tokens")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export_link_returns_the_data_stored_about_the_subscriber() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app).await;
+    insert_new_subscription(&test_app, "export_me@gmail.com", "Export Me").await;
+
+    let response = test_app
+        .post_data_requests("email=export_me%40gmail.com&kind=export")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let export_link = extract_link(&test_app, &received_requests[0].body, "/data_export");
+
+    let response = reqwest::get(&export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let content_disposition = response.headers()["content-disposition"].to_str().unwrap();
+    assert!(content_disposition.starts_with("attachment"));
+    let export: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(export["subscription"]["email"], "export_me@gmail.com");
+    assert_eq!(export["subscription"]["name"], "Export Me");
+    assert_eq!(export["subscription"]["status"], "confirmed");
+    assert_eq!(export["data_requests"][0]["kind"], "export");
+    assert!(export.get("subscription_tokens").is_none());
+
+    // The export link can be used more than once until it expires
+    let response = reqwest::get(&export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn erasure_link_deletes_the_subscriber_and_keeps_a_hashed_suppression() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app).await;
+    insert_new_subscription(&test_app, "forget_me@gmail.com", "Forget Me").await;
+
+    test_app
+        .post_data_requests("email=forget_me%40gmail.com&kind=erasure")
+        .await;
+
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let erasure_link = extract_link(&test_app, &received_requests[0].body, "/erase");
+
+    let response = reqwest::Client::new()
+        .post(&erasure_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscriptions = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(subscriptions.is_empty());
+    let suppression = sqlx::query!("SELECT email_hash, (reason :: TEXT), source FROM suppressions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch the suppression");
+    let email = SubscriberEmail::parse("Forget_Me@gmail.com".to_owned()).unwrap();
+    assert_eq!(suppression.email_hash, email.canonical_hash());
+    assert_eq!(suppression.reason, Some("erasure".to_owned()));
+    assert_eq!(suppression.source, "data_request");
+
+    // The token is gone together with the subscriber
+    let response = reqwest::Client::new()
+        .post(&erasure_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dead_letters_are_exported_and_erased_with_the_subscriber() {
+    let test_app = common::spawn_app().await;
+    // The confirmation email fails and its event is dead lettered
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    common::eventually(
+        || async {
+            let dead_letters = sqlx::query!("SELECT id FROM dead_letters")
+                .fetch_all(&test_app.db_pool)
+                .await?;
+            anyhow::ensure!(!dead_letters.is_empty(), "No dead letter yet");
+            Ok(())
+        },
+        100,
+        50,
+    )
+    .await;
+
+    test_app
+        .post_data_requests("email=ursula_le_guin%40gmail.com&kind=export")
+        .await;
+    let received_requests = wait_for_emails(&test_app, 2).await;
+    let export_link = extract_link(&test_app, &received_requests[1].body, "/data_export");
+    let export: serde_json::Value = reqwest::get(&export_link)
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(export["dead_letters"][0]["subject"], "subscription-created");
+
+    test_app
+        .post_data_requests("email=ursula_le_guin%40gmail.com&kind=erasure")
+        .await;
+    let received_requests = wait_for_emails(&test_app, 3).await;
+    let erasure_link = extract_link(&test_app, &received_requests[2].body, "/erase");
+    let response = reqwest::Client::new()
+        .post(&erasure_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let dead_letters = sqlx::query!("SELECT id FROM dead_letters")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(dead_letters.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn following_the_erasure_link_only_asks_for_a_confirmation() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app).await;
+    insert_new_subscription(&test_app, "forget_me@gmail.com", "Forget Me").await;
+
+    test_app
+        .post_data_requests("email=forget_me%40gmail.com&kind=erasure")
+        .await;
+
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let erasure_link = extract_link(&test_app, &received_requests[0].body, "/erase");
+
+    let response = reqwest::get(&erasure_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("forget_me@gmail.com"));
+    assert!(page.contains(r#"method="post""#));
+    let subscriptions = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn export_token_cannot_be_used_for_erasure() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app).await;
+    insert_new_subscription(&test_app, "export_only@gmail.com", "Export Only").await;
+
+    test_app
+        .post_data_requests("email=export_only%40gmail.com&kind=export")
+        .await;
+
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let export_link = extract_link(&test_app, &received_requests[0].body, "/data_export");
+    let erasure_link = export_link.replace("/data_export", "/erase");
+
+    let response = reqwest::get(&erasure_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::Client::new()
+        .post(&erasure_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let subscriptions = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.len(), 1);
+}
+
+fn extract_link(test_app: &TestApp, body: &[u8], expected_path_suffix: &str) -> String {
+    let body: SendEmailRequest = serde_json::from_slice(body).unwrap();
+
+    let links: Vec<_> = linkify::LinkFinder::new()
+        .links(body.content.first().unwrap().value.as_ref())
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        // Skips the preference center link in the footer
+        .filter(|l| l.as_str().contains("data_request_token"))
+        .collect();
+    assert_eq!(links.len(), 1);
+
+    let mut url = Url::parse(links.first().unwrap().as_str()).unwrap();
+    assert_eq!(url.host_str().unwrap(), "127.0.0.1");
+    assert!(url.path().ends_with(expected_path_suffix));
+
+    // replace the port form .env with test app random port
+    url.set_port(Some(test_app.port)).unwrap();
+    url.to_string()
+}
+
+async fn insert_new_subscription(test_app: &TestApp, email: &str, name: &str) {
+    let mut tx = test_app.db_pool.begin().await.unwrap();
+    let sub = NewSubscriber {
+        email: SubscriberEmail::parse(email.to_string()).unwrap(),
+        name: SubscriberName::parse(name.to_string()).unwrap(),
+        attributes: SubscriberAttributes::default(),
+    };
+    SubscriptionQueries::insert_subscriber(&mut tx, &sub, SubscriptionStatus::Confirmed)
+        .await
+        .expect("Failed to save a new subscriber");
+    tx.commit().await.unwrap();
+}
+
+async fn wait_for_emails(test_app: &TestApp, count: usize) -> Vec<wiremock::Request> {
+    common::eventually(
+        || async {
+            let requests = test_app.get_received_requests().await?;
+            anyhow::ensure!(requests.len() >= count, "Only {} emails", requests.len());
+            Ok(requests)
+        },
+        100,
+        50,
+    )
+    .await
+}
+
+async fn mock_mail_send(test_app: &TestApp) {
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+}
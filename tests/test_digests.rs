@@ -0,0 +1,223 @@
+use crate::common::{SentEmail, TestApp};
+use reqwest::Method;
+use uuid::Uuid;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn digest_subscribers_get_every_new_issue_in_a_single_email() {
+    let test_app = common::spawn_app().await;
+    // Two issues for the immediate subscriber, one digest
+    test_app.mock_mail_send(3).await;
+    // Published before the digest subscriber joined
+    insert_published_issue(&test_app, "Old news").await;
+    insert_confirmed_member(&test_app, "digest@gmail.com", "daily").await;
+    insert_confirmed_member(&test_app, "immediate@gmail.com", "immediate").await;
+
+    let first = add_issue(&test_app, "Issue #1").await;
+    let second = add_issue(&test_app, "Issue #2").await;
+    schedule_now(&test_app, &[&first, &second]).await;
+    test_app.wait_for_emails(2).await;
+
+    // A day later
+    travel_back(&test_app, "1 day").await;
+    let requests = test_app.wait_for_emails(3).await;
+    let digests: Vec<&SentEmail> = requests
+        .iter()
+        .filter(|email| email.to == "digest@gmail.com")
+        .collect();
+    assert_eq!(digests.len(), 1);
+    assert_eq!(digests[0].subject, "Your daily digest");
+    let text = &digests[0].text_content;
+    assert!(text.contains("Newsletter - Issue #1"));
+    assert!(text.contains("Newsletter - Issue #2"));
+    assert!(!text.contains("Old news"));
+    assert!(text.find("Issue #1").unwrap() < text.find("Issue #2").unwrap());
+    assert!(text.contains("/subscriptions/unsubscribe?unsubscribe_token="));
+    assert!(text.contains("/preferences?preferences_token="));
+
+    let via_digest = sqlx::query!("SELECT issue_id FROM issue_deliveries WHERE via_digest")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(via_digest.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_next_digest_only_has_issues_published_since_the_previous_one() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(2).await;
+    insert_confirmed_member(&test_app, "weekly@gmail.com", "weekly").await;
+
+    let first = add_issue(&test_app, "Issue #1").await;
+    schedule_now(&test_app, &[&first]).await;
+    wait_for_publication(&test_app, &first).await;
+    travel_back(&test_app, "7 days").await;
+    test_app.wait_for_emails(1).await;
+
+    // Within the week: the new issue waits for the next digest
+    let second = add_issue(&test_app, "Issue #2").await;
+    schedule_now(&test_app, &[&second]).await;
+    wait_for_publication(&test_app, &second).await;
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(test_app.get_received_requests().await.unwrap().len(), 1);
+
+    // A week later
+    travel_back(&test_app, "7 days").await;
+    let requests = test_app.wait_for_emails(2).await;
+    assert_eq!(requests[1].subject, "Your weekly digest");
+    let text = &requests[1].text_content;
+    assert!(!text.contains("Issue #1"));
+    assert!(text.contains("Issue #2"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn digests_leave_out_the_issues_of_other_segments() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(1).await;
+    insert_confirmed_member(&test_app, "digest@gmail.com", "daily").await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/segments")
+        .json(&serde_json::json!({
+            "name": "example",
+            "definition": {"email_domain": "example.com"}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response = test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&serde_json::json!({
+            "list": "newsletter",
+            "subject": "Example only",
+            "text_content": "For example.com",
+            "segment": "example"
+        }))
+        .send()
+        .await
+        .unwrap();
+    let body: serde_json::Value = response.json().await.unwrap();
+    let targeted = Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+    let everyone = add_issue(&test_app, "For everyone").await;
+    schedule_now(&test_app, &[&targeted, &everyone]).await;
+    wait_for_publication(&test_app, &targeted).await;
+    wait_for_publication(&test_app, &everyone).await;
+
+    travel_back(&test_app, "1 day").await;
+    let requests = test_app.wait_for_emails(1).await;
+    let text = &requests[0].text_content;
+    assert!(text.contains("Newsletter - For everyone"));
+    assert!(!text.contains("Example only"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn digests_follow_calendar_periods() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(0).await;
+    // Subscribed in the previous period, nothing published since
+    insert_confirmed_member(&test_app, "quiet@gmail.com", "daily").await;
+    travel_back(&test_app, "1 day").await;
+    common::eventually(
+        || async {
+            let cursor = sqlx::query!("SELECT last_digest_at FROM digest_cursors")
+                .fetch_one(&test_app.db_pool)
+                .await?;
+            Ok(cursor)
+        },
+        100,
+        50,
+    )
+    .await;
+    // Subscribed today
+    insert_confirmed_member(&test_app, "new@gmail.com", "daily").await;
+
+    // Both wait for tomorrow
+    let issue_id = add_issue(&test_app, "Issue #1").await;
+    schedule_now(&test_app, &[&issue_id]).await;
+    wait_for_publication(&test_app, &issue_id).await;
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(test_app.get_received_requests().await.is_err());
+}
+
+async fn add_issue(test_app: &TestApp, subject: &str) -> Uuid {
+    test_app
+        .add_issue(subject, &format!("This is {}", subject))
+        .await
+}
+
+/// All at once, so that the scheduler publishes them in the same tick
+async fn schedule_now(test_app: &TestApp, issue_ids: &[&Uuid]) {
+    let issue_ids: Vec<Uuid> = issue_ids.iter().map(|id| **id).collect();
+    sqlx::query!(
+        r#"
+        UPDATE issues
+        SET status = 'scheduled', send_at_local = now() :: TIMESTAMP, scheduled_at = now()
+        WHERE id = ANY($1)
+        "#,
+        &issue_ids,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn wait_for_publication(test_app: &TestApp, issue_id: &Uuid) {
+    common::eventually(
+        || async {
+            let issue = sqlx::query!("SELECT published_at FROM issues WHERE id = $1", issue_id)
+                .fetch_one(&test_app.db_pool)
+                .await?;
+            issue
+                .published_at
+                .ok_or_else(|| anyhow::anyhow!("The issue is not published yet"))
+        },
+        100,
+        50,
+    )
+    .await;
+}
+
+/// Moves the subscriptions and the previous digests back in time
+async fn travel_back(test_app: &TestApp, interval: &str) {
+    sqlx::query("UPDATE subscriptions SET subscribed_at = subscribed_at - $1 :: INTERVAL")
+        .bind(interval)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE digest_cursors SET last_digest_at = last_digest_at - $1 :: INTERVAL")
+        .bind(interval)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+}
+
+async fn insert_published_issue(test_app: &TestApp, subject: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO issues (id, list_id, subject, text_content, status, created_at, updated_at,
+                            published_at, sent_at)
+        SELECT $1, id, $2, $2, 'sent', now(), now(),
+               now() - INTERVAL '1 hour', now() - INTERVAL '1 hour'
+        FROM lists WHERE slug = 'newsletter'
+        "#,
+        Uuid::new_v4(),
+        subject,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn insert_confirmed_member(test_app: &TestApp, email: &str, delivery_cadence: &str) {
+    let subscription_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&subscription_id, email)
+        .await;
+    sqlx::query("UPDATE subscriptions SET delivery_cadence = $1 :: delivery_cadence WHERE id = $2")
+        .bind(delivery_cadence)
+        .bind(subscription_id)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+}
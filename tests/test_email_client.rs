@@ -1,4 +1,6 @@
 use claim::assert_err;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
 use std::time::Duration;
 
 use fake::faker::internet::en::SafeEmail;
@@ -9,8 +11,10 @@ use wiremock::matchers::{any, header, header_exists, method, path};
 use wiremock::{Match, Mock, MockServer, Request, ResponseTemplate};
 use zero2prod::config::Config;
 
+use uuid::Uuid;
+use zero2prod::domain::email_template::EmailTemplate;
 use zero2prod::domain::subscriber_email::SubscriberEmail;
-use zero2prod::email_client::EmailClient;
+use zero2prod::email_client::{EmailClient, SendOutcome};
 
 struct MatchSendEmailBody;
 
@@ -49,12 +53,26 @@ async fn succeeds_if_the_server_returns_200() {
         .await;
 
     // Act
-    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000);
-    let _ = email_client
-        .send_email(&recipient, &subject, &content)
+    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000, 1);
+    let outcome = email_client
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &recipient,
+            &subject,
+            &content,
+        )
         .await
         .unwrap();
 
+    match outcome {
+        SendOutcome::Sent(sent) => {
+            assert_eq!(sent.status, 200);
+            assert_eq!(sent.attempt, 1);
+        }
+        SendOutcome::Suppressed => panic!("Expected the email to be sent"),
+    }
+
     // Wiremock assertions performed on Drop
 }
 
@@ -75,9 +93,15 @@ async fn fails_when_sending_takes_too_long() {
         .await;
 
     // Act
-    let email_client = create_email_client(sender.as_ref(), &mock_server, 100);
+    let email_client = create_email_client(sender.as_ref(), &mock_server, 100, 1);
     let outcome = email_client
-        .send_email(&recipient, &subject, &content)
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &recipient,
+            &subject,
+            &content,
+        )
         .await;
 
     assert_err!(outcome);
@@ -100,9 +124,15 @@ async fn fails_if_the_server_returns_500() {
         .await;
 
     // Act
-    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000);
+    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000, 1);
     let outcome = email_client
-        .send_email(&subscriber_email, &subject, &content)
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &subscriber_email,
+            &subject,
+            &content,
+        )
         .await;
 
     // Assert
@@ -111,14 +141,147 @@ async fn fails_if_the_server_returns_500() {
     // Wiremock assertions performed on Drop
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn retries_if_the_server_returns_500() {
+    // Arrange
+    let mock_server = MockServer::start().await;
+    let sender = random_email();
+    let recipient = random_email();
+    let subject: String = Sentence(1..2).fake();
+    let content: String = Paragraph(1..10).fake();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(202).insert_header("X-Message-Id", "message-id"))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Act
+    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000, 3);
+    let outcome = email_client
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &recipient,
+            &subject,
+            &content,
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    match outcome {
+        SendOutcome::Sent(sent) => {
+            assert_eq!(sent.status, 202);
+            assert_eq!(sent.attempt, 2);
+            assert_eq!(sent.provider_message_id.as_deref(), Some("message-id"));
+        }
+        SendOutcome::Suppressed => panic!("Expected the email to be sent"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn does_not_retry_if_the_server_returns_400() {
+    // Arrange
+    let mock_server = MockServer::start().await;
+    let sender = random_email();
+    let recipient = random_email();
+    let subject: String = Sentence(1..2).fake();
+    let content: String = Paragraph(1..10).fake();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(400))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Act
+    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000, 3);
+    let outcome = email_client
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &recipient,
+            &subject,
+            &content,
+        )
+        .await;
+
+    // Assert
+    assert_err!(outcome);
+
+    // Wiremock assertions performed on Drop
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn deliver_records_the_delivery() {
+    // Arrange
+    let mock_server = MockServer::start().await;
+    let sender = random_email();
+    let recipient = random_email();
+    let message_id = Uuid::new_v4().to_string();
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(202).insert_header("X-Message-Id", message_id.as_str()))
+        .expect(1)
+        .mount(&mock_server)
+        .await;
+
+    // Act
+    let email_client = create_email_client(sender.as_ref(), &mock_server, 1000, 1);
+    email_client
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &recipient,
+            "Subject",
+            "Content",
+        )
+        .await
+        .unwrap();
+
+    // Assert
+    let config = Config::new().expect("Failed to load config");
+    let pg_pool = PgPool::connect(config.database_url.expose_secret())
+        .await
+        .expect("Failed to connect to Postgres");
+    let delivery = sqlx::query!(
+        r#"
+        SELECT template, (outcome :: TEXT) AS outcome, http_status, attempt
+        FROM email_deliveries
+        WHERE provider_message_id = $1
+        "#,
+        message_id
+    )
+    .fetch_one(&pg_pool)
+    .await
+    .expect("Failed to fetch the email delivery");
+    assert_eq!(delivery.template, "subscription_confirmation");
+    assert_eq!(delivery.outcome.as_deref(), Some("sent"));
+    assert_eq!(delivery.http_status, Some(202));
+    assert_eq!(delivery.attempt, 1);
+}
+
 fn random_email() -> SubscriberEmail {
     SubscriberEmail::parse(SafeEmail().fake()).unwrap()
 }
 
-fn create_email_client(sender: &str, mock_server: &MockServer, timeout_millis: u16) -> EmailClient {
+fn create_email_client(
+    sender: &str,
+    mock_server: &MockServer,
+    timeout_millis: u16,
+    max_attempts: u32,
+) -> EmailClient {
     let mut config = Config::new().expect("Failed to load config");
     config.email_client_sender_email = sender.to_owned();
     config.email_client_base_url = mock_server.uri();
     config.email_client_timeout_millis = timeout_millis;
-    EmailClient::new(&config)
+    config.email_client_max_attempts = max_attempts;
+    // Nothing is suppressed there, but the pool is needed for the suppression check
+    let pg_pool = PgPool::connect_lazy(config.database_url.expose_secret())
+        .expect("Failed to create a Postgres connection pool");
+    EmailClient::new(&config, pg_pool)
 }
@@ -0,0 +1,322 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use claim::assert_err;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tokio::sync::mpsc;
+use zero2prod::config::Config;
+use zero2prod::event_bus::{Envelope, Event, EventBus, EventBusBackend, Handler};
+use zero2prod::events::subscriber_tag_changed::{SubscriberTagChanged, TagChange};
+use zero2prod::events::subscription_created::SubscriptionCreated;
+
+/// Version 1 had no greeting
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Greeted {
+    name: String,
+    greeting: String,
+}
+
+impl Event for Greeted {
+    const SUBJECT: &'static str = "greeted";
+    const SCHEMA_VERSION: u32 = 2;
+
+    fn upcast(version: u32, mut payload: serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        match version {
+            0 => Ok(payload),
+            1 => {
+                payload["greeting"] = json!("Hello");
+                Ok(payload)
+            }
+            _ => anyhow::bail!("Unknown version {}", version),
+        }
+    }
+}
+
+struct ForwardingHandler(mpsc::UnboundedSender<Envelope<Greeted>>);
+
+#[async_trait]
+impl Handler<Greeted> for ForwardingHandler {
+    async fn handle(&self, envelope: Envelope<Greeted>) -> anyhow::Result<()> {
+        self.0.send(envelope)?;
+        Ok(())
+    }
+}
+
+/// Fails the first `failures` deliveries, then reports the attempt that succeeded.
+/// Reports the attempts so far when the bus gives up instead.
+struct FlakyHandler {
+    failures: u32,
+    attempts: AtomicU32,
+    sender: mpsc::UnboundedSender<(&'static str, u32)>,
+}
+
+impl FlakyHandler {
+    fn new(failures: u32, sender: mpsc::UnboundedSender<(&'static str, u32)>) -> Self {
+        Self {
+            failures,
+            attempts: AtomicU32::new(0),
+            sender,
+        }
+    }
+}
+
+#[async_trait]
+impl Handler<Greeted> for FlakyHandler {
+    async fn handle(&self, _envelope: Envelope<Greeted>) -> anyhow::Result<()> {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt <= self.failures {
+            anyhow::bail!("Attempt {} failed", attempt);
+        }
+        self.sender.send(("handled", attempt))?;
+        Ok(())
+    }
+
+    async fn give_up(&self, _envelope: Envelope<Greeted>) -> anyhow::Result<()> {
+        self.sender
+            .send(("given up", self.attempts.load(Ordering::SeqCst)))?;
+        Ok(())
+    }
+}
+
+fn greeted(name: &str) -> Greeted {
+    Greeted {
+        name: name.to_owned(),
+        greeting: "Hi".to_owned(),
+    }
+}
+
+#[test]
+fn envelopes_keep_the_metadata_and_the_headers() {
+    let envelope = Envelope::new(greeted("Ann")).with_header("source", "tests");
+    let decoded = Envelope::<Greeted>::decode(&envelope.encode().unwrap()).unwrap();
+    assert_eq!(decoded.event_id, envelope.event_id);
+    assert_eq!(decoded.occurred_at, envelope.occurred_at);
+    assert_eq!(decoded.schema_version, 2);
+    assert_eq!(decoded.headers["source"], "tests");
+    assert_eq!(decoded.payload, greeted("Ann"));
+}
+
+#[test]
+fn older_payloads_are_upcast() {
+    let data = json!({
+        "event_id": uuid::Uuid::new_v4(),
+        "occurred_at": "2022-06-29T10:00:00Z",
+        "schema_version": 1,
+        "payload": { "name": "Ann" }
+    });
+    let decoded = Envelope::<Greeted>::decode(data.to_string().as_bytes()).unwrap();
+    assert_eq!(decoded.schema_version, 1);
+    assert!(decoded.headers.is_empty());
+    assert_eq!(decoded.payload.greeting, "Hello");
+}
+
+#[test]
+fn payloads_without_an_envelope_are_upcast_from_version_0() {
+    let data = json!({ "name": "Ann", "occurred_at": "2022-06-29T10:00:00Z" }).to_string();
+
+    let decoded = Envelope::<Greeted>::decode(data.as_bytes()).unwrap();
+    assert_eq!(decoded.schema_version, 0);
+    assert_eq!(
+        decoded.occurred_at.to_rfc3339(),
+        "2022-06-29T10:00:00+00:00"
+    );
+    assert_eq!(decoded.payload.greeting, "Hello");
+    // Redeliveries have the same event id
+    let redelivered = Envelope::<Greeted>::decode(data.as_bytes()).unwrap();
+    assert_eq!(redelivered.event_id, decoded.event_id);
+}
+
+#[test]
+fn tag_changes_published_with_an_occurred_at_are_upcast() {
+    let subscription_id = uuid::Uuid::new_v4();
+    let payload = json!({
+        "subscription_id": subscription_id,
+        "email": "ann@gmail.com",
+        "tag": "vip",
+        "change": "added",
+        "occurred_at": "2022-06-29T10:00:00Z"
+    });
+    let enveloped = json!({
+        "event_id": uuid::Uuid::new_v4(),
+        "occurred_at": "2022-06-29T10:00:00Z",
+        "schema_version": 1,
+        "payload": payload
+    });
+
+    for data in [payload, enveloped] {
+        let decoded =
+            Envelope::<SubscriberTagChanged>::decode(data.to_string().as_bytes()).unwrap();
+        assert_eq!(decoded.payload.subscription_id, subscription_id);
+        assert_eq!(decoded.payload.change, TagChange::Added);
+        assert_eq!(
+            decoded.occurred_at.to_rfc3339(),
+            "2022-06-29T10:00:00+00:00"
+        );
+    }
+}
+
+#[test]
+fn subscriptions_created_before_the_lists_are_upcast_to_the_default_list() {
+    let data = r#"{"email":"ursula_le_guin@gmail.com","name":"le guin","subscription_token":"8c9f5a5e-4e0e-4b6c-9a57-6f4f2d3e1b2a","subscription_id":"2f4e6b1a-3c5d-4e7f-8a9b-0c1d2e3f4a5b"}"#;
+
+    let decoded = Envelope::<SubscriptionCreated>::decode(data.as_bytes()).unwrap();
+    assert_eq!(decoded.schema_version, 0);
+    assert_eq!(decoded.payload.email.as_ref(), "ursula_le_guin@gmail.com");
+    assert_eq!(
+        decoded.payload.subscription_id.to_string(),
+        "2f4e6b1a-3c5d-4e7f-8a9b-0c1d2e3f4a5b"
+    );
+    assert_eq!(decoded.payload.lists.len(), 1);
+    assert_eq!(decoded.payload.lists[0].slug, "newsletter");
+    assert_eq!(decoded.payload.lists[0].name, "Newsletter");
+    assert_eq!(
+        decoded.payload.lists[0].subscription_token.to_string(),
+        "8c9f5a5e-4e0e-4b6c-9a57-6f4f2d3e1b2a"
+    );
+}
+
+#[test]
+fn newer_payloads_are_rejected() {
+    let data = json!({
+        "event_id": uuid::Uuid::new_v4(),
+        "occurred_at": "2022-06-29T10:00:00Z",
+        "schema_version": 3,
+        "payload": { "name": "Ann", "greeting": "Hi", "emoji": "wave" }
+    });
+    assert_err!(Envelope::<Greeted>::decode(data.to_string().as_bytes()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn published_events_reach_the_handler_through_nats() {
+    let event_bus = event_bus(EventBusBackend::Nats).await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    event_bus
+        .subscribe(ForwardingHandler(sender))
+        .await
+        .unwrap();
+
+    let event_id = event_bus.publish(greeted("Ann")).await.unwrap();
+    let envelope = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("The event was not handled")
+        .unwrap();
+    assert_eq!(envelope.event_id, event_id);
+    assert_eq!(envelope.payload, greeted("Ann"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events_published_before_the_consumer_starts_are_handled_through_jetstream() {
+    let event_bus = event_bus(EventBusBackend::JetStream).await;
+    let event_id = event_bus.publish(greeted("Ann")).await.unwrap();
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    event_bus
+        .subscribe(ForwardingHandler(sender))
+        .await
+        .unwrap();
+    let envelope = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("The event was not handled")
+        .unwrap();
+    assert_eq!(envelope.event_id, event_id);
+    // Acked, so not delivered again
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), receiver.recv())
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn failed_events_are_redelivered_through_jetstream() {
+    let event_bus = event_bus(EventBusBackend::JetStream).await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    event_bus
+        .subscribe(FlakyHandler::new(2, sender))
+        .await
+        .unwrap();
+
+    event_bus.publish(greeted("Ann")).await.unwrap();
+    let outcome = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("The event was not handled")
+        .unwrap();
+    assert_eq!(outcome, ("handled", 3));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_handler_gives_up_after_the_last_delivery() {
+    let test_cases = vec![
+        (EventBusBackend::JetStream, 3, "JetStream"),
+        // Nothing is redelivered
+        (EventBusBackend::InProcess, 1, "the in-process backend"),
+    ];
+    for (backend, attempts, description) in test_cases {
+        let event_bus = event_bus(backend).await;
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        event_bus
+            .subscribe(FlakyHandler::new(u32::MAX, sender))
+            .await
+            .unwrap();
+
+        event_bus.publish(greeted("Ann")).await.unwrap();
+        let outcome = tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+            .await
+            .expect("The handler did not give up")
+            .unwrap();
+        assert_eq!(
+            outcome,
+            ("given up", attempts),
+            "The handler did not give up after {} attempts with {}.",
+            attempts,
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn each_event_is_handled_once_per_queue_group_in_process() {
+    let event_bus = event_bus(EventBusBackend::InProcess).await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    for _ in 0..2 {
+        event_bus
+            .subscribe(ForwardingHandler(sender.clone()))
+            .await
+            .unwrap();
+    }
+    let mut listener = event_bus.listen::<Greeted>().await.unwrap();
+
+    let mut event_ids = vec![];
+    for name in ["Ann", "Bob", "Cid", "Dan"] {
+        event_ids.push(event_bus.publish(greeted(name)).await.unwrap());
+    }
+    let mut handled = vec![];
+    let mut listened = vec![];
+    for _ in 0..4 {
+        handled.push(receiver.recv().await.unwrap().event_id);
+        listened.push(listener.next().await.unwrap().unwrap().event_id);
+    }
+    assert_eq!(listened, event_ids);
+    handled.sort();
+    event_ids.sort();
+    assert_eq!(handled, event_ids);
+    // Not handled by the second member of the group too
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), receiver.recv())
+            .await
+            .is_err()
+    );
+}
+
+async fn event_bus(backend: EventBusBackend) -> EventBus {
+    let mut config = Config::new().expect("Failed to load config");
+    // Keeps the NATS subjects and the JetStream streams of concurrent tests apart
+    config.application_id = std::thread::current().name().unwrap().to_string();
+    config.jetstream_stream = config.application_id.clone();
+    config.jetstream_max_deliver = 3;
+    config.jetstream_nack_delay_millis = 10;
+    config.event_bus_backend = backend;
+    EventBus::connect(&config).await.unwrap()
+}
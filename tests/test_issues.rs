@@ -0,0 +1,461 @@
+use crate::common::TestApp;
+use chrono::{Duration, NaiveDateTime, Utc};
+use reqwest::Method;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::email_client::SendEmailRequest;
+use zero2prod::issue_scheduler::IssueScheduler;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn scheduled_issues_are_sent_once_their_time_has_come() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 1).await;
+    insert_confirmed_member(&test_app, "newsletter", "reader@gmail.com", None).await;
+
+    let issue_id = add_issue(&test_app, "newsletter").await;
+    let response = schedule_issue(&test_app, &issue_id, utc_wall_clock(-1), "list").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    wait_for_issue_status(&test_app, &issue_id, "sent").await;
+    let received_requests = test_app.get_received_requests().await.unwrap();
+    let body: SendEmailRequest = serde_json::from_slice(&received_requests[0].body).unwrap();
+    assert_eq!(body.subject, "Issue #1");
+    assert_eq!(body.from.email, "test@example.com");
+    assert_eq!(body.personalizations[0].to[0].email, "reader@gmail.com");
+    let text = &body.content[0].value;
+    assert!(text.starts_with("Hello readers"));
+    assert!(text.contains("/subscriptions/unsubscribe?unsubscribe_token="));
+    assert!(text.contains("/preferences?preferences_token="));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_list_timezone_and_sender_apply_by_default() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 1).await;
+    add_list(&test_app, "tokyo", "Asia/Tokyo").await;
+    insert_confirmed_member(&test_app, "tokyo", "tokyo@gmail.com", None).await;
+
+    // An hour from now in UTC is already past in Tokyo (UTC+9)
+    let issue_id = add_issue(&test_app, "tokyo").await;
+    schedule_issue(&test_app, &issue_id, utc_wall_clock(60), "list").await;
+
+    wait_for_issue_status(&test_app, &issue_id, "sent").await;
+    let received_requests = test_app.get_received_requests().await.unwrap();
+    let body: SendEmailRequest = serde_json::from_slice(&received_requests[0].body).unwrap();
+    assert_eq!(body.from.email, "tokyo@example.com");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn issues_can_be_cancelled_or_rescheduled_before_they_are_sent() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 1).await;
+    insert_confirmed_member(&test_app, "newsletter", "reader@gmail.com", None).await;
+
+    let cancelled_id = add_issue(&test_app, "newsletter").await;
+    schedule_issue(&test_app, &cancelled_id, utc_wall_clock(24 * 60), "list").await;
+    let response = cancel_issue(&test_app, &cancelled_id).await;
+    assert_eq!(response.status().as_u16(), 200);
+    // Cancelled issues stay cancelled
+    let response = cancel_issue(&test_app, &cancelled_id).await;
+    assert_eq!(response.status().as_u16(), 409);
+    let response = schedule_issue(&test_app, &cancelled_id, utc_wall_clock(-1), "list").await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let rescheduled_id = add_issue(&test_app, "newsletter").await;
+    schedule_issue(&test_app, &rescheduled_id, utc_wall_clock(24 * 60), "list").await;
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert!(test_app.get_received_requests().await.is_err());
+    let response = schedule_issue(&test_app, &rescheduled_id, utc_wall_clock(-1), "list").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    wait_for_issue_status(&test_app, &rescheduled_id, "sent").await;
+    // Sent issues cannot be cancelled
+    let response = cancel_issue(&test_app, &rescheduled_id).await;
+    assert_eq!(response.status().as_u16(), 409);
+    assert_eq!(issue_status(&test_app, &cancelled_id).await, "cancelled");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscriber_timezones_get_the_issue_at_their_own_wall_clock_time() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 1).await;
+    // UTC+14 and UTC-11
+    insert_confirmed_member(
+        &test_app,
+        "newsletter",
+        "early@gmail.com",
+        Some("Pacific/Kiritimati"),
+    )
+    .await;
+    insert_confirmed_member(
+        &test_app,
+        "newsletter",
+        "late@gmail.com",
+        Some("Pacific/Pago_Pago"),
+    )
+    .await;
+
+    let issue_id = add_issue(&test_app, "newsletter").await;
+    schedule_issue(&test_app, &issue_id, utc_wall_clock(0), "subscriber").await;
+
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let body: SendEmailRequest = serde_json::from_slice(&received_requests[0].body).unwrap();
+    assert_eq!(body.personalizations[0].to[0].email, "early@gmail.com");
+    // Still waiting for Pago Pago
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    assert_eq!(issue_status(&test_app, &issue_id).await, "sending");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_schedulers_send_every_issue_once() {
+    const MEMBERS: u64 = 5;
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, MEMBERS).await;
+    for i in 0..MEMBERS {
+        let email = format!("reader{}@gmail.com", i);
+        insert_confirmed_member(&test_app, "newsletter", &email, None).await;
+    }
+    let issue_id = add_issue(&test_app, "newsletter").await;
+    schedule_issue(&test_app, &issue_id, utc_wall_clock(-1), "list").await;
+
+    let email_client = std::sync::Arc::new(zero2prod::email_client::EmailClient::new(
+        &test_app.config,
+        test_app.db_pool.clone(),
+    ));
+    let handles: Vec<_> = (0..10)
+        .map(|_| {
+            let config = test_app.config.clone();
+            let email_client = email_client.clone();
+            let db_pool = test_app.db_pool.clone();
+            tokio::spawn(
+                async move { IssueScheduler::tick(&config, &email_client, &db_pool).await },
+            )
+        })
+        .collect();
+    for handle in handles {
+        handle.await.unwrap().unwrap();
+    }
+
+    wait_for_issue_status(&test_app, &issue_id, "sent").await;
+    let deliveries = sqlx::query!("SELECT subscription_id FROM issue_deliveries")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len() as u64, MEMBERS);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn issues_for_unknown_lists_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&serde_json::json!({
+            "list": "unknown",
+            "subject": "Issue #1",
+            "text_content": "Hello readers"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = cancel_issue(&test_app, &Uuid::new_v4().to_string()).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+/// The current UTC wall clock time, shifted by some minutes
+#[tokio::test(flavor = "multi_thread")]
+async fn previews_render_the_issue_with_the_data_of_a_sample_subscriber() {
+    let test_app = common::spawn_app().await;
+    insert_confirmed_member(&test_app, "newsletter", "reader@gmail.com", None).await;
+    let issue_id = add_issue(&test_app, "newsletter").await;
+    let tokens = sqlx::query!(
+        r#"
+        SELECT m.unsubscribe_token AS "unsubscribe_token!", s.preferences_token AS "preferences_token!"
+        FROM list_memberships m JOIN subscriptions s ON s.id = m.subscription_id
+        "#
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    let response = preview_issue(&test_app, &issue_id, "reader@gmail.com").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let preview: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(preview["from"], "test@example.com");
+    assert_eq!(preview["to"], "reader@gmail.com");
+    assert_eq!(preview["subject"], "Issue #1");
+    let text = preview["text_content"].as_str().unwrap();
+    assert!(text.starts_with("Hello readers"));
+    assert!(text.contains(&format!(
+        "/subscriptions/unsubscribe?unsubscribe_token={}",
+        tokens.unsubscribe_token
+    )));
+    assert!(text.contains(&format!(
+        "/preferences?preferences_token={}",
+        tokens.preferences_token
+    )));
+    let html = preview["html_content"].as_str().unwrap();
+    assert!(html.starts_with("<p>Hello readers</p>"));
+    assert!(html.contains(&format!(
+        "/subscriptions/unsubscribe?unsubscribe_token={}",
+        tokens.unsubscribe_token
+    )));
+    assert!(html.contains(&format!(
+        "/preferences?preferences_token={}",
+        tokens.preferences_token
+    )));
+
+    let not_a_member = preview_issue(&test_app, &issue_id, "stranger@gmail.com").await;
+    assert_eq!(not_a_member.status().as_u16(), 404);
+    let unknown_issue =
+        preview_issue(&test_app, &Uuid::new_v4().to_string(), "reader@gmail.com").await;
+    assert_eq!(unknown_issue.status().as_u16(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sends_only_go_to_seed_addresses_and_are_not_deliveries() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 2).await;
+    insert_confirmed_member(&test_app, "newsletter", "reader@gmail.com", None).await;
+    let issue_id = add_issue(&test_app, "newsletter").await;
+
+    let response = test_send_issue(
+        &test_app,
+        &issue_id,
+        &["seed@example.com", "reader@gmail.com"],
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(test_app.get_received_requests().await.is_err());
+
+    let response = test_send_issue(
+        &test_app,
+        &issue_id,
+        &["seed@example.com", "editor@example.com"],
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let received_requests = test_app.get_received_requests().await.unwrap();
+    let recipients: Vec<String> = received_requests
+        .iter()
+        .map(|request| {
+            let body: SendEmailRequest = serde_json::from_slice(&request.body).unwrap();
+            assert_eq!(body.subject, "Issue #1");
+            assert!(body.content[0].value.starts_with("Hello readers"));
+            assert_eq!(body.content[1].r#type, "text/html");
+            assert!(body.content[1].value.starts_with("<p>Hello readers</p>"));
+            // The same footer as the deliveries
+            assert!(body.content[0]
+                .value
+                .contains("/preferences?preferences_token="));
+            body.personalizations[0].to[0].email.to_owned()
+        })
+        .collect();
+    assert_eq!(recipients, vec!["seed@example.com", "editor@example.com"]);
+
+    let issue_deliveries = sqlx::query!("SELECT issue_id FROM issue_deliveries")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(issue_deliveries.is_empty());
+    let email_deliveries = sqlx::query!("SELECT id FROM email_deliveries")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(email_deliveries.is_empty());
+    assert_eq!(issue_status(&test_app, &issue_id).await, "draft");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn previews_require_the_admin_token() {
+    let test_app = common::spawn_app().await;
+    let issue_id = add_issue(&test_app, "newsletter").await;
+
+    let response = reqwest::Client::new()
+        .get(format!(
+            "{}/admin/issues/{}/preview?subscriber=reader@gmail.com",
+            test_app.address, issue_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+fn utc_wall_clock(minutes: i64) -> NaiveDateTime {
+    (Utc::now() + Duration::minutes(minutes)).naive_utc()
+}
+
+async fn add_list(test_app: &TestApp, slug: &str, timezone: &str) {
+    let response = test_app
+        .admin_request(Method::POST, "/admin/lists")
+        .json(&serde_json::json!({
+            "slug": slug,
+            "name": slug,
+            "from_address": format!("{}@example.com", slug),
+            "timezone": timezone
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn add_issue(test_app: &TestApp, list: &str) -> String {
+    let response = test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&serde_json::json!({
+            "list": list,
+            "subject": "Issue #1",
+            "text_content": "Hello readers"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["id"].as_str().unwrap().to_owned()
+}
+
+async fn schedule_issue(
+    test_app: &TestApp,
+    issue_id: &str,
+    send_at: NaiveDateTime,
+    timezone: &str,
+) -> reqwest::Response {
+    test_app
+        .admin_request(
+            Method::POST,
+            &format!("/admin/issues/{}/schedule", issue_id),
+        )
+        .json(&serde_json::json!({
+            "send_at": send_at.format("%Y-%m-%dT%H:%M:%S").to_string(),
+            "timezone": timezone
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn preview_issue(test_app: &TestApp, issue_id: &str, subscriber: &str) -> reqwest::Response {
+    test_app
+        .admin_request(
+            Method::GET,
+            &format!(
+                "/admin/issues/{}/preview?subscriber={}",
+                issue_id, subscriber
+            ),
+        )
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn test_send_issue(
+    test_app: &TestApp,
+    issue_id: &str,
+    recipients: &[&str],
+) -> reqwest::Response {
+    test_app
+        .admin_request(
+            Method::POST,
+            &format!("/admin/issues/{}/test-send", issue_id),
+        )
+        .json(&serde_json::json!({
+            "subscriber": "reader@gmail.com",
+            "recipients": recipients
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn cancel_issue(test_app: &TestApp, issue_id: &str) -> reqwest::Response {
+    test_app
+        .admin_request(Method::POST, &format!("/admin/issues/{}/cancel", issue_id))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn issue_status(test_app: &TestApp, issue_id: &str) -> String {
+    let issues: serde_json::Value = test_app
+        .admin_request(Method::GET, "/admin/issues")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    issues
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|issue| issue["id"] == issue_id)
+        .map(|issue| issue["status"].as_str().unwrap().to_owned())
+        .unwrap()
+}
+
+async fn wait_for_issue_status(test_app: &TestApp, issue_id: &str, expected: &str) {
+    common::eventually(
+        || async {
+            let status = issue_status(test_app, issue_id).await;
+            if status == expected {
+                Ok(())
+            } else {
+                anyhow::bail!("The issue is {}", status)
+            }
+        },
+        100,
+        50,
+    )
+    .await;
+}
+
+async fn insert_confirmed_member(
+    test_app: &TestApp,
+    list: &str,
+    email: &str,
+    timezone: Option<&str>,
+) {
+    let subscription_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, status, subscribed_at, timezone)
+        VALUES ($1, $2, 'Reader', 'confirmed', now(), $3)
+        "#,
+        subscription_id,
+        email,
+        timezone,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                      created_at, updated_at)
+        SELECT $1, id, 'confirmed', $2, now(), now() FROM lists WHERE slug = $3
+        "#,
+        subscription_id,
+        Uuid::new_v4().to_string(),
+        list,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn mock_mail_send(test_app: &TestApp, expected_requests: u64) {
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected_requests)
+        .mount(&test_app.mock_server)
+        .await;
+}
@@ -0,0 +1,32 @@
+use claim::assert_err;
+use zero2prod::domain::list_slug::ListSlug;
+
+#[test]
+fn uppercase_and_whitespace_are_rejected() {
+    assert_err!(ListSlug::parse("News".to_string()));
+    assert_err!(ListSlug::parse("news letter".to_string()));
+}
+
+#[test]
+fn too_long_slugs_are_rejected() {
+    assert_err!(ListSlug::parse("a".repeat(65)));
+}
+
+#[test]
+fn comma_separated_slugs_are_parsed_without_duplicates() {
+    let slugs = ListSlug::parse_many("releases, newsletter,releases").unwrap();
+    let slugs: Vec<&str> = slugs.iter().map(|slug| slug.as_ref().as_str()).collect();
+    assert_eq!(slugs, vec!["releases", "newsletter"]);
+}
+
+#[test]
+fn no_slugs_means_the_default_list() {
+    let slugs = ListSlug::parse_many(" ").unwrap();
+    let slugs: Vec<&str> = slugs.iter().map(|slug| slug.as_ref().as_str()).collect();
+    assert_eq!(slugs, vec!["newsletter"]);
+}
+
+#[test]
+fn an_invalid_slug_fails_the_whole_list() {
+    assert_err!(ListSlug::parse_many("newsletter,Releases!"));
+}
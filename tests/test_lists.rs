@@ -0,0 +1,233 @@
+use crate::common::TestApp;
+use reqwest::{Method, Url};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::email_client::SendEmailRequest;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admins_can_add_and_list_mailing_lists() {
+    let test_app = common::spawn_app().await;
+    add_list(&test_app, "releases", 200).await;
+    // Slugs are unique
+    add_list(&test_app, "releases", 409).await;
+    add_list(&test_app, "Not A Slug", 400).await;
+
+    let response = test_app
+        .admin_request(Method::GET, "/admin/lists")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let lists: serde_json::Value = response.json().await.unwrap();
+    let lists = lists.as_array().unwrap();
+    assert_eq!(lists.len(), 2);
+    assert_eq!(lists[0]["slug"], "newsletter");
+    assert_eq!(lists[1]["slug"], "releases");
+    assert_eq!(lists[1]["name"], "Releases");
+    assert_eq!(lists[1]["from_address"], "releases@example.com");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribing_to_an_unknown_list_is_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let response = test_app
+        .post_subscriptions("name=Lists&email=lists%40gmail.com&lists=newsletter,unknown")
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn confirmation_is_scoped_to_each_list() {
+    let test_app = common::spawn_app().await;
+    add_list(&test_app, "releases", 200).await;
+    mock_mail_send(&test_app).await;
+
+    let response = test_app
+        .post_subscriptions("name=Lists&email=lists%40gmail.com&lists=newsletter,releases")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // A single email names both lists, each with its own link
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let body: SendEmailRequest = serde_json::from_slice(&received_requests[0].body).unwrap();
+    let text = body.content.first().unwrap().value.to_string();
+    assert!(text.contains("Newsletter"));
+    assert!(text.contains("Releases"));
+    let links = extract_links(&test_app, &text);
+    assert_eq!(links.len(), 2);
+
+    let releases_link = links
+        .iter()
+        .zip(
+            text.lines()
+                .filter(|line| line.contains("subscription_token")),
+        )
+        .find(|(_, line)| line.starts_with("Releases"))
+        .map(|(link, _)| link)
+        .unwrap();
+    let response = reqwest::get(releases_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let statuses = membership_statuses(&test_app, "lists@gmail.com").await;
+    assert_eq!(
+        statuses,
+        vec![
+            ("newsletter".to_owned(), "pending".to_owned()),
+            ("releases".to_owned(), "confirmed".to_owned())
+        ]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsubscribing_only_leaves_one_list() {
+    let test_app = common::spawn_app().await;
+    add_list(&test_app, "releases", 200).await;
+    mock_mail_send(&test_app).await;
+
+    test_app
+        .post_subscriptions("name=Leaver&email=leaver%40gmail.com&lists=newsletter,releases")
+        .await;
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let body: SendEmailRequest = serde_json::from_slice(&received_requests[0].body).unwrap();
+    for link in extract_links(&test_app, &body.content.first().unwrap().value) {
+        let response = reqwest::get(&link).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let unsubscribe_token = sqlx::query!(
+        r#"
+        SELECT m.unsubscribe_token AS "unsubscribe_token!"
+        FROM list_memberships m JOIN lists l ON l.id = m.list_id
+        WHERE l.slug = 'releases'
+        "#
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap()
+    .unsubscribe_token;
+    let unsubscribe_link = format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+        test_app.address, unsubscribe_token
+    );
+    // Following the link only shows the confirmation page
+    let response = reqwest::get(&unsubscribe_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Unsubscribe from Releases"));
+    let statuses = membership_statuses(&test_app, "leaver@gmail.com").await;
+    assert_eq!(statuses[1], ("releases".to_owned(), "confirmed".to_owned()));
+
+    // The one-click unsubscribe of RFC 8058, unsubscribing again is harmless
+    for _ in 0..2 {
+        let response = reqwest::Client::new()
+            .post(&unsubscribe_link)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("List-Unsubscribe=One-Click")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let statuses = membership_statuses(&test_app, "leaver@gmail.com").await;
+    assert_eq!(
+        statuses,
+        vec![
+            ("newsletter".to_owned(), "confirmed".to_owned()),
+            ("releases".to_owned(), "unsubscribed".to_owned())
+        ]
+    );
+
+    // Signing up again needs a new confirmation
+    let response = test_app
+        .post_subscriptions("name=Leaver&email=leaver%40gmail.com&lists=releases")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let statuses = membership_statuses(&test_app, "leaver@gmail.com").await;
+    assert_eq!(statuses[1], ("releases".to_owned(), "pending".to_owned()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn unsubscribe_with_an_unknown_token_is_rejected_with_a_401() {
+    let test_app = common::spawn_app().await;
+    let unsubscribe_link = format!(
+        "{}/subscriptions/unsubscribe?unsubscribe_token={}",
+        test_app.address,
+        uuid::Uuid::new_v4()
+    );
+    let response = reqwest::get(&unsubscribe_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let response = reqwest::Client::new()
+        .post(&unsubscribe_link)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+async fn add_list(test_app: &TestApp, slug: &str, expected_status: u16) {
+    let response = test_app
+        .admin_request(Method::POST, "/admin/lists")
+        .json(&serde_json::json!({
+            "slug": slug,
+            "name": "Releases",
+            "from_address": "releases@example.com",
+            "description": "Release announcements"
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), expected_status);
+}
+
+async fn membership_statuses(test_app: &TestApp, email: &str) -> Vec<(String, String)> {
+    let response = test_app
+        .admin_request(
+            Method::GET,
+            &format!("/admin/subscriptions/{}/events", email),
+        )
+        .send()
+        .await
+        .unwrap();
+    let history: serde_json::Value = response.json().await.unwrap();
+    history["lists"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|membership| {
+            (
+                membership["list"].as_str().unwrap().to_owned(),
+                membership["status"].as_str().unwrap().to_owned(),
+            )
+        })
+        .collect()
+}
+
+fn extract_links(test_app: &TestApp, text: &str) -> Vec<String> {
+    linkify::LinkFinder::new()
+        .links(text)
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        // Skips the preference center link in the footer
+        .filter(|l| l.as_str().contains("subscription_token"))
+        .map(|link| {
+            let mut url = Url::parse(link.as_str()).unwrap();
+            url.set_port(Some(test_app.port)).unwrap();
+            url.to_string()
+        })
+        .collect()
+}
+
+async fn mock_mail_send(test_app: &TestApp) {
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.mock_server)
+        .await;
+}
@@ -0,0 +1,99 @@
+use secrecy::ExposeSecret;
+use uuid::Uuid;
+use zero2prod::migrations::{Migrations, SchemaMismatch, MIGRATOR};
+
+use crate::common::TestApp;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_replicas_apply_the_pending_migrations_once() {
+    let test_app = common::spawn_app().await;
+    let db_pool =
+        common::create_db(&connection_string(&test_app), &Uuid::new_v4().to_string()).await;
+    let pending = Migrations::pending(&db_pool).await.unwrap();
+    assert_eq!(pending.len(), MIGRATOR.iter().count());
+
+    let (first, second) = tokio::join!(Migrations::run(&db_pool), Migrations::run(&db_pool));
+
+    let mut applied = vec![first.unwrap().len(), second.unwrap().len()];
+    applied.sort_unstable();
+    assert_eq!(applied, vec![0, pending.len()]);
+    assert!(Migrations::pending(&db_pool).await.unwrap().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_schema_newer_than_the_binary_is_refused() {
+    let test_app = common::spawn_app().await;
+    insert_unknown_migration(&test_app).await;
+
+    let pending_err = Migrations::pending(&test_app.db_pool).await.unwrap_err();
+    let run_err = Migrations::run(&test_app.db_pool).await.unwrap_err();
+
+    for err in [pending_err, run_err] {
+        assert_eq!(
+            err.downcast_ref::<SchemaMismatch>(),
+            Some(&SchemaMismatch::UnknownMigration(29991231000000))
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_migration_changed_after_being_applied_is_refused() {
+    let test_app = common::spawn_app().await;
+    let first = MIGRATOR.iter().next().unwrap();
+    // The table of sqlx, not in the schema the queries are checked against
+    sqlx::query("UPDATE _sqlx_migrations SET checksum = '\\x00' WHERE version = $1")
+        .bind(first.version)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let err = Migrations::pending(&test_app.db_pool).await.unwrap_err();
+
+    assert_eq!(
+        err.downcast_ref::<SchemaMismatch>(),
+        Some(&SchemaMismatch::ChecksumMismatch {
+            version: first.version,
+            description: first.description.to_string(),
+        })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_binary_older_than_the_schema_refuses_to_start() {
+    let test_app = common::spawn_app().await;
+    insert_unknown_migration(&test_app).await;
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_zero2prod-worker"))
+        .env(
+            "DATABASE_URL",
+            format!("{}/{}", connection_string(&test_app), test_app.db_name),
+        )
+        .env("DATABASE_MIGRATE_ON_STARTUP", "true")
+        .output()
+        .expect("Failed to run zero2prod-worker");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("The database schema is newer than this binary"));
+}
+
+async fn insert_unknown_migration(test_app: &TestApp) {
+    sqlx::query(
+        r#"
+        INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES (29991231000000, 'from the future', TRUE, '\x00', 0)
+        "#,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
+
+/// The database URL of the test config, without the database name
+fn connection_string(test_app: &TestApp) -> String {
+    let database_url = test_app.config.database_url.expose_secret();
+    let last_slash_index = database_url.rfind('/').unwrap();
+    database_url[0..last_slash_index].to_owned()
+}
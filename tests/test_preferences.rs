@@ -0,0 +1,211 @@
+use crate::common::TestApp;
+use reqwest::{Method, Url};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::email_client::SendEmailRequest;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn every_subscriber_email_links_to_the_preference_center() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app).await;
+    test_app
+        .post_subscriptions("name=Footer&email=footer%40gmail.com")
+        .await;
+
+    let preferences_link = receive_preferences_link(&test_app).await;
+    let response = reqwest::get(&preferences_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/html; charset=utf-8"
+    );
+    let page = response.text().await.unwrap();
+    assert!(page.contains("footer@gmail.com"));
+    assert!(page.contains(r#"name="name" value="Footer""#));
+    assert!(page.contains(r#"value="newsletter" checked"#));
+    assert!(page.contains(r#"value="immediate" checked"#));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn saving_preferences_updates_the_subscription_and_its_lists() {
+    let test_app = common::spawn_app().await;
+    add_list(&test_app, "releases").await;
+    mock_mail_send(&test_app).await;
+    test_app
+        .post_subscriptions("name=Prefs&email=prefs%40gmail.com")
+        .await;
+    let preferences_link = receive_preferences_link(&test_app).await;
+
+    let response = reqwest::Client::new()
+        .post(&preferences_link)
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=New%20Name&delivery_cadence=daily&preferred_language=pt-BR&lists=releases&do_not_track=on")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("Your preferences have been saved."));
+    assert!(page.contains(r#"value="releases" checked"#));
+    assert!(!page.contains(r#"value="newsletter" checked"#));
+    assert!(page.contains(r#"name="do_not_track" value="on" checked"#));
+
+    let saved = sqlx::query!(
+        "SELECT name, (delivery_cadence :: TEXT), preferred_language, do_not_track \
+         FROM subscriptions"
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.name, "New Name");
+    assert_eq!(saved.delivery_cadence, Some("daily".to_owned()));
+    assert_eq!(saved.preferred_language, "pt-BR");
+    assert!(saved.do_not_track);
+
+    let history = fetch_consent_history(&test_app, "prefs@gmail.com").await;
+    let statuses: Vec<(&str, &str)> = history["lists"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|membership| {
+            (
+                membership["list"].as_str().unwrap(),
+                membership["status"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![("newsletter", "unsubscribed"), ("releases", "confirmed")]
+    );
+    let sources = history["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|event| event["source"] == "preferences_page")
+        .count();
+    // Leaving the newsletter, creating and confirming the releases membership
+    assert_eq!(sources, 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_preferences_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app).await;
+    test_app
+        .post_subscriptions("name=Invalid&email=invalid%40gmail.com")
+        .await;
+    let preferences_link = receive_preferences_link(&test_app).await;
+
+    let test_cases = vec![
+        (
+            "name=%3Cscript%3E&delivery_cadence=daily&preferred_language=en",
+            "invalid name",
+        ),
+        (
+            "name=Ok&delivery_cadence=monthly&preferred_language=en",
+            "unknown cadence",
+        ),
+        (
+            "name=Ok&delivery_cadence=daily&preferred_language=english",
+            "invalid language",
+        ),
+        (
+            "name=Ok&delivery_cadence=daily&preferred_language=en&lists=unknown",
+            "unknown list",
+        ),
+        (
+            "delivery_cadence=daily&preferred_language=en",
+            "missing name",
+        ),
+    ];
+    for (body, description) in test_cases {
+        let response = reqwest::Client::new()
+            .post(&preferences_link)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload had an {}.",
+            description
+        );
+    }
+    let saved = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "Invalid");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn preferences_with_an_unknown_token_are_rejected() {
+    let test_app = common::spawn_app().await;
+    let response = reqwest::get(format!(
+        "{}/preferences?preferences_token={}",
+        test_app.address,
+        uuid::Uuid::new_v4()
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::get(format!(
+        "{}/preferences?preferences_token=not-a-token",
+        test_app.address
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+async fn receive_preferences_link(test_app: &TestApp) -> String {
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let body: SendEmailRequest = serde_json::from_slice(&received_requests[0].body).unwrap();
+    let link = linkify::LinkFinder::new()
+        .links(body.content.first().unwrap().value.as_ref())
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        .find(|l| l.as_str().contains("preferences_token"))
+        .unwrap();
+    let mut url = Url::parse(link.as_str()).unwrap();
+    url.set_port(Some(test_app.port)).unwrap();
+    url.to_string()
+}
+
+async fn fetch_consent_history(test_app: &TestApp, email: &str) -> serde_json::Value {
+    test_app
+        .admin_request(
+            Method::GET,
+            &format!("/admin/subscriptions/{}/events", email),
+        )
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+async fn add_list(test_app: &TestApp, slug: &str) {
+    let response = test_app
+        .admin_request(Method::POST, "/admin/lists")
+        .json(&serde_json::json!({ "slug": slug, "name": slug }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn mock_mail_send(test_app: &TestApp) {
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.mock_server)
+        .await;
+}
@@ -0,0 +1,31 @@
+use claim::{assert_err, assert_ok};
+use zero2prod::domain::delivery_cadence::DeliveryCadence;
+use zero2prod::domain::preferred_language::PreferredLanguage;
+
+#[test]
+fn language_codes_with_and_without_a_region_are_accepted() {
+    assert_ok!(PreferredLanguage::parse("en".to_string()));
+    assert_ok!(PreferredLanguage::parse("pt-BR".to_string()));
+}
+
+#[test]
+fn malformed_language_codes_are_rejected() {
+    assert_err!(PreferredLanguage::parse("".to_string()));
+    assert_err!(PreferredLanguage::parse("EN".to_string()));
+    assert_err!(PreferredLanguage::parse("eng".to_string()));
+    assert_err!(PreferredLanguage::parse("pt-br".to_string()));
+    assert_err!(PreferredLanguage::parse("pt-BR-x".to_string()));
+}
+
+#[test]
+fn only_known_delivery_cadences_are_accepted() {
+    assert_eq!(
+        DeliveryCadence::parse("daily").unwrap(),
+        DeliveryCadence::Daily
+    );
+    assert_eq!(
+        DeliveryCadence::parse("weekly").unwrap(),
+        DeliveryCadence::Weekly
+    );
+    assert_err!(DeliveryCadence::parse("monthly"));
+}
@@ -0,0 +1,198 @@
+use crate::common::TestApp;
+use chrono::Utc;
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::config::Config;
+use zero2prod::domain::subscriber_email::SubscriberEmail;
+use zero2prod::domain::subscriber_name::SubscriberName;
+use zero2prod::event_bus::Envelope;
+use zero2prod::events::subscription_created::{SubscriptionCreated, SubscriptionCreatedHandler};
+use zero2prod::processed_events_pruner::ProcessedEventsPruner;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_redelivered_event_sends_a_single_email() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+
+    let envelope = Envelope::new(subscription_created());
+    for _ in 0..3 {
+        test_app
+            .event_bus
+            .publish_envelope(&envelope)
+            .await
+            .unwrap();
+    }
+
+    let duplicates = common::eventually(
+        || async {
+            let duplicates = processed_event_duplicates(&test_app, &envelope.event_id).await?;
+            anyhow::ensure!(duplicates == 2, "{} duplicates so far", duplicates);
+            Ok(duplicates)
+        },
+        50,
+        100,
+    )
+    .await;
+    assert_eq!(duplicates, 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn an_event_is_not_recorded_as_processed_when_the_email_fails() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+
+    let envelope = Envelope::new(subscription_created());
+    test_app
+        .event_bus
+        .publish_envelope(&envelope)
+        .await
+        .unwrap();
+
+    common::eventually(
+        || async {
+            let requests = test_app.mock_server.received_requests().await.unwrap();
+            anyhow::ensure!(!requests.is_empty(), "The email was not sent yet");
+            Ok(())
+        },
+        50,
+        100,
+    )
+    .await;
+    // The handler rolled back, so a replay sends the email again
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert!(processed_event_duplicates(&test_app, &envelope.event_id)
+        .await
+        .is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn an_event_claimed_by_a_crashed_delivery_is_taken_over_once_the_lease_expires() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+    let envelope = Envelope::new(subscription_created());
+    let live_claim = Envelope::new(subscription_created());
+    for (event_id, lease_expires_at) in [
+        (envelope.event_id, Utc::now() - chrono::Duration::seconds(1)),
+        (
+            live_claim.event_id,
+            Utc::now() + chrono::Duration::minutes(1),
+        ),
+    ] {
+        sqlx::query!(
+            r#"
+            INSERT INTO processed_events (consumer, event_id, processed_at, lease_expires_at)
+            VALUES ($1, $2, now(), $3)
+            "#,
+            SubscriptionCreatedHandler::CONSUMER,
+            event_id,
+            lease_expires_at,
+        )
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    // Still being handled elsewhere: not sent twice
+    test_app
+        .event_bus
+        .publish_envelope(&live_claim)
+        .await
+        .unwrap();
+    test_app
+        .event_bus
+        .publish_envelope(&envelope)
+        .await
+        .unwrap();
+
+    common::eventually(
+        || async {
+            let record = sqlx::query!(
+                "SELECT lease_expires_at FROM processed_events WHERE event_id = $1",
+                envelope.event_id
+            )
+            .fetch_one(&test_app.db_pool)
+            .await?;
+            anyhow::ensure!(record.lease_expires_at.is_none(), "Not processed yet");
+            Ok(())
+        },
+        50,
+        100,
+    )
+    .await;
+}
+
+#[test]
+fn a_prune_interval_of_zero_is_rejected() {
+    let mut config = Config::new().unwrap();
+    config.processed_events_prune_interval_millis = 0;
+    assert!(config.validate().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn processed_events_are_pruned_after_the_retention_window() {
+    let test_app = common::spawn_app().await;
+    // Both within the default retention window, which the app prunes in the background
+    let old_event_id = Uuid::new_v4();
+    let recent_event_id = Uuid::new_v4();
+    for (event_id, days_ago) in [(old_event_id, 10), (recent_event_id, 1)] {
+        sqlx::query!(
+            r#"
+            INSERT INTO processed_events (consumer, event_id, processed_at)
+            VALUES ($1, $2, $3)
+            "#,
+            SubscriptionCreatedHandler::CONSUMER,
+            event_id,
+            Utc::now() - chrono::Duration::days(days_ago),
+        )
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    let deleted =
+        ProcessedEventsPruner::prune(&test_app.db_pool, Utc::now() - chrono::Duration::days(7))
+            .await
+            .unwrap();
+    assert_eq!(deleted, 1);
+    let remaining = sqlx::query!("SELECT event_id FROM processed_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].event_id, recent_event_id);
+}
+
+fn subscription_created() -> SubscriptionCreated {
+    SubscriptionCreated {
+        email: SubscriberEmail::parse("ursula_le_guin@gmail.com".to_owned()).unwrap(),
+        name: SubscriberName::parse("le guin".to_owned()).unwrap(),
+        subscription_id: Uuid::new_v4(),
+        lists: vec![],
+    }
+}
+
+async fn processed_event_duplicates(test_app: &TestApp, event_id: &Uuid) -> anyhow::Result<i32> {
+    let record = sqlx::query!(
+        "SELECT duplicates FROM processed_events WHERE consumer = $1 AND event_id = $2",
+        SubscriptionCreatedHandler::CONSUMER,
+        event_id,
+    )
+    .fetch_one(&test_app.db_pool)
+    .await?;
+    Ok(record.duplicates)
+}
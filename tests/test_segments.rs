@@ -0,0 +1,384 @@
+use crate::common::TestApp;
+use chrono::{Duration, TimeZone, Utc};
+use claim::{assert_err, assert_ok};
+use reqwest::Method;
+use serde_json::json;
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::segment::Segment;
+use zero2prod::domain::subscription_status::SubscriptionStatus;
+use zero2prod::email_client::SendEmailRequest;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn previews_count_and_sample_the_matching_subscribers() {
+    let test_app = common::spawn_app().await;
+    insert_subscribers(&test_app).await;
+    let test_cases = vec![
+        (
+            json!({"all": [{"status": "confirmed"}, {"subscribed_within_days": 30}]}),
+            vec!["ann@gmail.com"],
+        ),
+        (
+            json!({"subscribed_before": "2022-01-01"}),
+            vec!["old@gmail.com"],
+        ),
+        (
+            json!({"email_domain": "GMAIL.com"}),
+            vec!["ann@gmail.com", "old@gmail.com"],
+        ),
+        (
+            json!({"not": {"email_domain": "gmail.com"}}),
+            vec!["pending@example.com", "percent@example.com"],
+        ),
+        (json!({"name_matches": "ann*"}), vec!["ann@gmail.com"]),
+        // `%` and `_` are not wildcards
+        (
+            json!({"name_matches": "100%*"}),
+            vec!["percent@example.com"],
+        ),
+        (json!({"member_of": "newsletter"}), vec!["old@gmail.com"]),
+        (
+            json!({"any": [{"status": "pending"}, {"member_of": "newsletter"}]}),
+            vec!["pending@example.com", "old@gmail.com"],
+        ),
+    ];
+    for (definition, expected) in test_cases {
+        let response = test_app
+            .admin_request(Method::POST, "/admin/segments/preview")
+            .json(&json!({ "definition": definition }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200, "{}", definition);
+        let preview: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(preview["count"], expected.len(), "{}", definition);
+        let sample: Vec<&str> = preview["sample"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|member| member["email"].as_str().unwrap())
+            .collect();
+        assert_eq!(sample, expected, "{}", definition);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn samples_are_limited_but_the_count_is_not() {
+    let test_app = common::spawn_app().await;
+    insert_subscribers(&test_app).await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/segments/preview")
+        .json(&json!({"definition": {"subscribed_within_days": 3650}, "sample_size": 1}))
+        .send()
+        .await
+        .unwrap();
+    let preview: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(preview["count"], 4);
+    assert_eq!(preview["sample"].as_array().unwrap().len(), 1);
+
+    let response = test_app
+        .admin_request(Method::POST, "/admin/segments/preview")
+        .json(&json!({"definition": {"status": "confirmed"}, "sample_size": 101}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn saved_segments_can_be_listed_and_previewed_by_name() {
+    let test_app = common::spawn_app().await;
+    insert_subscribers(&test_app).await;
+    let definition = json!({"all": [{"status": "confirmed"}, {"email_domain": "gmail.com"}]});
+    let response = add_segment(&test_app, "gmail-readers", &definition).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = add_segment(&test_app, "gmail-readers", &json!({"status": "pending"})).await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let segments: serde_json::Value = test_app
+        .admin_request(Method::GET, "/admin/segments")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(segments[0]["name"], "gmail-readers");
+    assert_eq!(segments[0]["definition"], definition);
+
+    let response = test_app
+        .admin_request(
+            Method::GET,
+            "/admin/segments/gmail-readers/preview?sample_size=5",
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let preview: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(preview["count"], 2);
+
+    let response = test_app
+        .admin_request(Method::GET, "/admin/segments/unknown/preview")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_segments_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let test_cases = vec![
+        ("readers", json!({"country": "PT"}), "an unknown condition"),
+        ("readers", json!({"all": []}), "an empty combination"),
+        ("readers", json!("confirmed"), "a bare value"),
+        (" ", json!({"status": "confirmed"}), "an empty name"),
+    ];
+    for (name, definition, description) in test_cases {
+        let response = add_segment(&test_app, name, &definition).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the segment had {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn issues_targeting_a_segment_only_go_to_its_members() {
+    let test_app = common::spawn_app().await;
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&test_app.mock_server)
+        .await;
+    insert_subscribers(&test_app).await;
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                      created_at, updated_at)
+        SELECT s.id, l.id, 'confirmed', gen_random_uuid() :: TEXT, now(), now()
+        FROM subscriptions s, lists l
+        WHERE s.email IN ('ann@gmail.com', 'percent@example.com') AND l.slug = 'newsletter'
+        "#,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    let response = add_segment(&test_app, "gmail", &json!({"email_domain": "gmail.com"})).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let unknown_segment = add_issue(&test_app, "unknown").await;
+    assert_eq!(unknown_segment.status().as_u16(), 400);
+    let response = add_issue(&test_app, "gmail").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    sqlx::query(
+        r#"
+        UPDATE issues
+        SET status = 'scheduled', send_at_local = now() :: TIMESTAMP, scheduled_at = now()
+        WHERE id = $1 :: UUID
+        "#,
+    )
+    .bind(body["id"].as_str().unwrap())
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+
+    let mut recipients = common::eventually(
+        || async {
+            let requests = test_app.get_received_requests().await?;
+            if requests.len() < 2 {
+                anyhow::bail!("Only {} emails were sent", requests.len());
+            }
+            Ok(requests
+                .iter()
+                .map(|request| {
+                    let body: SendEmailRequest = serde_json::from_slice(&request.body).unwrap();
+                    body.personalizations[0].to[0].email.to_owned()
+                })
+                .collect::<Vec<String>>())
+        },
+        100,
+        50,
+    )
+    .await;
+    recipients.sort();
+    assert_eq!(recipients, vec!["ann@gmail.com", "old@gmail.com"]);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn issues_targeting_a_tag_only_go_to_the_tagged_subscribers() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(1).await;
+    let tagged_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&tagged_id, "tagged@gmail.com")
+        .await;
+    test_app
+        .insert_confirmed_member(&Uuid::new_v4(), "untagged@gmail.com")
+        .await;
+    sqlx::query!(
+        r#"
+        WITH tag AS (
+            INSERT INTO tags (id, name, created_at) VALUES (gen_random_uuid(), 'vip', now())
+            RETURNING id
+        )
+        INSERT INTO subscriber_tags (subscription_id, tag_id, created_at)
+        SELECT $1, id, now() FROM tag
+        "#,
+        tagged_id,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    let response = add_segment(&test_app, "vip", &json!({"tagged": "vip"})).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = add_issue(&test_app, "vip").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    let issue_id = Uuid::parse_str(body["id"].as_str().unwrap()).unwrap();
+    test_app.schedule_issue_now(&issue_id).await;
+
+    let emails = test_app.wait_for_emails(1).await;
+    // Give the scheduler a chance to send too many
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert_eq!(test_app.get_received_requests().await.unwrap().len(), 1);
+    assert_eq!(emails[0].to, "tagged@gmail.com");
+}
+
+#[test]
+fn segments_are_parsed_from_json() {
+    let segment = Segment::parse(json!({
+        "all": [
+            {"status": "confirmed"},
+            {"not": {"email_domain": "example.com"}},
+            {"any": [{"subscribed_within_days": 30}, {"subscribed_before": "2022-01-01"}]}
+        ]
+    }))
+    .unwrap();
+    match segment {
+        Segment::All(segments) => {
+            assert_eq!(segments[0], Segment::Status(SubscriptionStatus::Confirmed));
+            assert_eq!(segments.len(), 3);
+        }
+        _ => panic!("Expected an `all` segment"),
+    }
+}
+
+#[test]
+fn unknown_conditions_and_malformed_values_are_rejected() {
+    assert_err!(Segment::parse(json!({"country": "PT"})));
+    assert_err!(Segment::parse(json!({"status": "banned"})));
+    assert_err!(Segment::parse(json!({"subscribed_before": "yesterday"})));
+    assert_err!(Segment::parse(json!({"subscribed_within_days": -1})));
+    assert_err!(Segment::parse(json!({"member_of": "Not A Slug"})));
+    assert_err!(Segment::parse(json!({"tagged": "Not A Tag"})));
+    assert_err!(Segment::parse(json!({"email_domain": "me@example.com"})));
+    assert_err!(Segment::parse(json!({"name_matches": " "})));
+}
+
+#[test]
+fn combinations_need_at_least_one_condition() {
+    assert_err!(Segment::parse(json!({"all": []})));
+    assert_err!(Segment::parse(json!({"any": []})));
+    assert_ok!(Segment::parse(json!({"any": [{"status": "pending"}]})));
+}
+
+#[test]
+fn segments_cannot_nest_without_bounds() {
+    let mut definition = json!({"status": "confirmed"});
+    for _ in 0..7 {
+        definition = json!({ "not": definition });
+    }
+    assert_ok!(Segment::parse(definition.clone()));
+    assert_err!(Segment::parse(json!({ "not": definition })));
+}
+
+async fn add_segment(
+    test_app: &TestApp,
+    name: &str,
+    definition: &serde_json::Value,
+) -> reqwest::Response {
+    test_app
+        .admin_request(Method::POST, "/admin/segments")
+        .json(&json!({ "name": name, "definition": definition }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn add_issue(test_app: &TestApp, segment: &str) -> reqwest::Response {
+    test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&json!({
+            "list": "newsletter",
+            "subject": "Gmail news",
+            "text_content": "Hello readers",
+            "segment": segment
+        }))
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Newest first: pending, ann, percent, old
+async fn insert_subscribers(test_app: &TestApp) {
+    let now = Utc::now();
+    let subscribers = [
+        ("pending@example.com", "Pending", "pending", now),
+        (
+            "ann@gmail.com",
+            "Ann Lee",
+            "confirmed",
+            now - Duration::days(1),
+        ),
+        (
+            "percent@example.com",
+            "100% Reader",
+            "confirmed",
+            now - Duration::days(60),
+        ),
+        (
+            "old@gmail.com",
+            "1000 Readers",
+            "confirmed",
+            Utc.ymd(2021, 6, 1).and_hms(12, 0, 0),
+        ),
+    ];
+    for (email, name, status, subscribed_at) in subscribers {
+        sqlx::query(
+            r#"
+            INSERT INTO subscriptions (id, email, name, status, subscribed_at)
+            VALUES ($1, $2, $3, $4 :: subscription_status, $5)
+            "#,
+        )
+        .bind(Uuid::new_v4())
+        .bind(email)
+        .bind(name)
+        .bind(status)
+        .bind(subscribed_at)
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscription_id, list_id, status, unsubscribe_token,
+                                      created_at, updated_at)
+        SELECT s.id, l.id, 'confirmed', gen_random_uuid() :: TEXT, now(), now()
+        FROM subscriptions s, lists l
+        WHERE s.email = 'old@gmail.com' AND l.slug = 'newsletter'
+        "#,
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
@@ -0,0 +1,184 @@
+use std::time::{Duration, Instant};
+
+use crate::common::TestApp;
+use secrecy::ExposeSecret;
+use sqlx::{Connection, PgConnection};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_waits_for_the_email_in_flight_then_closes_the_connections() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_millis(500)))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    wait_for_the_email_send(&test_app).await;
+
+    let TestApp {
+        address,
+        db_pool,
+        db_name,
+        event_bus,
+        config,
+        shutdown,
+        mock_server: _mock_server,
+        ..
+    } = test_app;
+    shutdown
+        .shutdown(&event_bus, &db_pool, Duration::from_secs(10))
+        .await;
+
+    assert!(db_pool.is_closed());
+    assert!(reqwest::get(format!("{}/health_check", address))
+        .await
+        .is_err());
+    assert_eq!(
+        count_processed_events(&config.database_url, &db_name).await,
+        1,
+        "The SubscriptionCreated handler did not complete"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_aborts_the_tasks_still_running_at_the_deadline() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(30)))
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    wait_for_the_email_send(&test_app).await;
+
+    let TestApp {
+        db_pool,
+        db_name,
+        event_bus,
+        config,
+        shutdown,
+        ..
+    } = test_app;
+    let started = Instant::now();
+    shutdown
+        .shutdown(&event_bus, &db_pool, Duration::from_millis(300))
+        .await;
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    // Still claimed, to be handled again by a redelivery once the lease expires
+    assert_eq!(
+        count_processed_events(&config.database_url, &db_name).await,
+        0
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shutdown_lets_the_requests_in_flight_complete_and_handles_their_events() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+    // Holds the request in its handler until the shutdown started
+    let mut lock_connection = connect(&test_app.config.database_url, &test_app.db_name).await;
+    let mut lock = lock_connection.begin().await.unwrap();
+    sqlx::query!("LOCK TABLE subscriptions IN ACCESS EXCLUSIVE MODE")
+        .execute(&mut lock)
+        .await
+        .unwrap();
+    let address = test_app.address.clone();
+    let request = tokio::spawn(async move {
+        reqwest::Client::new()
+            .post(format!("{}/subscriptions", address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("name=le%20guin&email=ursula_le_guin%40gmail.com")
+            .send()
+            .await
+    });
+    common::eventually(
+        || async {
+            let record = sqlx::query!(
+                r#"SELECT COUNT(*) AS "count!" FROM pg_stat_activity WHERE datname = current_database() AND wait_event_type = 'Lock'"#
+            )
+            .fetch_one(&test_app.db_pool)
+            .await?;
+            anyhow::ensure!(record.count > 0, "The request is not waiting on the lock yet");
+            Ok(())
+        },
+        50,
+        100,
+    )
+    .await;
+
+    let TestApp {
+        db_pool,
+        db_name,
+        event_bus,
+        config,
+        shutdown,
+        mock_server: _mock_server,
+        ..
+    } = test_app;
+    let shutdown = tokio::spawn(async move {
+        shutdown
+            .shutdown(&event_bus, &db_pool, Duration::from_secs(10))
+            .await;
+    });
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    lock.commit().await.unwrap();
+    shutdown.await.unwrap();
+
+    let response = request.await.unwrap().unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        count_processed_events(&config.database_url, &db_name).await,
+        1,
+        "The event of the request in flight was not handled"
+    );
+}
+
+async fn wait_for_the_email_send(test_app: &TestApp) {
+    common::eventually(
+        || async {
+            let requests = test_app.mock_server.received_requests().await.unwrap();
+            anyhow::ensure!(!requests.is_empty(), "The email send did not start yet");
+            Ok(())
+        },
+        50,
+        100,
+    )
+    .await;
+}
+
+/// The pool of the app is closed by then
+/// Only the events handled to the end, not the ones still claimed
+async fn count_processed_events(database_url: &secrecy::Secret<String>, db_name: &str) -> i64 {
+    let mut connection = connect(database_url, db_name).await;
+    let record = sqlx::query!(
+        r#"SELECT COUNT(*) AS "count!" FROM processed_events WHERE lease_expires_at IS NULL"#
+    )
+    .fetch_one(&mut connection)
+    .await
+    .unwrap();
+    record.count
+}
+
+async fn connect(database_url: &secrecy::Secret<String>, db_name: &str) -> PgConnection {
+    let database_url = database_url.expose_secret();
+    let last_slash_index = database_url.rfind('/').unwrap();
+    PgConnection::connect(&format!(
+        "{}/{}",
+        &database_url[0..last_slash_index],
+        db_name
+    ))
+    .await
+    .expect("Failed to connect to Postgres")
+}
@@ -0,0 +1,270 @@
+use crate::common::{find_email, TestApp};
+use claim::{assert_err, assert_ok};
+use reqwest::Method;
+use uuid::Uuid;
+use zero2prod::domain::ab_test::{assign_subject, AbTest, SubjectAssignment};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn each_variant_goes_to_its_slice_and_the_winner_to_everyone_else() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(4).await;
+    let issue_id = add_ab_tested_issue(&test_app, &["Subject A", "Subject B"], 50).await;
+    // One subscriber per variant, two waiting for the winner
+    let mut slices: Vec<Vec<Uuid>> = vec![vec![], vec![], vec![]];
+    while slices[0].is_empty() || slices[1].is_empty() || slices[2].len() < 2 {
+        let subscription_id = Uuid::new_v4();
+        let slice = match assign_subject(&issue_id, &subscription_id, 50, 2) {
+            SubjectAssignment::Variant(position) => position,
+            SubjectAssignment::Holdout => 2,
+        };
+        if slices[slice].len() < if slice == 2 { 2 } else { 1 } {
+            slices[slice].push(subscription_id);
+        }
+    }
+    test_app
+        .insert_confirmed_member(&slices[0][0], "a@gmail.com")
+        .await;
+    test_app
+        .insert_confirmed_member(&slices[1][0], "b@gmail.com")
+        .await;
+    test_app
+        .insert_confirmed_member(&slices[2][0], "later1@gmail.com")
+        .await;
+    test_app
+        .insert_confirmed_member(&slices[2][1], "later2@gmail.com")
+        .await;
+    test_app.schedule_issue_now(&issue_id).await;
+
+    let requests = test_app.wait_for_emails(2).await;
+    let a = find_email(&requests, "a@gmail.com");
+    let b = find_email(&requests, "b@gmail.com");
+    assert_eq!(a.subject, "Subject A");
+    assert_eq!(b.subject, "Subject B");
+    // The links of tracked issues go through the redirect, the HTML part has the pixel
+    let text = &b.text_content;
+    assert!(!text.contains("https://example.com/article"));
+    let click_url = test_app.find_url(text, "/t/c/");
+    let html = b.html_content.as_deref().unwrap();
+    assert!(html.contains(r#">https://example.com/article</a>"#));
+    let open_url = test_app.find_url(html, "/t/o/");
+
+    // B is opened and clicked
+    let open = reqwest::get(&open_url).await.unwrap();
+    assert_eq!(open.status().as_u16(), 200);
+    assert_eq!(open.headers()["Content-Type"], "image/gif");
+    let click = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(&click_url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(click.status().as_u16(), 302);
+    assert_eq!(click.headers()["Location"], "https://example.com/article");
+
+    // The wait is over
+    sqlx::query!("UPDATE issues SET published_at = published_at - INTERVAL '61 minutes'")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+    let requests = test_app.wait_for_emails(4).await;
+    assert_eq!(
+        find_email(&requests, "later1@gmail.com").subject,
+        "Subject B"
+    );
+    assert_eq!(
+        find_email(&requests, "later2@gmail.com").subject,
+        "Subject B"
+    );
+
+    let stats: serde_json::Value = test_app
+        .admin_request(Method::GET, &format!("/admin/issues/{}/variants", issue_id))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["winning_variant_id"], stats["variants"][1]["id"]);
+    assert_eq!(stats["variants"][0]["sent"], 1);
+    assert_eq!(stats["variants"][0]["opens"], 0);
+    assert_eq!(stats["variants"][1]["sent"], 3);
+    assert_eq!(stats["variants"][1]["opens"], 1);
+    assert_eq!(stats["variants"][1]["clicks"], 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tampered_tracking_links_are_rejected_with_a_404() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(1).await;
+    let issue_id = add_ab_tested_issue(&test_app, &["Subject A", "Subject B"], 100).await;
+    test_app
+        .insert_confirmed_member(&Uuid::new_v4(), "reader@gmail.com")
+        .await;
+    test_app.schedule_issue_now(&issue_id).await;
+
+    let requests = test_app.wait_for_emails(1).await;
+    let click_url = test_app.find_url(&requests[0].text_content, "/t/c/");
+    let tampered = [
+        click_url.replacen("/t/c/c.", "/t/c/o.", 1),
+        format!("{}x", click_url),
+        format!(
+            "{}/t/c/c.{}.{}.0",
+            test_app.address,
+            issue_id,
+            Uuid::new_v4()
+        ),
+    ];
+    for url in tampered {
+        let response = reqwest::get(&url).await.unwrap();
+        assert_eq!(response.status().as_u16(), 404, "{}", url);
+    }
+    let clicks = sqlx::query!("SELECT id FROM issue_tracking_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(clicks.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_ab_tests_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({"subjects": ["Only one"], "audience_percent": 20, "wait_minutes": 60}),
+            "a single subject",
+        ),
+        (
+            serde_json::json!({"subjects": ["A", ""], "audience_percent": 20, "wait_minutes": 60}),
+            "an empty subject",
+        ),
+        (
+            serde_json::json!({"subjects": ["A", "B"], "audience_percent": 0, "wait_minutes": 60}),
+            "an empty audience",
+        ),
+        (
+            serde_json::json!({"subjects": ["A", "B"], "audience_percent": 101, "wait_minutes": 60}),
+            "more than the audience",
+        ),
+    ];
+    test_app.enable_tracking().await;
+    for (ab_test, description) in test_cases {
+        let response = test_app
+            .admin_request(Method::POST, "/admin/issues")
+            .json(&serde_json::json!({
+                "list": "newsletter",
+                "subject": "Subject A",
+                "text_content": "Hello readers",
+                "ab_test": ab_test
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the A/B test had {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn ab_tests_on_lists_without_tracking_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&serde_json::json!({
+            "list": "newsletter",
+            "subject": "Subject A",
+            "text_content": "Hello readers",
+            "ab_test": {"subjects": ["Subject A", "Subject B"], "audience_percent": 20, "wait_minutes": 60}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[test]
+fn ab_tests_need_at_least_two_non_empty_subjects() {
+    assert_ok!(AbTest::parse(subjects(&["A", "B"]), 20, 60));
+    assert_err!(AbTest::parse(subjects(&["A"]), 20, 60));
+    assert_err!(AbTest::parse(subjects(&["A", " "]), 20, 60));
+    assert_err!(AbTest::parse(vec!["A".to_owned(); 11], 20, 60));
+}
+
+#[test]
+fn the_audience_percent_is_between_1_and_100() {
+    assert_err!(AbTest::parse(subjects(&["A", "B"]), 0, 60));
+    assert_ok!(AbTest::parse(subjects(&["A", "B"]), 100, 60));
+    assert_err!(AbTest::parse(subjects(&["A", "B"]), 101, 60));
+}
+
+#[test]
+fn a_subscriber_always_gets_the_same_variant_of_an_issue() {
+    let issue_id = Uuid::new_v4();
+    for _ in 0..100 {
+        let subscription_id = Uuid::new_v4();
+        assert_eq!(
+            assign_subject(&issue_id, &subscription_id, 50, 3),
+            assign_subject(&issue_id, &subscription_id, 50, 3)
+        );
+    }
+}
+
+#[test]
+fn the_audience_is_split_evenly_between_the_variants() {
+    let issue_id = Uuid::new_v4();
+    let mut counts = [0; 3];
+    for _ in 0..3000 {
+        match assign_subject(&issue_id, &Uuid::new_v4(), 60, 2) {
+            SubjectAssignment::Variant(position) => counts[position] += 1,
+            SubjectAssignment::Holdout => counts[2] += 1,
+        }
+    }
+    // 900 expected per variant, 1200 in the holdout
+    assert!((750..1050).contains(&counts[0]), "{:?}", counts);
+    assert!((750..1050).contains(&counts[1]), "{:?}", counts);
+    assert!((1050..1350).contains(&counts[2]), "{:?}", counts);
+}
+
+#[test]
+fn nobody_waits_for_the_winner_when_the_whole_audience_is_tested() {
+    let issue_id = Uuid::new_v4();
+    for _ in 0..1000 {
+        assert_ne!(
+            assign_subject(&issue_id, &Uuid::new_v4(), 100, 2),
+            SubjectAssignment::Holdout
+        );
+    }
+}
+
+async fn add_ab_tested_issue(test_app: &TestApp, subjects: &[&str], audience_percent: u8) -> Uuid {
+    test_app.enable_tracking().await;
+    let response = test_app
+        .admin_request(Method::POST, "/admin/issues")
+        .json(&serde_json::json!({
+            "list": "newsletter",
+            "subject": subjects[0],
+            "text_content": "Hello readers\n\nRead more at https://example.com/article",
+            "ab_test": {
+                "subjects": subjects,
+                "audience_percent": audience_percent,
+                "wait_minutes": 60
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    Uuid::parse_str(body["id"].as_str().unwrap()).unwrap()
+}
+
+fn subjects(subjects: &[&str]) -> Vec<String> {
+    subjects.iter().map(|subject| subject.to_string()).collect()
+}
@@ -0,0 +1,142 @@
+use claim::{assert_err, assert_ok};
+use serde_json::{json, Map, Value};
+use zero2prod::domain::attribute_kind::AttributeKind;
+use zero2prod::domain::subscriber_attributes::{AttributeDefinition, AttributeSchema};
+use zero2prod::personalisation::personalise;
+
+fn schema() -> AttributeSchema {
+    AttributeSchema::new(vec![
+        AttributeDefinition::parse(
+            "country".to_owned(),
+            AttributeKind::Enum,
+            true,
+            vec!["PT".to_owned(), "DE".to_owned()],
+        )
+        .unwrap(),
+        AttributeDefinition::parse("company".to_owned(), AttributeKind::String, false, vec![])
+            .unwrap(),
+        AttributeDefinition::parse("seats".to_owned(), AttributeKind::Number, false, vec![])
+            .unwrap(),
+        AttributeDefinition::parse("beta".to_owned(), AttributeKind::Bool, false, vec![]).unwrap(),
+        AttributeDefinition::parse("renewal".to_owned(), AttributeKind::Date, false, vec![])
+            .unwrap(),
+    ])
+}
+
+fn input(value: Value) -> Map<String, Value> {
+    value.as_object().unwrap().clone()
+}
+
+#[test]
+fn attribute_names_are_lowercase_identifiers() {
+    assert_ok!(AttributeDefinition::parse(
+        "signup_source2".to_owned(),
+        AttributeKind::String,
+        false,
+        vec![]
+    ));
+    for name in ["", "Country", "2fa", "sign-up", "a b"] {
+        assert_err!(AttributeDefinition::parse(
+            name.to_owned(),
+            AttributeKind::String,
+            false,
+            vec![]
+        ));
+    }
+}
+
+#[test]
+fn only_enums_have_values() {
+    assert_err!(AttributeDefinition::parse(
+        "plan".to_owned(),
+        AttributeKind::Enum,
+        false,
+        vec![]
+    ));
+    assert_err!(AttributeDefinition::parse(
+        "plan".to_owned(),
+        AttributeKind::Enum,
+        false,
+        vec!["free".to_owned(), "free".to_owned()]
+    ));
+    assert_err!(AttributeDefinition::parse(
+        "plan".to_owned(),
+        AttributeKind::String,
+        false,
+        vec!["free".to_owned()]
+    ));
+}
+
+#[test]
+fn values_are_validated_and_normalised_by_type() {
+    let attributes = schema()
+        .validate(input(json!({
+            "country": "PT",
+            "company": "ACME",
+            "seats": "12",
+            "beta": "on",
+            "renewal": "2022-12-01"
+        })))
+        .unwrap();
+    assert_eq!(
+        Value::Object(attributes.as_ref().clone()),
+        json!({
+            "country": "PT",
+            "company": "ACME",
+            "seats": 12.0,
+            "beta": true,
+            "renewal": "2022-12-01"
+        })
+    );
+    let attributes = schema()
+        .validate(input(
+            json!({"country": "DE", "seats": 3, "beta": false, "company": ""}),
+        ))
+        .unwrap();
+    assert_eq!(
+        Value::Object(attributes.as_ref().clone()),
+        json!({"country": "DE", "seats": 3, "beta": false})
+    );
+}
+
+#[test]
+fn invalid_missing_or_unknown_attributes_are_rejected() {
+    let test_cases = vec![
+        json!({}),
+        json!({"country": ""}),
+        json!({"country": "FR"}),
+        json!({"country": "PT", "seats": "many"}),
+        json!({"country": "PT", "beta": "yes"}),
+        json!({"country": "PT", "renewal": "01/12/2022"}),
+        json!({"country": "PT", "company": 42}),
+        json!({"country": "PT", "shoe_size": "42"}),
+    ];
+    for test_case in test_cases {
+        assert_err!(schema().validate(input(test_case.clone())), "{}", test_case);
+    }
+}
+
+#[test]
+fn placeholders_are_replaced_with_the_subscriber_data() {
+    let attributes = input(json!({"company": "ACME", "seats": 12}));
+    assert_eq!(
+        personalise(
+            "Hi {{ name }} from {{attributes.company}}, {{ attributes.seats }} seats",
+            "Ann",
+            &attributes
+        ),
+        "Hi Ann from ACME, 12 seats"
+    );
+    assert_eq!(
+        personalise(
+            "Hello {{ attributes.country | reader }}{{ attributes.country }}!",
+            "Ann",
+            &attributes
+        ),
+        "Hello reader!"
+    );
+    assert_eq!(
+        personalise("{{ unknown }} and {{ unclosed", "Ann", &attributes),
+        "{{ unknown }} and {{ unclosed"
+    );
+}
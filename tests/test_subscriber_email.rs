@@ -18,3 +18,10 @@ fn email_missing_subject_is_rejected() {
     let email = "@domain.com".to_string();
     assert_err!(SubscriberEmail::parse(email));
 }
+
+#[test]
+fn canonical_hash_ignores_the_case() {
+    let lowercase = SubscriberEmail::parse("ursula@domain.com".to_string()).unwrap();
+    let mixed_case = SubscriberEmail::parse("Ursula@Domain.com".to_string()).unwrap();
+    assert_eq!(lowercase.canonical_hash(), mixed_case.canonical_hash());
+}
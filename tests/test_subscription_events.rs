@@ -0,0 +1,160 @@
+use crate::common::TestApp;
+use reqwest::{Method, Url};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::email_client::SendEmailRequest;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consent_history_records_subscription_and_confirmation() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 200).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/subscriptions", &test_app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("User-Agent", "consent-test/1.0")
+        .header("Origin", "https://example.com")
+        .body("name=Consent&email=consent%40gmail.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let received_requests =
+        common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
+    let confirmation_link = extract_confirmation_link(&test_app, &received_requests[0].body);
+    let response = reqwest::get(&confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let history = fetch_consent_history(&test_app, "consent@gmail.com").await;
+    assert_eq!(history["status"], "confirmed");
+    let events = history["events"].as_array().unwrap();
+    assert_eq!(events.len(), 4);
+    let token = Url::parse(&confirmation_link)
+        .unwrap()
+        .query_pairs()
+        .find(|(key, _)| key == "subscription_token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap();
+
+    // The newsletter membership and the subscription itself are created together
+    let (list_events, subscription_events): (Vec<_>, Vec<_>) =
+        events.iter().partition(|event| !event["list"].is_null());
+    for created in [&list_events[0], &subscription_events[0]] {
+        assert_eq!(created["old_status"], serde_json::Value::Null);
+        assert_eq!(created["new_status"], "pending");
+        assert_eq!(created["source"], "subscribe_form");
+        assert_eq!(created["user_agent"], "consent-test/1.0");
+        assert_eq!(created["origin"], "https://example.com");
+        assert!(created["ip_address"]
+            .as_str()
+            .unwrap()
+            .starts_with("127.0.0.1"));
+        assert_eq!(created["token"], token.as_str());
+    }
+    assert_eq!(list_events[0]["list"], "newsletter");
+    for confirmed in [&list_events[1], &subscription_events[1]] {
+        assert_eq!(confirmed["old_status"], "pending");
+        assert_eq!(confirmed["new_status"], "confirmed");
+        assert_eq!(confirmed["source"], "confirmation_link");
+        assert_eq!(confirmed["token"], token.as_str());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consent_history_records_a_failed_confirmation_email() {
+    let test_app = common::spawn_app().await;
+    mock_mail_send(&test_app, 500).await;
+
+    test_app
+        .post_subscriptions("name=Will%20Fail&email=will_fail%40gmail.com")
+        .await;
+
+    let events = common::eventually(
+        || async {
+            let history = fetch_consent_history(&test_app, "will_fail@gmail.com").await;
+            let events = history["events"].as_array().unwrap().clone();
+            if events.len() == 3 {
+                Ok(events)
+            } else {
+                anyhow::bail!("The failure is not recorded yet")
+            }
+        },
+        100,
+        50,
+    )
+    .await;
+    assert_eq!(events[2]["list"], serde_json::Value::Null);
+    assert_eq!(events[2]["old_status"], "pending");
+    assert_eq!(events[2]["new_status"], "failed");
+    assert_eq!(events[2]["source"], "confirmation_email_failure");
+    assert_eq!(events[2]["ip_address"], serde_json::Value::Null);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn consent_history_returns_404_for_unknown_subscribers() {
+    let test_app = common::spawn_app().await;
+
+    let response = test_app
+        .admin_request(Method::GET, "/admin/subscriptions/unknown@gmail.com/events")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = reqwest::get(format!(
+        "{}/admin/subscriptions/unknown@gmail.com/events",
+        test_app.address
+    ))
+    .await
+    .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscription_events_cannot_be_updated() {
+    let test_app = common::spawn_app().await;
+    test_app
+        .post_subscriptions("name=Append%20Only&email=append_only%40gmail.com")
+        .await;
+
+    let result = sqlx::query("UPDATE subscription_events SET source = 'rewritten'")
+        .execute(&test_app.db_pool)
+        .await;
+    assert!(result.is_err());
+}
+
+async fn fetch_consent_history(test_app: &TestApp, email: &str) -> serde_json::Value {
+    let response = test_app
+        .admin_request(
+            Method::GET,
+            &format!("/admin/subscriptions/{}/events", email),
+        )
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+fn extract_confirmation_link(test_app: &TestApp, body: &[u8]) -> String {
+    let body: SendEmailRequest = serde_json::from_slice(body).unwrap();
+    let link = linkify::LinkFinder::new()
+        .links(body.content.first().unwrap().value.as_ref())
+        .find(|l| *l.kind() == linkify::LinkKind::Url)
+        .unwrap();
+    let mut confirmation_url = Url::parse(link.as_str()).unwrap();
+    confirmation_url.set_port(Some(test_app.port)).unwrap();
+    confirmation_url.to_string()
+}
+
+async fn mock_mail_send(test_app: &TestApp, status: u16) {
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(status))
+        .expect(1)
+        .mount(&test_app.mock_server)
+        .await;
+}
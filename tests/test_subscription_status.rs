@@ -0,0 +1,56 @@
+use claim::{assert_err, assert_ok};
+use zero2prod::domain::subscription_status::{StatusOwner, SubscriptionStatus};
+
+#[test]
+fn pending_subscriptions_can_be_confirmed_or_failed() {
+    assert_ok!(SubscriptionStatus::Pending
+        .transition_to(SubscriptionStatus::Confirmed, StatusOwner::Subscription));
+    assert_ok!(SubscriptionStatus::Pending
+        .transition_to(SubscriptionStatus::Failed, StatusOwner::Subscription));
+}
+
+#[test]
+fn failed_subscriptions_can_be_retried_or_confirmed() {
+    assert_ok!(SubscriptionStatus::Failed
+        .transition_to(SubscriptionStatus::Pending, StatusOwner::Subscription));
+    assert_ok!(SubscriptionStatus::Failed
+        .transition_to(SubscriptionStatus::Confirmed, StatusOwner::Subscription));
+}
+
+#[test]
+fn confirmed_memberships_can_only_be_unsubscribed() {
+    assert_ok!(SubscriptionStatus::Confirmed.transition_to(
+        SubscriptionStatus::Unsubscribed,
+        StatusOwner::ListMembership
+    ));
+    assert_err!(SubscriptionStatus::Confirmed
+        .transition_to(SubscriptionStatus::Failed, StatusOwner::ListMembership));
+    assert_err!(SubscriptionStatus::Confirmed
+        .transition_to(SubscriptionStatus::Pending, StatusOwner::ListMembership));
+}
+
+#[test]
+fn unsubscribed_memberships_can_only_sign_up_again() {
+    assert_ok!(SubscriptionStatus::Unsubscribed
+        .transition_to(SubscriptionStatus::Pending, StatusOwner::ListMembership));
+    assert_err!(SubscriptionStatus::Unsubscribed
+        .transition_to(SubscriptionStatus::Confirmed, StatusOwner::ListMembership));
+}
+
+#[test]
+fn only_subscriptions_fail_and_only_memberships_are_unsubscribed() {
+    assert_err!(SubscriptionStatus::Pending
+        .transition_to(SubscriptionStatus::Failed, StatusOwner::ListMembership));
+    assert_err!(SubscriptionStatus::Pending
+        .transition_to(SubscriptionStatus::Unsubscribed, StatusOwner::Subscription));
+    assert_err!(SubscriptionStatus::Confirmed
+        .transition_to(SubscriptionStatus::Unsubscribed, StatusOwner::Subscription));
+}
+
+#[test]
+fn transitions_to_the_same_status_are_rejected() {
+    assert_err!(SubscriptionStatus::Pending
+        .transition_to(SubscriptionStatus::Pending, StatusOwner::Subscription));
+    assert_err!(SubscriptionStatus::Failed
+        .transition_to(SubscriptionStatus::Failed, StatusOwner::Subscription));
+}
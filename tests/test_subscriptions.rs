@@ -97,6 +97,6 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
         .execute(&app.db_pool)
         .await
         .unwrap();
-    let response = app.post_subscriptions(body.into()).await;
+    let response = app.post_subscriptions(body).await;
     assert_eq!(response.status().as_u16(), 500);
 }
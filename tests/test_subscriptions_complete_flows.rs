@@ -4,6 +4,7 @@ use wiremock::matchers::{method, path};
 use wiremock::{Mock, ResponseTemplate};
 use zero2prod::db::subscription_queries::SubscriptionQueries;
 use zero2prod::domain::new_subscriber::NewSubscriber;
+use zero2prod::domain::subscriber_attributes::SubscriberAttributes;
 use zero2prod::domain::subscriber_email::SubscriberEmail;
 use zero2prod::domain::subscriber_name::SubscriberName;
 use zero2prod::domain::subscription_status::SubscriptionStatus;
@@ -107,12 +108,81 @@ async fn sets_subscription_as_failed_when_email_sending_fails() {
     common::eventually(|| async { test_app.get_received_requests().await }, 100, 50).await;
 }
 
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_subscriptions_for_the_same_email_create_a_single_subscription() {
+    const REQUESTS: usize = 20;
+    let test_app = common::spawn_app().await;
+    Mock::given(path("/mail/send"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(REQUESTS as u64)
+        .mount(&test_app.mock_server)
+        .await;
+
+    let handles: Vec<_> = (0..REQUESTS)
+        .map(|_| {
+            let url = format!("{}/subscriptions", &test_app.address);
+            tokio::spawn(async move {
+                reqwest::Client::new()
+                    .post(url)
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body("name=Hammered&email=hammered%40gmail.com")
+                    .send()
+                    .await
+                    .expect("Failed to execute request")
+            })
+        })
+        .collect();
+    for handle in handles {
+        let response = handle.await.unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let subscriptions = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.len(), 1);
+    // Every confirmation email carries a token of the one subscription
+    let tokens = sqlx::query!("SELECT subscriber_id FROM subscription_tokens")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens.len(), REQUESTS);
+    assert!(tokens
+        .iter()
+        .all(|token| token.subscriber_id == subscriptions[0].id));
+    let creations = sqlx::query!(
+        "SELECT id FROM subscription_events WHERE old_status IS NULL AND list_id IS NULL"
+    )
+    .fetch_all(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(creations.len(), 1);
+
+    common::eventually(
+        || async {
+            let requests = test_app.get_received_requests().await?;
+            if requests.len() == REQUESTS {
+                Ok(())
+            } else {
+                anyhow::bail!("Not every confirmation email is sent yet")
+            }
+        },
+        100,
+        50,
+    )
+    .await;
+}
+
 fn extract_confirmation_link(test_app: &TestApp, body: &[u8]) -> String {
     let body: SendEmailRequest = serde_json::from_slice(body).unwrap();
 
     let links: Vec<_> = linkify::LinkFinder::new()
         .links(body.content.first().unwrap().value.as_ref())
         .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        // Skips the preference center link in the footer
+        .filter(|l| l.as_str().contains("subscription_token"))
         .collect();
     assert_eq!(links.len(), 1);
 
@@ -164,6 +234,7 @@ async fn insert_new_subscription(
     let sub = NewSubscriber {
         email: SubscriberEmail::parse(email.to_string()).unwrap(),
         name: SubscriberName::parse(name.to_string()).unwrap(),
+        attributes: SubscriberAttributes::default(),
     };
     SubscriptionQueries::insert_subscriber(&mut tx, &sub, status)
         .await
@@ -32,7 +32,7 @@ async fn confirmations_with_a_non_existing_token_are_rejected_with_a_401() {
     let response = reqwest::get(&format!(
         "{}/subscriptions/confirm?subscription_token={}",
         app.address,
-        Uuid::new_v4().to_string()
+        Uuid::new_v4()
     ))
     .await
     .unwrap();
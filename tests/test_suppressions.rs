@@ -0,0 +1,156 @@
+use reqwest::Method;
+use std::time::Duration;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::domain::email_template::EmailTemplate;
+use zero2prod::domain::subscriber_email::SubscriberEmail;
+use zero2prod::email_client::{EmailClient, SendOutcome};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn admin_suppression_routes_reject_missing_or_invalid_tokens() {
+    let test_app = common::spawn_app().await;
+    let url = format!("{}/admin/suppressions", test_app.address);
+
+    let response = reqwest::get(&url).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = reqwest::Client::new()
+        .get(&url)
+        .bearer_auth("not-the-admin-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn suppressions_can_be_added_listed_and_removed() {
+    let test_app = common::spawn_app().await;
+    let email = SubscriberEmail::parse("bounced@gmail.com".to_owned()).unwrap();
+
+    let response = test_app
+        .admin_request(Method::POST, "/admin/suppressions")
+        .json(&serde_json::json!({ "email": "Bounced@gmail.com", "reason": "hard_bounce" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = test_app
+        .admin_request(Method::GET, "/admin/suppressions")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let suppressions: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(suppressions.as_array().unwrap().len(), 1);
+    assert_eq!(suppressions[0]["email_hash"], email.canonical_hash());
+    assert_eq!(suppressions[0]["reason"], "hard_bounce");
+    assert_eq!(suppressions[0]["source"], "admin");
+
+    let response = test_app
+        .admin_request(Method::DELETE, "/admin/suppressions/bounced@gmail.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Already removed
+    let response = test_app
+        .admin_request(Method::DELETE, "/admin/suppressions/bounced@gmail.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn adding_a_suppression_with_invalid_data_is_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({ "email": "not-an-email", "reason": "manual" }),
+            "invalid email",
+        ),
+        (
+            serde_json::json!({ "email": "ursula@gmail.com", "reason": "bored" }),
+            "unknown reason",
+        ),
+        (serde_json::json!({ "reason": "manual" }), "missing email"),
+    ];
+    for (body, description) in test_cases {
+        let response = test_app
+            .admin_request(Method::POST, "/admin/suppressions")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not return a 400 Bad Request when the payload was {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn email_client_does_not_send_to_suppressed_recipients() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .admin_request(Method::POST, "/admin/suppressions")
+        .json(&serde_json::json!({ "email": "complained@gmail.com", "reason": "complaint" }))
+        .send()
+        .await
+        .unwrap();
+
+    let email_client = EmailClient::new(&test_app.config, test_app.db_pool.clone());
+    let recipient = SubscriberEmail::parse("COMPLAINED@gmail.com".to_owned()).unwrap();
+    let outcome = email_client
+        .deliver(
+            None,
+            EmailTemplate::SubscriptionConfirmation,
+            &recipient,
+            "Subject",
+            "Content",
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(outcome, SendOutcome::Suppressed);
+
+    // Wiremock asserts on drop
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn suppressed_subscribers_do_not_receive_a_confirmation_email() {
+    let test_app = common::spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&test_app.mock_server)
+        .await;
+    test_app
+        .admin_request(Method::POST, "/admin/suppressions")
+        .json(&serde_json::json!({ "email": "unsubscribed@gmail.com", "reason": "unsubscribe" }))
+        .send()
+        .await
+        .unwrap();
+
+    let response = test_app
+        .post_subscriptions("name=Unsubscribed&email=unsubscribed%40gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Give the background consumer a chance to (not) send anything
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    // Wiremock asserts on drop
+}
@@ -0,0 +1,20 @@
+use claim::{assert_err, assert_ok};
+use zero2prod::domain::tag_name::TagName;
+
+#[test]
+fn lowercase_names_with_dashes_and_underscores_are_accepted() {
+    assert_ok!(TagName::parse("vip".to_string()));
+    assert_ok!(TagName::parse("early-adopter_2022".to_string()));
+}
+
+#[test]
+fn uppercase_whitespace_and_empty_names_are_rejected() {
+    assert_err!(TagName::parse("VIP".to_string()));
+    assert_err!(TagName::parse("early adopter".to_string()));
+    assert_err!(TagName::parse("".to_string()));
+}
+
+#[test]
+fn too_long_names_are_rejected() {
+    assert_err!(TagName::parse("a".repeat(65)));
+}
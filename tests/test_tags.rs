@@ -0,0 +1,206 @@
+use std::time::Duration;
+
+use crate::common::TestApp;
+use reqwest::Method;
+use serde_json::json;
+use uuid::Uuid;
+use zero2prod::events::subscriber_tag_changed::{SubscriberTagChanged, TagChange};
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_are_tagged_by_id_or_email_and_every_change_is_published() {
+    let test_app = common::spawn_app().await;
+    let ann_id = insert_subscriber(&test_app, "ann@gmail.com", 2).await;
+    insert_subscriber(&test_app, "bob@gmail.com", 1).await;
+    let mut published = test_app
+        .event_bus
+        .listen::<SubscriberTagChanged>()
+        .await
+        .unwrap();
+
+    let unknown_id = Uuid::new_v4();
+    let response = change_tag(
+        &test_app,
+        "add",
+        "vip",
+        json!({"ids": [ann_id, unknown_id], "emails": ["bob@gmail.com", "nobody@gmail.com"]}),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["changed"], 2);
+    assert_eq!(
+        report["not_found"],
+        json!([unknown_id.to_string(), "nobody@gmail.com"])
+    );
+
+    let mut events = vec![];
+    for _ in 0..2 {
+        let message = tokio::time::timeout(Duration::from_secs(5), published.next())
+            .await
+            .expect("SubscriberTagChanged was not published")
+            .unwrap();
+        let event = message.unwrap().payload;
+        assert_eq!(event.tag, "vip");
+        assert_eq!(event.change, TagChange::Added);
+        events.push(event.email);
+    }
+    events.sort();
+    assert_eq!(events, vec!["ann@gmail.com", "bob@gmail.com"]);
+
+    // Tagging again changes nothing
+    let response = change_tag(&test_app, "add", "vip", json!({ "ids": [ann_id] })).await;
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["changed"], 0);
+    let tags: serde_json::Value = test_app
+        .admin_request(Method::GET, "/admin/tags")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        tags,
+        json!([{"name": "vip", "subscribers": 2, "created_at": tags[0]["created_at"]}])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn untagging_publishes_a_removed_event_for_the_subscribers_that_had_the_tag() {
+    let test_app = common::spawn_app().await;
+    let ann_id = insert_subscriber(&test_app, "ann@gmail.com", 2).await;
+    let bob_id = insert_subscriber(&test_app, "bob@gmail.com", 1).await;
+    change_tag(&test_app, "add", "vip", json!({ "ids": [ann_id] })).await;
+    let mut published = test_app
+        .event_bus
+        .listen::<SubscriberTagChanged>()
+        .await
+        .unwrap();
+
+    let response = change_tag(
+        &test_app,
+        "remove",
+        "vip",
+        json!({ "ids": [ann_id, bob_id] }),
+    )
+    .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["changed"], 1);
+
+    let message = tokio::time::timeout(Duration::from_secs(5), published.next())
+        .await
+        .expect("SubscriberTagChanged was not published")
+        .unwrap();
+    let event = message.unwrap().payload;
+    assert_eq!(event.subscription_id, ann_id);
+    assert_eq!(event.change, TagChange::Removed);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_subscriber_listing_filters_by_tag() {
+    let test_app = common::spawn_app().await;
+    let ann_id = insert_subscriber(&test_app, "ann@gmail.com", 3).await;
+    let bob_id = insert_subscriber(&test_app, "bob@gmail.com", 2).await;
+    insert_subscriber(&test_app, "cid@gmail.com", 1).await;
+    change_tag(&test_app, "add", "vip", json!({ "ids": [ann_id, bob_id] })).await;
+    change_tag(&test_app, "add", "beta", json!({ "ids": [ann_id] })).await;
+
+    let everyone = list_subscribers(&test_app, "").await;
+    let emails: Vec<&str> = everyone
+        .iter()
+        .map(|subscriber| subscriber["email"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        emails,
+        vec!["cid@gmail.com", "bob@gmail.com", "ann@gmail.com"]
+    );
+    assert_eq!(everyone[0]["tags"], json!([]));
+    assert_eq!(everyone[2]["tags"], json!(["beta", "vip"]));
+
+    let vips = list_subscribers(&test_app, "?tag=vip&limit=1&offset=1").await;
+    assert_eq!(vips.len(), 1);
+    assert_eq!(vips[0]["id"], ann_id.to_string());
+
+    let response = test_app
+        .admin_request(Method::GET, "/admin/subscriptions?tag=VIP")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_tag_changes_are_rejected() {
+    let test_app = common::spawn_app().await;
+    let test_cases = vec![
+        (
+            "add",
+            "Not A Tag",
+            json!({ "ids": [] }),
+            400,
+            "an invalid tag",
+        ),
+        (
+            "add",
+            "vip",
+            json!({ "emails": ["not-an-email"] }),
+            400,
+            "an invalid email",
+        ),
+        ("remove", "vip", json!({ "ids": [] }), 404, "an unknown tag"),
+    ];
+    for (action, tag, body, status, description) in test_cases {
+        let response = change_tag(&test_app, action, tag, body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            status,
+            "The API did not fail with {} when the request had {}.",
+            status,
+            description
+        );
+    }
+}
+
+async fn change_tag(
+    test_app: &TestApp,
+    action: &str,
+    tag: &str,
+    body: serde_json::Value,
+) -> reqwest::Response {
+    test_app
+        .admin_request(Method::POST, &format!("/admin/tags/{}/{}", tag, action))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn list_subscribers(test_app: &TestApp, query: &str) -> Vec<serde_json::Value> {
+    let response = test_app
+        .admin_request(Method::GET, &format!("/admin/subscriptions{}", query))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    response.json().await.unwrap()
+}
+
+async fn insert_subscriber(test_app: &TestApp, email: &str, days_ago: i64) -> Uuid {
+    let id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, status, subscribed_at)
+        VALUES ($1, $2, 'Reader', 'confirmed', $3)
+        "#,
+        id,
+        email,
+        chrono::Utc::now() - chrono::Duration::days(days_ago),
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+    id
+}
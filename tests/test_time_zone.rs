@@ -0,0 +1,58 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use claim::assert_err;
+use zero2prod::domain::issue_status::IssueStatus;
+use zero2prod::domain::time_zone::{earliest_instant, IanaTimeZone};
+
+#[test]
+fn unknown_time_zones_are_rejected() {
+    assert_err!(IanaTimeZone::parse("Mars/Olympus_Mons"));
+    assert_err!(IanaTimeZone::parse("+02:00"));
+}
+
+#[test]
+fn wall_clock_times_follow_daylight_saving_time() {
+    let berlin = IanaTimeZone::parse("Europe/Berlin").unwrap();
+    let winter = NaiveDate::from_ymd(2022, 1, 11).and_hms(9, 0, 0);
+    let summer = NaiveDate::from_ymd(2022, 6, 14).and_hms(9, 0, 0);
+    assert_eq!(
+        berlin.to_utc(&winter),
+        Utc.ymd(2022, 1, 11).and_hms(8, 0, 0)
+    );
+    assert_eq!(
+        berlin.to_utc(&summer),
+        Utc.ymd(2022, 6, 14).and_hms(7, 0, 0)
+    );
+}
+
+#[test]
+fn skipped_and_repeated_wall_clock_times_are_resolved() {
+    let berlin = IanaTimeZone::parse("Europe/Berlin").unwrap();
+    // 02:30 does not exist on the night clocks go forward
+    let skipped = NaiveDate::from_ymd(2022, 3, 27).and_hms(2, 30, 0);
+    assert_eq!(
+        berlin.to_utc(&skipped),
+        Utc.ymd(2022, 3, 27).and_hms(1, 0, 0)
+    );
+    // 02:30 happens twice on the night clocks go back, the first one wins
+    let repeated = NaiveDate::from_ymd(2022, 10, 30).and_hms(2, 30, 0);
+    assert_eq!(
+        berlin.to_utc(&repeated),
+        Utc.ymd(2022, 10, 30).and_hms(0, 30, 0)
+    );
+}
+
+#[test]
+fn the_earliest_instant_is_in_the_first_time_zone_of_the_day() {
+    let kiritimati = IanaTimeZone::parse("Pacific/Kiritimati").unwrap();
+    let local = NaiveDate::from_ymd(2022, 6, 14).and_hms(9, 0, 0);
+    assert_eq!(earliest_instant(&local), kiritimati.to_utc(&local));
+}
+
+#[test]
+fn sending_issues_can_no_longer_be_cancelled_or_rescheduled() {
+    assert!(IssueStatus::Scheduled.can_transition_to(IssueStatus::Scheduled));
+    assert!(IssueStatus::Scheduled.can_transition_to(IssueStatus::Cancelled));
+    assert!(!IssueStatus::Sending.can_transition_to(IssueStatus::Cancelled));
+    assert!(!IssueStatus::Sending.can_transition_to(IssueStatus::Scheduled));
+    assert!(!IssueStatus::Cancelled.can_transition_to(IssueStatus::Scheduled));
+}
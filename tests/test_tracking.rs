@@ -0,0 +1,188 @@
+use crate::common::{find_email, TestApp};
+use uuid::Uuid;
+use zero2prod::tracking::{TrackingSigner, TrackingToken};
+
+mod common;
+
+const TEXT_CONTENT: &str = "Hello readers\n\nRead more at https://example.com/article";
+
+#[tokio::test(flavor = "multi_thread")]
+async fn lists_without_tracking_get_the_original_links_and_no_pixel() {
+    let test_app = common::spawn_app().await;
+    test_app.mock_mail_send(1).await;
+    test_app
+        .insert_confirmed_member(&Uuid::new_v4(), "reader@gmail.com")
+        .await;
+    let issue_id = add_issue(&test_app).await;
+    test_app.schedule_issue_now(&issue_id).await;
+
+    let requests = test_app.wait_for_emails(1).await;
+    // Neither in the text nor in the HTML part
+    for content in [
+        &requests[0].text_content,
+        requests[0].html_content.as_ref().unwrap(),
+    ] {
+        assert!(content.contains("https://example.com/article"));
+        assert!(!content.contains("/t/"));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn subscribers_who_do_not_want_tracking_are_not_tracked() {
+    let test_app = common::spawn_app().await;
+    test_app.enable_tracking().await;
+    test_app.mock_mail_send(2).await;
+    let tracked_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&tracked_id, "tracked@gmail.com")
+        .await;
+    let private_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&private_id, "private@gmail.com")
+        .await;
+    do_not_track(&test_app, &private_id).await;
+    let issue_id = add_issue(&test_app).await;
+    test_app.schedule_issue_now(&issue_id).await;
+
+    let requests = test_app.wait_for_emails(2).await;
+    let private = find_email(&requests, "private@gmail.com");
+    for content in [
+        &private.text_content,
+        private.html_content.as_ref().unwrap(),
+    ] {
+        assert!(content.contains("https://example.com/article"));
+        assert!(!content.contains("/t/"));
+    }
+
+    // Opting out after the email was sent stops the tracking too
+    let tracked = find_email(&requests, "tracked@gmail.com");
+    let open_url = test_app.find_url(tracked.html_content.as_deref().unwrap(), "/t/o/");
+    let click_url = test_app.find_url(&tracked.text_content, "/t/c/");
+    do_not_track(&test_app, &tracked_id).await;
+    let open = reqwest::get(&open_url).await.unwrap();
+    assert_eq!(open.status().as_u16(), 200);
+    let click = no_redirect_client().get(&click_url).send().await.unwrap();
+    assert_eq!(click.status().as_u16(), 302);
+    assert_eq!(click.headers()["Location"], "https://example.com/article");
+    let events = sqlx::query!("SELECT id FROM issue_tracking_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(events.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clicks_are_recorded_per_subscriber_and_link() {
+    let test_app = common::spawn_app().await;
+    test_app.enable_tracking().await;
+    test_app.mock_mail_send(1).await;
+    let subscription_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&subscription_id, "reader@gmail.com")
+        .await;
+    let issue_id = add_issue(&test_app).await;
+    test_app.schedule_issue_now(&issue_id).await;
+
+    let requests = test_app.wait_for_emails(1).await;
+    let click_url = test_app.find_url(&requests[0].text_content, "/t/c/");
+    let click = no_redirect_client().get(&click_url).send().await.unwrap();
+    assert_eq!(click.status().as_u16(), 302);
+
+    let event = sqlx::query!(
+        r#"
+        SELECT e.subscription_id AS "subscription_id!", e.issue_id AS "issue_id!",
+               l.position AS "position!", l.url AS "url!"
+        FROM issue_tracking_events e
+        JOIN issue_links l ON l.id = e.link_id
+        WHERE e.kind = 'click'
+        "#
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(event.subscription_id, subscription_id);
+    assert_eq!(event.issue_id, issue_id);
+    assert_eq!(event.position, 0);
+    assert_eq!(event.url, "https://example.com/article");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_links_of_issues_written_before_they_were_stored_are_backfilled() {
+    let test_app = common::spawn_app().await;
+    test_app.enable_tracking().await;
+    test_app.mock_mail_send(1).await;
+    let subscription_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&subscription_id, "reader@gmail.com")
+        .await;
+    let issue_id = add_issue(&test_app).await;
+    test_app.schedule_issue_now(&issue_id).await;
+    let requests = test_app.wait_for_emails(1).await;
+    sqlx::query!("DELETE FROM issue_links")
+        .execute(&test_app.db_pool)
+        .await
+        .unwrap();
+
+    let click_url = test_app.find_url(&requests[0].text_content, "/t/c/");
+    let click = no_redirect_client().get(&click_url).send().await.unwrap();
+
+    assert_eq!(click.status().as_u16(), 302);
+    assert_eq!(click.headers()["Location"], "https://example.com/article");
+    let event = sqlx::query!(
+        r#"
+        SELECT l.url AS "url!"
+        FROM issue_tracking_events e
+        JOIN issue_links l ON l.id = e.link_id
+        WHERE e.kind = 'click'
+        "#
+    )
+    .fetch_one(&test_app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(event.url, "https://example.com/article");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn clicks_on_links_that_are_not_in_the_issue_are_rejected_with_a_404() {
+    let test_app = common::spawn_app().await;
+    test_app.enable_tracking().await;
+    let subscription_id = Uuid::new_v4();
+    test_app
+        .insert_confirmed_member(&subscription_id, "reader@gmail.com")
+        .await;
+    let issue_id = add_issue(&test_app).await;
+
+    // Correctly signed, but the issue has a single link
+    let token = TrackingSigner::new(&test_app.config).sign(&TrackingToken::Click {
+        issue_id,
+        subscription_id,
+        link: 1,
+    });
+    let response = no_redirect_client()
+        .get(format!("{}/t/c/{}", test_app.address, token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+fn no_redirect_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+async fn add_issue(test_app: &TestApp) -> Uuid {
+    test_app.add_issue("Weekly news", TEXT_CONTENT).await
+}
+
+async fn do_not_track(test_app: &TestApp, subscription_id: &Uuid) {
+    sqlx::query!(
+        "UPDATE subscriptions SET do_not_track = TRUE WHERE id = $1",
+        subscription_id
+    )
+    .execute(&test_app.db_pool)
+    .await
+    .unwrap();
+}
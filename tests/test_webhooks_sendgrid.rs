@@ -0,0 +1,215 @@
+use crate::common::TestApp;
+use p256::ecdsa::SigningKey;
+use rand_core::OsRng;
+use std::time::Duration;
+use zero2prod::db::subscription_queries::SubscriptionQueries;
+use zero2prod::domain::new_subscriber::NewSubscriber;
+use zero2prod::domain::subscriber_attributes::SubscriberAttributes;
+use zero2prod::domain::subscriber_email::SubscriberEmail;
+use zero2prod::domain::subscriber_name::SubscriberName;
+use zero2prod::domain::subscription_status::SubscriptionStatus;
+use zero2prod::events::email_event_received::EmailEventReceived;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn webhook_requests_without_a_valid_signature_are_rejected_with_a_401() {
+    let mut test_app = common::spawn_app().await;
+    let body = events_body(&[("someone@gmail.com", "open", "event-1", None)]);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/webhooks/sendgrid", test_app.address))
+        .header("Content-Type", "application/json")
+        .body(body.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+
+    // Signed with a key SendGrid does not have
+    test_app.sendgrid_signing_key = SigningKey::random(&mut OsRng);
+    let response = test_app.post_sendgrid_events(&body).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let saved = sqlx::query!("SELECT id FROM email_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_webhook_payloads_are_rejected_with_a_400() {
+    let test_app = common::spawn_app().await;
+    let response = test_app
+        .post_sendgrid_events("{\"not\": \"a batch\"}")
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hard_bounces_suppress_the_recipient_and_fail_the_subscription() {
+    let test_app = common::spawn_app().await;
+    insert_new_subscription(&test_app, "bounced@gmail.com", SubscriptionStatus::Pending).await;
+    let mut published = test_app
+        .event_bus
+        .listen::<EmailEventReceived>()
+        .await
+        .unwrap();
+
+    let body = events_body(&[("bounced@gmail.com", "bounce", "event-1", Some("bounce"))]);
+    let response = test_app.post_sendgrid_events(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let event = sqlx::query!("SELECT event_type, provider_message_id FROM email_events")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch the email event");
+    assert_eq!(event.event_type, "bounce");
+    assert_eq!(event.provider_message_id, Some("message-id".to_owned()));
+    let suppression = sqlx::query!("SELECT email_hash, (reason :: TEXT), source FROM suppressions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .expect("Failed to fetch the suppression");
+    let email = SubscriberEmail::parse("bounced@gmail.com".to_owned()).unwrap();
+    assert_eq!(suppression.email_hash, email.canonical_hash());
+    assert_eq!(suppression.reason, Some("hard_bounce".to_owned()));
+    assert_eq!(suppression.source, "sendgrid_webhook");
+    let subscription = sqlx::query!("SELECT (status :: TEXT) FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, Some("failed".to_owned()));
+
+    let message = tokio::time::timeout(Duration::from_secs(5), published.next())
+        .await
+        .expect("EmailEventReceived was not published")
+        .unwrap();
+    let published_event = message.unwrap().payload;
+    assert_eq!(published_event.email, "bounced@gmail.com");
+    assert_eq!(published_event.event_type, "bounce");
+    assert!(published_event.suppressed);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn hard_bounces_do_not_undo_a_confirmation() {
+    let test_app = common::spawn_app().await;
+    insert_new_subscription(
+        &test_app,
+        "confirmed@gmail.com",
+        SubscriptionStatus::Confirmed,
+    )
+    .await;
+
+    let body = events_body(&[("confirmed@gmail.com", "bounce", "event-1", Some("bounce"))]);
+    let response = test_app.post_sendgrid_events(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscription = sqlx::query!("SELECT (status :: TEXT) FROM subscriptions")
+        .fetch_one(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, Some("confirmed".to_owned()));
+    // Emails stop anyway
+    let suppressions = sqlx::query!("SELECT email_hash FROM suppressions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppressions.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spam_reports_suppress_the_recipient_but_other_events_do_not() {
+    let test_app = common::spawn_app().await;
+    let body = events_body(&[
+        ("opened@gmail.com", "open", "event-1", None),
+        ("blocked@gmail.com", "bounce", "event-2", Some("blocked")),
+        ("complained@gmail.com", "spamreport", "event-3", None),
+    ]);
+    let response = test_app.post_sendgrid_events(&body).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let events = sqlx::query!("SELECT event_type FROM email_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 3);
+    let suppressions = sqlx::query!("SELECT email_hash, (reason :: TEXT) FROM suppressions")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(suppressions.len(), 1);
+    let email = SubscriberEmail::parse("complained@gmail.com".to_owned()).unwrap();
+    assert_eq!(suppressions[0].email_hash, email.canonical_hash());
+    assert_eq!(suppressions[0].reason, Some("complaint".to_owned()));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn redelivered_webhook_events_are_recorded_once() {
+    let test_app = common::spawn_app().await;
+    let body = events_body(&[("opened@gmail.com", "open", "event-1", None)]);
+
+    for _ in 0..2 {
+        let response = test_app.post_sendgrid_events(&body).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let events = sqlx::query!("SELECT event_type FROM email_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn events_with_an_out_of_range_timestamp_are_skipped() {
+    let test_app = common::spawn_app().await;
+    let mut events: Vec<serde_json::Value> = serde_json::from_str(&events_body(&[
+        ("opened@gmail.com", "open", "event-1", None),
+        ("opened@gmail.com", "click", "event-2", None),
+    ]))
+    .unwrap();
+    events[0]["timestamp"] = i64::MAX.into();
+
+    let response = test_app
+        .post_sendgrid_events(&serde_json::to_string(&events).unwrap())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let events = sqlx::query!("SELECT event_type FROM email_events")
+        .fetch_all(&test_app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event_type, "click");
+}
+
+fn events_body(events: &[(&str, &str, &str, Option<&str>)]) -> String {
+    let events: Vec<_> = events
+        .iter()
+        .map(|(email, event, event_id, bounce_type)| {
+            serde_json::json!({
+                "email": email,
+                "timestamp": chrono::Utc::now().timestamp(),
+                "event": event,
+                "sg_event_id": event_id,
+                "sg_message_id": "message-id.filter0001.16648.5515E0B88.0",
+                "type": bounce_type,
+            })
+        })
+        .collect();
+    serde_json::to_string(&events).unwrap()
+}
+
+async fn insert_new_subscription(test_app: &TestApp, email: &str, status: SubscriptionStatus) {
+    let mut tx = test_app.db_pool.begin().await.unwrap();
+    let sub = NewSubscriber {
+        email: SubscriberEmail::parse(email.to_string()).unwrap(),
+        name: SubscriberName::parse("Some Name".to_string()).unwrap(),
+        attributes: SubscriberAttributes::default(),
+    };
+    SubscriptionQueries::insert_subscriber(&mut tx, &sub, status)
+        .await
+        .expect("Failed to save a new subscriber");
+    tx.commit().await.unwrap();
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+use zero2prod::email_client::EmailClient;
+use zero2prod::shutdown::ShutdownCoordinator;
+use zero2prod::startup::spawn_event_consumers;
+
+mod common;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_api_leaves_the_events_to_the_worker_when_its_consumers_are_disabled() {
+    let test_app = common::spawn_app_with(|config| config.event_consumers_enabled = false).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&test_app.mock_server)
+        .await;
+
+    test_app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com")
+        .await;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let requests = test_app.mock_server.received_requests().await.unwrap();
+    assert!(requests.is_empty(), "The API sent the confirmation email");
+
+    // What the worker binary runs, on the same event bus
+    spawn_event_consumers(
+        Arc::new(test_app.config.clone()),
+        Arc::new(EmailClient::new(&test_app.config, test_app.db_pool.clone())),
+        Arc::new(test_app.db_pool.clone()),
+        &test_app.event_bus,
+        &mut ShutdownCoordinator::new(),
+    )
+    .await
+    .unwrap();
+    test_app
+        .post_subscriptions("name=ged&email=ged%40gmail.com")
+        .await;
+    common::eventually(
+        || async {
+            let requests = test_app.mock_server.received_requests().await.unwrap();
+            anyhow::ensure!(requests.len() == 1, "{} emails sent", requests.len());
+            Ok(())
+        },
+        50,
+        100,
+    )
+    .await;
+}
+
+#[test]
+fn the_worker_refuses_to_start_with_the_in_process_backend() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_zero2prod-worker"))
+        .env("EVENT_BUS_BACKEND", "in_process")
+        .output()
+        .expect("Failed to run zero2prod-worker");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("The worker cannot receive the events of the API"));
+}
+
+#[test]
+fn the_api_refuses_to_leave_the_events_to_a_worker_with_the_in_process_backend() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_zero2prod"))
+        .env("EVENT_BUS_BACKEND", "in_process")
+        .env("EVENT_CONSUMERS_ENABLED", "false")
+        .output()
+        .expect("Failed to run zero2prod");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("EVENT_CONSUMERS_ENABLED=false"));
+}